@@ -0,0 +1,48 @@
+//! Cell text cleaning on a 50k-row table, through `Extractor::tables` as a run would
+//! call it. Run with `cargo bench --bench clean_cell_text`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use scraper::Html;
+use tabex::{ExtractOptions, Extractor};
+
+const ROWS: usize = 50_000;
+
+/// A table whose cells have the markup cleaning has to see through: runs of
+/// whitespace and newlines, inline elements, `<br>` and non-breaking spaces.
+fn fixture() -> String {
+    let mut html = String::from(
+        "<html><body><table><caption>Fixture</caption>\
+         <thead><tr><th>Id</th><th>Name</th><th>Region</th><th>Amount</th><th>Note</th></tr></thead><tbody>",
+    );
+    for i in 0..ROWS {
+        html.push_str(&format!(
+            "<tr><td>{i}</td>\
+             <td>\n    <a href=\"/p/{i}\">Person   <b>{i}</b></a>\n  </td>\
+             <td><span>North</span>&nbsp;<span>East</span></td>\
+             <td>  $1,{:03}.50 </td>\
+             <td>first line<br>second\tline <i>with   emphasis</i></td></tr>",
+            i % 1000
+        ));
+    }
+    html.push_str("</tbody></table></body></html>");
+    html
+}
+
+fn bench_clean_cell_text(c: &mut Criterion) {
+    let document = Html::parse_document(&fixture());
+    let extractor = Extractor::new(ExtractOptions::default());
+    let mut group = c.benchmark_group("clean_cell_text");
+    group.sample_size(10);
+    group.bench_function("50k_rows", |b| {
+        b.iter(|| {
+            extractor
+                .tables(&document)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_clean_cell_text);
+criterion_main!(benches);
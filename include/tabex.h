@@ -0,0 +1,39 @@
+#ifndef TABEX_H
+#define TABEX_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+
+
+/**
+ * Extracts the page metadata and tables of `html`, recorded as loaded from `url`,
+ * with default options. Returns the result as JSON in the layout `tabex -f json`
+ * uses for a single page, or `{"error": "..."}` when extraction fails. Returns
+ * NULL when either argument is NULL or not UTF-8.
+ *
+ * The returned string is owned by the caller and must be freed with
+ * `tabex_free_string`.
+ *
+ * # Safety
+ *
+ * `html` and `url` must be NULL or point to NUL-terminated strings that stay valid
+ * for the duration of the call.
+ */
+char *tabex_extract_html(const char *html, const char *url);
+
+/**
+ * Frees a string returned by `tabex_extract_html`. Does nothing for NULL.
+ *
+ * # Safety
+ *
+ * `s` must be NULL or a pointer returned by `tabex_extract_html` that has not been
+ * freed yet.
+ */
+void tabex_free_string(char *s);
+
+#endif  /* TABEX_H */
@@ -0,0 +1,530 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Table;
+use crate::events::Events;
+use crate::matching::HeaderMatcher;
+
+/// Column profiling results, present when `--analyze` or `--key-columns` is given.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableAnalysis {
+    /// Columns whose values are all present and distinct.
+    pub candidate_keys: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_check: Option<KeyCheck>,
+    pub outliers: Vec<Outlier>,
+}
+
+/// A value far outside the rest of its numeric column.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Outlier {
+    pub column: String,
+    /// 1-based data row
+    pub row: usize,
+    pub value: String,
+    pub z_score: f64,
+}
+
+/// Uniqueness of the columns named with `--key-columns`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyCheck {
+    pub columns: Vec<String>,
+    pub duplicate_count: usize,
+    /// Up to ten duplicated key values, columns joined with " | ".
+    pub duplicates: Vec<String>,
+}
+
+/// A column whose values all appear in another table's key column.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Relationship {
+    pub from_table: usize,
+    pub from_column: String,
+    pub to_table: usize,
+    pub to_column: String,
+    pub distinct_values: usize,
+}
+
+/// Display name for a column, falling back to its position for header-less tables.
+pub fn column_name(table: &Table, index: usize) -> String {
+    match table.data.headers.get(index) {
+        Some(header) if !header.is_empty() => header.clone(),
+        _ => format!("column_{}", index + 1),
+    }
+}
+
+pub fn column_values(table: &Table, index: usize) -> impl Iterator<Item = &str> {
+    table
+        .data
+        .rows
+        .iter()
+        .map(move |row| row.get(index).map(String::as_str).unwrap_or(""))
+}
+
+fn data_width(table: &Table) -> usize {
+    table
+        .data
+        .rows
+        .iter()
+        .map(Vec::len)
+        .max()
+        .unwrap_or(0)
+        .max(table.data.headers.len())
+}
+
+/// Whether a table looks like a crosstab: text row labels down the side,
+/// column labels across the top and a numeric body.
+pub fn is_matrix(table: &Table) -> bool {
+    let conventions = table.metadata.sign_conventions;
+    let headers = &table.data.headers;
+    let rows = &table.data.rows;
+    if headers.len() < 3 || rows.len() < 2 {
+        return false;
+    }
+
+    let labels_are_text = rows.iter().all(|row| {
+        row.first()
+            .is_some_and(|label| !label.is_empty() && conventions.parse_number(label).is_none())
+    });
+
+    let body: Vec<&String> = rows
+        .iter()
+        .flat_map(|row| row.iter().skip(1))
+        .filter(|cell| !cell.is_empty())
+        .collect();
+    let numeric = body
+        .iter()
+        .filter(|cell| conventions.parse_number(cell).is_some())
+        .count();
+
+    labels_are_text && !body.is_empty() && numeric * 10 >= body.len() * 9
+}
+
+/// Header pairs that announce a two-column table of attributes and their values.
+const KEY_HEADERS: &[&str] = &[
+    "attribute",
+    "property",
+    "field",
+    "key",
+    "name",
+    "spec",
+    "specification",
+    "feature",
+    "parameter",
+    "item",
+];
+const VALUE_HEADERS: &[&str] = &["value", "values", "details", "description", "setting"];
+
+/// Whether a table looks like a spec sheet: two columns of unique text attribute
+/// names and their values, with the names in row header cells, no headers at all,
+/// or headers such as "Attribute | Value".
+pub fn is_key_value(table: &Table, row_headed: bool) -> bool {
+    let rows = &table.data.rows;
+    if data_width(table) != 2 || rows.len() < 2 {
+        return false;
+    }
+    let mut seen = HashSet::new();
+    let keys_are_text = rows.iter().all(|row| {
+        row.first().is_some_and(|key| {
+            !key.is_empty()
+                && table.metadata.sign_conventions.parse_number(key).is_none()
+                && seen.insert(key.to_lowercase())
+        })
+    });
+    let headers = &table.data.headers;
+    let announced = |index: usize, names: &[&str]| {
+        headers
+            .get(index)
+            .is_some_and(|header| names.contains(&header.trim().to_lowercase().as_str()))
+    };
+    keys_are_text
+        && (row_headed
+            || headers.is_empty()
+            || (announced(0, KEY_HEADERS) && announced(1, VALUE_HEADERS)))
+}
+
+/// Indices of columns that could serve as a primary key.
+pub fn candidate_key_indices(table: &Table) -> Vec<usize> {
+    if table.data.rows.len() < 2 {
+        return Vec::new();
+    }
+
+    (0..data_width(table))
+        .filter(|&index| {
+            let mut seen = HashSet::new();
+            column_values(table, index).all(|value| !value.is_empty() && seen.insert(value))
+        })
+        .collect()
+}
+
+fn check_key(
+    table: &mut Table,
+    key_columns: &[String],
+    matcher: &HeaderMatcher,
+) -> Option<KeyCheck> {
+    let indices = key_columns
+        .iter()
+        .map(|column| matcher.column_index(table, column))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut order = Vec::new();
+    for row in &table.data.rows {
+        let key = indices
+            .iter()
+            .map(|&i| row.get(i).map(String::as_str).unwrap_or(""))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let count = counts.entry(key.clone()).or_insert(0);
+        *count += 1;
+        if *count == 2 {
+            order.push(key);
+        }
+    }
+
+    let duplicate_count = counts
+        .values()
+        .filter(|&&count| count > 1)
+        .map(|count| count - 1)
+        .sum();
+    Some(KeyCheck {
+        columns: indices.iter().map(|&i| column_name(table, i)).collect(),
+        duplicate_count,
+        duplicates: order.into_iter().take(10).collect(),
+    })
+}
+
+/// Numeric values for a column, or None when the column isn't mostly numeric.
+fn numeric_column(table: &Table, index: usize) -> Option<Vec<(usize, f64)>> {
+    let mut present = 0;
+    let mut numbers = Vec::new();
+    for (row, value) in column_values(table, index).enumerate() {
+        if value.is_empty() {
+            continue;
+        }
+        present += 1;
+        if let Some(number) = table.metadata.sign_conventions.parse_number(value) {
+            numbers.push((row, number));
+        }
+    }
+    (present > 0 && numbers.len() * 5 >= present * 4).then_some(numbers)
+}
+
+pub fn is_numeric_column(table: &Table, index: usize) -> bool {
+    numeric_column(table, index).is_some_and(|numbers| !numbers.is_empty())
+}
+
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = (sorted.len() - 1) as f64 * q;
+    let (lower, upper) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (pos - lower as f64)
+}
+
+/// Flags values beyond 3 standard deviations or 3 IQRs from the quartiles.
+fn find_outliers(table: &Table) -> Vec<Outlier> {
+    let mut outliers = Vec::new();
+
+    for index in 0..data_width(table) {
+        let Some(numbers) = numeric_column(table, index) else {
+            continue;
+        };
+        if numbers.len() < 4 {
+            continue;
+        }
+
+        let count = numbers.len() as f64;
+        let mean = numbers.iter().map(|(_, n)| n).sum::<f64>() / count;
+        let std_dev = (numbers.iter().map(|(_, n)| (n - mean).powi(2)).sum::<f64>() / count).sqrt();
+
+        let mut sorted: Vec<f64> = numbers.iter().map(|(_, n)| *n).collect();
+        sorted.sort_by(f64::total_cmp);
+        let (q1, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+        let iqr = q3 - q1;
+
+        for (row, number) in numbers {
+            let z_score = if std_dev > 0.0 {
+                (number - mean) / std_dev
+            } else {
+                0.0
+            };
+            let beyond_fences = iqr > 0.0 && (number < q1 - 3.0 * iqr || number > q3 + 3.0 * iqr);
+            if z_score.abs() > 3.0 || beyond_fences {
+                outliers.push(Outlier {
+                    column: column_name(table, index),
+                    row: row + 1,
+                    value: table.data.rows[row][index].clone(),
+                    z_score: (z_score * 100.0).round() / 100.0,
+                });
+            }
+        }
+    }
+
+    outliers
+}
+
+pub fn analyze_tables(
+    tables: &mut [Table],
+    key_columns: &[String],
+    matcher: &HeaderMatcher,
+    events: &Events,
+) {
+    for table in tables {
+        let candidate_keys = candidate_key_indices(table)
+            .into_iter()
+            .map(|index| column_name(table, index))
+            .collect();
+
+        let key_check = if key_columns.is_empty() {
+            None
+        } else {
+            check_key(table, key_columns, matcher)
+        };
+        if let Some(check) = &key_check
+            && check.duplicate_count > 0
+        {
+            events.warning(
+                "duplicate_keys",
+                &format!(
+                    "key column(s) {} in table {} contain {} duplicate value(s)",
+                    check.columns.join(", "),
+                    table.metadata.position,
+                    check.duplicate_count
+                ),
+                serde_json::json!({
+                    "table": table.metadata.position,
+                    "columns": check.columns,
+                    "duplicates": check.duplicate_count,
+                }),
+            );
+        }
+
+        let outliers = find_outliers(table);
+
+        table.metadata.analysis = Some(TableAnalysis {
+            candidate_keys,
+            key_check,
+            outliers,
+        });
+    }
+}
+
+/// Finds likely foreign keys between tables on the same page.
+pub fn find_relationships(tables: &[Table]) -> Vec<Relationship> {
+    let distinct = |table: &Table, index: usize| -> HashSet<String> {
+        column_values(table, index)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    let keys: Vec<Vec<(usize, HashSet<String>)>> = tables
+        .iter()
+        .map(|table| {
+            candidate_key_indices(table)
+                .into_iter()
+                .map(|index| (index, distinct(table, index)))
+                .collect()
+        })
+        .collect();
+
+    let mut relationships = Vec::new();
+    for (from, from_table) in tables.iter().enumerate() {
+        for from_index in 0..data_width(from_table) {
+            let values = distinct(from_table, from_index);
+            // A single repeated value says little about a relationship
+            if values.len() < 2 {
+                continue;
+            }
+            for (to, to_table) in tables.iter().enumerate() {
+                if to == from {
+                    continue;
+                }
+                for (to_index, key_values) in &keys[to] {
+                    if values.is_subset(key_values) {
+                        relationships.push(Relationship {
+                            from_table: from_table.metadata.position,
+                            from_column: column_name(from_table, from_index),
+                            to_table: to_table.metadata.position,
+                            to_column: column_name(to_table, *to_index),
+                            distinct_values: values.len(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    relationships
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::events::tests::collect;
+    use crate::tests::extract;
+
+    fn table(html: &str) -> Table {
+        extract(html, ExtractOptions::default()).remove(0)
+    }
+
+    #[test]
+    fn finds_columns_with_distinct_present_values() {
+        let table = table(
+            "<table><tr><th>Id</th><th>Name</th><th>Team</th></tr>
+             <tr><td>1</td><td>Ann</td><td>A</td></tr>
+             <tr><td>2</td><td>Bob</td><td>A</td></tr>
+             <tr><td>3</td><td></td><td>B</td></tr></table>",
+        );
+        assert_eq!(candidate_key_indices(&table), [0]);
+    }
+
+    #[test]
+    fn needs_two_rows_for_a_key() {
+        let table = table("<table><tr><th>Id</th></tr><tr><td>1</td></tr></table>");
+        assert!(candidate_key_indices(&table).is_empty());
+    }
+
+    #[test]
+    fn counts_duplicates_of_a_compound_key() {
+        let mut tables = vec![table(
+            "<table><tr><th>Year</th><th>Team</th><th>Wins</th></tr>
+             <tr><td>2023</td><td>A</td><td>5</td></tr>
+             <tr><td>2023</td><td>A</td><td>6</td></tr>
+             <tr><td>2023</td><td>A</td><td>7</td></tr>
+             <tr><td>2024</td><td>A</td><td>8</td></tr></table>",
+        )];
+        let key = ["year".to_string(), "Team".to_string()];
+        let (events, reported) = collect();
+        analyze_tables(&mut tables, &key, &HeaderMatcher::default(), &events);
+        let check = tables[0]
+            .metadata
+            .analysis
+            .as_ref()
+            .and_then(|analysis| analysis.key_check.as_ref())
+            .unwrap();
+        assert_eq!(check.columns, ["Year", "Team"]);
+        assert_eq!(check.duplicate_count, 2);
+        assert_eq!(check.duplicates, ["2023 | A"]);
+        assert_eq!(reported.events(), ["warning duplicate_keys"]);
+    }
+
+    #[test]
+    fn skips_the_key_check_for_a_missing_column() {
+        let mut tables = vec![table(
+            "<table><tr><th>Id</th></tr><tr><td>1</td></tr><tr><td>1</td></tr></table>",
+        )];
+        analyze_tables(
+            &mut tables,
+            &["Code".to_string()],
+            &HeaderMatcher::default(),
+            &Events::default(),
+        );
+        let analysis = tables[0].metadata.analysis.as_ref().unwrap();
+        assert!(analysis.key_check.is_none());
+    }
+
+    #[test]
+    fn relates_a_column_to_another_tables_key() {
+        let tables = extract(
+            "<table><tr><th>Code</th><th>Country</th></tr>
+             <tr><td>FR</td><td>France</td></tr>
+             <tr><td>DE</td><td>Germany</td></tr>
+             <tr><td>IT</td><td>Italy</td></tr></table>
+             <table><tr><th>City</th><th>Country code</th></tr>
+             <tr><td>Paris</td><td>FR</td></tr>
+             <tr><td>Lyon</td><td>FR</td></tr>
+             <tr><td>Berlin</td><td>DE</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        let relationships = find_relationships(&tables);
+        assert_eq!(relationships.len(), 1);
+        let relationship = &relationships[0];
+        assert_eq!(
+            (
+                relationship.from_table,
+                relationship.from_column.as_str(),
+                relationship.to_table,
+                relationship.to_column.as_str(),
+                relationship.distinct_values
+            ),
+            (2, "Country code", 1, "Code", 2)
+        );
+    }
+
+    #[test]
+    fn ignores_columns_with_a_single_value_or_values_outside_the_key() {
+        let tables = extract(
+            "<table><tr><th>Code</th></tr><tr><td>FR</td></tr><tr><td>DE</td></tr></table>
+             <table><tr><th>City</th><th>Code</th></tr>
+             <tr><td>Paris</td><td>FR</td></tr>
+             <tr><td>Lyon</td><td>FR</td></tr></table>
+             <table><tr><th>City</th><th>Code</th></tr>
+             <tr><td>Rome</td><td>IT</td></tr>
+             <tr><td>Paris</td><td>FR</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        let relationships = find_relationships(&tables);
+        assert!(
+            relationships
+                .iter()
+                .all(|r| !(r.from_column == "Code" && r.to_table == 1)),
+            "{:?}",
+            relationships
+        );
+    }
+
+    #[test]
+    fn detects_crosstabs_with_text_labels_and_a_numeric_body() {
+        let matrix = table(
+            "<table><tr><th>Region</th><th>2023</th><th>2024</th></tr>
+             <tr><td>North</td><td>1,200</td><td>1,350</td></tr>
+             <tr><td>South</td><td>980</td><td></td></tr></table>",
+        );
+        assert!(matrix.metadata.matrix);
+
+        let numeric_labels = table(
+            "<table><tr><th>Year</th><th>A</th><th>B</th></tr>
+             <tr><td>2023</td><td>1</td><td>2</td></tr>
+             <tr><td>2024</td><td>3</td><td>4</td></tr></table>",
+        );
+        assert!(!numeric_labels.metadata.matrix);
+
+        let text_body = table(
+            "<table><tr><th>Name</th><th>Team</th><th>Goals</th></tr>
+             <tr><td>Ann</td><td>Reds</td><td>3</td></tr>
+             <tr><td>Bob</td><td>Blues</td><td>1</td></tr></table>",
+        );
+        assert!(!text_body.metadata.matrix);
+    }
+
+    fn outliers(values: &[&str]) -> Vec<(usize, String)> {
+        let rows: String = values
+            .iter()
+            .map(|value| format!("<tr><td>{}</td></tr>", value))
+            .collect();
+        let table = table(&format!("<table><tr><th>Price</th></tr>{}</table>", rows));
+        find_outliers(&table)
+            .into_iter()
+            .map(|outlier| (outlier.row, outlier.value))
+            .collect()
+    }
+
+    #[test]
+    fn flags_values_far_outside_their_column() {
+        assert_eq!(
+            outliers(&["10", "11", "12", "10", "11", "9", "950"]),
+            [(7, "950".to_string())]
+        );
+        assert_eq!(
+            outliers(&["$1.00", "$1.10", "n/a", "$0.90", "$1.05", "$40"]),
+            [(6, "$40".to_string())]
+        );
+    }
+
+    #[test]
+    fn leaves_short_uniform_and_text_columns_alone() {
+        assert!(outliers(&["1", "2", "900"]).is_empty());
+        assert!(outliers(&["5", "5", "5", "5", "5"]).is_empty());
+        assert!(outliers(&["a", "b", "c", "d", "1000"]).is_empty());
+    }
+}
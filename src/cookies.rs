@@ -0,0 +1,193 @@
+//! `--cookies` and `--save-cookies`: a cookie jar shared by the run's requests, read
+//! from and written to a JSON array of cookies.
+
+use std::fs;
+use std::sync::Arc;
+
+use cookie_store::{CookieDomain, CookieExpiration, CookieStore};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::TabexError;
+use crate::events::Events;
+
+/// A cookie as stored in a cookie file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CookieRecord {
+    pub name: String,
+    pub value: String,
+    /// Host the cookie is sent to, with its subdomains unless `host_only`
+    pub domain: String,
+    #[serde(default = "root_path")]
+    pub path: String,
+    #[serde(default)]
+    pub host_only: bool,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    /// Unix time the cookie expires; session cookies have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<i64>,
+}
+
+fn root_path() -> String {
+    "/".to_string()
+}
+
+impl CookieRecord {
+    /// The cookie as a Set-Cookie value and a URL that could have set it, or None once
+    /// it has expired.
+    fn set_cookie(&self, now: i64) -> Option<(String, reqwest::Url)> {
+        let host = self.domain.trim_start_matches('.');
+        let url = reqwest::Url::parse(&format!("https://{}{}", host, self.path)).ok()?;
+        let mut header = format!("{}={}; Path={}", self.name, self.value, self.path);
+        if !self.host_only {
+            header.push_str(&format!("; Domain={}", host));
+        }
+        if self.secure {
+            header.push_str("; Secure");
+        }
+        if self.http_only {
+            header.push_str("; HttpOnly");
+        }
+        if let Some(expires) = self.expires {
+            if expires <= now {
+                return None;
+            }
+            header.push_str(&format!("; Max-Age={}", expires - now));
+        }
+        Some((header, url))
+    }
+}
+
+/// A jar holding the cookies of `path`, or an empty one without a file. Cookies that
+/// can't be stored, such as those for a public suffix, are skipped with a warning.
+pub fn load_jar(path: Option<&str>, events: &Events) -> Result<Arc<CookieStoreMutex>, TabexError> {
+    let mut store = CookieStore::default();
+    if let Some(path) = path {
+        let contents = fs::read_to_string(path).map_err(|e| TabexError::File {
+            path: path.to_string(),
+            source: e,
+        })?;
+        let records: Vec<CookieRecord> = serde_json::from_str(&contents)
+            .map_err(|e| TabexError::Parse(format!("Invalid cookie file {}: {}", path, e)))?;
+        let now = chrono::Utc::now().timestamp();
+        for record in &records {
+            let Some((header, url)) = record.set_cookie(now) else {
+                continue;
+            };
+            if let Err(e) = store.parse(&header, &url) {
+                events.warning(
+                    "cookie_skipped",
+                    &format!(
+                        "skipping cookie {} for {}: {}",
+                        record.name, record.domain, e
+                    ),
+                    serde_json::json!({
+                        "name": record.name,
+                        "domain": record.domain,
+                        "error": e.to_string(),
+                    }),
+                );
+            }
+        }
+    }
+    Ok(Arc::new(CookieStoreMutex::new(store)))
+}
+
+/// Writes the jar's unexpired cookies to `path`, session cookies included, in the
+/// format `load_jar` reads.
+pub fn save_jar(jar: &CookieStoreMutex, path: &str, events: &Events) -> Result<(), TabexError> {
+    let mut records: Vec<CookieRecord> = {
+        let store = jar.lock().unwrap_or_else(|e| e.into_inner());
+        store
+            .iter_unexpired()
+            .filter_map(|cookie| {
+                let (domain, host_only) = match &cookie.domain {
+                    CookieDomain::HostOnly(host) => (host.clone(), true),
+                    CookieDomain::Suffix(suffix) => (suffix.clone(), false),
+                    CookieDomain::NotPresent | CookieDomain::Empty => return None,
+                };
+                Some(CookieRecord {
+                    name: cookie.name().to_string(),
+                    value: cookie.value().to_string(),
+                    domain,
+                    path: String::from(&cookie.path),
+                    host_only,
+                    secure: cookie.secure().unwrap_or(false),
+                    http_only: cookie.http_only().unwrap_or(false),
+                    expires: match &cookie.expires {
+                        CookieExpiration::AtUtc(at) => Some(at.unix_timestamp()),
+                        CookieExpiration::SessionEnd => None,
+                    },
+                })
+            })
+            .collect()
+    };
+    records.sort_by(|a, b| (&a.domain, &a.path, &a.name).cmp(&(&b.domain, &b.path, &b.name)));
+    let json = serde_json::to_string_pretty(&records)?;
+    fs::write(path, json + "\n")?;
+    events.status(
+        "cookies",
+        &format!("Saved {} cookie(s) to {}", records.len(), path),
+        serde_json::json!({ "path": path, "cookies": records.len() }),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_unexpired_cookies() {
+        let dir = std::env::temp_dir();
+        let (input, output) = (
+            dir.join("tabex-cookies-test-in.json"),
+            dir.join("tabex-cookies-test-out.json"),
+        );
+        let (input, output) = (input.to_string_lossy(), output.to_string_lossy());
+        let expires = chrono::Utc::now().timestamp() + 3600;
+        let records = serde_json::json!([
+            { "name": "session", "value": "abc", "domain": "example.com", "host_only": true,
+              "secure": true, "http_only": true },
+            { "name": "lang", "value": "de", "domain": ".example.com", "path": "/docs",
+              "expires": expires },
+            { "name": "old", "value": "x", "domain": "example.com", "expires": 1 },
+        ]);
+        fs::write(&*input, records.to_string()).unwrap();
+
+        let (events, reported) = crate::events::tests::collect();
+        let jar = load_jar(Some(&input), &events).unwrap();
+        save_jar(&jar, &output, &events).unwrap();
+        let saved: Vec<CookieRecord> =
+            serde_json::from_str(&fs::read_to_string(&*output).unwrap()).unwrap();
+        fs::remove_file(&*input).unwrap();
+        fs::remove_file(&*output).unwrap();
+
+        let summary: Vec<_> = saved
+            .iter()
+            .map(|c| {
+                let flags = (c.host_only, c.secure, c.http_only);
+                (
+                    c.name.as_str(),
+                    c.value.as_str(),
+                    c.domain.as_str(),
+                    c.path.as_str(),
+                    flags,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("session", "abc", "example.com", "/", (true, true, true)),
+                ("lang", "de", "example.com", "/docs", (false, false, false)),
+            ]
+        );
+        assert_eq!(saved[0].expires, None);
+        assert_eq!(saved[1].expires, Some(expires));
+        assert_eq!(reported.events(), ["status cookies"]);
+    }
+}
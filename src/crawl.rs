@@ -0,0 +1,300 @@
+//! `--crawl`: follows each page's links to more pages to extract, breadth first from
+//! the start URLs, visiting every page once.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use scraper::Html;
+
+use crate::fetch::HostFilter;
+use crate::{LINK_SELECTOR, PageMetadata, canonicalize_url, has_robots_directive};
+
+/// How far `--crawl` follows links.
+#[derive(Debug, Clone, Copy)]
+pub struct Crawl {
+    /// Links followed from a start URL to reach a page; 0 visits the start URLs only
+    pub max_depth: usize,
+    /// Only follow links to the start URLs' hosts and their subdomains
+    pub same_domain: bool,
+    /// Follow nofollow links and extract noindex pages
+    pub ignore_robots_meta: bool,
+}
+
+impl Crawl {
+    /// Whether a page the crawl found, rather than a start URL, is left out of the
+    /// results because its robots meta says noindex. Its links are still followed
+    /// unless it says nofollow too.
+    pub fn skips(&self, page: &PageMetadata, depth: usize) -> bool {
+        depth > 0
+            && !self.ignore_robots_meta
+            && page
+                .robots
+                .as_deref()
+                .is_some_and(|robots| has_robots_directive(robots, "noindex"))
+    }
+}
+
+/// Links on a page worth visiting: absolute http(s) URLs, without their fragment.
+/// Unless `ignore_robots_meta`, links marked `rel="nofollow"` and all links of a page
+/// whose robots meta says nofollow are left out.
+pub fn page_links(document: &Html, page: &PageMetadata, ignore_robots_meta: bool) -> Vec<String> {
+    if !ignore_robots_meta
+        && page
+            .robots
+            .as_deref()
+            .is_some_and(|robots| has_robots_directive(robots, "nofollow"))
+    {
+        return Vec::new();
+    }
+    let Ok(base) = reqwest::Url::parse(&page.url) else {
+        return Vec::new();
+    };
+    document
+        .select(&LINK_SELECTOR)
+        .filter(|link| {
+            ignore_robots_meta
+                || !link.value().attr("rel").is_some_and(|rel| {
+                    rel.split_whitespace()
+                        .any(|r| r.eq_ignore_ascii_case("nofollow"))
+                })
+        })
+        .filter_map(|link| {
+            let href = link.value().attr("href")?.trim();
+            if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+                return None;
+            }
+            let mut url = base.join(href).ok()?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return None;
+            }
+            url.set_fragment(None);
+            Some(url.into())
+        })
+        .collect()
+}
+
+/// Whether `host` is `domain` or one of its subdomains.
+fn within(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// The pages of a run still to visit, with how many links away from a start URL each
+/// one is. Without `--crawl` these are just the given URLs, in order.
+pub struct Frontier {
+    crawl: Option<Crawl>,
+    strip_params: Vec<String>,
+    host_filter: Arc<HostFilter>,
+    /// Hosts of the start URLs, without a leading "www."
+    domains: Vec<String>,
+    queue: VecDeque<(String, usize)>,
+    seen: HashSet<String>,
+}
+
+impl Frontier {
+    pub fn new(
+        urls: &[String],
+        crawl: Option<Crawl>,
+        strip_params: &[String],
+        host_filter: Arc<HostFilter>,
+    ) -> Frontier {
+        let domains = urls
+            .iter()
+            .filter_map(|url| {
+                reqwest::Url::parse(url)
+                    .ok()?
+                    .host_str()
+                    .map(str::to_string)
+            })
+            .map(|host| {
+                host.strip_prefix("www.")
+                    .unwrap_or(&host)
+                    .to_ascii_lowercase()
+            })
+            .collect();
+        Frontier {
+            crawl,
+            strip_params: strip_params.to_vec(),
+            host_filter,
+            domains,
+            queue: urls.iter().map(|url| (url.clone(), 0)).collect(),
+            seen: urls.iter().cloned().collect(),
+        }
+    }
+
+    /// The next page to visit and its depth.
+    pub fn pop(&mut self) -> Option<(String, usize)> {
+        self.queue.pop_front()
+    }
+
+    /// Whether links on a page at `depth` are followed.
+    pub fn follows(&self, depth: usize) -> bool {
+        self.crawl.is_some_and(|crawl| depth < crawl.max_depth)
+    }
+
+    /// Queues the links found on a page at `depth` that haven't been seen, are allowed
+    /// by `--allow-hosts`/`--deny-hosts` and, with `--same-domain`, stay on the start
+    /// URLs' domains. Links are compared after canonicalizing, so tracking parameters
+    /// don't make a page look new.
+    pub fn add_links(&mut self, links: Vec<String>, depth: usize) {
+        let Some(crawl) = self.crawl else {
+            return;
+        };
+        for link in links {
+            let Ok(url) = canonicalize_url(&link, &self.strip_params) else {
+                continue;
+            };
+            let Ok(parsed) = reqwest::Url::parse(&url) else {
+                continue;
+            };
+            if !self.host_filter.allows(&parsed) {
+                continue;
+            }
+            if crawl.same_domain {
+                let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+                if !self.domains.iter().any(|domain| within(&host, domain)) {
+                    continue;
+                }
+            }
+            if self.seen.insert(url.clone()) {
+                self.queue.push_back((url, depth + 1));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::page;
+
+    const CRAWL: Crawl = Crawl {
+        max_depth: 2,
+        same_domain: true,
+        ignore_robots_meta: false,
+    };
+
+    fn start(urls: &[&str], crawl: Option<Crawl>, deny: &str) -> Frontier {
+        let urls: Vec<String> = urls.iter().map(|url| url.to_string()).collect();
+        let strip = vec!["utm_*".to_string()];
+        let host_filter = HostFilter {
+            allow: Vec::new(),
+            deny: HostFilter::parse_list(deny),
+        };
+        Frontier::new(&urls, crawl, &strip, Arc::new(host_filter))
+    }
+
+    fn links(links: &[&str]) -> Vec<String> {
+        links.iter().map(|link| link.to_string()).collect()
+    }
+
+    fn drain(frontier: &mut Frontier) -> Vec<(String, usize)> {
+        std::iter::from_fn(|| frontier.pop()).collect()
+    }
+
+    #[test]
+    fn visits_breadth_first_and_each_page_once() {
+        let mut frontier = start(&["https://www.example.com/"], Some(CRAWL), "");
+        assert_eq!(
+            frontier.pop(),
+            Some(("https://www.example.com/".to_string(), 0))
+        );
+        frontier.add_links(
+            links(&[
+                "https://www.example.com/a?utm_source=x",
+                "https://www.example.com/a",
+                "https://www.example.com/",
+                "https://blog.example.com/b",
+            ]),
+            0,
+        );
+        frontier.add_links(links(&["https://www.example.com/c"]), 1);
+        assert_eq!(
+            drain(&mut frontier),
+            [
+                ("https://www.example.com/a".to_string(), 1),
+                ("https://blog.example.com/b".to_string(), 1),
+                ("https://www.example.com/c".to_string(), 2),
+            ]
+        );
+        assert!(frontier.follows(1));
+        assert!(!frontier.follows(2));
+    }
+
+    #[test]
+    fn keeps_to_the_start_domains_and_allowed_hosts() {
+        let mut frontier = start(&["https://example.com/"], Some(CRAWL), "ads.example.com");
+        frontier.pop();
+        frontier.add_links(
+            links(&[
+                "https://other.org/",
+                "https://notexample.com/",
+                "https://ads.example.com/x",
+                "https://shop.example.com/y",
+            ]),
+            0,
+        );
+        assert_eq!(
+            drain(&mut frontier),
+            [("https://shop.example.com/y".to_string(), 1)]
+        );
+
+        let open = Crawl {
+            same_domain: false,
+            ..CRAWL
+        };
+        let mut frontier = start(&["https://example.com/"], Some(open), "");
+        frontier.pop();
+        frontier.add_links(links(&["https://other.org/"]), 0);
+        assert_eq!(
+            drain(&mut frontier),
+            [("https://other.org/".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn follows_nothing_without_a_crawl() {
+        let mut frontier = start(
+            &["https://example.com/a", "https://example.com/b"],
+            None,
+            "",
+        );
+        frontier.add_links(links(&["https://example.com/c"]), 0);
+        assert!(!frontier.follows(0));
+        assert_eq!(
+            drain(&mut frontier),
+            [
+                ("https://example.com/a".to_string(), 0),
+                ("https://example.com/b".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_links_without_nofollow_or_fragments() {
+        let document = Html::parse_document(
+            "<a href=\"/a#top\">a</a> <a href=\"b\" rel=\"nofollow\">b</a>
+             <a href=\"#top\">top</a> <a href=\"mailto:x@example.com\">mail</a>
+             <a href=\"javascript:void(0)\">js</a> <a href=\"https://other.org/c\">c</a>",
+        );
+        let mut metadata = page("https://example.com/dir/page");
+        assert_eq!(
+            page_links(&document, &metadata, false),
+            ["https://example.com/a", "https://other.org/c"]
+        );
+        assert_eq!(
+            page_links(&document, &metadata, true),
+            [
+                "https://example.com/a",
+                "https://example.com/dir/b",
+                "https://other.org/c"
+            ]
+        );
+        metadata.robots = Some("noindex, nofollow".to_string());
+        assert!(page_links(&document, &metadata, false).is_empty());
+        assert!(CRAWL.skips(&metadata, 1));
+        assert!(!CRAWL.skips(&metadata, 0));
+    }
+}
@@ -0,0 +1,104 @@
+//! `Table::to_dataframe()`, behind the `polars` feature: a table as a typed Polars
+//! DataFrame, for pipelines that go from extraction to analysis without writing the
+//! table out first.
+
+use polars::prelude::*;
+
+use crate::Table;
+use crate::output::record_keys;
+use crate::sql::{ColumnType, infer_type};
+use crate::types::DeclaredType;
+
+impl Table {
+    /// The table as a DataFrame with one column per header, named like the keys of
+    /// JSON records and typed as the SQL sink types it: Int64, Float64, Decimal for
+    /// `--types` decimals and money, else String. Empty cells and cells that don't
+    /// fit the column's type are null.
+    pub fn to_dataframe(&self) -> PolarsResult<DataFrame> {
+        let width = self
+            .data
+            .rows
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(self.data.headers.len());
+        let conventions = self.metadata.sign_conventions;
+        let columns = record_keys(&self.data.headers, width)
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let name = PlSmallStr::from(name);
+                let cells = self.data.rows.iter().map(|row| {
+                    row.get(index)
+                        .map(|cell| cell.trim())
+                        .filter(|cell| !cell.is_empty())
+                });
+                let column = match infer_type(self, index) {
+                    ColumnType::Integer => {
+                        let values: Vec<Option<i64>> = cells
+                            .map(|cell| Some(DeclaredType::Int.number(cell?, conventions)? as i64))
+                            .collect();
+                        Column::new(name, values)
+                    }
+                    ColumnType::Real => {
+                        let values: Vec<Option<f64>> = cells
+                            .map(|cell| DeclaredType::Float.number(cell?, conventions))
+                            .collect();
+                        Column::new(name, values)
+                    }
+                    ColumnType::Decimal(precision, scale) => {
+                        let declared = DeclaredType::Decimal { precision, scale };
+                        // Decimals come back rescaled to `scale`, so the mantissa is
+                        // the value Polars stores
+                        let values = cells
+                            .map(|cell| Some(declared.decimal(cell?, conventions)?.mantissa()));
+                        Int128Chunked::from_iter_options(name, values)
+                            .into_decimal(precision as usize, scale as usize)?
+                            .into_series()
+                            .into()
+                    }
+                    ColumnType::Text => Column::new(name, cells.collect::<Vec<_>>()),
+                };
+                Ok(column)
+            })
+            .collect::<PolarsResult<Vec<Column>>>()?;
+        DataFrame::new(self.data.rows.len(), columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    #[test]
+    fn types_columns_as_the_sql_sink_does() {
+        let table = extract(
+            "<table><tr><th>Team</th><th>Played</th><th>Rate</th><th>Budget</th><th></th></tr>
+             <tr><td>Ann</td><td>3</td><td>0.5</td><td>$1,200.50</td><td>x</td></tr>
+             <tr><td>Bob</td><td></td><td>1</td><td>$80</td><td></td></tr></table>",
+            ExtractOptions::default(),
+        )
+        .remove(0);
+        let frame = table.to_dataframe().unwrap();
+        let names: Vec<&str> = frame
+            .get_column_names()
+            .into_iter()
+            .map(PlSmallStr::as_str)
+            .collect();
+        assert_eq!(names, ["team", "played", "rate", "budget", "column_5"]);
+        let dtypes: Vec<DataType> = frame.dtypes();
+        assert_eq!(dtypes[0], DataType::String);
+        assert_eq!(dtypes[1], DataType::Int64);
+        assert_eq!(dtypes[2], DataType::Float64);
+        assert!(
+            matches!(dtypes[3], DataType::Decimal(_, 2)),
+            "{:?}",
+            dtypes[3]
+        );
+        assert_eq!(frame.column("played").unwrap().null_count(), 1);
+        assert_eq!(frame.column("column_5").unwrap().null_count(), 1);
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use crate::error::TabexError;
+use crate::events::Events;
+use crate::manifest;
+use crate::output::write_table_csv;
+use crate::{ExtractionResult, Table};
+
+fn sanitize(part: &str) -> String {
+    part.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Directory for a page's tables inside the data repo, built from the host and
+/// path, e.g. `example.com/stats/2024` for https://example.com/stats/2024.
+fn page_path(url: &str) -> PathBuf {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return PathBuf::from(sanitize(url));
+    };
+    let mut path = PathBuf::from(sanitize(parsed.host_str().unwrap_or("local")));
+    let mut segments: Vec<String> = parsed
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty() && *segment != "." && *segment != "..")
+        .map(sanitize)
+        .collect();
+    if segments.is_empty() {
+        segments.push("index".to_string());
+    }
+    // Pages that differ only by query string get their own directory
+    if let Some(query) = parsed.query().filter(|query| !query.is_empty())
+        && let Some(last) = segments.last_mut()
+    {
+        last.push('_');
+        last.push_str(&sanitize(query));
+    }
+    path.extend(segments);
+    path
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<Output, TabexError> {
+    Ok(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()?)
+}
+
+fn git_checked(repo: &Path, args: &[&str]) -> Result<(), TabexError> {
+    let output = git(repo, args)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(TabexError::Git(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// File name for a table: its templated name, else `table_<position>`. A name an
+/// earlier table of the page took gets `_<position>`, numbered further if that is taken
+/// too, so no table overwrites another; names are compared ignoring case, as some
+/// filesystems do.
+fn table_file_name(table: &Table, taken: &[String]) -> String {
+    let is_taken = |name: &str| taken.iter().any(|other| other.eq_ignore_ascii_case(name));
+    let base = match &table.metadata.name {
+        Some(name) => sanitize(name),
+        None => format!("table_{}", table.metadata.position),
+    };
+    let mut name = format!("{}.csv", base);
+    if !is_taken(&name) {
+        return name;
+    }
+    let base = format!("{}_{}", base, table.metadata.position);
+    name = format!("{}.csv", base);
+    let mut n = 2;
+    while is_taken(&name) {
+        name = format!("{}_{}.csv", base, n);
+        n += 1;
+    }
+    name
+}
+
+/// Writes each table as canonical CSV under the page's directory in `repo`, named by
+/// `table_file_name`, then commits the directory if anything changed. Returns whether
+/// a commit was made.
+pub fn commit_tables(
+    repo: &Path,
+    result: &ExtractionResult,
+    extracted_at: &str,
+    events: &Events,
+) -> Result<bool, TabexError> {
+    git_checked(repo, &["rev-parse", "--is-inside-work-tree"])?;
+
+    let relative = page_path(&result.page.url);
+    let dir = repo.join(&relative);
+
+    // Tables no longer on the page, or renamed, are removed along with the rest
+    if dir.is_dir() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            if name.ends_with(".csv") {
+                fs::remove_file(&path)?;
+            }
+        }
+    }
+    fs::create_dir_all(&dir)?;
+    let mut names: Vec<String> = Vec::new();
+    for table in &result.tables {
+        let name = table_file_name(table, &names);
+        let path = dir.join(&name);
+        names.push(name);
+        let path = path.to_string_lossy();
+        manifest::write_recorded("csv", Some(&path), [table], events, |out| {
+            write_table_csv(table, true, out)
+        })?;
+    }
+
+    let relative = relative.to_string_lossy();
+    git_checked(repo, &["add", "-A", "--", &relative])?;
+
+    // Exit status 1 means the staged tree differs from HEAD
+    let diff = git(repo, &["diff", "--cached", "--quiet", "--", &relative])?;
+    if diff.status.success() {
+        return Ok(false);
+    }
+
+    let message = format!(
+        "Update tables from {}\n\nSource: {}\nExtracted at: {}\n",
+        result.page.url, result.page.url, extracted_at
+    );
+    git_checked(repo, &["commit", "-q", "-m", &message, "--", &relative])?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    #[test]
+    fn builds_page_directories_from_the_url() {
+        let path = |url: &str| page_path(url).to_string_lossy().replace('\\', "/");
+        assert_eq!(
+            path("https://example.com/stats/2024"),
+            "example.com/stats/2024"
+        );
+        assert_eq!(path("https://example.com/"), "example.com/index");
+        assert_eq!(
+            path("https://example.com/?page=2"),
+            "example.com/index_page_2"
+        );
+        assert_eq!(
+            path("https://example.com/stats/?year=2024&team=a b"),
+            "example.com/stats_year_2024_team_a_20b"
+        );
+        // Dot segments never leave the page's directory
+        assert_eq!(path("https://example.com/a/../../b"), "example.com/b");
+        assert_eq!(path("https://example.com/%2E%2E/%2e%2e/x"), "example.com/x");
+        assert_eq!(path("https://example.com/a/./b/"), "example.com/a/b");
+    }
+
+    #[test]
+    fn gives_tables_with_the_same_name_their_own_file() {
+        let mut tables = extract(
+            "<table><tr><th>A</th></tr><tr><td>1</td></tr></table>
+             <table><tr><th>B</th></tr><tr><td>2</td></tr></table>
+             <table><tr><th>C</th></tr><tr><td>3</td></tr></table>
+             <table><tr><th>D</th></tr><tr><td>4</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        let named = ["Scores", "scores", "Scores/2024", "table_1"];
+        for (table, name) in tables.iter_mut().zip(named) {
+            table.metadata.name = Some(name.to_string());
+        }
+        let mut names: Vec<String> = Vec::new();
+        for table in &tables {
+            let name = table_file_name(table, &names);
+            names.push(name);
+        }
+        assert_eq!(
+            names,
+            [
+                "Scores.csv",
+                "scores_2.csv",
+                "Scores_2024.csv",
+                "table_1.csv"
+            ]
+        );
+        tables[3].metadata.name = None;
+        assert_eq!(
+            table_file_name(&tables[3], &["table_4.csv".to_string()]),
+            "table_4_4.csv"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+use crate::lineage;
+use crate::plugin::Transform;
+use crate::transform::{OriginalValue, keep_originals};
+use crate::{PageMetadata, Table};
+
+/// Date and time layouts read as local times, tried in order. Dates come first in
+/// every layout because a time without a date can't be placed in a zone.
+const LOCAL_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%d %B %Y %H:%M",
+    "%d %b %Y %H:%M",
+    "%B %d, %Y %H:%M",
+    "%b %d, %Y %H:%M",
+    "%d %B %Y %I:%M %p",
+    "%d %b %Y %I:%M %p",
+    "%B %d, %Y %I:%M %p",
+    "%b %d, %Y %I:%M %p",
+];
+
+/// Layouts that carry their own offset, so the zone doesn't apply.
+const OFFSET_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f%:z", "%Y-%m-%d %H:%M:%S%.f%z"];
+
+/// A cell's date and time, with the offset it was written with or none.
+enum Parsed {
+    Offset(DateTime<FixedOffset>),
+    Local(NaiveDateTime),
+}
+
+fn parse_datetime(cell: &str) -> Option<Parsed> {
+    let cell = cell.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(cell) {
+        return Some(Parsed::Offset(datetime));
+    }
+    if let Some(datetime) = OFFSET_FORMATS
+        .iter()
+        .find_map(|format| DateTime::parse_from_str(cell, format).ok())
+    {
+        return Some(Parsed::Offset(datetime));
+    }
+    // "UTC" and "GMT" suffixes name the zone outright
+    let (local, utc) = match cell
+        .strip_suffix(" UTC")
+        .or_else(|| cell.strip_suffix(" GMT"))
+    {
+        Some(local) => (local, true),
+        None => (cell, false),
+    };
+    let datetime = LOCAL_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(local, format).ok())?;
+    Some(if utc {
+        Parsed::Offset(datetime.and_utc().fixed_offset())
+    } else {
+        Parsed::Local(datetime)
+    })
+}
+
+/// The cell as RFC 3339. Local times are read in `timezone`, taking the earlier
+/// instant when a clock change repeats the hour; a time the change skips, or a cell
+/// that isn't a date and time, gives None.
+pub fn normalize_datetime(cell: &str, timezone: Tz) -> Option<String> {
+    let datetime = match parse_datetime(cell)? {
+        Parsed::Offset(datetime) => datetime,
+        Parsed::Local(datetime) => timezone
+            .from_local_datetime(&datetime)
+            .earliest()?
+            .fixed_offset(),
+    };
+    Some(datetime.to_rfc3339())
+}
+
+/// `--timezone`: rewrites date-time cells as RFC 3339 with the offset they had on the
+/// page, or else the zone's, in columns where at least half the cells are date-times.
+/// The page's text is kept in `metadata.original_values`.
+pub struct NormalizeDatetimes {
+    pub timezone: Tz,
+}
+
+impl Transform for NormalizeDatetimes {
+    fn name(&self) -> &str {
+        "normalize-datetimes"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        for table in tables {
+            let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+            let mut originals = Vec::new();
+            for column in 0..width {
+                let cells = || {
+                    table
+                        .data
+                        .rows
+                        .iter()
+                        .filter_map(move |row| row.get(column))
+                        .map(|cell| cell.trim())
+                        .filter(|cell| !cell.is_empty())
+                };
+                let total = cells().count();
+                let datetimes = cells()
+                    .filter(|cell| parse_datetime(cell).is_some())
+                    .count();
+                if total == 0 || datetimes * 2 < total {
+                    continue;
+                }
+
+                let mut changed = false;
+                for (r, row) in table.data.rows.iter_mut().enumerate() {
+                    let Some(cell) = row.get_mut(column) else {
+                        continue;
+                    };
+                    if let Some(normalized) = normalize_datetime(cell, self.timezone)
+                        && normalized != *cell
+                    {
+                        originals.push(OriginalValue {
+                            row: r + 1,
+                            column,
+                            original: std::mem::replace(cell, normalized),
+                        });
+                        changed = true;
+                    }
+                }
+                if changed {
+                    lineage::changed(table, column, "normalize-datetimes");
+                }
+            }
+            keep_originals(table, originals);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_local_times_in_the_zone() {
+        assert_eq!(
+            normalize_datetime("2024-06-01 12:00", Tz::Europe__Paris).as_deref(),
+            Some("2024-06-01T12:00:00+02:00")
+        );
+        assert_eq!(
+            normalize_datetime("1 June 2024 3:30 PM", Tz::America__New_York).as_deref(),
+            Some("2024-06-01T15:30:00-04:00")
+        );
+    }
+
+    #[test]
+    fn keeps_offsets_written_on_the_page() {
+        assert_eq!(
+            normalize_datetime("2024-06-01T12:00:00+05:30", Tz::Europe__Paris).as_deref(),
+            Some("2024-06-01T12:00:00+05:30")
+        );
+        assert_eq!(
+            normalize_datetime("2024-06-01 12:00 UTC", Tz::Europe__Paris).as_deref(),
+            Some("2024-06-01T12:00:00+00:00")
+        );
+    }
+
+    #[test]
+    fn handles_clock_changes() {
+        // The hour repeated when clocks go back reads as its first occurrence
+        assert_eq!(
+            normalize_datetime("2024-11-03 01:30", Tz::America__New_York).as_deref(),
+            Some("2024-11-03T01:30:00-04:00")
+        );
+        // The hour skipped when clocks go forward never happened
+        assert_eq!(
+            normalize_datetime("2024-03-10 02:30", Tz::America__New_York),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_cells_that_are_not_date_times() {
+        for cell in ["", "2024-06-01", "12:00", "2024-13-01 00:00", "soon"] {
+            assert_eq!(normalize_datetime(cell, Tz::UTC), None, "{:?}", cell);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::lineage;
+use crate::plugin::Transform;
+use crate::profile::parse_range;
+use crate::transform::{OriginalValue, keep_originals, mostly};
+use crate::types::format_number;
+use crate::units::with_unit;
+use crate::{PageMetadata, Table};
+
+/// "2:15:30", "1:02:03.4" and lap times such as "1:23.45". A plain "9:30" is left
+/// alone, as it is more often a time of day.
+static CLOCK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:(\d+):([0-5]\d):([0-5]\d(?:\.\d+)?)|(\d+):([0-5]\d\.\d+))$").unwrap()
+});
+
+/// ISO 8601 durations: "PT1H23M", "P1DT2H", "PT45.5S".
+static ISO_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^P(?:(\d+)D)?(?:T(?:(\d+(?:\.\d+)?)H)?(?:(\d+(?:\.\d+)?)M)?(?:(\d+(?:\.\d+)?)S)?)?$",
+    )
+    .unwrap()
+});
+
+/// One amount and unit of a written duration such as "1h 23m" or "2 hrs 5 mins".
+static PART_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)(\d+(?:\.\d+)?)\s*(ms|d|days?|h|hrs?|hours?|m|mins?|minutes?|s|secs?|seconds?)\b,?",
+    )
+    .unwrap()
+});
+
+fn unit_seconds(unit: &str) -> f64 {
+    match unit.to_ascii_lowercase().as_str() {
+        "ms" => 0.001,
+        "d" | "day" | "days" => 86400.0,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600.0,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60.0,
+        _ => 1.0,
+    }
+}
+
+/// The length of a written duration in seconds, or None for anything else.
+/// A lone "100m" is taken for metres; "m" is read as minutes only next to hours or
+/// seconds, as in "1h 23m".
+pub fn parse_duration(cell: &str) -> Option<f64> {
+    let cell = cell.trim();
+    if let Some(captures) = CLOCK_RE.captures(cell) {
+        let number = |i: usize| {
+            captures
+                .get(i)
+                .map_or(Some(0.0), |m| m.as_str().parse().ok())
+        };
+        return Some(match captures.get(1) {
+            Some(_) => number(1)? * 3600.0 + number(2)? * 60.0 + number(3)?,
+            None => number(4)? * 60.0 + number(5)?,
+        });
+    }
+    if let Some(captures) = ISO_RE.captures(cell)
+        && cell != "P"
+        && !cell.ends_with('T')
+    {
+        let number = |i: usize| {
+            captures
+                .get(i)
+                .map_or(Some(0.0), |m| m.as_str().parse().ok())
+        };
+        return Some(number(1)? * 86400.0 + number(2)? * 3600.0 + number(3)? * 60.0 + number(4)?);
+    }
+
+    let parts: Vec<(f64, &str)> = PART_RE
+        .captures_iter(cell)
+        .map(|captures| Some((captures[1].parse().ok()?, captures.get(2)?.as_str())))
+        .collect::<Option<_>>()?;
+    // Every character belongs to a part, apart from spaces and an "and"
+    let rest = PART_RE.replace_all(cell, "");
+    if parts.is_empty() || rest.split_whitespace().any(|word| word != "and") {
+        return None;
+    }
+    if parts.len() == 1 && parts[0].1 == "m" {
+        return None;
+    }
+    Some(
+        parts
+            .iter()
+            .map(|(amount, unit)| amount * unit_seconds(unit))
+            .sum(),
+    )
+}
+
+/// `--durations`: rewrites duration columns such as "1h 23m" or "02:15:30" as seconds,
+/// with the header's unit set to "s", and appends `start` and `end` columns after
+/// time-range columns such as "09:00–17:30". Rewritten cells keep their page text in
+/// `metadata.original_values`.
+pub struct ParseDurations;
+
+impl Transform for ParseDurations {
+    fn name(&self) -> &str {
+        "durations"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        for table in tables {
+            let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+            let mut originals = Vec::new();
+            let mut ranges = Vec::new();
+            for column in 0..width {
+                if mostly(table, column, |cell| parse_range(cell).is_some()) {
+                    ranges.push(column);
+                    continue;
+                }
+                if !mostly(table, column, |cell| parse_duration(cell).is_some()) {
+                    continue;
+                }
+                for (r, row) in table.data.rows.iter_mut().enumerate() {
+                    let Some(cell) = row.get_mut(column) else {
+                        continue;
+                    };
+                    if let Some(seconds) = parse_duration(cell) {
+                        originals.push(OriginalValue {
+                            row: r + 1,
+                            column,
+                            original: std::mem::replace(cell, format_number(seconds)),
+                        });
+                    }
+                }
+                if let Some(header) = table.data.headers.get_mut(column) {
+                    *header = with_unit(header, "s");
+                }
+                lineage::changed(table, column, "durations");
+            }
+            keep_originals(table, originals);
+
+            // Range columns are appended as start/end pairs, in column order
+            let columns = table.data.headers.len().max(width);
+            for &column in &ranges {
+                if let Some(header) = table.data.headers.get(column).cloned() {
+                    for suffix in ["start", "end"] {
+                        let name = format!("{} {}", header, suffix);
+                        lineage::push_derived(table, &name, &[column], "durations");
+                        table.data.headers.push(name);
+                    }
+                }
+            }
+            if ranges.is_empty() {
+                continue;
+            }
+            for row in &mut table.data.rows {
+                let range_cells: Vec<String> = ranges
+                    .iter()
+                    .flat_map(|&column| {
+                        let (start, end) = row
+                            .get(column)
+                            .and_then(|cell| parse_range(cell))
+                            .map_or((String::new(), String::new()), |(start, end)| {
+                                (
+                                    start.format("%H:%M:%S").to_string(),
+                                    end.format("%H:%M:%S").to_string(),
+                                )
+                            });
+                        [start, end]
+                    })
+                    .collect();
+                row.resize(columns, String::new());
+                row.extend(range_cells);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_clock_and_lap_times() {
+        assert_eq!(parse_duration("2:15:30"), Some(8130.0));
+        assert_eq!(parse_duration("1:02:03.5"), Some(3723.5));
+        assert_eq!(parse_duration("1:23.25"), Some(83.25));
+    }
+
+    #[test]
+    fn parses_iso_8601_durations() {
+        assert_eq!(parse_duration("PT1H23M"), Some(4980.0));
+        assert_eq!(parse_duration("P1DT2H"), Some(93600.0));
+        assert_eq!(parse_duration("PT45.5S"), Some(45.5));
+    }
+
+    #[test]
+    fn parses_written_durations() {
+        assert_eq!(parse_duration("1h 23m"), Some(4980.0));
+        assert_eq!(parse_duration("2 hrs 5 mins"), Some(7500.0));
+        assert_eq!(parse_duration("1 hour and 30 minutes"), Some(5400.0));
+        assert_eq!(parse_duration("3 days, 4h"), Some(273600.0));
+        assert_eq!(parse_duration("250ms"), Some(0.25));
+    }
+
+    #[test]
+    fn rejects_times_of_day_distances_and_malformed_text() {
+        for cell in [
+            "", "9:30", "1:60:00", "P", "PT", "P1H", "100m", "1h fast", "fast", "h 23m",
+        ] {
+            assert_eq!(parse_duration(cell), None, "{:?}", cell);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::{error::Error, fmt, io};
+
+/// Failure categories of a run. `exit_code` maps each to the CLI's exit status.
+#[derive(Debug)]
+pub enum TabexError {
+    /// A command-line option or combination of options that can't be used
+    Usage(String),
+    /// Malformed input such as a URL, expression or --replace rule
+    Parse(String),
+    /// The server answered with a non-success status
+    Fetch {
+        status: u16,
+        url: String,
+    },
+    /// The response was not a content type we extract from
+    ContentType {
+        content_type: String,
+        url: String,
+    },
+    /// The request failed before a response arrived
+    Http(reqwest::Error),
+    /// --allow-hosts or --deny-hosts rule out the host of a page or redirect
+    HostNotAllowed {
+        url: String,
+    },
+    /// The --doh-url resolver had no usable answer for a host
+    Dns {
+        host: String,
+        message: String,
+    },
+    /// --wayback found no archived captures of the page, or none could be loaded
+    NoSnapshots {
+        url: String,
+    },
+    /// --sitemap listed no pages, or none that --sitemap-filter matches and that parse as URLs
+    EmptySitemap {
+        url: String,
+    },
+    /// A --file input could not be read
+    File {
+        path: String,
+        source: io::Error,
+    },
+    /// The deadline passed before a page could be fetched
+    Timeout {
+        url: String,
+    },
+    /// A request took longer than --timeout or couldn't connect within
+    /// --connect-timeout
+    RequestTimeout {
+        url: String,
+    },
+    Io(io::Error),
+    Json(serde_json::Error),
+    Xlsx(rust_xlsxwriter::XlsxError),
+    /// A git command run by `tabex commit` failed
+    Git(String),
+    /// A CSS selector given in an option, such as a --route `selector:` rule, doesn't parse
+    Selector {
+        selector: String,
+        message: String,
+    },
+}
+
+impl TabexError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TabexError::Usage(_) | TabexError::Parse(_) => 2,
+            TabexError::Fetch { .. }
+            | TabexError::ContentType { .. }
+            | TabexError::Http(_)
+            | TabexError::Dns { .. }
+            | TabexError::HostNotAllowed { .. }
+            | TabexError::NoSnapshots { .. }
+            | TabexError::EmptySitemap { .. }
+            | TabexError::RequestTimeout { .. }
+            | TabexError::File { .. } => 3,
+            TabexError::Timeout { .. } => 4,
+            TabexError::Io(_) | TabexError::Json(_) | TabexError::Xlsx(_) | TabexError::Git(_) => 5,
+            TabexError::Selector { .. } => 6,
+        }
+    }
+}
+
+impl fmt::Display for TabexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TabexError::Usage(message) | TabexError::Parse(message) => write!(f, "{}", message),
+            TabexError::Fetch { status, url } => {
+                write!(f, "Failed to fetch {}: HTTP {}", url, status)
+            }
+            TabexError::ContentType { content_type, url } => write!(
+                f,
+                "Skipping {}: content type {} is not accepted (see --accept-content-type)",
+                url, content_type
+            ),
+            TabexError::Http(e) => write!(f, "Request failed: {}", e),
+            TabexError::HostNotAllowed { url } => write!(
+                f,
+                "Refusing to fetch {}: host is not allowed (see --allow-hosts and --deny-hosts)",
+                url
+            ),
+            TabexError::Dns { host, message } => {
+                write!(f, "Could not resolve {}: {}", host, message)
+            }
+            TabexError::NoSnapshots { url } => write!(
+                f,
+                "No archived snapshots of {} could be loaded in the requested range",
+                url
+            ),
+            TabexError::EmptySitemap { url } => write!(
+                f,
+                "Sitemap {} lists no pages to extract (see --sitemap-filter)",
+                url
+            ),
+            TabexError::File { path, source } => write!(f, "Could not read {}: {}", path, source),
+            TabexError::Timeout { url } => write!(f, "Deadline reached while fetching {}", url),
+            TabexError::RequestTimeout { url } => write!(
+                f,
+                "Request to {} timed out (see --timeout and --connect-timeout)",
+                url
+            ),
+            TabexError::Io(e) => write!(f, "I/O error: {}", e),
+            TabexError::Json(e) => write!(f, "JSON error: {}", e),
+            TabexError::Xlsx(e) => write!(f, "Excel error: {}", e),
+            TabexError::Git(message) => write!(f, "{}", message),
+            TabexError::Selector { selector, message } => {
+                write!(f, "Invalid selector '{}': {}", selector, message)
+            }
+        }
+    }
+}
+
+impl Error for TabexError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TabexError::Http(e) => Some(e),
+            TabexError::Io(e) | TabexError::File { source: e, .. } => Some(e),
+            TabexError::Json(e) => Some(e),
+            TabexError::Xlsx(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for TabexError {
+    fn from(e: reqwest::Error) -> TabexError {
+        // A failed --doh-url lookup reaches us inside the connect error
+        let mut source = e.source();
+        while let Some(error) = source {
+            if let Some(TabexError::Dns { host, message }) = error.downcast_ref::<TabexError>() {
+                return TabexError::Dns {
+                    host: host.clone(),
+                    message: message.clone(),
+                };
+            }
+            source = error.source();
+        }
+        TabexError::Http(e)
+    }
+}
+
+impl From<io::Error> for TabexError {
+    fn from(e: io::Error) -> TabexError {
+        TabexError::Io(e)
+    }
+}
+
+// CSV writes only fail on the underlying writer
+impl From<csv::Error> for TabexError {
+    fn from(e: csv::Error) -> TabexError {
+        TabexError::Io(e.into())
+    }
+}
+
+impl From<serde_json::Error> for TabexError {
+    fn from(e: serde_json::Error) -> TabexError {
+        TabexError::Json(e)
+    }
+}
+
+impl From<rust_xlsxwriter::XlsxError> for TabexError {
+    fn from(e: rust_xlsxwriter::XlsxError) -> TabexError {
+        TabexError::Xlsx(e)
+    }
+}
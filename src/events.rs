@@ -0,0 +1,211 @@
+//! Run status for people or, with `--json-logs`, for orchestrators.
+//!
+//! Library code reports through an [`Events`] handle, which is silent unless the
+//! caller supplies a [`Reporter`]. The `tabex` binary reports to a [`Console`], and
+//! with `--manifest` to a `manifest::Recorder` in front of it.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+
+use crate::robots::RobotsRules;
+
+/// Something a manifest of the run lists, besides its warnings.
+pub enum Record<'a> {
+    /// A request about to be sent to `url`
+    Request { url: &'a str },
+    /// robots.txt rules read for the host of `url`, counting the request for them
+    Robots {
+        url: &'a str,
+        rules: &'a RobotsRules,
+    },
+    /// A page fetched although its host's robots.txt disallows it
+    Disallowed { url: &'a str },
+    /// A page loaded, or tried; `final_url` is None when loading failed
+    Input {
+        url: &'a str,
+        final_url: Option<&'a str>,
+    },
+    /// A file written, or `-` for stdout, with its size and checksum
+    Output {
+        path: &'a str,
+        format: &'a str,
+        tables: usize,
+        rows: usize,
+        bytes: u64,
+        sha256: String,
+    },
+}
+
+/// Receives what a run reports as it goes.
+pub trait Reporter: Send + Sync {
+    /// Progress or outcome of the run, e.g. a fetch or a written file.
+    fn status(&self, event: &str, message: &str, fields: Value);
+
+    fn warning(&self, event: &str, message: &str, fields: Value);
+
+    /// Requests, inputs and outputs, for reporters that keep a manifest. Ignored by
+    /// default.
+    fn record(&self, _record: Record) {}
+}
+
+/// Where library code sends status, warnings and records. The default handle
+/// reports nothing.
+#[derive(Clone, Default)]
+pub struct Events {
+    reporter: Option<Arc<dyn Reporter>>,
+}
+
+impl Events {
+    pub fn new(reporter: Arc<dyn Reporter>) -> Events {
+        Events {
+            reporter: Some(reporter),
+        }
+    }
+
+    pub fn status(&self, event: &str, message: &str, fields: Value) {
+        if let Some(reporter) = &self.reporter {
+            reporter.status(event, message, fields);
+        }
+    }
+
+    pub fn warning(&self, event: &str, message: &str, fields: Value) {
+        if let Some(reporter) = &self.reporter {
+            reporter.warning(event, message, fields);
+        }
+    }
+
+    pub fn record(&self, record: Record) {
+        if let Some(reporter) = &self.reporter {
+            reporter.record(record);
+        }
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Events")
+            .field("reporter", &self.reporter.is_some())
+            .finish()
+    }
+}
+
+/// Reports to the terminal. Human messages go to stdout, unless it carries table
+/// data, and warnings to stderr. In JSON mode every status, warning and error is one
+/// NDJSON object on stderr instead, so stdout carries only table data.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Console {
+    pub json_logs: bool,
+    /// Stdout carries table data, so human status messages go to stderr too
+    pub status_to_stderr: bool,
+}
+
+impl Console {
+    /// Writes `{"time", "level", "event", "message", ...fields}` as one line on stderr.
+    fn emit(&self, level: &str, event: &str, message: &str, fields: Value) {
+        let mut object = Map::new();
+        object.insert(
+            "time".to_string(),
+            Value::String(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+        );
+        object.insert("level".to_string(), Value::String(level.to_string()));
+        object.insert("event".to_string(), Value::String(event.to_string()));
+        object.insert("message".to_string(), Value::String(message.to_string()));
+        if let Value::Object(fields) = fields {
+            object.extend(fields);
+        }
+        // A closed stderr leaves nowhere to report to
+        let _ = writeln!(std::io::stderr().lock(), "{}", Value::Object(object));
+    }
+
+    /// An empty line between human status messages, e.g. before the run's summary.
+    pub fn blank_line(&self) {
+        if self.json_logs {
+            return;
+        }
+        if self.status_to_stderr {
+            eprintln!();
+        } else {
+            println!();
+        }
+    }
+
+    /// The error ending the run, with the exit code the process is about to return.
+    pub fn error(&self, message: &str, exit_code: i32) {
+        if self.json_logs {
+            self.emit(
+                "error",
+                "error",
+                message,
+                serde_json::json!({ "exit_code": exit_code }),
+            );
+        } else {
+            eprintln!("Error: {}", message);
+        }
+    }
+}
+
+impl Reporter for Console {
+    fn status(&self, event: &str, message: &str, fields: Value) {
+        if self.json_logs {
+            self.emit("info", event, message, fields);
+        } else if self.status_to_stderr {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+
+    fn warning(&self, event: &str, message: &str, fields: Value) {
+        if self.json_logs {
+            self.emit("warning", event, message, fields);
+        } else {
+            eprintln!("Warning: {}", message);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Keeps the level and event name of each report.
+    #[derive(Default)]
+    pub(crate) struct Reported(Mutex<Vec<String>>);
+
+    impl Reported {
+        pub(crate) fn events(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl Reporter for Reported {
+        fn status(&self, event: &str, _message: &str, _fields: Value) {
+            self.0.lock().unwrap().push(format!("status {}", event));
+        }
+
+        fn warning(&self, event: &str, _message: &str, _fields: Value) {
+            self.0.lock().unwrap().push(format!("warning {}", event));
+        }
+    }
+
+    /// A handle reporting to a fresh `Reported`.
+    pub(crate) fn collect() -> (Events, Arc<Reported>) {
+        let reported = Arc::new(Reported::default());
+        (Events::new(reported.clone()), reported)
+    }
+
+    #[test]
+    fn reports_only_to_a_supplied_reporter() {
+        // Nowhere to send it, so this mustn't print or panic
+        Events::default().warning("test", "unheard", Value::Null);
+        let (events, reported) = collect();
+        events.status("fetch", "Fetching", Value::Null);
+        events.warning("retry", "Retrying", Value::Null);
+        assert_eq!(reported.events(), ["status fetch", "warning retry"]);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::error::TabexError;
+use crate::types::Value;
+
+/// A parsed `--add-column` expression.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Literal(Value),
+    Column(String),
+    Call(String, Vec<Expr>),
+    Negate(Box<Expr>),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+/// Values that are fixed for the whole run rather than per row.
+#[derive(Debug, Clone)]
+pub struct EvalContext {
+    pub now: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Column(String),
+    Op(char),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_digit() || c == '.' {
+                        number.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let value = number
+                    .parse()
+                    .map_err(|_| format!("Invalid number '{}' in expression", number))?;
+                tokens.push(Token::Number(value));
+            }
+            '\'' | '"' | '[' | '`' => {
+                chars.next();
+                let close = if c == '[' { ']' } else { c };
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(c) if c == close => break,
+                        Some(c) => text.push(c),
+                        None => return Err(format!("Unterminated {} in expression", c)),
+                    }
+                }
+                tokens.push(match c {
+                    '[' | '`' => Token::Column(text),
+                    _ => Token::Text(text),
+                });
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            '+' | '-' | '*' | '/' | '(' | ')' | ',' => {
+                chars.next();
+                tokens.push(Token::Op(c));
+            }
+            _ => return Err(format!("Unexpected character '{}' in expression", c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            left = Expr::Binary(Box::new(left), op, Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.peek().cloned() {
+            self.pos += 1;
+            left = Expr::Binary(Box::new(left), op, Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(Value::Number(n))),
+            Some(Token::Text(text)) => Ok(Expr::Literal(Value::Text(text))),
+            Some(Token::Column(name)) => Ok(Expr::Column(name)),
+            Some(Token::Ident(name)) => {
+                if !self.eat('(') {
+                    return Ok(Expr::Column(name));
+                }
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return Err(format!("Expected ',' or ')' in call to {}", name));
+                        }
+                    }
+                }
+                check_call(&name, args.len())?;
+                Ok(Expr::Call(name.to_ascii_lowercase(), args))
+            }
+            Some(Token::Op('(')) => {
+                let inner = self.expr()?;
+                if !self.eat(')') {
+                    return Err("Expected ')' in expression".to_string());
+                }
+                Ok(inner)
+            }
+            Some(token) => Err(format!("Unexpected {:?} in expression", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn check_call(name: &str, args: usize) -> Result<(), String> {
+    let ok = match name.to_ascii_lowercase().as_str() {
+        "now" => args == 0,
+        "round" => args == 1 || args == 2,
+        "concat" | "coalesce" => args >= 1,
+        _ => return Err(format!("Unknown function {}()", name)),
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(format!("Wrong number of arguments to {}()", name))
+    }
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Expr, TabexError> {
+        let mut parser = Parser {
+            tokens: tokenize(input).map_err(TabexError::Parse)?,
+            pos: 0,
+        };
+        let expr = parser.expr().map_err(TabexError::Parse)?;
+        if parser.pos < parser.tokens.len() {
+            return Err(TabexError::Parse(format!(
+                "Unexpected trailing input in expression: {}",
+                input
+            )));
+        }
+        Ok(expr)
+    }
+
+    /// Names of all columns the expression reads.
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Expr::Literal(_) => Vec::new(),
+            Expr::Column(name) => vec![name.as_str()],
+            Expr::Call(_, args) => args.iter().flat_map(Expr::columns).collect(),
+            Expr::Negate(inner) => inner.columns(),
+            Expr::Binary(left, _, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+        }
+    }
+
+    /// Evaluates against one row; `lookup` resolves column names to cell values.
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Value, context: &EvalContext) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Column(name) => lookup(name),
+            Expr::Negate(inner) => match inner.eval(lookup, context) {
+                Value::Number(n) => Value::Number(-n),
+                _ => Value::Null,
+            },
+            Expr::Binary(left, op, right) => {
+                let left = left.eval(lookup, context);
+                let right = right.eval(lookup, context);
+                match (left, *op, right) {
+                    (Value::Number(a), '+', Value::Number(b)) => Value::Number(a + b),
+                    (Value::Number(a), '-', Value::Number(b)) => Value::Number(a - b),
+                    (Value::Number(a), '*', Value::Number(b)) => Value::Number(a * b),
+                    (Value::Number(a), '/', Value::Number(b)) if b != 0.0 => Value::Number(a / b),
+                    // `+` joins text so source tags can be built from columns
+                    (Value::Text(a), '+', b) => Value::Text(format!("{}{}", a, b)),
+                    (a @ Value::Number(_), '+', Value::Text(b)) => {
+                        Value::Text(format!("{}{}", a, b))
+                    }
+                    _ => Value::Null,
+                }
+            }
+            Expr::Call(name, args) => {
+                let mut values = args.iter().map(|arg| arg.eval(lookup, context));
+                match name.as_str() {
+                    "now" => Value::Text(context.now.clone()),
+                    "round" => {
+                        let value = values.next().unwrap_or(Value::Null);
+                        let digits = match values.next() {
+                            Some(Value::Number(d)) => d as i32,
+                            _ => 0,
+                        };
+                        match value {
+                            Value::Number(n) => {
+                                let scale = 10f64.powi(digits);
+                                Value::Number((n * scale).round() / scale)
+                            }
+                            _ => Value::Null,
+                        }
+                    }
+                    "concat" => Value::Text(values.map(|v| v.to_string()).collect()),
+                    "coalesce" => values.find(|v| *v != Value::Null).unwrap_or(Value::Null),
+                    _ => Value::Null,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> Value {
+        let lookup = |name: &str| match name {
+            "Price" => Value::Number(2.5),
+            "Qty" => Value::Number(4.0),
+            "Name" => Value::Text("Widget".to_string()),
+            _ => Value::Null,
+        };
+        let context = EvalContext {
+            now: "2024-06-01T00:00:00Z".to_string(),
+        };
+        Expr::parse(input).unwrap().eval(&lookup, &context)
+    }
+
+    #[test]
+    fn evaluates_arithmetic_with_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Value::Number(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Value::Number(9.0));
+        assert_eq!(eval("-(1 + 2) - -1"), Value::Number(-2.0));
+        assert_eq!(eval("[Price] * `Qty` / 2"), Value::Number(5.0));
+        assert_eq!(eval("Price / 0"), Value::Null);
+        assert_eq!(eval("Missing + 1"), Value::Null);
+    }
+
+    #[test]
+    fn joins_text_and_calls_functions() {
+        assert_eq!(
+            eval("Name + ' x' + Qty"),
+            Value::Text("Widget x4".to_string())
+        );
+        assert_eq!(
+            eval("CONCAT(Name, \"-\", 1)"),
+            Value::Text("Widget-1".to_string())
+        );
+        assert_eq!(eval("round(1.26, 1)"), Value::Number(1.3));
+        assert_eq!(eval("round(Price)"), Value::Number(3.0));
+        assert_eq!(eval("coalesce(Missing, Qty)"), Value::Number(4.0));
+        assert_eq!(
+            eval("now()"),
+            Value::Text("2024-06-01T00:00:00Z".to_string())
+        );
+    }
+
+    #[test]
+    fn lists_the_columns_read() {
+        let expr = Expr::parse("round([Unit price] * Qty, 2) + Price").unwrap();
+        assert_eq!(expr.columns(), vec!["Unit price", "Qty", "Price"]);
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for input in [
+            "",
+            "1 +",
+            "(1 + 2",
+            "1 2",
+            "1 $ 2",
+            "1..2",
+            "'open",
+            "[Price",
+            "median(Price)",
+            "round()",
+            "now(1)",
+            "concat(1 2)",
+        ] {
+            assert!(
+                matches!(Expr::parse(input), Err(TabexError::Parse(_))),
+                "{:?}",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! C interface, so services in other languages can link tabex instead of running
+//! the binary. `include/tabex.h` declares these functions; regenerate it with
+//! `cbindgen --output include/tabex.h` after changing them.
+
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Instant;
+
+use scraper::Html;
+
+use crate::error::TabexError;
+use crate::{ExtractOptions, ExtractionResult, Extractor, units};
+
+fn extract_html(html: &str, url: &str) -> Result<ExtractionResult, TabexError> {
+    let start = Instant::now();
+    let document = Html::parse_document(html);
+    let extractor = Extractor::new(ExtractOptions::default());
+    let page = extractor.page_metadata(&document, url);
+    let mut tables = extractor.extract_tables(&document)?;
+    for table in &mut tables.tables {
+        table.metadata.schema = units::build_schema(table, true);
+    }
+    Ok(ExtractionResult {
+        page,
+        tables: tables.tables,
+        relationships: None,
+        timed_out: tables.timed_out,
+        attempts: None,
+        extraction_time_ms: start.elapsed().as_millis(),
+    })
+}
+
+/// Extracts the page metadata and tables of `html`, recorded as loaded from `url`,
+/// with default options. Returns the result as JSON in the layout `tabex -f json`
+/// uses for a single page, or `{"error": "..."}` when extraction fails. Returns
+/// NULL when either argument is NULL or not UTF-8.
+///
+/// The returned string is owned by the caller and must be freed with
+/// `tabex_free_string`.
+///
+/// # Safety
+///
+/// `html` and `url` must be NULL or point to NUL-terminated strings that stay valid
+/// for the duration of the call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tabex_extract_html(
+    html: *const c_char,
+    url: *const c_char,
+) -> *mut c_char {
+    if html.is_null() || url.is_null() {
+        return std::ptr::null_mut();
+    }
+    // SAFETY: both are non-null and NUL-terminated, as the caller guarantees
+    let (html, url) = unsafe { (CStr::from_ptr(html), CStr::from_ptr(url)) };
+    let (Ok(html), Ok(url)) = (html.to_str(), url.to_str()) else {
+        return std::ptr::null_mut();
+    };
+    // A panic must not unwind into the caller's frames
+    let json = panic::catch_unwind(AssertUnwindSafe(|| {
+        match extract_html(html, url).and_then(|result| Ok(serde_json::to_string(&result)?)) {
+            Ok(json) => json,
+            Err(e) => serde_json::json!({ "error": e.to_string() }).to_string(),
+        }
+    }));
+    // JSON escapes control characters, so it holds no NUL
+    json.ok()
+        .and_then(|json| CString::new(json).ok())
+        .map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+/// Frees a string returned by `tabex_extract_html`. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `s` must be NULL or a pointer returned by `tabex_extract_html` that has not been
+/// freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tabex_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: `s` came from `CString::into_raw` in `tabex_extract_html`
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(html: &str, url: &str) -> serde_json::Value {
+        let (html, url) = (CString::new(html).unwrap(), CString::new(url).unwrap());
+        unsafe {
+            let json = tabex_extract_html(html.as_ptr(), url.as_ptr());
+            assert!(!json.is_null());
+            let value = serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            tabex_free_string(json);
+            value
+        }
+    }
+
+    #[test]
+    fn returns_the_result_as_json() {
+        let result = extract(
+            "<title>Scores</title><table><tr><th>Team</th><th>Goals</th></tr>
+             <tr><td>Ann</td><td>3</td></tr></table>",
+            "https://example.com/scores",
+        );
+        assert_eq!(result["page"]["url"], "https://example.com/scores");
+        assert_eq!(result["page"]["title"], "Scores");
+        assert_eq!(
+            result["tables"][0]["data"]["headers"],
+            serde_json::json!(["Team", "Goals"])
+        );
+        assert_eq!(result["timed_out"], false);
+    }
+
+    #[test]
+    fn returns_null_for_missing_arguments() {
+        let url = CString::new("https://example.com/").unwrap();
+        unsafe {
+            assert!(tabex_extract_html(std::ptr::null(), url.as_ptr()).is_null());
+            tabex_free_string(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::fs;
+use std::io::{self, Read};
+use std::sync::LazyLock;
+
+use regex::bytes::Regex;
+
+use crate::error::TabexError;
+use crate::events::Events;
+use crate::plugin::{SourceDocument, TableSource};
+
+/// `<meta charset="...">` or the http-equiv Content-Type form, near the top of a page.
+static META_CHARSET_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)<meta[^>]+charset\s*=\s*["']?([\w.:-]+)"#).unwrap());
+
+/// The location standing for standard input, as in `--url -`.
+pub const STDIN_LOCATION: &str = "-";
+
+/// How far into a file to look for a meta charset, as browsers do.
+const CHARSET_SNIFF_BYTES: usize = 1024;
+
+/// Decodes a saved page: a byte order mark wins, then a meta charset, then UTF-8.
+fn decode_file(body: &[u8]) -> String {
+    let head = &body[..body.len().min(CHARSET_SNIFF_BYTES)];
+    let encoding = META_CHARSET_RE
+        .captures(head)
+        .and_then(|captures| encoding_rs::Encoding::for_label(&captures[1]))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(body).0.into_owned()
+}
+
+/// Reads saved HTML pages from disk, for `--file`. The path is recorded as the page URL.
+#[derive(Default)]
+pub struct FileSource {
+    pub events: Events,
+}
+
+impl TableSource for FileSource {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn handles(&self, location: &str) -> bool {
+        location != STDIN_LOCATION
+            && !reqwest::Url::parse(location)
+                .is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+    }
+
+    fn load(&self, location: &str) -> Result<SourceDocument, TabexError> {
+        self.events.status(
+            "read",
+            &format!("Reading file: {}", location),
+            serde_json::json!({ "path": location }),
+        );
+        let body = fs::read(location).map_err(|e| TabexError::File {
+            path: location.to_string(),
+            source: e,
+        })?;
+        Ok(SourceDocument {
+            url: location.to_string(),
+            html: decode_file(&body),
+            attempts: None,
+        })
+    }
+}
+
+/// Reads a page piped in on standard input, for `--stdin` or `--url -`, so pages can
+/// be fetched with other tools. The page URL is recorded as `-`.
+#[derive(Default)]
+pub struct StdinSource {
+    pub events: Events,
+}
+
+impl TableSource for StdinSource {
+    fn name(&self) -> &str {
+        "stdin"
+    }
+
+    fn handles(&self, location: &str) -> bool {
+        location == STDIN_LOCATION
+    }
+
+    fn load(&self, location: &str) -> Result<SourceDocument, TabexError> {
+        self.events.status(
+            "read",
+            "Reading HTML from stdin",
+            serde_json::json!({ "path": location }),
+        );
+        let mut body = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut body)
+            .map_err(|e| TabexError::File {
+                path: "stdin".to_string(),
+                source: e,
+            })?;
+        Ok(SourceDocument {
+            url: location.to_string(),
+            html: decode_file(&body),
+            attempts: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_by_bom_then_meta_charset_then_utf8() {
+        let mut latin1 = b"<meta charset=\"windows-1252\"><td>caf".to_vec();
+        latin1.push(0xE9);
+        assert!(decode_file(&latin1).ends_with("<td>café"));
+
+        let mut http_equiv =
+            b"<META http-equiv='Content-Type' content='text/html; charset=ISO-8859-1'>".to_vec();
+        http_equiv.push(0xE9);
+        assert!(decode_file(&http_equiv).ends_with('é'));
+
+        // A byte order mark wins over the meta charset and isn't kept
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(
+                "<meta charset=windows-1252>é"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            )
+            .collect();
+        assert_eq!(decode_file(&utf16), "<meta charset=windows-1252>é");
+        let mut utf8 = vec![0xEF, 0xBB, 0xBF];
+        utf8.extend("<meta charset=windows-1252>é".as_bytes());
+        assert_eq!(decode_file(&utf8), "<meta charset=windows-1252>é");
+
+        assert_eq!(decode_file("<td>é</td>".as_bytes()), "<td>é</td>");
+    }
+
+    #[test]
+    fn handles_paths_but_not_web_urls_or_stdin() {
+        let source = FileSource::default();
+        assert!(source.handles("page.html"));
+        assert!(source.handles("file:///tmp/page.html"));
+        assert!(!source.handles("https://example.com/"));
+        assert!(!source.handles(STDIN_LOCATION));
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::{HashMap, HashSet};
+
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+
+use crate::error::TabexError;
+use crate::lineage::{self, ColumnLineage};
+use crate::matching::HeaderMatcher;
+use crate::plugin::Registry;
+use crate::{DocumentTables, Extractor, Table, canonicalize_url};
+
+/// What `--follow-column` joined onto an index table.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FollowSummary {
+    pub column: String,
+    /// Position of the table taken from each detail page; None takes the best scoring one
+    pub detail_table: Option<usize>,
+    /// Detail pages whose rows were joined
+    pub pages: usize,
+    /// Detail pages that could not be fetched or had no matching table
+    pub failed: Vec<String>,
+}
+
+pub struct FollowOptions<'a> {
+    pub column: &'a str,
+    pub detail_table: Option<usize>,
+    pub strip_params: &'a [String],
+    pub matcher: &'a HeaderMatcher,
+}
+
+/// Fetches the page linked from each row's `column` cell and joins the rows of one
+/// of its tables onto the index row, after a `detail_url` column. Rows without a
+/// link or without detail rows are kept once with the detail columns empty. Pages
+/// linked from several rows are fetched once. Returns whether a passed deadline
+/// stopped the detail fetches.
+pub fn follow_links(
+    tables: &mut [Table],
+    page_url: &str,
+    options: &FollowOptions,
+    registry: &Registry,
+    extractor: &Extractor,
+) -> bool {
+    let base = reqwest::Url::parse(page_url).ok();
+    let mut pages: HashMap<String, Option<Table>> = HashMap::new();
+    let mut timed_out = false;
+
+    for table in tables {
+        let Some(links) = table.links.take() else {
+            continue;
+        };
+        let Some(column) = options.matcher.column_index(table, options.column) else {
+            continue;
+        };
+
+        let width = table.data.headers.len();
+        let mut detail_headers: Option<Vec<String>> = None;
+        let mut detail_lineage: Option<Vec<ColumnLineage>> = None;
+        let mut joined_pages: HashSet<String> = HashSet::new();
+        let mut failed: Vec<String> = Vec::new();
+        let mut rows: Vec<Vec<String>> = Vec::new();
+
+        for (mut row, row_links) in std::mem::take(&mut table.data.rows).into_iter().zip(links) {
+            let url = row_links
+                .get(column)
+                .cloned()
+                .flatten()
+                .and_then(|href| base.as_ref()?.join(&href).ok())
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+                .and_then(|url| canonicalize_url(url.as_str(), options.strip_params).ok());
+
+            if row.len() < width {
+                row.resize(width, String::new());
+            }
+            row.push(url.clone().unwrap_or_default());
+
+            let detail = url.as_ref().and_then(|url| {
+                if !pages.contains_key(url) && !timed_out {
+                    let detail = load_detail(url, registry, extractor).and_then(|page| {
+                        timed_out |= page.timed_out;
+                        choose_detail(page.tables, options.detail_table)
+                    });
+                    pages.insert(url.clone(), detail);
+                }
+                pages.get(url)?.as_ref()
+            });
+            match (detail, url) {
+                (Some(detail), Some(url)) if !detail.data.rows.is_empty() => {
+                    detail_headers.get_or_insert_with(|| detail.data.headers.clone());
+                    detail_lineage.get_or_insert_with(|| lineage::read_from(detail, "follow"));
+                    joined_pages.insert(url);
+                    for detail_row in &detail.data.rows {
+                        let mut joined = row.clone();
+                        joined.extend(detail_row.iter().cloned());
+                        rows.push(joined);
+                    }
+                }
+                (_, Some(url)) => {
+                    if !failed.contains(&url) {
+                        failed.push(url);
+                    }
+                    rows.push(row);
+                }
+                (_, None) => rows.push(row),
+            }
+        }
+
+        table.data.headers.push("detail_url".to_string());
+        table
+            .data
+            .headers
+            .extend(detail_headers.unwrap_or_default());
+        lineage::push_derived(table, "detail_url", &[column], "follow");
+        if let Some(lineage) = table.metadata.lineage.as_mut() {
+            lineage.extend(detail_lineage.unwrap_or_default());
+        }
+        table.data.rows = rows;
+        table.metadata.follow = Some(FollowSummary {
+            column: options.column.to_string(),
+            detail_table: options.detail_table,
+            pages: joined_pages.len(),
+            failed,
+        });
+    }
+    timed_out
+}
+
+/// The tables of a linked page, or None when it can't be had. A passed deadline
+/// leaves no tables and `timed_out` set.
+fn load_detail(url: &str, registry: &Registry, extractor: &Extractor) -> Option<DocumentTables> {
+    let tables = registry
+        .source_for(url)
+        .ok_or_else(|| TabexError::Usage(format!("No source can load {}", url)))
+        .and_then(|source| source.load(url))
+        .and_then(|document| extractor.extract_tables(&Html::parse_document(&document.html)));
+    match tables {
+        Ok(tables) => Some(tables),
+        Err(TabexError::Timeout { .. }) => Some(DocumentTables {
+            tables: Vec::new(),
+            timed_out: true,
+        }),
+        Err(e) => {
+            extractor.events().warning(
+                "detail_page_skipped",
+                &format!("skipping detail page {}: {}", url, e),
+                serde_json::json!({ "url": url, "error": e.to_string() }),
+            );
+            None
+        }
+    }
+}
+
+/// The table at position `detail_table` of a detail page, or its best scoring one.
+fn choose_detail(tables: Vec<Table>, detail_table: Option<usize>) -> Option<Table> {
+    let mut tables = tables.into_iter();
+    match detail_table {
+        Some(position) => tables.find(|table| table.metadata.position == position),
+        // Earliest table wins ties, as with --best
+        None => tables.rev().max_by_key(|table| table.metadata.score),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::plugin::{SourceDocument, TableSource};
+    use crate::tests::extract;
+
+    /// Serves detail pages from memory, noting each load.
+    struct Pages {
+        pages: HashMap<&'static str, &'static str>,
+        loads: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl TableSource for Pages {
+        fn name(&self) -> &str {
+            "pages"
+        }
+
+        fn handles(&self, _location: &str) -> bool {
+            true
+        }
+
+        fn load(&self, location: &str) -> Result<SourceDocument, TabexError> {
+            self.loads.borrow_mut().push(location.to_string());
+            match self.pages.get(location) {
+                Some(html) => Ok(SourceDocument {
+                    url: location.to_string(),
+                    html: html.to_string(),
+                    attempts: None,
+                }),
+                None => Err(TabexError::Fetch {
+                    status: 404,
+                    url: location.to_string(),
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn joins_detail_rows_fetching_shared_pages_once() {
+        let (events, reported) = crate::events::tests::collect();
+        let options = || ExtractOptions {
+            cell_links: true,
+            events: events.clone(),
+            ..ExtractOptions::default()
+        };
+        let mut tables = extract(
+            "<table><tr><th>Name</th><th>Team</th></tr>
+             <tr><td><a href='/p/ann'>Ann</a></td><td>A</td></tr>
+             <tr><td>Bob</td><td>B</td></tr>
+             <tr><td><a href='/p/shared#top'>Cat</a></td><td>C</td></tr>
+             <tr><td><a href='/p/shared'>Dan</a></td><td>D</td></tr>
+             <tr><td><a href='/p/missing'>Eve</a></td><td>E</td></tr></table>",
+            options(),
+        );
+        let detail = "<table><tr><th>Age</th><th>City</th></tr>
+                      <tr><td>30</td><td>Oslo</td></tr></table>";
+        let loads = Rc::default();
+        let source = Pages {
+            pages: HashMap::from([
+                ("https://example.com/p/ann", detail),
+                ("https://example.com/p/shared", detail),
+            ]),
+            loads: Rc::clone(&loads),
+        };
+        let mut registry = Registry::default();
+        registry.register_source(Box::new(source));
+
+        let timed_out = follow_links(
+            &mut tables,
+            "https://example.com/index",
+            &FollowOptions {
+                column: "Name",
+                detail_table: None,
+                strip_params: &[],
+                matcher: &HeaderMatcher::default(),
+            },
+            &registry,
+            &Extractor::new(options()),
+        );
+        assert!(!timed_out);
+        let table = &tables[0];
+        assert_eq!(
+            table.data.headers,
+            ["Name", "Team", "detail_url", "Age", "City"]
+        );
+        assert_eq!(
+            table.data.rows,
+            [
+                vec!["Ann", "A", "https://example.com/p/ann", "30", "Oslo"],
+                vec!["Bob", "B", ""],
+                vec!["Cat", "C", "https://example.com/p/shared", "30", "Oslo"],
+                vec!["Dan", "D", "https://example.com/p/shared", "30", "Oslo"],
+                vec!["Eve", "E", "https://example.com/p/missing"],
+            ]
+        );
+        let follow = table.metadata.follow.as_ref().unwrap();
+        assert_eq!(follow.pages, 2);
+        assert_eq!(follow.failed, ["https://example.com/p/missing"]);
+        assert_eq!(reported.events(), ["warning detail_page_skipped"]);
+        assert_eq!(
+            *loads.borrow(),
+            [
+                "https://example.com/p/ann",
+                "https://example.com/p/shared",
+                "https://example.com/p/missing",
+            ]
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use std::io::Write;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::error::TabexError;
+use crate::lineage;
+use crate::matching::HeaderMatcher;
+use crate::output::{record_keys, slug};
+use crate::plugin::{Sink, Transform};
+use crate::transform::mostly;
+use crate::types::{format_number, parse_number};
+use crate::units::declared_type;
+use crate::{ExtractionResult, PageMetadata, PageOutcome, Table};
+
+/// One half of a coordinate with its hemisphere: "48°51′24″N", "2°21′E", "48.8566°N"
+/// or "48.8566 N". Minutes and seconds need a degree sign before them.
+static COMPONENT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(\d{1,3}(?:\.\d+)?)\s*([°º˚])?\s*(?:(\d{1,2}(?:\.\d+)?)\s*['′’]\s*)?(?:(\d{1,2}(?:\.\d+)?)\s*(?:["″”]|'')\s*)?([NSEW])\b"#,
+    )
+    .unwrap()
+});
+
+/// A signed decimal pair such as "48.8566, 2.3522" or "48.8566; 2.3522".
+static DECIMAL_PAIR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\(?\s*([-−]?\d{1,2}\.\d+)\s*[,;]\s*([-−]?\d{1,3}\.\d+)\s*\)?$").unwrap()
+});
+
+/// A component's value in degrees, signed by hemisphere, and whether it is a
+/// latitude.
+fn component(captures: &regex::Captures) -> Option<(f64, bool)> {
+    let number = |i: usize| -> Option<f64> {
+        captures
+            .get(i)
+            .map_or(Some(0.0), |m| m.as_str().parse().ok())
+    };
+    let degrees_text = captures.get(1)?.as_str();
+    let has_degree_sign = captures.get(2).is_some();
+    let (minutes, seconds) = (number(3)?, number(4)?);
+    // "12 N" is more likely a count than a coordinate
+    if !has_degree_sign && (!degrees_text.contains('.') || captures.get(3).is_some()) {
+        return None;
+    }
+    if minutes >= 60.0 || seconds >= 60.0 {
+        return None;
+    }
+    let value = number(1)? + minutes / 60.0 + seconds / 3600.0;
+    let hemisphere = captures.get(5)?.as_str();
+    let sign = if matches!(hemisphere, "S" | "W") {
+        -1.0
+    } else {
+        1.0
+    };
+    Some((sign * value, matches!(hemisphere, "N" | "S")))
+}
+
+fn in_range(lat: f64, lon: f64) -> bool {
+    lat.abs() <= 90.0 && lon.abs() <= 180.0
+}
+
+/// The first latitude/longitude pair in a cell, in either order when hemispheres are
+/// marked: "48°51′24″N 2°21′03″E", "48.8566°N, 2.3522°E", "48.8566, 2.3522". Cells
+/// giving the same place several ways, as Wikipedia's "… / 48.8566; 2.3522" does, read
+/// the first.
+pub fn parse_coordinates(cell: &str) -> Option<(f64, f64)> {
+    let components: Vec<(f64, bool, std::ops::Range<usize>)> = COMPONENT_RE
+        .captures_iter(cell)
+        .filter_map(|captures| {
+            let (value, is_latitude) = component(&captures)?;
+            Some((value, is_latitude, captures.get(0)?.range()))
+        })
+        .collect();
+    let hemispheres = components.windows(2).find_map(|pair| {
+        let between = &cell[pair[0].2.end..pair[1].2.start];
+        if !between
+            .chars()
+            .all(|c| c.is_whitespace() || matches!(c, ',' | ';'))
+        {
+            return None;
+        }
+        match (pair[0].1, pair[1].1) {
+            (true, false) => Some((pair[0].0, pair[1].0)),
+            (false, true) => Some((pair[1].0, pair[0].0)),
+            _ => None,
+        }
+    });
+    if let Some((lat, lon)) = hemispheres {
+        return in_range(lat, lon).then_some((lat, lon));
+    }
+
+    cell.split('/').find_map(|part| {
+        let captures = DECIMAL_PAIR_RE.captures(part.trim())?;
+        let lat = parse_number(&captures[1])?;
+        let lon = parse_number(&captures[2])?;
+        in_range(lat, lon).then_some((lat, lon))
+    })
+}
+
+/// Degrees to six decimals, about 10 cm, which is finer than any table gives them.
+fn round_degrees(degrees: f64) -> f64 {
+    (degrees * 1e6).round() / 1e6
+}
+
+fn format_degrees(degrees: f64) -> String {
+    format_number(round_degrees(degrees))
+}
+
+/// `--coordinates`: appends `<header> lat` and `<header> lon` columns in decimal
+/// degrees after each column where at least half the cells are coordinates.
+pub struct ParseCoordinates;
+
+impl Transform for ParseCoordinates {
+    fn name(&self) -> &str {
+        "coordinates"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        for table in tables {
+            let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+            let columns: Vec<usize> = (0..width)
+                .filter(|&column| mostly(table, column, |cell| parse_coordinates(cell).is_some()))
+                .collect();
+            if columns.is_empty() {
+                continue;
+            }
+
+            let padded = table.data.headers.len().max(width);
+            for &column in &columns {
+                if let Some(header) = table.data.headers.get(column).cloned() {
+                    for suffix in ["lat", "lon"] {
+                        let name = format!("{} {}", header, suffix);
+                        lineage::push_derived(table, &name, &[column], "coordinates");
+                        table.data.headers.push(name);
+                    }
+                }
+            }
+            for row in &mut table.data.rows {
+                let located: Vec<String> = columns
+                    .iter()
+                    .flat_map(|&column| {
+                        match row.get(column).and_then(|cell| parse_coordinates(cell)) {
+                            Some((lat, lon)) => [format_degrees(lat), format_degrees(lon)],
+                            None => [String::new(), String::new()],
+                        }
+                    })
+                    .collect();
+                row.resize(padded, String::new());
+                row.extend(located);
+            }
+        }
+    }
+}
+
+const LATITUDE_NAMES: &[&str] = &["lat", "latitude"];
+const LONGITUDE_NAMES: &[&str] = &["lon", "lng", "long", "longitude"];
+
+/// A latitude or longitude on its own: "48.8566", "48.8566°" or "48°51′24″N".
+fn parse_degrees(cell: &str) -> Option<f64> {
+    let cell = cell.trim();
+    if let Some(captures) = COMPONENT_RE.captures(cell)
+        && captures.get(0)?.range() == (0..cell.len())
+    {
+        return component(&captures).map(|(degrees, _)| degrees);
+    }
+    parse_number(cell.trim_end_matches(['°', 'º', '˚']))
+}
+
+/// Where a table's rows keep their location.
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Columns {
+        lat: usize,
+        lon: usize,
+    },
+    /// Both halves in one cell, as `parse_coordinates` reads them
+    Cell(usize),
+}
+
+impl Location {
+    fn point(self, row: &[String]) -> Option<(f64, f64)> {
+        match self {
+            Location::Columns { lat, lon } => {
+                let lat = parse_degrees(row.get(lat)?)?;
+                let lon = parse_degrees(row.get(lon)?)?;
+                in_range(lat, lon).then_some((lat, lon))
+            }
+            Location::Cell(column) => parse_coordinates(row.get(column)?),
+        }
+    }
+
+    fn covers(self, column: usize) -> bool {
+        match self {
+            Location::Columns { lat, lon } => column == lat || column == lon,
+            Location::Cell(cell) => column == cell,
+        }
+    }
+}
+
+/// Columns whose header ends in one of `names` ("Latitude", "Start lat") and whose
+/// cells are mostly degrees, with the rest of the header's slug.
+fn named_columns(table: &Table, names: &[&str]) -> Vec<(usize, String)> {
+    table
+        .data
+        .headers
+        .iter()
+        .enumerate()
+        .filter_map(|(column, header)| {
+            let slug = slug(header);
+            let (prefix, last) = slug.rsplit_once('_').unwrap_or(("", &slug));
+            (names.contains(&last) && mostly(table, column, |cell| parse_degrees(cell).is_some()))
+                .then(|| (column, prefix.to_string()))
+        })
+        .collect()
+}
+
+/// Latitude and longitude columns, preferring a pair named alike such as the
+/// "Coordinates lat" and "Coordinates lon" that `--coordinates` adds, else the first
+/// column of coordinate cells.
+fn detect_location(table: &Table) -> Option<Location> {
+    let lats = named_columns(table, LATITUDE_NAMES);
+    let lons = named_columns(table, LONGITUDE_NAMES);
+    let pair = lats
+        .iter()
+        .find_map(|(lat, prefix)| {
+            let (lon, _) = lons.iter().find(|(_, other)| other == prefix)?;
+            Some((*lat, *lon))
+        })
+        .or_else(|| Some((lats.first()?.0, lons.first()?.0)));
+    if let Some((lat, lon)) = pair {
+        return Some(Location::Columns { lat, lon });
+    }
+    let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+    (0..width)
+        .find(|&column| mostly(table, column, |cell| parse_coordinates(cell).is_some()))
+        .map(Location::Cell)
+}
+
+/// Parses `--geo-columns`: `LAT,LON` for separate latitude and longitude columns, or
+/// one column holding both.
+pub fn parse_geo_columns(list: &str) -> Result<Vec<String>, TabexError> {
+    let columns: Vec<String> = list.split(',').map(|c| c.trim().to_string()).collect();
+    if columns.len() > 2 || columns.iter().any(String::is_empty) {
+        return Err(TabexError::Parse(format!(
+            "Invalid --geo-columns {} (expected LAT,LON or a single coordinates column)",
+            list
+        )));
+    }
+    Ok(columns)
+}
+
+/// A GeoJSON FeatureCollection with a Point for each row that has a location, and the
+/// row's other columns as properties. Tables without a location are left out, as are
+/// rows whose location is missing or out of range.
+pub struct GeoJsonSink {
+    /// `--geo-columns`, found in each table with the header matcher; tables without
+    /// them fall back to detection
+    pub columns: Vec<String>,
+    pub matcher: HeaderMatcher,
+}
+
+impl GeoJsonSink {
+    fn location(&self, table: &Table) -> Option<Location> {
+        let column = |name: &str| Some(self.matcher.find(&table.data.headers, name)?.column);
+        let declared = match self.columns.as_slice() {
+            [cell] => column(cell).map(Location::Cell),
+            [lat, lon] => column(lat)
+                .zip(column(lon))
+                .map(|(lat, lon)| Location::Columns { lat, lon }),
+            _ => None,
+        };
+        declared.or_else(|| detect_location(table))
+    }
+
+    /// The Features of a page's located rows.
+    fn features(&self, result: &ExtractionResult) -> Vec<serde_json::Value> {
+        let mut features = Vec::new();
+        for table in &result.tables {
+            let Some(location) = self.location(table) else {
+                continue;
+            };
+            let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+            let keys = record_keys(&table.data.headers, width);
+            for row in &table.data.rows {
+                let Some((lat, lon)) = location.point(row) else {
+                    continue;
+                };
+                // Typed like JSON records: --types columns as numbers, the rest as written
+                let properties: serde_json::Map<String, serde_json::Value> = keys
+                    .iter()
+                    .zip(row)
+                    .enumerate()
+                    .filter(|(index, _)| !location.covers(*index))
+                    .map(|(index, (key, cell))| {
+                        let value = match declared_type(table, index) {
+                            Some(declared) => {
+                                declared.json_value(cell, table.metadata.sign_conventions)
+                            }
+                            None => serde_json::Value::from(cell.as_str()),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect();
+                // GeoJSON positions put longitude first
+                features.push(serde_json::json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Point",
+                        "coordinates": [round_degrees(lon), round_degrees(lat)],
+                    },
+                    "properties": properties,
+                }));
+            }
+        }
+        features
+    }
+}
+
+impl Sink for GeoJsonSink {
+    fn name(&self) -> &str {
+        "geojson"
+    }
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError> {
+        write_collection(self.features(result), out)
+    }
+
+    /// One FeatureCollection holding the features of every page.
+    fn write_pages(&self, pages: &[PageOutcome], out: &mut dyn Write) -> Result<(), TabexError> {
+        let features = pages
+            .iter()
+            .filter_map(|page| page.result.as_ref())
+            .flat_map(|result| self.features(result))
+            .collect();
+        write_collection(features, out)
+    }
+}
+
+fn write_collection(
+    features: Vec<serde_json::Value>,
+    out: &mut dyn Write,
+) -> Result<(), TabexError> {
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    writeln!(out, "{}", serde_json::to_string_pretty(&collection)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Option<(f64, f64)>, expected: (f64, f64)) {
+        let (lat, lon) = actual.expect("coordinates");
+        assert!(
+            (lat - expected.0).abs() < 1e-6 && (lon - expected.1).abs() < 1e-6,
+            "{:?} is not {:?}",
+            (lat, lon),
+            expected
+        );
+    }
+
+    #[test]
+    fn parses_degrees_minutes_seconds() {
+        assert_near(
+            parse_coordinates("48°51′24″N 2°21′03″E"),
+            (
+                48.0 + 51.0 / 60.0 + 24.0 / 3600.0,
+                2.0 + 21.0 / 60.0 + 3.0 / 3600.0,
+            ),
+        );
+        assert_near(
+            parse_coordinates("33°52'S, 151°12'E"),
+            (-(33.0 + 52.0 / 60.0), 151.2),
+        );
+    }
+
+    #[test]
+    fn parses_decimal_degrees_in_either_order() {
+        assert_near(parse_coordinates("48.8566°N, 2.3522°E"), (48.8566, 2.3522));
+        assert_near(parse_coordinates("2.3522°E 48.8566°N"), (48.8566, 2.3522));
+        assert_near(parse_coordinates("40.7128 N 74.0060 W"), (40.7128, -74.006));
+    }
+
+    #[test]
+    fn parses_signed_decimal_pairs() {
+        assert_near(parse_coordinates("48.8566, 2.3522"), (48.8566, 2.3522));
+        assert_near(
+            parse_coordinates("(-33.8688; 151.2093)"),
+            (-33.8688, 151.2093),
+        );
+        assert_near(
+            parse_coordinates("48°51′N 2°21′E / 48.8566; 2.3522"),
+            (48.85, 2.35),
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_malformed_coordinates() {
+        for cell in [
+            "",
+            "999°N 2°E",
+            "48°N 999°E",
+            "48°61′N 2°E",
+            "12 N 5 E",
+            "48°N 2°N",
+            "48°N text 2°E",
+            "95.1, 2.3",
+            "48, 2",
+        ] {
+            assert_eq!(parse_coordinates(cell), None, "{:?}", cell);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use scraper::ElementRef;
+use serde::{Deserialize, Serialize};
+
+use crate::error::TabexError;
+use crate::rows::{self, StyleFlags};
+use crate::tooltips;
+use crate::{CELL_SELECTOR, cell_href, clean_cell_text, span_attr};
+
+/// How data rows treat cells with colspan or rowspan.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SpanMode {
+    /// One value per cell in markup order, so spans shift later cells left
+    #[default]
+    Off,
+    /// Repeat a spanned cell into every slot it covers
+    Duplicate,
+    /// Keep a spanned cell in its first slot and leave the others empty
+    Blank,
+}
+
+impl SpanMode {
+    pub fn parse(name: &str) -> Result<SpanMode, TabexError> {
+        match name {
+            "duplicate" => Ok(SpanMode::Duplicate),
+            "blank" => Ok(SpanMode::Blank),
+            "off" => Ok(SpanMode::Off),
+            other => Err(TabexError::Usage(format!(
+                "Unknown span mode {} (expected duplicate, blank or off)",
+                other
+            ))),
+        }
+    }
+}
+
+/// One slot in a row after colspan/rowspan expansion.
+#[derive(Debug, Clone)]
+pub struct Slot {
+    pub text: String,
+    /// True for the top-left slot the cell actually occupies in the markup
+    pub origin: bool,
+    pub colspan: usize,
+    /// Target of the first link in the cell
+    pub href: Option<String>,
+    pub tooltip: Option<String>,
+    pub style: Option<StyleFlags>,
+}
+
+/// A header cell spanning several columns, such as "2023" over Q1–Q4.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColumnGroup {
+    pub name: String,
+    /// 0 for the top header row
+    pub level: usize,
+    pub start_column: usize,
+    pub span: usize,
+    /// Leaf header names of the covered columns
+    pub columns: Vec<String>,
+}
+
+/// Lays the rows' cells out on a grid, repeating spanned cells into every slot they cover.
+pub fn expand_spans<'a>(rows: &[ElementRef<'a>]) -> Vec<Vec<Option<Slot>>> {
+    let mut grid: Vec<Vec<Option<Slot>>> = vec![Vec::new(); rows.len()];
+
+    for (r, row) in rows.iter().enumerate() {
+        let mut column = 0;
+        for cell in row.select(&CELL_SELECTOR) {
+            // Skip slots already filled by rowspans from above
+            while grid[r].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+
+            let colspan = span_attr(cell, "colspan");
+            // rowspan="0" extends to the end of the row group
+            let rowspan = match cell.value().attr("rowspan").map(str::trim) {
+                Some("0") => rows.len() - r,
+                _ => span_attr(cell, "rowspan"),
+            };
+            let text = clean_cell_text(cell);
+            let href = cell_href(cell);
+            let tooltip = tooltips::cell_tooltip(cell);
+            let style = rows::style_flags(cell);
+
+            for dr in 0..rowspan.min(rows.len() - r) {
+                let slots = &mut grid[r + dr];
+                if slots.len() < column + colspan {
+                    slots.resize(column + colspan, None);
+                }
+                for dc in 0..colspan {
+                    slots[column + dc] = Some(Slot {
+                        text: text.clone(),
+                        origin: dr == 0 && dc == 0,
+                        colspan,
+                        href: href.clone(),
+                        tooltip: tooltip.clone(),
+                        style: style.clone(),
+                    });
+                }
+            }
+            column += colspan;
+        }
+    }
+
+    grid
+}
+
+/// Flattens multi-row headers into one name per column ("2023 Q1") and
+/// lists the spanning cells of the upper rows as column groups.
+pub fn flatten_headers(header_rows: &[ElementRef]) -> (Vec<String>, Vec<ColumnGroup>) {
+    let grid = expand_spans(header_rows);
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let text_at = |r: usize, c: usize| {
+        grid[r]
+            .get(c)
+            .and_then(Option::as_ref)
+            .map(|slot| slot.text.as_str())
+            .unwrap_or("")
+    };
+
+    let headers = (0..width)
+        .map(|c| {
+            let mut parts: Vec<&str> = Vec::new();
+            for r in 0..grid.len() {
+                let text = text_at(r, c);
+                // A rowspan repeats the same label down the header rows
+                if !text.is_empty() && parts.last() != Some(&text) {
+                    parts.push(text);
+                }
+            }
+            parts.join(" ")
+        })
+        .collect();
+
+    let leaf = grid.len().saturating_sub(1);
+    let mut groups = Vec::new();
+    for (r, row) in grid.iter().enumerate().take(leaf) {
+        for (c, slot) in row.iter().enumerate() {
+            let Some(slot) = slot else { continue };
+            if slot.origin && slot.colspan > 1 && !slot.text.is_empty() {
+                groups.push(ColumnGroup {
+                    name: slot.text.clone(),
+                    level: r,
+                    start_column: c,
+                    span: slot.colspan,
+                    columns: (c..c + slot.colspan)
+                        .map(|c| text_at(leaf, c).to_string())
+                        .collect(),
+                });
+            }
+        }
+    }
+
+    (headers, groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::extract;
+    use crate::{ExtractOptions, Table};
+
+    fn table(html: &str, spans: SpanMode) -> Table {
+        let options = ExtractOptions {
+            spans,
+            ..ExtractOptions::default()
+        };
+        extract(html, options).remove(0)
+    }
+
+    #[test]
+    fn flattens_two_header_rows_into_group_and_leaf_names() {
+        let table = table(
+            "<table>
+             <tr><th rowspan=\"2\">Region</th><th colspan=\"2\">2023</th><th colspan=\"2\">2024</th></tr>
+             <tr><th>H1</th><th>H2</th><th>H1</th><th>H2</th></tr>
+             <tr><td>North</td><td>1</td><td>2</td><td>3</td><td>4</td></tr></table>",
+            SpanMode::Off,
+        );
+        assert_eq!(
+            table.data.headers,
+            ["Region", "2023 H1", "2023 H2", "2024 H1", "2024 H2"]
+        );
+        let groups: Vec<(&str, usize, usize, usize, &[String])> = table
+            .metadata
+            .column_groups
+            .iter()
+            .map(|group| {
+                (
+                    group.name.as_str(),
+                    group.level,
+                    group.start_column,
+                    group.span,
+                    &group.columns[..],
+                )
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("2023", 0, 1, 2, &["H1".to_string(), "H2".to_string()][..]),
+                ("2024", 0, 3, 2, &["H1".to_string(), "H2".to_string()][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_single_header_rows_without_groups() {
+        let table = table(
+            "<table><tr><th colspan=\"2\">Name</th><th>Age</th></tr>
+             <tr><td>Ann</td><td>Lee</td><td>30</td></tr></table>",
+            SpanMode::Off,
+        );
+        assert_eq!(table.data.headers, ["Name", "Age"]);
+        assert!(table.metadata.column_groups.is_empty());
+    }
+
+    const SCHEDULE: &str = "<table><tr><th>Day</th><th>Time</th><th>Room</th></tr>
+        <tr><td rowspan=\"2\">Mon</td><td>9:00</td><td>A</td></tr>
+        <tr><td>11:00</td><td>B</td></tr>
+        <tr><td>Tue</td><td colspan=\"2\">Closed</td></tr></table>";
+
+    #[test]
+    fn shifts_spanned_rows_left_without_expansion() {
+        let table = table(SCHEDULE, SpanMode::Off);
+        assert_eq!(
+            table.data.rows,
+            [
+                vec!["Mon", "9:00", "A"],
+                vec!["11:00", "B"],
+                vec!["Tue", "Closed"],
+            ]
+        );
+    }
+
+    #[test]
+    fn repeats_spanned_cells_into_every_slot() {
+        let table = table(SCHEDULE, SpanMode::Duplicate);
+        assert_eq!(
+            table.data.rows,
+            [
+                ["Mon", "9:00", "A"],
+                ["Mon", "11:00", "B"],
+                ["Tue", "Closed", "Closed"],
+            ]
+        );
+    }
+
+    #[test]
+    fn blanks_the_slots_a_span_covers() {
+        let table = table(SCHEDULE, SpanMode::Blank);
+        assert_eq!(
+            table.data.rows,
+            [
+                ["Mon", "9:00", "A"],
+                ["", "11:00", "B"],
+                ["Tue", "Closed", ""]
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_rowspan_zero_to_the_last_row_and_pads_short_rows() {
+        let table = table(
+            "<table><tr><th>A</th><th>B</th><th>C</th></tr>
+             <tr><td rowspan=\"0\">x</td><td>1</td></tr>
+             <tr><td>2</td></tr>
+             <tr><td>3</td><td>4</td></tr></table>",
+            SpanMode::Duplicate,
+        );
+        assert_eq!(
+            table.data.rows,
+            [["x", "1", ""], ["x", "2", ""], ["x", "3", "4"]]
+        );
+    }
+
+    #[test]
+    fn parses_span_modes() {
+        assert_eq!(SpanMode::parse("duplicate").unwrap(), SpanMode::Duplicate);
+        assert_eq!(SpanMode::parse("blank").unwrap(), SpanMode::Blank);
+        assert_eq!(SpanMode::parse("off").unwrap(), SpanMode::Off);
+        assert!(SpanMode::parse("repeat").is_err());
+    }
+}
@@ -0,0 +1,1396 @@
+//! Extracts tables and page metadata from HTML documents.
+//!
+//! [`Extractor`] is the entry point: parse a document with [`scraper::Html`], then
+//! read its [`PageMetadata`] and [`Table`]s. The `tabex` binary is a command line
+//! front end over this crate.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+
+pub mod analysis;
+pub mod cookies;
+pub mod crawl;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+pub mod datarepo;
+pub mod datetime;
+pub mod duration;
+pub mod error;
+pub mod events;
+pub mod expr;
+pub mod fetch;
+pub mod ffi;
+pub mod file;
+pub mod follow;
+pub mod geo;
+pub mod grid;
+pub mod license;
+pub mod lineage;
+pub mod links;
+pub mod manifest;
+pub mod matching;
+pub mod naming;
+pub mod output;
+pub mod plugin;
+pub mod profile;
+pub mod redact;
+pub mod report;
+pub mod robots;
+pub mod routing;
+pub mod rows;
+pub mod semantic;
+pub mod sitemap;
+pub mod sql;
+pub mod tooltips;
+pub mod transform;
+pub mod types;
+pub mod units;
+pub mod wayback;
+pub mod xlsx;
+
+use analysis::{Relationship, TableAnalysis};
+use error::TabexError;
+use events::Events;
+use follow::FollowSummary;
+use grid::{ColumnGroup, SpanMode};
+use license::License;
+use lineage::ColumnLineage;
+use matching::HeaderMatch;
+use profile::Standings;
+use redact::Redaction;
+use rows::{CellStyle, RowHint, RowStyle, StyleFlags, TotalRow};
+use tooltips::{CellTooltip, HeaderTooltip};
+use transform::OriginalValue;
+use types::SignConventions;
+use units::ColumnSchema;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableMetadata {
+    /// Set by --table-name-template and used by every sink that names tables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub id: Option<String>,
+    pub class: Option<String>,
+    pub caption: Option<String>,
+    pub position: usize,
+    /// Identifies the same table across runs even when its position shifts
+    pub table_key: String,
+    pub row_count: usize,
+    pub column_count: usize,
+    pub header_row_count: usize,
+    pub footer_row_count: usize,
+    pub parent_section: Option<String>,
+    pub preceding_heading: Option<String>,
+    pub score: i32,
+    pub score_breakdown: Vec<ScoreSignal>,
+    pub matrix: bool,
+    /// Rows of "Attribute | Value" pairs, as on a spec sheet; see --transpose
+    pub key_value: bool,
+    pub hierarchical_rows: bool,
+    pub total_rows: Vec<TotalRow>,
+    pub colgroups: Vec<ColGroup>,
+    pub column_groups: Vec<ColumnGroup>,
+    pub notes: Vec<String>,
+    pub schema: Vec<ColumnSchema>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<TableAnalysis>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row_styles: Option<Vec<RowStyle>>,
+    /// Presentation of styled data cells, with --cell-styles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_styles: Option<Vec<CellStyle>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub standings: Option<Standings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow: Option<FollowSummary>,
+    /// How columns named in options were found, with --fuzzy-headers or --header-synonyms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_matches: Option<Vec<HeaderMatch>>,
+    /// Page text of cells --expand-suffixes or --timezone rewrote, such as "3.4M"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_values: Option<Vec<OriginalValue>>,
+    /// Where each column came from and the steps applied to it, with --lineage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lineage: Option<Vec<ColumnLineage>>,
+    /// Capture times of the --wayback snapshots the table's rows were stacked from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshots: Option<Vec<String>>,
+    /// `title` and `<abbr>` expansions on header cells, which often spell out
+    /// abbreviations like "GD"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_tooltips: Option<Vec<HeaderTooltip>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_tooltips: Option<Vec<CellTooltip>>,
+    /// Columns `--redact` masked or hashed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redactions: Option<Vec<Redaction>>,
+    /// What a table tabex adds that isn't on the page holds, e.g. "links"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auxiliary: Option<String>,
+    /// How the page writes negative numbers, which every step reading the table's
+    /// numbers uses
+    #[serde(skip)]
+    pub sign_conventions: SignConventions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScoreSignal {
+    pub signal: String,
+    pub points: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColGroup {
+    pub name: Option<String>,
+    pub class: Option<String>,
+    pub width: Option<String>,
+    pub start_column: usize,
+    pub span: usize,
+    /// Headers of the columns covered by the group
+    pub columns: Vec<String>,
+    pub cols: Vec<Col>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Col {
+    pub class: Option<String>,
+    pub width: Option<String>,
+    pub span: usize,
+}
+
+/// Options that change how tables are read from the DOM.
+#[derive(Debug, Default)]
+pub struct ExtractOptions {
+    pub row_groups: bool,
+    pub exclude_totals: bool,
+    pub tag_totals: bool,
+    /// Lay data rows out on the span-expanded grid instead of reading td cells in order
+    pub spans: SpanMode,
+    /// Record each data row's class and background colour
+    pub row_styles: bool,
+    /// Keep each data cell's link target for --follow-column
+    pub cell_links: bool,
+    /// Record bold, struck-through and coloured data cells
+    pub cell_styles: bool,
+    /// Tables not started by this instant are skipped
+    pub deadline: Option<Instant>,
+    /// Set from another thread to skip the tables not yet started, as a passed
+    /// deadline does
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// How negative numbers are written, as with `--negatives`
+    pub sign_conventions: SignConventions,
+    /// Where steps that load further pages, such as `--follow-column`, report
+    pub events: Events,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableData {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Table {
+    pub metadata: TableMetadata,
+    pub data: TableData,
+    /// Link target of each data cell, laid out like `data.rows`, when `cell_links` is set
+    #[serde(skip)]
+    pub links: Option<Vec<Vec<Option<String>>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageMetadata {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub published_date: Option<String>,
+    pub last_modified: Option<String>,
+    pub robots: Option<String>,
+    pub noindex: bool,
+    /// License the page states for its content, if any
+    pub license: Option<License>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtractionResult {
+    pub page: PageMetadata,
+    pub tables: Vec<Table>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relationships: Option<Vec<Relationship>>,
+    /// The deadline passed before every table was extracted
+    pub timed_out: bool,
+    /// Requests made to fetch the page, more than 1 when `--retries` sent it again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+    pub extraction_time_ms: u128,
+}
+
+/// The tables of one document, from [`Extractor::extract_tables`].
+#[derive(Debug)]
+pub struct DocumentTables {
+    pub tables: Vec<Table>,
+    /// The deadline passed or extraction was cancelled before every table was
+    /// extracted
+    pub timed_out: bool,
+}
+
+/// One page of a run given several URLs: its result, or why it has none.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageOutcome {
+    pub url: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub result: Option<ExtractionResult>,
+}
+
+/// Tracking parameters removed from every URL before fetching.
+pub const DEFAULT_STRIP_PARAMS: &[&str] = &["utm_*", "gclid", "fbclid", "mc_cid", "mc_eid", "_ga"];
+
+pub fn canonicalize_url(url: &str, strip_params: &[String]) -> Result<String, TabexError> {
+    // Parsing lowercases the scheme and host, punycodes IDN hosts and drops default ports
+    let mut parsed = reqwest::Url::parse(url)
+        .map_err(|e| TabexError::Parse(format!("Invalid URL {}: {}", url, e)))?;
+    parsed.set_fragment(None);
+
+    let is_stripped = |key: &str| {
+        strip_params
+            .iter()
+            .any(|param| match param.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => key == param,
+            })
+    };
+    let mut query: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(key, _)| !is_stripped(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    query.sort();
+
+    if query.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(query);
+    }
+
+    Ok(parsed.into())
+}
+
+pub fn canonicalize_urls(
+    urls: &[&str],
+    strip_params: &[String],
+) -> Result<Vec<String>, TabexError> {
+    let mut seen = std::collections::HashSet::new();
+    let mut canonical = Vec::new();
+    for url in urls {
+        let url = canonicalize_url(url, strip_params)?;
+        if seen.insert(url.clone()) {
+            canonical.push(url);
+        }
+    }
+    Ok(canonical)
+}
+
+/// The URLs of a `--url` value: a comma-separated list when every part is an absolute
+/// http(s) URL, else the value as one URL, since URLs such as
+/// `https://en.wikipedia.org/wiki/Washington,_D.C.` contain commas themselves.
+pub fn split_url_list(value: &str) -> Vec<&str> {
+    let parts: Vec<&str> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    let is_web_url = |part: &&str| {
+        reqwest::Url::parse(part).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+    };
+    if parts.len() > 1 && parts.iter().all(is_web_url) {
+        parts
+    } else {
+        vec![value]
+    }
+}
+
+/// Whether a robots meta `content` such as "noindex, nofollow" gives `directive`;
+/// "none" gives both.
+pub fn has_robots_directive(content: &str, directive: &str) -> bool {
+    content
+        .split(',')
+        .map(str::trim)
+        .any(|d| d.eq_ignore_ascii_case(directive) || d.eq_ignore_ascii_case("none"))
+}
+
+// Selectors are compiled once and shared by every document in a run
+pub static TABLE_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("table").unwrap());
+static THEAD_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("thead").unwrap());
+static CAPTION_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("caption").unwrap());
+static TR_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("tr").unwrap());
+static TH_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("th").unwrap());
+static TD_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("td").unwrap());
+static CELL_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("th, td").unwrap());
+static HEADING_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("h1, h2, h3, h4, h5, h6").unwrap());
+static SECTION_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("section, article, div[role='main']").unwrap());
+static TITLE_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("title").unwrap());
+static META_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("meta[name], meta[property]").unwrap());
+static ANY_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("*").unwrap());
+static LINK_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a[href]").unwrap());
+
+/// Reads page metadata and tables out of parsed documents. One extractor is
+/// meant to be reused for every document in a run.
+pub struct Extractor {
+    options: ExtractOptions,
+}
+
+impl Extractor {
+    pub fn new(options: ExtractOptions) -> Extractor {
+        Extractor { options }
+    }
+
+    pub fn page_metadata(&self, document: &Html, url: &str) -> PageMetadata {
+        // Index meta tags by name and property in one pass; the first occurrence wins
+        let mut meta: HashMap<&str, &str> = HashMap::new();
+        for element in document.select(&META_SELECTOR) {
+            let Some(content) = element.value().attr("content") else {
+                continue;
+            };
+            for key in [
+                element.value().attr("name"),
+                element.value().attr("property"),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                meta.entry(key).or_insert(content);
+            }
+        }
+        let get_meta_content = |name: &str| meta.get(name).map(|content| content.to_string());
+
+        let title = document
+            .select(&TITLE_SELECTOR)
+            .next()
+            .map(|el| el.inner_html().trim().to_string());
+
+        let description =
+            get_meta_content("description").or_else(|| get_meta_content("og:description"));
+        let author = get_meta_content("author");
+        let published_date =
+            get_meta_content("article:published_time").or_else(|| get_meta_content("pubdate"));
+        let last_modified =
+            get_meta_content("article:modified_time").or_else(|| get_meta_content("lastmod"));
+
+        // Robots directives, e.g. "noindex, nofollow"
+        let robots = get_meta_content("robots");
+        let noindex = robots
+            .as_deref()
+            .is_some_and(|content| has_robots_directive(content, "noindex"));
+        let license = license::page_license(document, url, &meta);
+
+        PageMetadata {
+            url: url.to_string(),
+            title,
+            description,
+            author,
+            published_date,
+            last_modified,
+            robots,
+            noindex,
+            license,
+        }
+    }
+
+    pub fn events(&self) -> &Events {
+        &self.options.events
+    }
+
+    /// Whether the deadline has passed or extraction was cancelled, so no further
+    /// tables will be started.
+    pub fn expired(&self) -> bool {
+        self.options
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || self
+                .options
+                .cancel
+                .as_ref()
+                .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Extracts every table of `document`, noting whether the deadline or a
+    /// cancellation cut the list short.
+    pub fn extract_tables(&self, document: &Html) -> Result<DocumentTables, TabexError> {
+        let tables = self.tables(document).collect::<Result<Vec<_>, _>>()?;
+        // `tables` yields one table per element unless it stopped early
+        let timed_out = tables.len() < document.select(&TABLE_SELECTOR).count();
+        Ok(DocumentTables { tables, timed_out })
+    }
+
+    /// Yields tables lazily in document order, so callers can stop at the first match.
+    /// Iteration ends early once [`Extractor::expired`].
+    pub fn tables<'a>(
+        &'a self,
+        document: &'a Html,
+    ) -> impl Iterator<Item = Result<Table, TabexError>> + 'a {
+        document
+            .select(&TABLE_SELECTOR)
+            .enumerate()
+            .take_while(move |_| !self.expired())
+            .map(move |(index, table_element)| {
+                self.extract_table(document, table_element, index + 1)
+            })
+            // Identical tables in the same container are told apart by their order
+            .scan(
+                HashMap::new(),
+                |seen: &mut HashMap<String, usize>, table| {
+                    Some(table.map(|mut table| {
+                        let count = seen.entry(table.metadata.table_key.clone()).or_insert(0);
+                        *count += 1;
+                        if *count > 1 {
+                            table.metadata.table_key =
+                                format!("{}-{}", table.metadata.table_key, count);
+                        }
+                        table
+                    }))
+                },
+            )
+    }
+
+    fn extract_table(
+        &self,
+        document: &Html,
+        table_element: scraper::ElementRef,
+        table_position: usize,
+    ) -> Result<Table, TabexError> {
+        // Get table attributes
+        let id = table_element.value().attr("id").map(String::from);
+        let class = table_element.value().attr("class").map(String::from);
+
+        // Get caption
+        let caption = table_element
+            .select(&CAPTION_SELECTOR)
+            .next()
+            .map(|cap| cap.inner_html().trim().to_string());
+
+        // Get parent section
+        let parent_section = find_parent_with_selector(table_element, &SECTION_SELECTOR)
+            .and_then(|section| {
+                section
+                    .value()
+                    .attr("id")
+                    .or_else(|| section.value().attr("class"))
+            })
+            .map(String::from);
+
+        // Find preceding heading
+        let preceding_heading = find_preceding_heading(table_element, document);
+
+        // Process rows
+        let rows_elements: Vec<_> = table_element.select(&TR_SELECTOR).collect();
+        let row_count = rows_elements.len();
+
+        // Spec sheets name each row in a leading th, e.g. <th>Weight</th><td>1.2 kg</td>;
+        // those rows are data, not header or footer rows
+        let is_row_headed = |row: &scraper::ElementRef| {
+            row.select(&CELL_SELECTOR)
+                .next()
+                .is_some_and(|cell| cell.value().name() == "th")
+                && row.select(&TH_SELECTOR).count() == 1
+                && row.select(&TD_SELECTOR).next().is_some()
+        };
+        let leading_header_rows = rows_elements
+            .iter()
+            .take_while(|row| row.select(&TH_SELECTOR).next().is_some() && !is_row_headed(row))
+            .count();
+        let row_headed = row_count >= leading_header_rows + 2
+            && rows_elements[leading_header_rows..]
+                .iter()
+                .filter(|row| find_parent_with_tag(**row, "tfoot").is_none())
+                .all(is_row_headed);
+
+        // Count header and footer rows
+        let header_row_count = if row_headed {
+            leading_header_rows
+        } else {
+            rows_elements
+                .iter()
+                .take_while(|row| row.select(&TH_SELECTOR).next().is_some())
+                .count()
+        };
+
+        // Count footer rows (rows in tfoot or with th elements at end)
+        let footer_row_count = rows_elements
+            .iter()
+            .rev()
+            .take_while(|row| {
+                let is_in_tfoot = find_parent_with_tag(**row, "tfoot").is_some();
+                is_in_tfoot || (!row_headed && row.select(&TH_SELECTOR).next().is_some())
+            })
+            .count();
+
+        let data_row_count = if row_count > header_row_count + footer_row_count {
+            row_count - header_row_count - footer_row_count
+        } else {
+            0 // Fallback to 0 if counts are invalid
+        };
+
+        // Extract headers, flattening multi-row headers into "Group Leaf" names
+        let (mut headers, column_groups) = if header_row_count > 1 {
+            grid::flatten_headers(&rows_elements[..header_row_count])
+        } else if header_row_count > 0 {
+            let headers = rows_elements[0]
+                .select(&TH_SELECTOR)
+                .map(clean_cell_text)
+                .collect::<Vec<String>>(); // Using turbofish
+            (headers, Vec::new())
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+        let mut header_tooltips = match header_row_count {
+            0 => Vec::new(),
+            1 => rows_elements[0]
+                .select(&TH_SELECTOR)
+                .map(tooltips::cell_tooltip)
+                .collect(),
+            _ => tooltips::header_tooltips(&rows_elements[..header_row_count]),
+        };
+
+        // Count columns based on the row with the most cells
+        let mut column_count = rows_elements
+            .iter()
+            .map(|row| row.select(&TH_SELECTOR).count() + row.select(&TD_SELECTOR).count())
+            .max()
+            .unwrap_or(0);
+
+        let colgroups = extract_colgroups(table_element, &headers);
+        let notes = extract_notes(table_element, document);
+
+        // Map header cell ids to their columns for cells using the headers attribute
+        let header_columns = header_columns_by_id(&rows_elements[..header_row_count]);
+
+        // Extract data rows, with the row header cells of spec sheets
+        let data_cell_selector: &Selector = if row_headed {
+            &CELL_SELECTOR
+        } else {
+            &TD_SELECTOR
+        };
+        let data_row_elements = &rows_elements[header_row_count..header_row_count + data_row_count];
+        let expand_spans = self.options.spans != SpanMode::Off;
+        let mut data_rows: Vec<Vec<String>> = if expand_spans {
+            // Repeat rowspan/colspan cells (e.g. a day spanning several sessions) into every
+            // row, or only into their first slot, then pad rows out to the full width
+            let blank = self.options.spans == SpanMode::Blank;
+            let mut data_rows: Vec<Vec<String>> = grid::expand_spans(data_row_elements)
+                .into_iter()
+                .map(|slots| {
+                    slots
+                        .into_iter()
+                        .map(|slot| match slot {
+                            Some(slot) if slot.origin || !blank => slot.text,
+                            _ => String::new(),
+                        })
+                        .collect()
+                })
+                .collect();
+            column_count = data_rows
+                .iter()
+                .map(Vec::len)
+                .chain([column_count, headers.len()])
+                .max()
+                .unwrap_or(0);
+            for row in &mut data_rows {
+                row.resize(column_count, String::new());
+            }
+            data_rows
+        } else {
+            data_row_elements
+                .iter()
+                .map(|row| {
+                    let mut cells: Vec<String> = Vec::new();
+                    for (position, cell) in row.select(data_cell_selector).enumerate() {
+                        let column =
+                            resolve_headers_attr(cell, &header_columns).unwrap_or(position);
+                        if cells.len() <= column {
+                            cells.resize(column + 1, String::new());
+                        }
+                        cells[column] = clean_cell_text(cell);
+                    }
+                    cells
+                })
+                .collect()
+        };
+
+        // Per-cell extras share the data rows' layout so they survive totals and grouping
+        let mut data_links: Vec<Vec<Option<String>>> = if self.options.cell_links {
+            lay_out_cells(
+                data_row_elements,
+                expand_spans,
+                data_cell_selector,
+                &header_columns,
+                |slot| slot.href,
+                cell_href,
+            )
+        } else {
+            Vec::new()
+        };
+        let mut data_tooltips = lay_out_cells(
+            data_row_elements,
+            expand_spans,
+            data_cell_selector,
+            &header_columns,
+            |slot| slot.tooltip,
+            tooltips::cell_tooltip,
+        );
+        let mut data_styles: Vec<Vec<Option<StyleFlags>>> = if self.options.cell_styles {
+            lay_out_cells(
+                data_row_elements,
+                expand_spans,
+                data_cell_selector,
+                &header_columns,
+                |slot| slot.style,
+                rows::style_flags,
+            )
+        } else {
+            Vec::new()
+        };
+
+        // Fold indented and grouped row labels into a group column when asked
+        let mut row_hints = rows::row_hints(data_row_elements, &data_rows);
+        let hierarchical_rows = rows::is_hierarchical(&row_hints);
+
+        // Classify totals, then drop or tag them
+        let row_totals =
+            rows::classify_totals(&data_rows, &row_hints, self.options.sign_conventions);
+        if self.options.exclude_totals {
+            data_links = drop_totals(data_links, &row_totals);
+            data_tooltips = drop_totals(data_tooltips, &row_totals);
+            data_styles = drop_totals(data_styles, &row_totals);
+            (data_rows, row_hints) = data_rows
+                .into_iter()
+                .zip(row_hints)
+                .zip(&row_totals)
+                .filter(|(_, total)| total.is_none())
+                .map(|(row, _)| row)
+                .unzip();
+        } else if self.options.tag_totals {
+            let width = headers.len();
+            if width > 0 {
+                headers.push("row_type".to_string());
+            }
+            for (row, total) in data_rows.iter_mut().zip(&row_totals) {
+                if row.len() < width {
+                    row.resize(width, String::new());
+                }
+                row.push(total.as_ref().map(|t| t.kind.clone()).unwrap_or_default());
+            }
+        }
+        let total_rows: Vec<TotalRow> = row_totals.into_iter().flatten().collect();
+        let grouped = self.options.row_groups && hierarchical_rows;
+        if grouped {
+            data_rows = rows::apply_row_groups(&mut headers, data_rows, &row_hints);
+            data_links = group_layout(data_links, &row_hints);
+            data_tooltips = group_layout(data_tooltips, &row_hints);
+            data_styles = group_layout(data_styles, &row_hints);
+            header_tooltips.insert(0, None);
+        }
+
+        let row_styles = self.options.row_styles.then(|| {
+            row_hints
+                .iter()
+                // Grouping folds group header rows into the group column
+                .filter(|hint| !(grouped && hint.group_header))
+                .enumerate()
+                .filter(|(_, hint)| hint.class.is_some() || hint.background.is_some())
+                .map(|(i, hint)| RowStyle {
+                    row: i + 1,
+                    class: hint.class.clone(),
+                    background: hint.background.clone(),
+                })
+                .collect()
+        });
+
+        // Score the table so layout tables can be told apart from data tables
+        let score_breakdown = score_table(
+            table_element,
+            &rows_elements,
+            header_row_count,
+            column_count,
+            caption.is_some(),
+        );
+        let score = score_breakdown.iter().map(|signal| signal.points).sum();
+        let key = table_key(table_element, caption.as_deref(), &headers);
+        let header_tooltips = tooltips::list_header_tooltips(header_tooltips, &headers);
+        let cell_tooltips = tooltips::list_cell_tooltips(data_tooltips);
+        let cell_styles = self
+            .options
+            .cell_styles
+            .then(|| rows::list_cell_styles(data_styles));
+
+        // Create table object
+        let mut table = Table {
+            metadata: TableMetadata {
+                name: None,
+                id,
+                class,
+                caption,
+                position: table_position,
+                table_key: key,
+                row_count,
+                column_count,
+                header_row_count,
+                footer_row_count,
+                parent_section,
+                preceding_heading,
+                score,
+                score_breakdown,
+                matrix: false,
+                key_value: false,
+                hierarchical_rows,
+                total_rows,
+                colgroups,
+                column_groups,
+                notes,
+                schema: Vec::new(),
+                analysis: None,
+                row_styles,
+                cell_styles,
+                standings: None,
+                follow: None,
+                header_matches: None,
+                original_values: None,
+                lineage: None,
+                snapshots: None,
+                header_tooltips: (!header_tooltips.is_empty()).then_some(header_tooltips),
+                cell_tooltips: (!cell_tooltips.is_empty()).then_some(cell_tooltips),
+                redactions: None,
+                auxiliary: None,
+                sign_conventions: self.options.sign_conventions,
+            },
+            data: TableData {
+                headers,
+                rows: data_rows,
+            },
+            links: self.options.cell_links.then_some(data_links),
+        };
+        table.metadata.matrix = analysis::is_matrix(&table);
+        table.metadata.key_value = analysis::is_key_value(&table, row_headed);
+
+        Ok(table)
+    }
+}
+
+/// Lays a per-cell value out like the data rows: on the span-expanded grid, or by
+/// the column each td (or its headers attribute) lands in.
+fn lay_out_cells<'a, T: Clone>(
+    rows: &[scraper::ElementRef<'a>],
+    expand_spans: bool,
+    cell_selector: &Selector,
+    header_columns: &HashMap<String, usize>,
+    from_slot: fn(grid::Slot) -> Option<T>,
+    from_cell: fn(scraper::ElementRef<'a>) -> Option<T>,
+) -> Vec<Vec<Option<T>>> {
+    if expand_spans {
+        return grid::expand_spans(rows)
+            .into_iter()
+            .map(|slots| {
+                slots
+                    .into_iter()
+                    .map(|slot| slot.and_then(from_slot))
+                    .collect()
+            })
+            .collect();
+    }
+    rows.iter()
+        .map(|row| {
+            let mut values: Vec<Option<T>> = Vec::new();
+            for (position, cell) in row.select(cell_selector).enumerate() {
+                let column = resolve_headers_attr(cell, header_columns).unwrap_or(position);
+                if values.len() <= column {
+                    values.resize(column + 1, None);
+                }
+                values[column] = from_cell(cell);
+            }
+            values
+        })
+        .collect()
+}
+
+/// Drops the rows of a per-cell layout that --exclude-totals removed.
+fn drop_totals<T>(layout: Vec<T>, totals: &[Option<TotalRow>]) -> Vec<T> {
+    layout
+        .into_iter()
+        .zip(totals)
+        .filter(|(_, total)| total.is_none())
+        .map(|(row, _)| row)
+        .collect()
+}
+
+/// Follows --row-groups on a per-cell layout: group header rows go and the group
+/// column comes first.
+fn group_layout<T>(layout: Vec<Vec<Option<T>>>, hints: &[RowHint]) -> Vec<Vec<Option<T>>> {
+    layout
+        .into_iter()
+        .zip(hints)
+        .filter(|(_, hint)| !hint.group_header)
+        .map(|(mut row, _)| {
+            row.insert(0, None);
+            row
+        })
+        .collect()
+}
+
+/// Collects notes that travel with a table: elements referenced by `aria-describedby`
+/// and an immediately following `<small>` or note-like paragraph.
+fn extract_notes(table_element: scraper::ElementRef, document: &Html) -> Vec<String> {
+    let mut notes = Vec::new();
+
+    if let Some(ids) = table_element.value().attr("aria-describedby") {
+        for id in ids.split_whitespace() {
+            let Ok(selector) = Selector::parse(&format!("[id=\"{}\"]", id.replace('"', "\\\"")))
+            else {
+                continue;
+            };
+            if let Some(element) = document.select(&selector).next() {
+                notes.push(clean_cell_text(element));
+            }
+        }
+    }
+
+    let next_element = table_element
+        .next_siblings()
+        .find(|node| match node.value() {
+            scraper::Node::Text(text) => !text.trim().is_empty(),
+            scraper::Node::Element(_) => true,
+            _ => false,
+        })
+        .and_then(scraper::ElementRef::wrap);
+    if let Some(element) = next_element {
+        let tag = element.value().name();
+        let class = element
+            .value()
+            .attr("class")
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let is_note = tag == "small"
+            || (matches!(tag, "p" | "div" | "span")
+                && ["note", "footnote", "source"]
+                    .iter()
+                    .any(|marker| class.contains(marker)));
+        if is_note {
+            let text = clean_cell_text(element);
+            if !text.is_empty() && !notes.contains(&text) {
+                notes.push(text);
+            }
+        }
+    }
+
+    notes
+}
+
+fn span_attr(element: scraper::ElementRef, name: &str) -> usize {
+    element
+        .value()
+        .attr(name)
+        .and_then(|span| span.trim().parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1)
+}
+
+fn child_elements<'a>(element: scraper::ElementRef<'a>, tag: &str) -> Vec<scraper::ElementRef<'a>> {
+    element
+        .children()
+        .filter_map(scraper::ElementRef::wrap)
+        .filter(|child| child.value().name().eq_ignore_ascii_case(tag))
+        .collect()
+}
+
+/// Reads the table's own `<colgroup>`/`<col>` elements (not those of nested tables).
+fn extract_colgroups(table_element: scraper::ElementRef, headers: &[String]) -> Vec<ColGroup> {
+    let attr =
+        |element: scraper::ElementRef, name: &str| element.value().attr(name).map(String::from);
+
+    let mut colgroups = Vec::new();
+    let mut start_column = 0;
+    for colgroup in child_elements(table_element, "colgroup") {
+        let cols: Vec<Col> = child_elements(colgroup, "col")
+            .into_iter()
+            .map(|col| Col {
+                class: attr(col, "class"),
+                width: attr(col, "width"),
+                span: span_attr(col, "span"),
+            })
+            .collect();
+        let span = if cols.is_empty() {
+            span_attr(colgroup, "span")
+        } else {
+            cols.iter().map(|col| col.span).sum()
+        };
+
+        colgroups.push(ColGroup {
+            name: attr(colgroup, "id")
+                .or_else(|| attr(colgroup, "title"))
+                .or_else(|| attr(colgroup, "class")),
+            class: attr(colgroup, "class"),
+            width: attr(colgroup, "width"),
+            start_column,
+            span,
+            columns: headers
+                .iter()
+                .skip(start_column)
+                .take(span)
+                .cloned()
+                .collect(),
+            cols,
+        });
+        start_column += span;
+    }
+
+    colgroups
+}
+
+/// Column index of every header cell that has an id, accounting for colspans.
+fn header_columns_by_id(header_rows: &[scraper::ElementRef]) -> HashMap<String, usize> {
+    let mut columns = HashMap::new();
+
+    for row in header_rows {
+        let mut column = 0;
+        for cell in row.select(&CELL_SELECTOR) {
+            if let Some(id) = cell.value().attr("id") {
+                columns.insert(id.to_string(), column);
+            }
+            column += span_attr(cell, "colspan");
+        }
+    }
+
+    columns
+}
+
+/// Resolves a cell's `headers="r1 c3"` attribute to the column of its column header.
+/// Ids that don't name a column header (such as row headers) are ignored.
+fn resolve_headers_attr(
+    cell: scraper::ElementRef,
+    header_columns: &HashMap<String, usize>,
+) -> Option<usize> {
+    cell.value()
+        .attr("headers")?
+        .split_whitespace()
+        .filter_map(|id| header_columns.get(id).copied())
+        .max()
+}
+
+fn score_table(
+    table_element: scraper::ElementRef,
+    rows: &[scraper::ElementRef],
+    header_row_count: usize,
+    column_count: usize,
+    has_caption: bool,
+) -> Vec<ScoreSignal> {
+    let mut signals = Vec::new();
+    let mut add = |signal: &str, points: i32| {
+        signals.push(ScoreSignal {
+            signal: signal.to_string(),
+            points,
+        })
+    };
+
+    if header_row_count > 0 {
+        add("header_cells", 3);
+    }
+    if has_caption {
+        add("caption", 2);
+    }
+    if table_element.select(&THEAD_SELECTOR).next().is_some() {
+        add("thead", 1);
+    }
+
+    match rows.len() {
+        0 | 1 => add("single_row", -2),
+        2 => {}
+        _ => add("multiple_rows", 1),
+    }
+    if column_count <= 1 {
+        add("single_column", -2);
+    } else {
+        add("multiple_columns", 1);
+    }
+
+    if table_element.select(&TABLE_SELECTOR).next().is_some() {
+        add("nested_table", -3);
+    }
+
+    let role = table_element.value().attr("role").unwrap_or("");
+    if role.eq_ignore_ascii_case("presentation") || role.eq_ignore_ascii_case("none") {
+        add("presentation_role", -5);
+    }
+
+    // Mostly empty grids are usually spacers rather than data
+    let (cells, empty_cells) = rows.iter().flat_map(|row| row.select(&CELL_SELECTOR)).fold(
+        (0, 0),
+        |(cells, empty), cell| {
+            let is_empty = cell.text().all(|text| text.trim().is_empty());
+            (cells + 1, empty + is_empty as usize)
+        },
+    );
+    if cells > 0 && empty_cells * 2 > cells {
+        add("sparse_cells", -2);
+    }
+
+    signals
+}
+
+/// 64-bit FNV-1a, stable across runs and platforms unlike std's hasher.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Hash of the table's DOM path, caption and headers. The path names each ancestor
+/// by tag, id and class but not by sibling index, so tables added or removed
+/// elsewhere on the page leave the key unchanged.
+fn table_key(
+    table_element: scraper::ElementRef,
+    caption: Option<&str>,
+    headers: &[String],
+) -> String {
+    let mut path: Vec<String> = table_element
+        .ancestors()
+        .filter_map(scraper::ElementRef::wrap)
+        .map(|ancestor| {
+            let element = ancestor.value();
+            let mut step = element.name().to_string();
+            if let Some(id) = element.attr("id") {
+                step.push('#');
+                step.push_str(id);
+            }
+            for class in element.classes() {
+                step.push('.');
+                step.push_str(class);
+            }
+            step
+        })
+        .collect();
+    path.reverse();
+
+    let signature = format!(
+        "{}\n{}\n{}",
+        path.join(">"),
+        caption.unwrap_or(""),
+        headers.join("\u{1f}")
+    );
+    format!("{:016x}", fnv1a(signature.bytes()))
+}
+
+fn find_parent_with_selector<'a>(
+    element: scraper::ElementRef<'a>,
+    selector: &Selector,
+) -> Option<scraper::ElementRef<'a>> {
+    let mut current = element;
+
+    while let Some(parent_node) = current.parent() {
+        if let Some(parent_element) = scraper::ElementRef::wrap(parent_node) {
+            if selector.matches(&parent_element) {
+                return Some(parent_element);
+            }
+            current = parent_element;
+        } else {
+            // If parent is not an element, skip it
+            current = scraper::ElementRef::wrap(parent_node.parent()?)?;
+        }
+    }
+    None
+}
+
+fn find_parent_with_tag<'a>(
+    element: scraper::ElementRef<'a>,
+    tag_name: &str,
+) -> Option<scraper::ElementRef<'a>> {
+    let mut current = element;
+
+    while let Some(parent_node) = current.parent() {
+        if let Some(parent_element) = scraper::ElementRef::wrap(parent_node) {
+            if parent_element.value().name().eq_ignore_ascii_case(tag_name) {
+                return Some(parent_element);
+            }
+            current = parent_element;
+        } else {
+            // If parent is not an element, skip it
+            current = scraper::ElementRef::wrap(parent_node.parent()?)?;
+        }
+    }
+    None
+}
+
+fn find_preceding_heading(element: scraper::ElementRef, document: &Html) -> Option<String> {
+    // This is a simplified approach - ideally you'd traverse the DOM tree
+    // For simplicity, we'll just get all headings and find the last one before our table
+    let all_headings: Vec<_> = document.select(&HEADING_SELECTOR).collect();
+    let all_elements: Vec<_> = document.select(&ANY_SELECTOR).collect();
+
+    let table_pos = all_elements.iter().position(|&el| el == element)?;
+
+    all_headings
+        .into_iter()
+        .filter_map(|heading| {
+            let heading_pos = all_elements.iter().position(|&el| el == heading)?;
+            if heading_pos < table_pos {
+                Some((heading_pos, heading.inner_html().trim().to_string()))
+            } else {
+                None
+            }
+        })
+        .max_by_key(|(pos, _)| *pos)
+        .map(|(_, text)| text)
+}
+
+/// The `href` of the first link in a cell, as written in the markup.
+fn cell_href(cell: scraper::ElementRef) -> Option<String> {
+    cell.select(&LINK_SELECTOR)
+        .next()
+        .and_then(|link| link.value().attr("href"))
+        .map(str::trim)
+        .filter(|href| !href.is_empty())
+        .map(String::from)
+}
+
+/// Text of a cell with whitespace runs collapsed to single spaces and trimmed. Reads
+/// the DOM's decoded text nodes directly in one pass, so entities such as `&amp;` and
+/// `&nbsp;` come out as characters rather than escapes.
+fn clean_cell_text(element: scraper::ElementRef) -> String {
+    let mut text = String::new();
+    let mut pending_space = false;
+    for piece in element.text() {
+        for c in piece.chars() {
+            if c.is_whitespace() {
+                pending_space = !text.is_empty();
+            } else {
+                if pending_space {
+                    text.push(' ');
+                    pending_space = false;
+                }
+                text.push(c);
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn extract(html: &str, options: ExtractOptions) -> Vec<Table> {
+        let document = Html::parse_document(html);
+        let extractor = Extractor::new(options);
+        extractor.tables(&document).map(Result::unwrap).collect()
+    }
+
+    /// Metadata of a page with nothing in its head.
+    pub(crate) fn page(url: &str) -> PageMetadata {
+        Extractor::new(ExtractOptions::default()).page_metadata(&Html::parse_document(""), url)
+    }
+
+    fn signals(table: &Table) -> Vec<&str> {
+        table
+            .metadata
+            .score_breakdown
+            .iter()
+            .map(|signal| signal.signal.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn scores_data_tables_above_layout_tables() {
+        let tables = extract(
+            "<table><caption>Sales</caption><thead><tr><th>Year</th><th>Units</th></tr></thead>
+             <tbody><tr><td>2023</td><td>10</td></tr><tr><td>2024</td><td>12</td></tr></tbody></table>
+             <table role=\"presentation\"><tr><td>x</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        let data = &tables[0];
+        assert_eq!(
+            signals(data),
+            [
+                "header_cells",
+                "caption",
+                "thead",
+                "multiple_rows",
+                "multiple_columns"
+            ]
+        );
+        assert_eq!(data.metadata.score, 8);
+
+        let layout = &tables[1];
+        assert_eq!(
+            signals(layout),
+            ["single_row", "single_column", "presentation_role"]
+        );
+        assert_eq!(layout.metadata.score, -9);
+    }
+
+    #[test]
+    fn penalizes_tables_that_wrap_other_tables() {
+        let tables = extract(
+            "<table><tr><td><table><tr><td>a</td><td>b</td></tr></table></td></tr></table>",
+            ExtractOptions::default(),
+        );
+        assert!(signals(&tables[0]).contains(&"nested_table"));
+        assert!(!signals(&tables[1]).contains(&"nested_table"));
+    }
+
+    #[test]
+    fn penalizes_mostly_empty_grids() {
+        let tables = extract(
+            "<table><tr><td></td><td></td></tr><tr><td>&nbsp;</td><td>x</td></tr>
+             <tr><td></td><td></td></tr></table>",
+            ExtractOptions::default(),
+        );
+        assert!(signals(&tables[0]).contains(&"sparse_cells"));
+    }
+
+    fn strip() -> Vec<String> {
+        DEFAULT_STRIP_PARAMS.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn canonicalizes_scheme_host_port_and_fragment() {
+        assert_eq!(
+            canonicalize_url("HTTPS://Example.COM:443/Stats#top", &strip()).unwrap(),
+            "https://example.com/Stats"
+        );
+        assert_eq!(
+            canonicalize_url("http://example.com:8080/a", &strip()).unwrap(),
+            "http://example.com:8080/a"
+        );
+        assert_eq!(
+            canonicalize_url("https://bücher.example/", &strip()).unwrap(),
+            "https://xn--bcher-kva.example/"
+        );
+    }
+
+    #[test]
+    fn strips_tracking_params_and_sorts_the_rest() {
+        assert_eq!(
+            canonicalize_url(
+                "https://example.com/t?b=2&utm_source=x&a=1&gclid=z&utm_medium=y",
+                &strip()
+            )
+            .unwrap(),
+            "https://example.com/t?a=1&b=2"
+        );
+        assert_eq!(
+            canonicalize_url("https://example.com/t?utm_source=x&fbclid=1", &strip()).unwrap(),
+            "https://example.com/t"
+        );
+        // Only a trailing * is a wildcard; "gclid" doesn't match "gclid_extra"
+        assert_eq!(
+            canonicalize_url("https://example.com/?gclid_extra=1", &strip()).unwrap(),
+            "https://example.com/?gclid_extra=1"
+        );
+    }
+
+    #[test]
+    fn rejects_urls_that_dont_parse() {
+        assert!(canonicalize_url("not a url", &strip()).is_err());
+        assert!(canonicalize_url("", &strip()).is_err());
+    }
+
+    #[test]
+    fn splits_url_lists_only_into_urls() {
+        assert_eq!(
+            split_url_list("https://example.com/a, https://example.com/b,"),
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        // Commas inside a single URL stay
+        for value in [
+            "https://en.wikipedia.org/wiki/Washington,_D.C.",
+            "https://example.com/?ids=1,2,3",
+            "https://example.com/a,b",
+            "-",
+        ] {
+            assert_eq!(split_url_list(value), [value]);
+        }
+    }
+
+    #[test]
+    fn deduplicates_urls_that_canonicalize_alike() {
+        let urls = canonicalize_urls(
+            &[
+                "https://example.com/a?x=1&y=2",
+                "https://EXAMPLE.com/a?y=2&x=1#frag",
+                "https://example.com/a?utm_source=feed&x=1&y=2",
+                "https://example.com/b",
+            ],
+            &strip(),
+        )
+        .unwrap();
+        assert_eq!(
+            urls,
+            ["https://example.com/a?x=1&y=2", "https://example.com/b"]
+        );
+    }
+
+    #[test]
+    fn places_cells_by_their_headers_attribute() {
+        let tables = extract(
+            "<table><tr><th id=\"name\">Name</th><th id=\"q1\">Q1</th><th id=\"q2\">Q2</th></tr>
+             <tr><td headers=\"name\">Ann</td><td headers=\"ann q2\">7</td></tr>
+             <tr><td headers=\"name\">Bob</td><td headers=\"q1\">3</td><td headers=\"q2\">4</td></tr>
+             </table>",
+            ExtractOptions::default(),
+        );
+        assert_eq!(
+            tables[0].data.rows,
+            [vec!["Ann", "", "7"], vec!["Bob", "3", "4"]]
+        );
+    }
+
+    #[test]
+    fn resolves_header_ids_across_colspans() {
+        let tables = extract(
+            "<table><tr><th colspan=\"2\" id=\"who\">Who</th><th id=\"score\">Score</th></tr>
+             <tr><td headers=\"score\">9</td><td headers=\"nowhere\">x</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        // An unknown id falls back to the cell's position
+        assert_eq!(tables[0].data.rows, [vec!["", "x", "9"]]);
+    }
+
+    #[test]
+    fn cancelling_stops_only_the_calls_it_overlaps() {
+        let document = Html::parse_document(
+            "<table><tr><td>1</td></tr></table><table><tr><td>2</td></tr></table>",
+        );
+        let cancel = Arc::new(AtomicBool::new(true));
+        let extractor = Extractor::new(ExtractOptions {
+            cancel: Some(Arc::clone(&cancel)),
+            ..ExtractOptions::default()
+        });
+        let cancelled = extractor.extract_tables(&document).unwrap();
+        assert!(cancelled.tables.is_empty() && cancelled.timed_out);
+
+        cancel.store(false, Ordering::Relaxed);
+        let resumed = extractor.extract_tables(&document).unwrap();
+        assert_eq!(resumed.tables.len(), 2);
+        assert!(!resumed.timed_out);
+    }
+
+    #[test]
+    fn extractor_can_be_shared_across_threads() {
+        fn shareable<T: Send + Sync>() {}
+        shareable::<Extractor>();
+    }
+}
@@ -0,0 +1,415 @@
+//! Usage rights a page states for its content: license links, schema.org markup,
+//! license meta tags, Creative Commons badges and footer notices, in that order of
+//! trust.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::clean_cell_text;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct License {
+    /// Short name such as "CC BY-SA 4.0", when the license is recognized
+    pub name: Option<String>,
+    /// The license's URL, resolved against the page
+    pub url: Option<String>,
+    /// Where it was found: "rel-license", "schema.org", "meta", "creative-commons" or
+    /// "footer"
+    pub source: String,
+}
+
+impl License {
+    /// The license for display, e.g. "CC BY 4.0 (https://creativecommons.org/licenses/by/4.0/)".
+    pub fn label(&self) -> String {
+        match (&self.name, &self.url) {
+            (Some(name), Some(url)) => format!("{} ({})", name, url),
+            (Some(name), None) => name.clone(),
+            (None, Some(url)) => url.clone(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+static REL_LICENSE_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("a[rel][href], link[rel][href]").unwrap());
+static JSON_LD_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("script[type='application/ld+json']").unwrap());
+static ITEMPROP_LICENSE_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("[itemprop~='license']").unwrap());
+static CC_LINK_SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse(
+        "a[href*='creativecommons.org/licenses/'], a[href*='creativecommons.org/publicdomain/'], \
+         img[src*='creativecommons.org/l/'], img[src*='licensebuttons.net/l/']",
+    )
+    .unwrap()
+});
+static FOOTER_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("footer, [role='contentinfo'], #footer, .footer").unwrap());
+
+/// Meta tag names that hold a license or rights statement, compared ignoring case.
+const LICENSE_META: &[&str] = &["license", "dcterms.license", "dc.rights", "dcterms.rights"];
+
+/// License URLs other than Creative Commons', by a distinctive part of the URL.
+const KNOWN_LICENSES: &[(&str, &str)] = &[
+    ("opendatacommons.org/licenses/odbl", "ODbL"),
+    ("opendatacommons.org/licenses/by", "ODC-By"),
+    ("opendatacommons.org/licenses/pddl", "PDDL"),
+    ("nationalarchives.gov.uk/doc/open-government-licence", "OGL"),
+    ("gnu.org/licenses/fdl", "GFDL"),
+    ("gnu.org/copyleft/fdl", "GFDL"),
+    ("opensource.org/licenses/mit", "MIT"),
+    ("apache.org/licenses/license-2.0", "Apache-2.0"),
+];
+
+/// A Creative Commons license or public domain tool URL, e.g.
+/// "https://creativecommons.org/licenses/by-sa/4.0/" or a badge image under "/l/by-sa/4.0/".
+static CC_URL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)/(?:licenses|l)/((?:by|nc|sa|nd)(?:-(?:nc|sa|nd))*)/(\d\.\d)|/(?:publicdomain|p)/(zero|mark)/(\d\.\d)")
+        .unwrap()
+});
+
+/// "Creative Commons Attribution-ShareAlike 4.0", as footers spell licenses out.
+static CC_TEXT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)creative\s+commons\s+(attribution(?:[-\s]+(?:share-?alike|non-?commercial|no-?deriv(?:ative)?s))*)(?:\s+(\d\.\d))?")
+        .unwrap()
+});
+
+/// "CC BY-NC 4.0" or "CC0".
+static CC_SHORT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bCC[ -]?(0|BY(?:-(?:NC|SA|ND))*)(?:[ -](\d\.\d))?\b").unwrap());
+
+/// Notices recognized in footer text that aren't Creative Commons licenses.
+static FOOTER_NOTICES: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    [
+        (r"(?i)open\s+government\s+licen[cs]e", "OGL"),
+        (r"(?i)\bpublic\s+domain\b", "Public domain"),
+        (r"(?i)\ball\s+rights\s+reserved\b", "All rights reserved"),
+    ]
+    .into_iter()
+    .map(|(pattern, name)| (Regex::new(pattern).unwrap(), name))
+    .collect()
+});
+
+/// The short name of a license URL, when it is one we know.
+fn name_from_url(url: &str) -> Option<String> {
+    if url.to_lowercase().contains("creativecommons.org") || url.contains("licensebuttons.net") {
+        let captures = CC_URL_RE.captures(url)?;
+        if let (Some(terms), Some(version)) = (captures.get(1), captures.get(2)) {
+            return Some(format!(
+                "CC {} {}",
+                terms.as_str().to_uppercase(),
+                version.as_str()
+            ));
+        }
+        let version = captures.get(4)?.as_str();
+        return Some(match captures.get(3)?.as_str().to_lowercase().as_str() {
+            "zero" => format!("CC0 {}", version),
+            _ => format!("Public Domain Mark {}", version),
+        });
+    }
+    let lower = url.to_lowercase();
+    KNOWN_LICENSES
+        .iter()
+        .find(|(fragment, _)| lower.contains(fragment))
+        .map(|(_, name)| name.to_string())
+}
+
+/// The short name of a license spelled out in text, e.g. "CC BY-SA 4.0" for "Creative
+/// Commons Attribution-ShareAlike 4.0 International".
+fn name_from_text(text: &str) -> Option<String> {
+    if let Some(captures) = CC_TEXT_RE.captures(text) {
+        let words = captures[1].to_lowercase();
+        let mut terms = vec!["BY"];
+        for (word, term) in [("commercial", "NC"), ("deriv", "ND"), ("alike", "SA")] {
+            if words.contains(word) {
+                terms.push(term);
+            }
+        }
+        let name = format!("CC {}", terms.join("-"));
+        return Some(match captures.get(2) {
+            Some(version) => format!("{} {}", name, version.as_str()),
+            None => name,
+        });
+    }
+    if let Some(captures) = CC_SHORT_RE.captures(text) {
+        let name = match &captures[1] {
+            "0" => "CC0".to_string(),
+            terms => format!("CC {}", terms),
+        };
+        return Some(match captures.get(2) {
+            Some(version) => format!("{} {}", name, version.as_str()),
+            None => name,
+        });
+    }
+    if text.to_lowercase().contains("creative commons zero") {
+        return Some("CC0".to_string());
+    }
+    None
+}
+
+/// A license from a URL or, failing that, a name given as text.
+fn license_from(value: &str, base: Option<&reqwest::Url>, source: &str) -> Option<License> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    let url = reqwest::Url::parse(value)
+        .ok()
+        .or_else(|| base?.join(value).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .filter(|_| !value.contains(char::is_whitespace));
+    let name = match &url {
+        Some(url) => name_from_url(url.as_str()),
+        None => name_from_text(value).or_else(|| Some(value.to_string())),
+    };
+    Some(License {
+        name,
+        url: url.map(String::from),
+        source: source.to_string(),
+    })
+}
+
+/// Meta tags and JSON-LD give a license as a URL or as a name such as "CC0" or
+/// "MIT", so only values with a path in them are resolved against the page.
+fn text_base<'a>(value: &str, base: Option<&'a reqwest::Url>) -> Option<&'a reqwest::Url> {
+    base.filter(|_| value.contains('/'))
+}
+
+fn rel_license(document: &Html, base: Option<&reqwest::Url>) -> Option<License> {
+    document
+        .select(&REL_LICENSE_SELECTOR)
+        .filter(|element| {
+            element.value().attr("rel").is_some_and(|rel| {
+                rel.split_whitespace()
+                    .any(|r| r.eq_ignore_ascii_case("license"))
+            })
+        })
+        .find_map(|element| {
+            let mut license = license_from(element.value().attr("href")?, base, "rel-license")?;
+            // The link text often names licenses we don't recognize by URL
+            if license.name.is_none() {
+                let text = clean_cell_text(element);
+                license.name = name_from_text(&text).or_else(|| (!text.is_empty()).then_some(text));
+            }
+            Some(license)
+        })
+}
+
+/// The first `license` property anywhere in a JSON-LD value, as a URL or name.
+fn json_ld_license(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Object(object) => {
+            if let Some(license) = object.get("license") {
+                let license = match license {
+                    serde_json::Value::Array(items) => items.first()?,
+                    license => license,
+                };
+                return match license {
+                    serde_json::Value::String(text) => Some(text.clone()),
+                    serde_json::Value::Object(object) => object
+                        .get("url")
+                        .or_else(|| object.get("@id"))
+                        .or_else(|| object.get("name"))
+                        .and_then(|value| value.as_str())
+                        .map(str::to_string),
+                    _ => None,
+                };
+            }
+            object.values().find_map(json_ld_license)
+        }
+        serde_json::Value::Array(items) => items.iter().find_map(json_ld_license),
+        _ => None,
+    }
+}
+
+fn schema_org_license(document: &Html, base: Option<&reqwest::Url>) -> Option<License> {
+    let from_json_ld = document.select(&JSON_LD_SELECTOR).find_map(|script| {
+        let value: serde_json::Value = serde_json::from_str(&script.inner_html()).ok()?;
+        let license = json_ld_license(&value)?;
+        license_from(&license, text_base(&license, base), "schema.org")
+    });
+    from_json_ld.or_else(|| {
+        document
+            .select(&ITEMPROP_LICENSE_SELECTOR)
+            .find_map(|element: ElementRef| {
+                if let Some(href) = element.value().attr("href") {
+                    return license_from(href, base, "schema.org");
+                }
+                let value = element
+                    .value()
+                    .attr("content")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| clean_cell_text(element));
+                license_from(&value, text_base(&value, base), "schema.org")
+            })
+    })
+}
+
+fn meta_license(meta: &HashMap<&str, &str>, base: Option<&reqwest::Url>) -> Option<License> {
+    LICENSE_META.iter().find_map(|name| {
+        let (_, content) = meta
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))?;
+        license_from(content, text_base(content, base), "meta")
+    })
+}
+
+fn creative_commons_marker(document: &Html, base: Option<&reqwest::Url>) -> Option<License> {
+    document.select(&CC_LINK_SELECTOR).find_map(|element| {
+        let target = element
+            .value()
+            .attr("href")
+            .or_else(|| element.value().attr("src"))?;
+        let license = license_from(target, base, "creative-commons")?;
+        let name = license.name?;
+        // Badge images point at the badge, not the license
+        let url = if element.value().name() == "img" {
+            None
+        } else {
+            license.url
+        };
+        Some(License {
+            name: Some(name),
+            url,
+            source: license.source,
+        })
+    })
+}
+
+fn footer_notice(document: &Html) -> Option<License> {
+    document.select(&FOOTER_SELECTOR).find_map(|footer| {
+        let text = clean_cell_text(footer);
+        let name = name_from_text(&text).or_else(|| {
+            FOOTER_NOTICES
+                .iter()
+                .find(|(pattern, _)| pattern.is_match(&text))
+                .map(|(_, name)| name.to_string())
+        })?;
+        Some(License {
+            name: Some(name),
+            url: None,
+            source: "footer".to_string(),
+        })
+    })
+}
+
+/// The license the page gives for its content, from the most explicit signal it has.
+/// `meta` holds the page's meta tags by name and property.
+pub fn page_license(document: &Html, url: &str, meta: &HashMap<&str, &str>) -> Option<License> {
+    let base = reqwest::Url::parse(url).ok();
+    let base = base.as_ref();
+    rel_license(document, base)
+        .or_else(|| schema_org_license(document, base))
+        .or_else(|| meta_license(meta, base))
+        .or_else(|| creative_commons_marker(document, base))
+        .or_else(|| footer_notice(document))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExtractOptions, Extractor};
+
+    fn license(html: &str) -> Option<License> {
+        Extractor::new(ExtractOptions::default())
+            .page_metadata(&Html::parse_document(html), "https://example.com/data/")
+            .license
+    }
+
+    fn found(name: Option<&str>, url: Option<&str>, source: &str) -> Option<License> {
+        Some(License {
+            name: name.map(String::from),
+            url: url.map(String::from),
+            source: source.to_string(),
+        })
+    }
+
+    #[test]
+    fn reads_rel_license_links_resolved_against_the_page() {
+        assert_eq!(
+            license(
+                "<a rel=\"license\" href=\"https://creativecommons.org/licenses/by-sa/4.0/\">x</a>"
+            ),
+            found(
+                Some("CC BY-SA 4.0"),
+                Some("https://creativecommons.org/licenses/by-sa/4.0/"),
+                "rel-license"
+            )
+        );
+        assert_eq!(
+            license("<a rel=\"external license\" href=\"../terms\">Site terms</a>"),
+            found(
+                Some("Site terms"),
+                Some("https://example.com/terms"),
+                "rel-license"
+            )
+        );
+    }
+
+    #[test]
+    fn prefers_the_most_explicit_signal() {
+        let html = "<head><meta name=\"DC.rights\" content=\"CC0\"></head>
+            <script type=\"application/ld+json\">
+              {\"@graph\": [{\"@type\": \"Dataset\", \"license\": {\"url\": \"https://opendatacommons.org/licenses/odbl/1-0/\"}}]}
+            </script>
+            <footer>All rights reserved</footer>";
+        assert_eq!(
+            license(html),
+            found(
+                Some("ODbL"),
+                Some("https://opendatacommons.org/licenses/odbl/1-0/"),
+                "schema.org"
+            )
+        );
+        assert_eq!(
+            license(
+                "<meta name=\"DC.rights\" content=\"CC0\"><footer>All rights reserved</footer>"
+            ),
+            found(Some("CC0"), None, "meta")
+        );
+        assert_eq!(
+            license("<meta name=\"license\" content=\"/terms#data\">"),
+            found(None, Some("https://example.com/terms#data"), "meta")
+        );
+    }
+
+    #[test]
+    fn names_creative_commons_badges_and_footer_notices() {
+        assert_eq!(
+            license("<img src=\"https://licensebuttons.net/l/by-nc/3.0/88x31.png\">"),
+            found(Some("CC BY-NC 3.0"), None, "creative-commons")
+        );
+        assert_eq!(
+            license(
+                "<footer>Licensed under Creative Commons Attribution-NonCommercial-ShareAlike 4.0</footer>"
+            ),
+            found(Some("CC BY-NC-SA 4.0"), None, "footer")
+        );
+        assert_eq!(
+            license(
+                "<div class=\"footer\">Contains public sector information licensed under the Open Government Licence</div>"
+            ),
+            found(Some("OGL"), None, "footer")
+        );
+        assert_eq!(license("<footer>Contact us</footer>"), None);
+    }
+
+    #[test]
+    fn names_licenses_from_urls_and_text() {
+        assert_eq!(
+            name_from_url("https://creativecommons.org/publicdomain/zero/1.0/").as_deref(),
+            Some("CC0 1.0")
+        );
+        assert_eq!(
+            name_from_url("https://creativecommons.org/publicdomain/mark/1.0/").as_deref(),
+            Some("Public Domain Mark 1.0")
+        );
+        assert_eq!(name_from_url("https://example.com/license"), None);
+        assert_eq!(name_from_text("CC BY 4.0").as_deref(), Some("CC BY 4.0"));
+        assert_eq!(name_from_text("Accessed Dec 2024"), None);
+    }
+}
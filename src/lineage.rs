@@ -0,0 +1,216 @@
+//! `--lineage`: where each output column came from. Tables start with one entry per
+//! header naming the header it was read from; steps that change a column add their
+//! name to its chain, and steps that add columns say which columns they read.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Table;
+
+/// A header of an extracted table that an output column was built from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineageSource {
+    pub table: usize,
+    pub table_key: String,
+    pub header: String,
+}
+
+/// Lineage of one output column, at the same index as its header.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnLineage {
+    pub column: String,
+    /// Empty for values that don't come from the page's cells, such as stamps
+    pub sources: Vec<LineageSource>,
+    /// Steps that changed or produced the column, in the order they ran
+    pub transforms: Vec<String>,
+}
+
+/// Starts lineage for each table, one entry per header.
+pub fn init(tables: &mut [Table]) {
+    for table in tables {
+        let metadata = &table.metadata;
+        let lineage = table
+            .data
+            .headers
+            .iter()
+            .map(|header| ColumnLineage {
+                column: header.clone(),
+                sources: vec![LineageSource {
+                    table: metadata.position,
+                    table_key: metadata.table_key.clone(),
+                    header: header.clone(),
+                }],
+                transforms: Vec::new(),
+            })
+            .collect();
+        table.metadata.lineage = Some(lineage);
+    }
+}
+
+/// Notes that `step` rewrote the column at `index`, renamed or not.
+pub fn changed(table: &mut Table, index: usize, step: &str) {
+    let header = table.data.headers.get(index).cloned();
+    if let Some(column) = table
+        .metadata
+        .lineage
+        .as_mut()
+        .and_then(|lineage| lineage.get_mut(index))
+    {
+        if let Some(header) = header {
+            column.column = header;
+        }
+        if column.transforms.last().map(String::as_str) != Some(step) {
+            column.transforms.push(step.to_string());
+        }
+    }
+}
+
+/// Lineage for a column that `step` computed from the columns at `inputs`: their
+/// sources, and their chains followed by the step.
+pub fn derived(table: &Table, name: &str, inputs: &[usize], step: &str) -> Option<ColumnLineage> {
+    let lineage = table.metadata.lineage.as_ref()?;
+    let mut column = ColumnLineage {
+        column: name.to_string(),
+        sources: Vec::new(),
+        transforms: Vec::new(),
+    };
+    for input in inputs.iter().filter_map(|&index| lineage.get(index)) {
+        for source in &input.sources {
+            if !column.sources.contains(source) {
+                column.sources.push(source.clone());
+            }
+        }
+        for transform in &input.transforms {
+            if !column.transforms.contains(transform) {
+                column.transforms.push(transform.clone());
+            }
+        }
+    }
+    column.transforms.push(step.to_string());
+    Some(column)
+}
+
+/// Appends lineage for a column that `step` added from the columns at `inputs`.
+pub fn push_derived(table: &mut Table, name: &str, inputs: &[usize], step: &str) {
+    if let Some(column) = derived(table, name, inputs, step)
+        && let Some(lineage) = table.metadata.lineage.as_mut()
+    {
+        lineage.push(column);
+    }
+}
+
+/// Replaces the column at `index` with columns that `step` split it into.
+pub fn split(table: &mut Table, index: usize, names: &[String], step: &str) {
+    let parts: Option<Vec<ColumnLineage>> = names
+        .iter()
+        .map(|name| derived(table, name, &[index], step))
+        .collect();
+    if let Some(parts) = parts
+        && let Some(lineage) = table.metadata.lineage.as_mut()
+        && index < lineage.len()
+    {
+        lineage.splice(index..=index, parts);
+    }
+}
+
+/// Lineage for columns `step` takes over from another table, such as a detail page's.
+pub fn read_from(source: &Table, step: &str) -> Vec<ColumnLineage> {
+    source
+        .data
+        .headers
+        .iter()
+        .map(|header| ColumnLineage {
+            column: header.clone(),
+            sources: vec![LineageSource {
+                table: source.metadata.position,
+                table_key: source.metadata.table_key.clone(),
+                header: header.clone(),
+            }],
+            transforms: vec![step.to_string()],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    fn table() -> Table {
+        extract(
+            "<table><tr><th>Start</th><th>End</th><th>Score</th></tr>
+             <tr><td>9</td><td>10</td><td>2-1</td></tr></table>",
+            ExtractOptions::default(),
+        )
+        .remove(0)
+    }
+
+    fn chain(table: &Table) -> Vec<(String, Vec<String>, Vec<String>)> {
+        table
+            .metadata
+            .lineage
+            .iter()
+            .flatten()
+            .map(|column| {
+                let sources = column.sources.iter().map(|s| s.header.clone()).collect();
+                (column.column.clone(), sources, column.transforms.clone())
+            })
+            .collect()
+    }
+
+    fn entry(
+        column: &str,
+        sources: &[&str],
+        transforms: &[&str],
+    ) -> (String, Vec<String>, Vec<String>) {
+        (
+            column.to_string(),
+            sources.iter().map(|s| s.to_string()).collect(),
+            transforms.iter().map(|t| t.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn follows_columns_through_changes_derivations_and_splits() {
+        let mut tables = vec![table()];
+        init(&mut tables);
+        let table = &mut tables[0];
+
+        table.data.headers[0] = "start".to_string();
+        changed(table, 0, "clean");
+        changed(table, 0, "clean");
+        changed(table, 1, "clean");
+        table.data.headers.push("duration".to_string());
+        push_derived(table, "duration", &[0, 1], "timetable");
+        table
+            .data
+            .headers
+            .splice(2..=2, ["W".to_string(), "L".to_string()]);
+        split(table, 2, &["W".to_string(), "L".to_string()], "sports");
+
+        assert_eq!(
+            chain(table),
+            [
+                entry("start", &["Start"], &["clean"]),
+                entry("End", &["End"], &["clean"]),
+                entry("W", &["Score"], &["sports"]),
+                entry("L", &["Score"], &["sports"]),
+                entry("duration", &["Start", "End"], &["clean", "timetable"]),
+            ]
+        );
+        assert_eq!(
+            table.metadata.lineage.as_ref().unwrap()[0].sources[0].table,
+            1
+        );
+    }
+
+    #[test]
+    fn leaves_tables_without_lineage_alone() {
+        let mut table = table();
+        changed(&mut table, 0, "clean");
+        push_derived(&mut table, "extra", &[0], "step");
+        split(&mut table, 2, &["W".to_string(), "L".to_string()], "sports");
+        assert!(table.metadata.lineage.is_none());
+        assert!(derived(&table, "extra", &[0], "step").is_none());
+    }
+}
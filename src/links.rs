@@ -0,0 +1,133 @@
+use std::sync::LazyLock;
+
+use scraper::{ElementRef, Html, Selector};
+
+use crate::types::SignConventions;
+use crate::{LINK_SELECTOR, Table, TableData, TableMetadata, clean_cell_text};
+
+/// Landmarks a link's section is named after, innermost first.
+static LANDMARK_SELECTOR: LazyLock<Selector> = LazyLock::new(|| {
+    Selector::parse("nav, header, footer, main, aside, article, section").unwrap()
+});
+
+/// The innermost landmark holding a link, as `tag` or `tag#id`.
+fn link_section(link: ElementRef) -> String {
+    link.ancestors()
+        .filter_map(ElementRef::wrap)
+        .find(|ancestor| LANDMARK_SELECTOR.matches(ancestor))
+        .map(|landmark| {
+            let tag = landmark.value().name();
+            match landmark.value().attr("id") {
+                Some(id) => format!("{}#{}", tag, id),
+                None => tag.to_string(),
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// Every `a[href]` on the page as an auxiliary `Links` table with text, absolute
+/// href, rel and section columns, placed after the page's tables at `position`.
+pub fn links_table(document: &Html, page_url: &str, position: usize) -> Table {
+    let base = reqwest::Url::parse(page_url).ok();
+    let rows: Vec<Vec<String>> = document
+        .select(&LINK_SELECTOR)
+        .filter_map(|link| {
+            let href = link.value().attr("href")?.trim();
+            // Fragment-only and javascript: links don't lead anywhere to crawl
+            if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+                return None;
+            }
+            let href = base
+                .as_ref()
+                .and_then(|base| base.join(href).ok())
+                .map(String::from)
+                .unwrap_or_else(|| href.to_string());
+            Some(vec![
+                clean_cell_text(link),
+                href,
+                link.value().attr("rel").unwrap_or("").trim().to_string(),
+                link_section(link),
+            ])
+        })
+        .collect();
+
+    let headers: Vec<String> = ["text", "href", "rel", "section"]
+        .iter()
+        .map(|header| header.to_string())
+        .collect();
+    Table {
+        metadata: TableMetadata {
+            name: None,
+            id: None,
+            class: None,
+            caption: Some("Links".to_string()),
+            position,
+            table_key: "links".to_string(),
+            row_count: rows.len() + 1,
+            column_count: headers.len(),
+            header_row_count: 1,
+            footer_row_count: 0,
+            parent_section: None,
+            preceding_heading: None,
+            score: 0,
+            score_breakdown: Vec::new(),
+            matrix: false,
+            key_value: false,
+            hierarchical_rows: false,
+            total_rows: Vec::new(),
+            colgroups: Vec::new(),
+            column_groups: Vec::new(),
+            notes: Vec::new(),
+            schema: Vec::new(),
+            analysis: None,
+            row_styles: None,
+            cell_styles: None,
+            standings: None,
+            follow: None,
+            header_matches: None,
+            original_values: None,
+            lineage: None,
+            snapshots: None,
+            header_tooltips: None,
+            cell_tooltips: None,
+            redactions: None,
+            auxiliary: Some("links".to_string()),
+            sign_conventions: SignConventions::DEFAULT,
+        },
+        data: TableData { headers, rows },
+        links: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_links_with_absolute_hrefs_and_sections() {
+        let document = Html::parse_document(
+            "<nav id='top'><a href='/about' rel='nofollow'>About  us</a></nav>
+             <main><section><a href='https://other.example/x'>Other</a></section>
+             <a href='#skip'>Skip</a><a href='javascript:void(0)'>Menu</a><a href=''>Empty</a></main>
+             <p><a href='page?id=2'>Page</a></p>",
+        );
+        let table = links_table(&document, "https://example.com/dir/index.html", 3);
+        assert_eq!(table.data.headers, ["text", "href", "rel", "section"]);
+        assert_eq!(
+            table.data.rows,
+            [
+                [
+                    "About us",
+                    "https://example.com/about",
+                    "nofollow",
+                    "nav#top"
+                ],
+                ["Other", "https://other.example/x", "", "section"],
+                ["Page", "https://example.com/dir/page?id=2", "", ""],
+            ]
+        );
+        assert_eq!(table.metadata.position, 3);
+        assert_eq!(table.metadata.row_count, 4);
+        assert_eq!(table.metadata.auxiliary.as_deref(), Some("links"));
+    }
+}
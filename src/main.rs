@@ -1,486 +1,1731 @@
-use std::{error::Error, fs::File, io::Write, time::Instant};
-
-use clap::{App, Arg};
-use regex::Regex;
-use scraper::{Html, Selector};
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Serialize, Deserialize)]
-struct TableMetadata {
-    id: Option<String>,
-    class: Option<String>,
-    caption: Option<String>,
-    position: usize,
-    row_count: usize,
-    column_count: usize,
-    header_row_count: usize,
-    footer_row_count: usize,
-    parent_section: Option<String>,
-    preceding_heading: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct TableData {
-    headers: Vec<String>,
-    rows: Vec<Vec<String>>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Table {
-    metadata: TableMetadata,
-    data: TableData,
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use clap::{App, AppSettings, Arg, SubCommand};
+use scraper::Html;
+use serde_json::json;
+
+use tabex::crawl::{self, Crawl, Frontier};
+use tabex::datetime::NormalizeDatetimes;
+use tabex::duration::ParseDurations;
+use tabex::error::TabexError;
+use tabex::events::{Console, Events, Record};
+use tabex::expr::EvalContext;
+use tabex::fetch::{Bandwidth, HostFilter, HttpSource};
+use tabex::file::{FileSource, STDIN_LOCATION, StdinSource};
+use tabex::follow::FollowOptions;
+use tabex::geo::{self, GeoJsonSink, ParseCoordinates};
+use tabex::grid::SpanMode;
+use tabex::manifest::Recorder;
+use tabex::matching::HeaderMatcher;
+use tabex::naming::{NameTemplate, page_slug};
+use tabex::output::{AsciiDocSink, CsvSink, JsonSink, OrgSink};
+use tabex::plugin::{Registry, Sink, Transform};
+use tabex::profile::{Profile, ProfileContext};
+use tabex::redact::{self, Pseudonymize, RedactMode, RedactPii};
+use tabex::report::ReportSink;
+use tabex::robots::RobotsCheck;
+use tabex::routing::Route;
+use tabex::semantic::{self, SemanticPack};
+use tabex::sitemap;
+use tabex::sql::{Dialect, SqlSink};
+use tabex::transform::{
+    DerivedColumn, DerivedColumns, ExpandSuffixes, MeltMatrix, ReplaceRule, Replacements,
+    StampColumn, StampColumns, Transpose, TransposeTables,
+};
+use tabex::types::{DeclaredType, SignConventions};
+use tabex::units::ConvertUnits;
+use tabex::wayback::WaybackRange;
+use tabex::xlsx::{XlsxOptions, XlsxSink};
+use tabex::{
+    DEFAULT_STRIP_PARAMS, DocumentTables, ExtractOptions, ExtractionResult, Extractor, PageOutcome,
+    TABLE_SELECTOR, analysis, canonicalize_url, cookies, datarepo, fetch, follow, lineage, links,
+    manifest, routing, split_url_list, units, wayback,
+};
+
+fn main() {
+    let mut bandwidth = None;
+    let mut console = Console::default();
+    let mut recorder = None;
+    let outcome = run(&mut bandwidth, &mut console, &mut recorder);
+    let events = reporting(console, recorder.as_ref());
+    // A failed run prints no summary, so say what it downloaded before giving up
+    if outcome.is_err()
+        && let Some(downloaded) = bandwidth.as_deref().and_then(downloaded)
+    {
+        events.status(
+            "bandwidth",
+            &downloaded,
+            json!({ "bytes_downloaded": bandwidth.as_deref().map(Bandwidth::bytes) }),
+        );
+    }
+    let finished = recorder.map_or(Ok(()), |recorder| recorder.finish(&outcome));
+    let outcome = match finished {
+        Ok(()) => outcome,
+        Err(e) => outcome.and(Err(e)),
+    };
+    if let Err(e) = outcome {
+        console.error(&e.to_string(), e.exit_code());
+        std::process::exit(e.exit_code());
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct PageMetadata {
-    url: String,
-    title: Option<String>,
-    description: Option<String>,
-    author: Option<String>,
-    published_date: Option<String>,
-    last_modified: Option<String>,
+/// Where the run reports: the console, through the manifest recorder with
+/// `--manifest`.
+fn reporting(console: Console, recorder: Option<&Arc<Recorder>>) -> Events {
+    match recorder {
+        Some(recorder) => Events::new(recorder.clone()),
+        None => Events::new(Arc::new(console)),
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ExtractionResult {
-    page: PageMetadata,
-    tables: Vec<Table>,
-    extraction_time_ms: u128,
+/// Options shared by a plain run and `tabex commit`.
+fn extraction_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("url")
+            .short("u")
+            .long("url")
+            .value_name("URL")
+            .help("URL of the website to extract tables from, or - to read HTML from stdin; repeat, or separate URLs with commas, to extract several pages in one run (a value is kept whole unless every comma-separated part is an http(s) URL)")
+            .required_unless_one(&["file", "stdin", "input-list", "sitemap"])
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        Arg::with_name("file")
+            .long("file")
+            .value_name("PATH")
+            .help("Saved HTML page to extract tables from instead of a URL; the path is recorded as the page URL")
+            .conflicts_with_all(&["url", "wayback"])
+            .takes_value(true),
+        Arg::with_name("stdin")
+            .long("stdin")
+            .help("Read the HTML page from stdin instead of fetching it, e.g. curl ... | tabex --stdin; same as --url -")
+            .conflicts_with_all(&["url", "file", "wayback"]),
+        Arg::with_name("input-list")
+            .long("input-list")
+            .value_name("FILE")
+            .help("File of URLs to extract, one per line; blank lines and lines starting with # are skipped. Adds to any --url")
+            .conflicts_with_all(&["file", "stdin"])
+            .takes_value(true),
+        Arg::with_name("sitemap")
+            .long("sitemap")
+            .value_name("URL")
+            .help("Extract every page listed in the sitemap at URL, following sitemap indexes to the sitemaps they name. Adds to any --url")
+            .conflicts_with_all(&["file", "stdin", "wayback"])
+            .takes_value(true),
+        Arg::with_name("sitemap-filter")
+            .long("sitemap-filter")
+            .value_name("REGEX")
+            .help("Only extract sitemap pages whose URL matches REGEX, e.g. \"/reports/20\\d\\d/\"")
+            .requires("sitemap")
+            .takes_value(true),
+        Arg::with_name("crawl")
+            .long("crawl")
+            .help("Also extract the pages each page links to, breadth first and each page once, up to --max-depth links away from the start URLs; nofollow links are skipped, and so are pages found by the crawl whose robots meta says noindex")
+            .conflicts_with_all(&["file", "stdin", "wayback"]),
+        Arg::with_name("max-depth")
+            .long("max-depth")
+            .value_name("N")
+            .help("Links --crawl follows away from a start URL (default 1)")
+            .requires("crawl")
+            .takes_value(true),
+        Arg::with_name("same-domain")
+            .long("same-domain")
+            .help("Only --crawl links to the start URLs' hosts and their subdomains")
+            .requires("crawl"),
+        Arg::with_name("ignore-robots-meta")
+            .long("ignore-robots-meta")
+            .help("Let --crawl follow nofollow links and extract pages whose robots meta says noindex")
+            .requires("crawl"),
+        Arg::with_name("output-dir")
+            .long("output-dir")
+            .value_name("DIR")
+            .help("Write each page to its own file in DIR, one per --format, named after the page's host and path, e.g. example_com_stats.csv")
+            .conflicts_with_all(&["output", "route"])
+            .takes_value(true),
+        Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("FILE")
+            .help("Output file (default is stdout); repeat to give one file per --format")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        Arg::with_name("format")
+            .short("f")
+            .long("format")
+            .value_name("FORMAT")
+            .help("Output format (json, csv, org, asciidoc, sql, report, xlsx or geojson); comma-separate several, e.g. json,xlsx, to write each to its own --output")
+            .default_value("json")
+            .takes_value(true),
+        Arg::with_name("route")
+            .long("route")
+            .value_name("MATCH=FORMAT:FILE")
+            .help("Write matching tables to their own file and skip unrouted ones, e.g. \"caption:Prices=sql:prices.sql\"; MATCH is caption:TEXT, heading:TEXT, selector:CSS or * (repeatable, first match wins)")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .conflicts_with("output"),
+        Arg::with_name("dialect")
+            .long("dialect")
+            .value_name("DIALECT")
+            .help("SQL dialect for --format sql: postgres (default), mysql or sqlite")
+            .takes_value(true),
+        Arg::with_name("xlsx-options")
+            .long("xlsx-options")
+            .value_name("OPTIONS")
+            .help("Workbook presentation for --format xlsx: freeze, filter, autofit, formats, metadata or all")
+            .takes_value(true),
+        Arg::with_name("table-name-template")
+            .long("table-name-template")
+            .value_name("TEMPLATE")
+            .help("Name tables for sheets, SQL tables, data repo files and JSON, e.g. \"{heading_slug}_{index}\"; placeholders: index, position, table_key, id, caption_slug, heading_slug, label_slug, page_slug")
+            .takes_value(true),
+        Arg::with_name("user-agent")
+            .long("user-agent")
+            .value_name("AGENT")
+            .help("User agent string to use for requests")
+            .default_value("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
+            .takes_value(true),
+        Arg::with_name("header")
+            .long("header")
+            .short("H")
+            .value_name("NAME: VALUE")
+            .help("Send this header with every request, e.g. \"Accept-Language: de\" or \"Referer: https://example.com/\"; replaces the header it names, including User-Agent (repeatable)")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("cookies")
+            .long("cookies")
+            .value_name("FILE")
+            .help("Send the cookies in this JSON file, e.g. a session cookie copied from a browser: [{\"name\", \"value\", \"domain\"}]")
+            .takes_value(true),
+        Arg::with_name("save-cookies")
+            .long("save-cookies")
+            .value_name("FILE")
+            .help("Write the cookies held at the end of the run, including any the sites set, to FILE, or back to the --cookies file")
+            .takes_value(true)
+            .min_values(0),
+        Arg::with_name("proxy")
+            .long("proxy")
+            .value_name("URL")
+            .help("Send requests through this HTTP(S) proxy, e.g. http://proxy.corp:3128; without it HTTP_PROXY, HTTPS_PROXY and NO_PROXY are honored")
+            .takes_value(true),
+        Arg::with_name("resolve")
+            .long("resolve")
+            .value_name("HOST:PORT:ADDR")
+            .help("Connect to ADDR for HOST instead of looking it up, as in curl (repeatable)")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("doh-url")
+            .long("doh-url")
+            .value_name("URL")
+            .help("Look up every host the run connects to with this DNS-over-HTTPS resolver, e.g. https://cloudflare-dns.com/dns-query")
+            .takes_value(true),
+        Arg::with_name("ipv4")
+            .long("ipv4")
+            .short("4")
+            .help("Only connect over IPv4")
+            .conflicts_with("ipv6"),
+        Arg::with_name("ipv6")
+            .long("ipv6")
+            .short("6")
+            .help("Only connect over IPv6"),
+        Arg::with_name("local-addr")
+            .long("local-addr")
+            .value_name("ADDR")
+            .help("Local IP address to send requests from")
+            .conflicts_with("interface")
+            .takes_value(true),
+        Arg::with_name("interface")
+            .long("interface")
+            .value_name("NAME")
+            .help("Network interface to send requests from, e.g. eth1")
+            .takes_value(true),
+        Arg::with_name("allow-hosts")
+            .long("allow-hosts")
+            .value_name("GLOBS")
+            .help("Comma-separated host globs that pages and redirects may be fetched from, e.g. \"example.com,*.example.com\"")
+            .takes_value(true),
+        Arg::with_name("deny-hosts")
+            .long("deny-hosts")
+            .value_name("GLOBS")
+            .help("Comma-separated host globs never fetched from, e.g. \"localhost,127.*,10.*\"; wins over --allow-hosts")
+            .takes_value(true),
+        Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("SECONDS")
+            .help("Give up on a request that hasn't finished after this many seconds (default 30)")
+            .takes_value(true),
+        Arg::with_name("connect-timeout")
+            .long("connect-timeout")
+            .value_name("SECONDS")
+            .help("Give up on connecting to a server after this many seconds")
+            .takes_value(true),
+        Arg::with_name("retries")
+            .long("retries")
+            .value_name("N")
+            .help("Send a request up to N more times when it fails with a connection error or a 408, 425, 429 or 5xx status, waiting longer after each try")
+            .takes_value(true),
+        Arg::with_name("max-bandwidth")
+            .long("max-bandwidth")
+            .value_name("RATE")
+            .help("Cap the combined download rate, e.g. 2MB/s or 512KiB/s")
+            .takes_value(true),
+        Arg::with_name("follow-column")
+            .long("follow-column")
+            .value_name("COLUMN")
+            .help("Fetch the page linked from each row's COLUMN cell and join one of its tables onto the row")
+            .takes_value(true),
+        Arg::with_name("detail-table")
+            .long("detail-table")
+            .value_name("POSITION")
+            .help("Table position to take from each followed page (default: the best scoring table)")
+            .requires("follow-column")
+            .takes_value(true),
+        Arg::with_name("extract-links")
+            .long("extract-links")
+            .help("Also output the page's links (text, href, rel, section) as a Links table"),
+        Arg::with_name("wayback")
+            .long("wayback")
+            .help("Extract from Internet Archive snapshots of the URL and stack each table's rows with a snapshot column"),
+        Arg::with_name("from")
+            .long("from")
+            .value_name("DATE")
+            .help("Earliest --wayback snapshot: YYYY, YYYY-MM or YYYY-MM-DD")
+            .requires("wayback")
+            .takes_value(true),
+        Arg::with_name("to")
+            .long("to")
+            .value_name("DATE")
+            .help("Latest --wayback snapshot: YYYY, YYYY-MM or YYYY-MM-DD")
+            .requires("wayback")
+            .takes_value(true),
+        Arg::with_name("every")
+            .long("every")
+            .value_name("INTERVAL")
+            .help("Keep one --wayback snapshot per interval, e.g. 7d, 2w, 1mo or 1y")
+            .requires("wayback")
+            .takes_value(true),
+        Arg::with_name("best")
+            .long("best")
+            .help("Only output the highest scoring table"),
+        Arg::with_name("min-score")
+            .long("min-score")
+            .value_name("SCORE")
+            .help("Drop tables scoring below this value (likely layout tables)")
+            .takes_value(true),
+        Arg::with_name("strip-params")
+            .long("strip-params")
+            .value_name("PARAMS")
+            .help("Extra comma-separated query params to strip when normalizing URLs (trailing * matches a prefix)")
+            .takes_value(true),
+        Arg::with_name("accept-content-type")
+            .long("accept-content-type")
+            .value_name("TYPE")
+            .help("Additional Content-Type to parse, e.g. application/pdf or text/* (repeatable)")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("replace")
+            .long("replace")
+            .value_name("COLUMN:s/PATTERN/REPLACEMENT/FLAGS")
+            .help("Regex substitution applied to a column's cells, e.g. \"Price:s/[^0-9.]//g\" (repeatable)")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("add-column")
+            .long("add-column")
+            .value_name("NAME = EXPR")
+            .help("Append a computed column, e.g. \"total = price * quantity\" or \"scraped_at = now()\" (repeatable)")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("stamp-columns")
+            .long("stamp-columns")
+            .value_name("COLUMNS")
+            .help("Append provenance columns to every row: url, title, caption, heading, position, table_id, extracted_at")
+            .takes_value(true),
+        Arg::with_name("records")
+            .long("records")
+            .help("Emit JSON rows as objects keyed by sanitized header names"),
+        Arg::with_name("analyze")
+            .long("analyze")
+            .help("Profile columns and report candidate keys in table metadata"),
+        Arg::with_name("fuzzy-headers")
+            .long("fuzzy-headers")
+            .help("Match column names in options ignoring punctuation and spacing, within --header-distance edits"),
+        Arg::with_name("header-distance")
+            .long("header-distance")
+            .value_name("EDITS")
+            .help("Largest edit distance --fuzzy-headers accepts (default 2)")
+            .requires("fuzzy-headers")
+            .takes_value(true),
+        Arg::with_name("header-synonyms")
+            .long("header-synonyms")
+            .value_name("GROUPS")
+            .help("Names that stand for the same column, e.g. \"Price|Cost;Qty|Quantity\"")
+            .takes_value(true),
+        Arg::with_name("key-columns")
+            .long("key-columns")
+            .value_name("COLUMNS")
+            .help("Comma-separated columns expected to uniquely identify rows; warns on duplicates")
+            .takes_value(true),
+        Arg::with_name("melt")
+            .long("melt")
+            .help("Convert matrix (crosstab) tables to long form: row_label, col_label, value"),
+        Arg::with_name("transpose")
+            .long("transpose")
+            .value_name("TABLES")
+            .help("Turn attribute-per-row tables into records with the attributes as headers: auto for tables detected as key-value, or table positions, e.g. 2,5")
+            .takes_value(true),
+        Arg::with_name("convert-units")
+            .long("convert-units")
+            .help("Convert values to base units from header units and scale notes, e.g. \"Revenue ($M)\" to \"Revenue (USD)\""),
+        Arg::with_name("row-groups")
+            .long("row-groups")
+            .help("Fold hierarchical row labels (indented or bold group rows) into a leading group column"),
+        Arg::with_name("exclude-totals")
+            .long("exclude-totals")
+            .help("Drop rows classified as totals or subtotals"),
+        Arg::with_name("tag-totals")
+            .long("tag-totals")
+            .help("Append a row_type column marking total and subtotal rows")
+            .conflicts_with("exclude-totals"),
+        Arg::with_name("types")
+            .long("types")
+            .value_name("COLUMN:TYPE,...")
+            .help("Column types for typed outputs (SQL, Excel, JSON records) instead of inferring them: string, int, float or decimal(P,S), e.g. \"id:string,year:int,price:decimal(10,2)\"")
+            .takes_value(true),
+        Arg::with_name("redact")
+            .long("redact")
+            .value_name("CATEGORY")
+            .help("Mask personal data before writing: pii finds name, email, phone and address columns and records what was redacted in the table metadata")
+            .takes_value(true),
+        Arg::with_name("redact-mode")
+            .long("redact-mode")
+            .value_name("MODE")
+            .help("How --redact replaces cells: mask (default) or hash, which keeps equal values equal")
+            .requires("redact")
+            .takes_value(true),
+        Arg::with_name("pseudonymize")
+            .long("pseudonymize")
+            .value_name("COLUMNS")
+            .help("Replace the identifiers in these comma-separated columns with keyed hashes that stay the same across runs, so tables remain joinable on them")
+            .takes_value(true),
+        Arg::with_name("salt-env")
+            .long("salt-env")
+            .value_name("VAR")
+            .help("Environment variable holding the --pseudonymize key (default TABEX_SALT)")
+            .requires("pseudonymize")
+            .takes_value(true),
+        Arg::with_name("semantic-pack")
+            .long("semantic-pack")
+            .value_name("PACKS")
+            .help("Also recognize a pack of specialized semantic types in the schema: finance (tickers, ISINs, IBANs and currency codes)")
+            .takes_value(true),
+        Arg::with_name("expand-suffixes")
+            .long("expand-suffixes")
+            .help("Rewrite numbers with magnitude suffixes (\"15k\", \"3.4M\", \"2.1bn\") or in scientific notation as plain numbers, keeping the page's text in the table metadata"),
+        Arg::with_name("durations")
+            .long("durations")
+            .help("Rewrite duration columns (\"1h 23m\", \"02:15:30\", \"PT45S\") as seconds, and add start and end columns after time-range columns (\"09:00–17:30\")"),
+        Arg::with_name("coordinates")
+            .long("coordinates")
+            .help("Add lat and lon columns in decimal degrees after coordinate columns (\"48°51′24″N 2°21′03″E\", \"48.8566, 2.3522\"); --format geojson maps them as points"),
+        Arg::with_name("geo-columns")
+            .long("geo-columns")
+            .value_name("LAT,LON")
+            .help("Columns holding each row's location for --format geojson: latitude and longitude, or one column of coordinates such as \"48.8566, 2.3522\" (default: detected from headers such as Latitude/Longitude or from coordinate cells)")
+            .takes_value(true),
+        Arg::with_name("negatives")
+            .long("negatives")
+            .value_name("CONVENTIONS")
+            .help("How negative numbers are written, comma separated: parens for accounting \"(1,234)\", trailing for \"1234-\", unicode for minus signs like \"\u{2212}3\"; all or none (default unicode)")
+            .takes_value(true),
+        Arg::with_name("spans")
+            .long("spans")
+            .value_name("MODE")
+            .help("Line data rows up with the headers by expanding colspan and rowspan cells: duplicate repeats a spanned cell into every column and row it covers, blank keeps it in the first only (default off, duplicate with --profile timetable)")
+            .takes_value(true),
+        Arg::with_name("cell-styles")
+            .long("cell-styles")
+            .help("Record bold, struck-through, text colour and background of styled data cells in the table metadata"),
+        Arg::with_name("dry-run")
+            .long("dry-run")
+            .help("Print the pages that would be fetched and an estimate of the requests, without fetching them; a --sitemap is still read, looking its hosts up with --doh-url if given"),
+        Arg::with_name("profile")
+            .long("profile")
+            .value_name("PROFILE")
+            .help("Apply layout conventions for a kind of table: financial, sports or timetable")
+            .takes_value(true),
+        Arg::with_name("timezone")
+            .long("timezone")
+            .value_name("TZ")
+            .help("IANA time zone that times without an offset are in, e.g. Europe/Berlin: date-time columns are rewritten as RFC 3339 with its offset, and schedule times are read in it (default UTC)")
+            .takes_value(true),
+        Arg::with_name("date-context")
+            .long("date-context")
+            .value_name("YYYY-MM-DD")
+            .help("Date for schedule times whose day isn't in the table")
+            .takes_value(true),
+        Arg::with_name("canonical")
+            .long("canonical")
+            .help("Write byte-stable output: sorted keys, normalized whitespace and no timing"),
+        Arg::with_name("deadline")
+            .long("deadline")
+            .value_name("SECONDS")
+            .help("Stop after this many seconds, keeping the tables completed so far")
+            .takes_value(true),
+        Arg::with_name("manifest")
+            .long("manifest")
+            .value_name("FILE")
+            .help("Write a JSON record of the run (inputs, outputs with row counts and checksums, warnings, exit status) when it ends")
+            .takes_value(true),
+        Arg::with_name("compliance-report")
+            .long("compliance-report")
+            .help("Add a compliance section to the --manifest: each site's robots.txt rules for the user agent, whether its crawl-delay was kept, requests per host, and options that change how the run identifies itself. The rules are reported, not enforced: disallowed pages are warned about but still fetched, and requests are not spaced out to a crawl-delay, so crawl_delay_honored may be false")
+            .requires("manifest"),
+        Arg::with_name("lineage")
+            .long("lineage")
+            .help("Record each output column's source table, source header and transform chain in the table metadata"),
+        Arg::with_name("json-logs")
+            .long("json-logs")
+            .help("Report status, warnings and errors as NDJSON on stderr, leaving stdout to the data"),
+    ]
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let matches = App::new("Web Table Extractor")
+/// Runs the command; `meter` is given the run's bandwidth meter once requests
+/// can be made, so a failed run can still report it. `console` and `recorder` are
+/// set from the options, for reporting the run's error and writing its manifest.
+fn run(
+    meter: &mut Option<Arc<Bandwidth>>,
+    console: &mut Console,
+    recorder: &mut Option<Arc<Recorder>>,
+) -> Result<(), TabexError> {
+    let app_matches = App::new("Web Table Extractor")
         .version("1.0")
         .author("Your Name")
         .about("Extracts tables and metadata from websites")
-        .arg(
-            Arg::with_name("url")
-                .short("u")
-                .long("url")
-                .value_name("URL")
-                .help("URL of the website to extract tables from")
-                .required(true)
-                .takes_value(true),
-        )
-        .arg(
-            Arg::with_name("output")
-                .short("o")
-                .long("output")
-                .value_name("FILE")
-                .help("Output file (default is stdout)")
-                .takes_value(true),
-        )
-        .arg(
-            Arg::with_name("format")
-                .short("f")
-                .long("format")
-                .value_name("FORMAT")
-                .help("Output format (json or csv)")
-                .default_value("json")
-                .takes_value(true),
-        )
-        .arg(
-            Arg::with_name("user-agent")
-                .long("user-agent")
-                .value_name("AGENT")
-                .help("User agent string to use for requests")
-                .default_value("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36")
-                .takes_value(true),
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .args(&extraction_args())
+        .subcommand(
+            SubCommand::with_name("commit")
+                .about("Write canonical per-table CSVs into a git working tree and commit changes")
+                .args(&extraction_args())
+                .arg(
+                    Arg::with_name("repo")
+                        .long("repo")
+                        .value_name("DIR")
+                        .help("Git working tree to write tables into")
+                        .required(true)
+                        .takes_value(true),
+                ),
         )
         .get_matches();
-
-    let url = matches.value_of("url").unwrap();
-    let format = matches.value_of("format").unwrap();
+    let (matches, commit_repo) = match app_matches.subcommand() {
+        ("commit", Some(commit)) => (commit, commit.value_of("repo")),
+        _ => (&app_matches, None),
+    };
+    console.json_logs = matches.is_present("json-logs");
+    // Without a file or repo to write to, table data goes to stdout
+    console.status_to_stderr = ["output", "output-dir", "route"]
+        .into_iter()
+        .all(|option| !matches.is_present(option))
+        && commit_repo.is_none();
+    if let Some(path) = matches.value_of("manifest") {
+        let inner = Events::new(Arc::new(*console));
+        *recorder = Some(Arc::new(Recorder::new(path, inner)));
+    }
+    let events = reporting(*console, recorder.as_ref());
+
+    let formats: Vec<&str> = matches
+        .value_of("format")
+        .unwrap()
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let outputs: Vec<&str> = matches
+        .values_of("output")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    let output_dir = matches.value_of("output-dir");
     let user_agent = matches.value_of("user-agent").unwrap();
-
-    // Start timing
-    let start = Instant::now();
-
-    // Fetch and parse the web page
-    println!("Fetching URL: {}", url);
-    let client = reqwest::blocking::Client::builder()
-        .user_agent(user_agent)
-        .build()?;
-
-    let resp = client.get(url).send()?;
-
-    if !resp.status().is_success() {
-        return Err(format!("Failed to fetch URL: HTTP {}", resp.status()).into());
+    let detail_table = matches
+        .value_of("detail-table")
+        .map(|s| {
+            s.parse::<usize>()
+                .ok()
+                .filter(|position| *position > 0)
+                .ok_or_else(|| TabexError::Usage("Invalid value for --detail-table".to_string()))
+        })
+        .transpose()?;
+    let wayback_range = if matches.is_present("wayback") {
+        Some(WaybackRange::parse(
+            matches.value_of("from"),
+            matches.value_of("to"),
+            matches.value_of("every"),
+        )?)
+    } else {
+        None
+    };
+    let name_template = matches
+        .value_of("table-name-template")
+        .map(NameTemplate::parse)
+        .transpose()?;
+    let min_score = matches
+        .value_of("min-score")
+        .map(|s| s.parse::<i32>())
+        .transpose()
+        .map_err(|_| TabexError::Usage("Invalid value for --min-score".to_string()))?;
+
+    let mut strip_params: Vec<String> =
+        DEFAULT_STRIP_PARAMS.iter().map(|p| p.to_string()).collect();
+    if let Some(extra) = matches.value_of("strip-params") {
+        strip_params.extend(extra.split(',').map(|p| p.trim().to_string()));
+    }
+    let input_list = matches
+        .value_of("input-list")
+        .map(|path| {
+            fs::read_to_string(path).map_err(|e| TabexError::File {
+                path: path.to_string(),
+                source: e,
+            })
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let given_urls: Vec<&str> = matches
+        .values_of("url")
+        .into_iter()
+        .flatten()
+        .flat_map(split_url_list)
+        .chain(
+            input_list
+                .lines()
+                .filter(|line| !line.trim_start().starts_with('#')),
+        )
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .collect();
+    let crawl = matches
+        .is_present("crawl")
+        .then(|| {
+            let max_depth = matches
+                .value_of("max-depth")
+                .map(|s| s.parse::<usize>())
+                .transpose()
+                .map_err(|_| TabexError::Usage("Invalid value for --max-depth".to_string()))?
+                .unwrap_or(1);
+            Ok::<_, TabexError>(Crawl {
+                max_depth,
+                same_domain: matches.is_present("same-domain"),
+                ignore_robots_meta: matches.is_present("ignore-robots-meta"),
+            })
+        })
+        .transpose()?;
+    let sitemap_url = matches.value_of("sitemap");
+    let sitemap_filter = matches
+        .value_of("sitemap-filter")
+        .map(|pattern| {
+            regex::Regex::new(pattern).map_err(|e| {
+                TabexError::Parse(format!("Invalid --sitemap-filter {}: {}", pattern, e))
+            })
+        })
+        .transpose()?;
+    // With several URLs one that doesn't parse is reported like a page that couldn't be
+    // fetched, so a bad line in an --input-list doesn't stop the others
+    let mut invalid_urls: Vec<(String, TabexError)> = Vec::new();
+    let mut urls = match matches.value_of("file") {
+        Some(path) => vec![path.to_string()],
+        None if matches.is_present("stdin") || given_urls == [STDIN_LOCATION] => {
+            vec![STDIN_LOCATION.to_string()]
+        }
+        None if given_urls.contains(&STDIN_LOCATION) => {
+            return Err(TabexError::Usage(
+                "--url - reads one page from stdin and can't be combined with other URLs"
+                    .to_string(),
+            ));
+        }
+        None => {
+            let mut seen = HashSet::new();
+            let mut canonical = Vec::new();
+            for url in &given_urls {
+                match canonicalize_url(url, &strip_params) {
+                    Ok(url) => {
+                        if seen.insert(url.clone()) {
+                            canonical.push(url);
+                        }
+                    }
+                    Err(e) if given_urls.len() == 1 => return Err(e),
+                    Err(e) => invalid_urls.push((url.to_string(), e)),
+                }
+            }
+            canonical
+        }
+    };
+    if urls.is_empty() && sitemap_url.is_none() {
+        return Err(match invalid_urls.pop() {
+            Some((_, e)) => e,
+            None => TabexError::Usage("--url needs a URL".to_string()),
+        });
+    }
+    if wayback_range.is_some()
+        && (urls.len() + invalid_urls.len() > 1
+            || urls.first().is_some_and(|url| url == STDIN_LOCATION))
+    {
+        return Err(TabexError::Usage(
+            "--wayback needs a single URL, not several or a page read from stdin".to_string(),
+        ));
     }
 
-    let html_content = resp.text()?;
-    let document = Html::parse_document(&html_content);
-
-    // Extract page metadata
-    let page_metadata = extract_page_metadata(&document, url);
+    let mut accepted_content_types: Vec<String> = fetch::DEFAULT_CONTENT_TYPES
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+    if let Some(types) = matches.values_of("accept-content-type") {
+        accepted_content_types.extend(types.map(|t| t.trim().to_ascii_lowercase()));
+    }
 
-    // Extract tables
-    let tables = extract_tables(&document);
+    let mut dns_overrides: BTreeMap<String, Vec<SocketAddr>> = BTreeMap::new();
+    for spec in matches.values_of("resolve").into_iter().flatten() {
+        let (host, addr) = fetch::parse_resolve(spec)?;
+        dns_overrides.entry(host).or_default().push(addr);
+    }
 
-    // Calculate extraction time
-    let extraction_time = start.elapsed().as_millis();
+    let mut request_headers = reqwest::header::HeaderMap::new();
+    for spec in matches.values_of("header").into_iter().flatten() {
+        let (name, value) = fetch::parse_header(spec)?;
+        request_headers.insert(name, value);
+    }
+    let robots_check = matches.is_present("compliance-report").then(|| {
+        let user_agent = request_headers
+            .get(reqwest::header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or(user_agent);
+        let mut overrides: Vec<String> =
+            ["user-agent", "cookies", "proxy", "interface", "local-addr"]
+                .into_iter()
+                .filter(|option| matches.occurrences_of(option) > 0)
+                .map(|option| format!("--{}", option))
+                .collect();
+        overrides.extend(
+            request_headers
+                .keys()
+                .map(|name| format!("--header {}", name)),
+        );
+        if let Some(recorder) = recorder {
+            recorder.start_compliance(user_agent, overrides);
+        }
+        RobotsCheck::new(user_agent)
+    });
+
+    let header_matcher = HeaderMatcher {
+        fuzzy: matches.is_present("fuzzy-headers"),
+        max_distance: matches
+            .value_of("header-distance")
+            .map(|s| s.parse::<usize>())
+            .transpose()
+            .map_err(|_| TabexError::Usage("Invalid value for --header-distance".to_string()))?
+            .unwrap_or(2),
+        synonyms: matches
+            .value_of("header-synonyms")
+            .map(HeaderMatcher::parse_synonyms)
+            .transpose()?
+            .unwrap_or_default(),
+    };
 
-    // Prepare result
-    let result = ExtractionResult {
-        page: page_metadata,
-        tables,
-        extraction_time_ms: extraction_time,
+    let replace_rules = matches
+        .values_of("replace")
+        .map(|rules| rules.map(ReplaceRule::parse).collect::<Result<Vec<_>, _>>())
+        .transpose()?
+        .unwrap_or_default();
+
+    let transpose = matches
+        .value_of("transpose")
+        .map(TransposeTables::parse)
+        .transpose()?;
+
+    let derived_columns = matches
+        .values_of("add-column")
+        .map(|columns| {
+            columns
+                .map(DerivedColumn::parse)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let stamp_columns = matches
+        .value_of("stamp-columns")
+        .map(StampColumn::parse_list)
+        .transpose()?
+        .unwrap_or_default();
+
+    let key_columns: Vec<String> = matches
+        .value_of("key-columns")
+        .map(|columns| columns.split(',').map(|c| c.trim().to_string()).collect())
+        .unwrap_or_default();
+    let analyze = matches.is_present("analyze") || !key_columns.is_empty();
+
+    let profile = matches
+        .value_of("profile")
+        .map(Profile::parse)
+        .transpose()?;
+    let declared_types = matches
+        .value_of("types")
+        .map(DeclaredType::parse_list)
+        .transpose()?
+        .unwrap_or_default();
+    let redact_mode = matches
+        .value_of("redact")
+        .map(|category| {
+            redact::parse_category(category)?;
+            matches
+                .value_of("redact-mode")
+                .map_or(Ok(RedactMode::Mask), RedactMode::parse)
+        })
+        .transpose()?;
+    let pseudonymize = matches
+        .value_of("pseudonymize")
+        .map(|columns| {
+            let salt = redact::salt_from_env(matches.value_of("salt-env").unwrap_or("TABEX_SALT"))?;
+            let columns: Vec<String> = columns
+                .split(',')
+                .map(|c| c.trim().to_string())
+                .filter(|c| !c.is_empty())
+                .collect();
+            Ok::<_, TabexError>((columns, salt))
+        })
+        .transpose()?;
+    let semantic_packs = matches
+        .value_of("semantic-pack")
+        .map(SemanticPack::parse_list)
+        .transpose()?
+        .unwrap_or_default();
+    let sign_conventions = matches
+        .value_of("negatives")
+        .map_or(Ok(SignConventions::DEFAULT), SignConventions::parse)?;
+
+    // Timetables span days and platforms across rows, so they are laid out on the grid
+    let spans = match matches.value_of("spans") {
+        Some(mode) => SpanMode::parse(mode)?,
+        None if profile == Some(Profile::Timetable) => SpanMode::Duplicate,
+        None => SpanMode::Off,
+    };
+    let given_timezone = matches
+        .value_of("timezone")
+        .map(|name| {
+            name.parse::<chrono_tz::Tz>()
+                .map_err(|_| TabexError::Usage(format!("Unknown time zone {}", name)))
+        })
+        .transpose()?;
+    let timezone = given_timezone.unwrap_or(chrono_tz::UTC);
+    let date_context = matches
+        .value_of("date-context")
+        .map(|date| {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                TabexError::Usage(format!(
+                    "Invalid --date-context {} (expected YYYY-MM-DD)",
+                    date
+                ))
+            })
+        })
+        .transpose()?;
+    // Financial statements state their scale in notes, so the profile converts units
+    let convert_units = matches.is_present("convert-units") || profile == Some(Profile::Financial);
+    let canonical = matches.is_present("canonical");
+    let seconds = |option: &str| {
+        matches
+            .value_of(option)
+            .map(|s| {
+                s.parse::<f64>()
+                    .ok()
+                    .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                    .map(Duration::from_secs_f64)
+                    .ok_or_else(|| TabexError::Usage(format!("Invalid value for --{}", option)))
+            })
+            .transpose()
+    };
+    let deadline = seconds("deadline")?.map(|duration| Instant::now() + duration);
+    let timeout = seconds("timeout")?;
+    let proxy = matches
+        .value_of("proxy")
+        .map(fetch::parse_proxy)
+        .transpose()?;
+    let connect_timeout = seconds("connect-timeout")?;
+    let save_cookies = match matches.value_of("save-cookies") {
+        Some(path) => Some(path),
+        None if matches.is_present("save-cookies") => {
+            Some(matches.value_of("cookies").ok_or_else(|| {
+                TabexError::Usage(
+                    "--save-cookies needs a FILE unless --cookies is given".to_string(),
+                )
+            })?)
+        }
+        None => None,
+    };
+    let cookie_jar = (matches.is_present("cookies") || save_cookies.is_some())
+        .then(|| cookies::load_jar(matches.value_of("cookies"), &events))
+        .transpose()?;
+    let extracted_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let eval_context = EvalContext {
+        now: extracted_at.clone(),
     };
 
-    // Output results
-    match format {
-        "json" => {
-            let json = serde_json::to_string_pretty(&result)?;
-            if let Some(output_file) = matches.value_of("output") {
-                let mut file = File::create(output_file)?;
-                file.write_all(json.as_bytes())?;
-                println!("Results written to {}", output_file);
+    // Binding to an address of one family restricts connections to that family
+    let ip_family = if matches.is_present("ipv6") {
+        Some(true)
+    } else if matches.is_present("ipv4") {
+        Some(false)
+    } else {
+        None
+    };
+    let local_addr = match (
+        matches.value_of("local-addr"),
+        matches.value_of("interface"),
+    ) {
+        (Some(addr), _) => Some(
+            addr.parse::<IpAddr>()
+                .map_err(|_| TabexError::Usage(format!("Invalid --local-addr {}", addr)))?,
+        ),
+        (None, Some(interface)) => Some(fetch::interface_address(interface, ip_family)?),
+        (None, None) => ip_family.map(|ipv6| {
+            if ipv6 {
+                IpAddr::from(Ipv6Addr::UNSPECIFIED)
             } else {
-                println!("{}", json);
+                IpAddr::from(Ipv4Addr::UNSPECIFIED)
             }
+        }),
+    };
+    if let (Some(addr), Some(ipv6)) = (local_addr, ip_family)
+        && addr.is_ipv6() != ipv6
+    {
+        return Err(TabexError::Usage(format!(
+            "--local-addr {} is not an {} address",
+            addr,
+            if ipv6 { "IPv6" } else { "IPv4" }
+        )));
+    }
+
+    // Built-in sources, cleaning steps and outputs, in pipeline order
+    let host_filter = Arc::new(HostFilter {
+        allow: matches
+            .value_of("allow-hosts")
+            .map(HostFilter::parse_list)
+            .unwrap_or_default(),
+        deny: matches
+            .value_of("deny-hosts")
+            .map(HostFilter::parse_list)
+            .unwrap_or_default(),
+    });
+    let mut client_builder = reqwest::blocking::Client::builder()
+        .user_agent(user_agent)
+        .local_address(local_addr)
+        .redirect(HostFilter::redirect_policy(Arc::clone(&host_filter)))
+        .default_headers(request_headers);
+    for (host, addrs) in &dns_overrides {
+        client_builder = client_builder.resolve_to_addrs(host, addrs);
+    }
+    // --resolve entries win over the DoH answer; IP literals need no lookup
+    if let Some(endpoint) = matches.value_of("doh-url") {
+        let mut doh_builder = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .local_address(local_addr);
+        if let Some(timeout) = timeout {
+            doh_builder = doh_builder.timeout(timeout);
         }
-        "csv" => {
-            if let Some(output_file) = matches.value_of("output") {
-                output_tables_as_csv(&result, output_file)?;
-                println!("Results written to {}", output_file);
-            } else {
-                output_tables_as_csv_to_stdout(&result)?;
+        if let Some(timeout) = connect_timeout {
+            doh_builder = doh_builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &proxy {
+            doh_builder = doh_builder.proxy(proxy.clone());
+        }
+        let resolver = fetch::DohResolver::new(doh_builder.build()?, endpoint, events.clone());
+        client_builder = client_builder.dns_resolver(Arc::new(resolver));
+    }
+    if let Some(timeout) = timeout {
+        client_builder = client_builder.timeout(timeout);
+    }
+    if let Some(timeout) = connect_timeout {
+        client_builder = client_builder.connect_timeout(timeout);
+    }
+    if let Some(proxy) = proxy {
+        client_builder = client_builder.proxy(proxy);
+    }
+    if let Some(jar) = &cookie_jar {
+        client_builder = client_builder.cookie_provider(Arc::clone(jar));
+    }
+    let client = client_builder.build()?;
+    // Sitemap pages join the given URLs, so a run over a sitemap or a crawl is always a
+    // batch
+    if let Some(sitemap) = sitemap_url {
+        let mut seen: HashSet<String> = urls.iter().cloned().collect();
+        for page in sitemap::page_urls(
+            &client,
+            &host_filter,
+            sitemap,
+            sitemap_filter.as_ref(),
+            &events,
+        )? {
+            match canonicalize_url(&page, &strip_params) {
+                Ok(page) if seen.insert(page.clone()) => urls.push(page),
+                Ok(_) => {}
+                Err(e) => events.warning(
+                    "page_skipped",
+                    &format!("skipping sitemap entry {}: {}", page, e),
+                    json!({ "url": page, "error": e.to_string() }),
+                ),
             }
         }
-        _ => return Err("Unsupported output format".into()),
+    }
+    // Only a sitemap can leave no URLs this late, when none of its entries parse
+    if urls.is_empty()
+        && let Some(sitemap) = sitemap_url
+    {
+        return Err(TabexError::EmptySitemap {
+            url: sitemap.to_string(),
+        });
+    }
+    let url = urls[0].as_str();
+    let batch = urls.len() + invalid_urls.len() > 1 || sitemap_url.is_some() || crawl.is_some();
+    let cdx_client = client.clone();
+    let cdx_host_filter = Arc::clone(&host_filter);
+    let crawl_host_filter = Arc::clone(&host_filter);
+    let bandwidth = Arc::new(Bandwidth::new(
+        matches
+            .value_of("max-bandwidth")
+            .map(fetch::parse_bandwidth)
+            .transpose()?,
+    ));
+    *meter = Some(Arc::clone(&bandwidth));
+    let retries = matches
+        .value_of("retries")
+        .map(|s| s.parse::<u32>())
+        .transpose()
+        .map_err(|_| TabexError::Usage("Invalid value for --retries".to_string()))?
+        .unwrap_or(0);
+    let mut registry = Registry::default();
+    registry.register_source(Box::new(StdinSource {
+        events: events.clone(),
+    }));
+    registry.register_source(Box::new(FileSource {
+        events: events.clone(),
+    }));
+    registry.register_source(Box::new(HttpSource {
+        client,
+        accepted_content_types,
+        deadline,
+        timeout,
+        bandwidth: Arc::clone(&bandwidth),
+        host_filter,
+        retries,
+        robots: robots_check,
+        events: events.clone(),
+    }));
+    if !replace_rules.is_empty() {
+        registry.register_transform(Box::new(Replacements {
+            rules: replace_rules,
+            matcher: header_matcher.clone(),
+        }));
+    }
+    if matches.is_present("expand-suffixes") {
+        registry.register_transform(Box::new(ExpandSuffixes));
+    }
+    if let Some(profile) = profile {
+        registry.register_transform(profile.transform(ProfileContext {
+            timezone,
+            date: date_context,
+        }));
+    }
+    // After the timetable profile, which reads its own times
+    if let Some(timezone) = given_timezone {
+        registry.register_transform(Box::new(NormalizeDatetimes { timezone }));
+    }
+    if matches.is_present("durations") {
+        registry.register_transform(Box::new(ParseDurations));
+    }
+    if matches.is_present("coordinates") {
+        registry.register_transform(Box::new(ParseCoordinates));
+    }
+    if convert_units {
+        registry.register_transform(Box::new(ConvertUnits));
+    }
+    if matches.is_present("melt") {
+        registry.register_transform(Box::new(MeltMatrix));
+    }
+    if let Some(tables) = transpose {
+        registry.register_transform(Box::new(Transpose { tables }));
+    }
+    if !derived_columns.is_empty() {
+        registry.register_transform(Box::new(DerivedColumns {
+            columns: derived_columns,
+            context: eval_context.clone(),
+            matcher: header_matcher.clone(),
+        }));
+    }
+    if !stamp_columns.is_empty() {
+        registry.register_transform(Box::new(StampColumns {
+            stamps: stamp_columns,
+            context: eval_context,
+        }));
+    }
+    // Last, so no step sees or derives from the values they replace; pseudonyms come
+    // first so --redact leaves them joinable
+    if let Some((columns, salt)) = pseudonymize {
+        registry.register_transform(Box::new(Pseudonymize {
+            columns,
+            salt,
+            matcher: header_matcher.clone(),
+        }));
+    }
+    if let Some(mode) = redact_mode {
+        registry.register_transform(Box::new(RedactPii {
+            mode,
+            events: events.clone(),
+        }));
+    }
+    registry.register_sink(Box::new(JsonSink {
+        records: matches.is_present("records"),
+        canonical,
+    }));
+    registry.register_sink(Box::new(CsvSink { canonical }));
+    registry.register_sink(Box::new(OrgSink));
+    registry.register_sink(Box::new(AsciiDocSink));
+    registry.register_sink(Box::new(ReportSink));
+    registry.register_sink(Box::new(XlsxSink {
+        options: matches
+            .value_of("xlsx-options")
+            .map(XlsxOptions::parse_list)
+            .transpose()?
+            .unwrap_or_default(),
+    }));
+    registry.register_sink(Box::new(SqlSink {
+        dialect: matches
+            .value_of("dialect")
+            .map(Dialect::parse)
+            .transpose()?
+            .unwrap_or(Dialect::Postgres),
+    }));
+    registry.register_sink(Box::new(GeoJsonSink {
+        columns: matches
+            .value_of("geo-columns")
+            .map(geo::parse_geo_columns)
+            .transpose()?
+            .unwrap_or_default(),
+        matcher: header_matcher.clone(),
+    }));
+
+    let sinks = formats
+        .iter()
+        .map(|format| {
+            registry.sink(format).ok_or_else(|| {
+                TabexError::Usage(format!(
+                    "Unsupported output format {} (expected one of: {})",
+                    format,
+                    registry.sink_names().join(", ")
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // Each format is written to the --output at the same place in the list
+    if commit_repo.is_none()
+        && output_dir.is_none()
+        && (formats.len() > 1 || outputs.len() > 1)
+        && formats.len() != outputs.len()
+    {
+        return Err(TabexError::Usage(format!(
+            "--format lists {} format(s) but {} --output file(s) were given; pass one --output per format",
+            formats.len(),
+            outputs.len()
+        )));
+    }
+    if let Some(path) = outputs
+        .iter()
+        .enumerate()
+        .find_map(|(i, path)| outputs[..i].contains(path).then_some(path))
+    {
+        return Err(TabexError::Usage(format!(
+            "--output {} is given more than once",
+            path
+        )));
+    }
+    let routes = matches
+        .values_of("route")
+        .map(|specs| specs.map(Route::parse).collect::<Result<Vec<_>, _>>())
+        .transpose()?
+        .unwrap_or_default();
+    if !routes.is_empty() && commit_repo.is_some() {
+        return Err(TabexError::Usage(
+            "--route is not supported with tabex commit".to_string(),
+        ));
+    }
+    if output_dir.is_some() && commit_repo.is_some() {
+        return Err(TabexError::Usage(
+            "--output-dir is not supported with tabex commit".to_string(),
+        ));
+    }
+    routing::destinations(&routes)?;
+    for route in &routes {
+        if registry.sink(&route.format).is_none() {
+            return Err(TabexError::Usage(format!(
+                "Unsupported output format {} in --route (expected one of: {})",
+                route.format,
+                registry.sink_names().join(", ")
+            )));
+        }
+    }
+    // Routes replace --format, so format-specific options apply to their formats
+    let written_formats: Vec<&str> = if routes.is_empty() {
+        formats.clone()
+    } else {
+        routes.iter().map(|route| route.format.as_str()).collect()
+    };
+    if matches.is_present("records") && !written_formats.contains(&"json") {
+        return Err(TabexError::Usage(
+            "--records is only supported with JSON output".to_string(),
+        ));
+    }
+    // The run summary also goes to stdout and would corrupt the workbook
+    if formats.contains(&"xlsx")
+        && routes.is_empty()
+        && outputs.is_empty()
+        && output_dir.is_none()
+        && commit_repo.is_none()
+    {
+        return Err(TabexError::Usage(
+            "--format xlsx needs --output FILE".to_string(),
+        ));
+    }
+    if batch && !routes.is_empty() {
+        return Err(TabexError::Usage(
+            "--route is only supported with a single --url".to_string(),
+        ));
+    }
+    if matches.is_present("xlsx-options") && !written_formats.contains(&"xlsx") {
+        return Err(TabexError::Usage(
+            "--xlsx-options is only supported with xlsx output".to_string(),
+        ));
+    }
+    if matches.is_present("dialect") && !written_formats.contains(&"sql") {
+        return Err(TabexError::Usage(
+            "--dialect is only supported with SQL output".to_string(),
+        ));
+    }
+    if matches.is_present("geo-columns") && !written_formats.contains(&"geojson") {
+        return Err(TabexError::Usage(
+            "--geo-columns is only supported with GeoJSON output".to_string(),
+        ));
     }
 
-    // Print summary
-    println!("\nExtraction Summary:");
-    println!("URL: {}", url);
-    println!("Tables found: {}", result.tables.len());
-    println!("Extraction time: {} ms", extraction_time);
+    if matches.is_present("dry-run") {
+        let plan = RequestPlan {
+            robots_txt: matches.is_present("compliance-report"),
+            wayback: wayback_range.is_some(),
+            follow: matches.is_present("follow-column"),
+            retries,
+        };
+        print_fetch_plan(&urls, crawl, &plan, &registry)?;
+        return Ok(());
+    }
 
-    Ok(())
-}
+    let extractor = Extractor::new(ExtractOptions {
+        row_groups: matches.is_present("row-groups"),
+        exclude_totals: matches.is_present("exclude-totals"),
+        tag_totals: matches.is_present("tag-totals"),
+        row_styles: profile == Some(Profile::Sports),
+        spans,
+        cell_links: matches.is_present("follow-column"),
+        cell_styles: matches.is_present("cell-styles"),
+        deadline,
+        cancel: None,
+        sign_conventions,
+        events: events.clone(),
+    });
+
+    // Tables of one fetched page, joined with detail pages and cleaned
+    let process = |document: &Html, page_url: &str| {
+        // Extract page metadata
+        let page_metadata = extractor.page_metadata(document, page_url);
+
+        // Extract tables
+        let mut tables = extractor.extract_tables(document)?;
+
+        if matches.is_present("lineage") {
+            lineage::init(&mut tables.tables);
+        }
 
-fn extract_page_metadata(document: &Html, url: &str) -> PageMetadata {
-    // Helper function to get meta tag content
-    let get_meta_content = |name: &str| {
-        let selector =
-            Selector::parse(&format!("meta[name='{}'], meta[property='{}']", name, name)).unwrap();
-        document
-            .select(&selector)
-            .next()
-            .and_then(|el| el.value().attr("content"))
-            .map(String::from)
+        // Join detail pages first so cleaning steps see the joined columns
+        if let Some(column) = matches.value_of("follow-column") {
+            let options = FollowOptions {
+                column,
+                detail_table,
+                strip_params: &strip_params,
+                matcher: &header_matcher,
+            };
+            tables.timed_out |= follow::follow_links(
+                &mut tables.tables,
+                page_url,
+                &options,
+                &registry,
+                &extractor,
+            );
+        }
+
+        // Apply cleaning and reshaping steps
+        registry.apply_transforms(&mut tables.tables, &page_metadata);
+        Ok::<_, TabexError>((page_metadata, tables))
     };
 
-    // Extract title
-    let title_selector = Selector::parse("title").unwrap();
-    let title = document
-        .select(&title_selector)
-        .next()
-        .map(|el| el.inner_html().trim().to_string());
-
-    // Extract other metadata
-    let description =
-        get_meta_content("description").or_else(|| get_meta_content("og:description"));
-    let author = get_meta_content("author");
-    let published_date =
-        get_meta_content("article:published_time").or_else(|| get_meta_content("pubdate"));
-    let last_modified =
-        get_meta_content("article:modified_time").or_else(|| get_meta_content("lastmod"));
-
-    PageMetadata {
-        url: url.to_string(),
-        title,
-        description,
-        author,
-        published_date,
-        last_modified,
-    }
-}
+    // One page from fetching to its finished result
+    let extract_page = |url: &str| {
+        let start = Instant::now();
+
+        // Fetch and parse the web page, or its archived snapshots
+        let source = registry
+            .source_for(url)
+            .ok_or_else(|| TabexError::Usage(format!("No source can load {}", url)))?;
+        let (document, page_metadata, tables, attempts) = match &wayback_range {
+            Some(range) => {
+                let snapshots =
+                    wayback::snapshots(&cdx_client, &cdx_host_filter, url, range, &events)?;
+                let (document, page_metadata, tables) =
+                    wayback::backfill(url, &snapshots, source, &events, process)?;
+                (document, page_metadata, tables, None)
+            }
+            None => {
+                let source_document = source.load(url);
+                events.record(Record::Input {
+                    url,
+                    final_url: source_document
+                        .as_ref()
+                        .ok()
+                        .map(|document| document.url.as_str()),
+                });
+                let source_document = source_document?;
+                let document = Html::parse_document(&source_document.html);
+                let (page_metadata, tables) = process(&document, &source_document.url)?;
+                (document, page_metadata, tables, source_document.attempts)
+            }
+        };
+        let DocumentTables {
+            mut tables,
+            timed_out,
+        } = tables;
+
+        // Filter tables by score
+        if let Some(min_score) = min_score {
+            tables.retain(|table| table.metadata.score >= min_score);
+        }
+        if matches.is_present("best") {
+            // Earliest table wins ties
+            if let Some(best) = tables
+                .iter()
+                .enumerate()
+                .max_by_key(|(i, table)| (table.metadata.score, std::cmp::Reverse(*i)))
+                .map(|(i, _)| i)
+            {
+                tables = vec![tables.swap_remove(best)];
+            }
+        }
 
-fn extract_tables(document: &Html) -> Vec<Table> {
-    let table_selector = Selector::parse("table").unwrap();
-    let h_selector = Selector::parse("h1, h2, h3, h4, h5, h6").unwrap();
-    let caption_selector = Selector::parse("caption").unwrap();
-    let tr_selector = Selector::parse("tr").unwrap();
-    let th_selector = Selector::parse("th").unwrap();
-    let td_selector = Selector::parse("td").unwrap();
-    let section_selector = Selector::parse("section, article, div[role='main']").unwrap();
-
-    let mut tables = Vec::new();
-    let mut table_position = 0;
-
-    for table_element in document.select(&table_selector) {
-        table_position += 1;
-
-        // Get table attributes
-        let id = table_element.value().attr("id").map(String::from);
-        let class = table_element.value().attr("class").map(String::from);
-
-        // Get caption
-        let caption = table_element
-            .select(&caption_selector)
-            .next()
-            .map(|cap| cap.inner_html().trim().to_string());
-
-        // Get parent section
-        let parent_section = find_parent_with_selector(table_element.clone(), &section_selector)
-            .and_then(|section| {
-                section
-                    .value()
-                    .attr("id")
-                    .or_else(|| section.value().attr("class"))
-            })
-            .map(String::from);
-
-        // Find preceding heading
-        let preceding_heading =
-            find_preceding_heading(table_element.clone(), document, &h_selector);
-
-        // Process rows
-        let rows_elements: Vec<_> = table_element.select(&tr_selector).collect();
-        let row_count = rows_elements.len();
-
-        // Count header and footer rows
-        let header_row_count = rows_elements
-            .iter()
-            .take_while(|row| row.select(&th_selector).next().is_some())
-            .count();
-
-        // Count footer rows (rows in tfoot or with th elements at end)
-        let footer_row_count = rows_elements
-            .iter()
-            .rev()
-            .take_while(|row| {
-                let is_in_tfoot = find_parent_with_tag((*row).clone(), "tfoot").is_some();
-                is_in_tfoot || row.select(&th_selector).next().is_some()
-            })
-            .count();
+        // After filtering, so --best and --min-score only choose among the page's tables
+        if matches.is_present("extract-links") {
+            let position = document.select(&TABLE_SELECTOR).count() + 1;
+            let mut links = vec![links::links_table(&document, &page_metadata.url, position)];
+            // The transforms have already run, and mailto: and tel: links are personal data
+            if let Some(mode) = redact_mode {
+                RedactPii {
+                    mode,
+                    events: events.clone(),
+                }
+                .apply(&mut links, &page_metadata);
+            }
+            tables.append(&mut links);
+        }
 
-        let data_row_count = if row_count > header_row_count + footer_row_count {
-            row_count - header_row_count - footer_row_count
-        } else {
-            0 // Fallback to 0 if counts are invalid
-        };
+        // Scale notes no longer apply once values have been converted
+        for table in &mut tables {
+            table.metadata.schema = units::build_schema(table, !convert_units);
+            units::declare_types(table, &declared_types, &header_matcher);
+            semantic::apply_packs(table, &semantic_packs);
+        }
 
-        // Extract headers
-        let headers = if header_row_count > 0 {
-            rows_elements[0]
-                .select(&th_selector)
-                .map(|cell| clean_cell_text(cell.inner_html()))
-                .collect::<Vec<String>>() // Using turbofish
+        let relationships = if analyze {
+            analysis::analyze_tables(&mut tables, &key_columns, &header_matcher, &events);
+            Some(analysis::find_relationships(&tables))
         } else {
-            Vec::new()
+            None
         };
 
-        // Count columns based on the row with the most cells
-        let column_count = rows_elements
-            .iter()
-            .map(|row| row.select(&th_selector).count() + row.select(&td_selector).count())
-            .max()
-            .unwrap_or(0);
-
-        // Extract data rows
-        let data_rows: Vec<Vec<String>> = rows_elements
-            .iter()
-            .skip(header_row_count)
-            .take(data_row_count)
-            .map(|row| {
-                row.select(&td_selector)
-                    .map(|cell| clean_cell_text(cell.inner_html()))
-                    .collect()
-            })
-            .collect();
-
-        // Create table object
-        let table = Table {
-            metadata: TableMetadata {
-                id,
-                class,
-                caption,
-                position: table_position,
-                row_count,
-                column_count,
-                header_row_count,
-                footer_row_count,
-                parent_section,
-                preceding_heading,
-            },
-            data: TableData {
-                headers,
-                rows: data_rows,
-            },
+        if let Some(template) = &name_template {
+            template.apply(&mut tables, &page_metadata);
+        }
+
+        let result = ExtractionResult {
+            page: page_metadata,
+            tables,
+            relationships,
+            timed_out,
+            attempts,
+            extraction_time_ms: start.elapsed().as_millis(),
         };
+        Ok::<_, TabexError>((document, result))
+    };
 
-        tables.push(table);
+    // With several URLs a page that can't be extracted is reported in the output
+    // instead of ending the run; only --route needs the page's document afterwards
+    let mut pages: Vec<PageOutcome> = Vec::new();
+    let mut document = None;
+    let mut failure = None;
+    for (url, e) in invalid_urls {
+        events.warning(
+            "page_failed",
+            &format!("skipping {}: {}", url, e),
+            json!({ "url": url, "error": e.to_string() }),
+        );
+        pages.push(PageOutcome {
+            url,
+            ok: false,
+            error: Some(e.to_string()),
+            result: None,
+        });
+        failure = Some(e);
     }
-
-    tables
-}
-
-fn find_parent_with_selector<'a>(
-    element: scraper::ElementRef<'a>,
-    selector: &Selector,
-) -> Option<scraper::ElementRef<'a>> {
-    let mut current = element;
-
-    while let Some(parent_node) = current.parent() {
-        if let Some(parent_element) = scraper::ElementRef::wrap(parent_node) {
-            if selector.matches(&parent_element) {
-                return Some(parent_element);
+    let mut frontier = Frontier::new(&urls, crawl, &strip_params, crawl_host_filter);
+    while let Some((url, depth)) = frontier.pop() {
+        let url = &url;
+        match extract_page(url) {
+            Ok((page_document, result)) => {
+                let ignore_robots_meta = crawl.is_some_and(|crawl| crawl.ignore_robots_meta);
+                if frontier.follows(depth) {
+                    frontier.add_links(
+                        crawl::page_links(&page_document, &result.page, ignore_robots_meta),
+                        depth,
+                    );
+                }
+                if crawl.is_some_and(|crawl| crawl.skips(&result.page, depth)) {
+                    events.status(
+                        "noindex",
+                        &format!("Skipping {}: its robots meta says noindex", url),
+                        json!({ "url": url }),
+                    );
+                    continue;
+                }
+                if !batch {
+                    document = Some(page_document);
+                }
+                pages.push(PageOutcome {
+                    url: url.clone(),
+                    ok: true,
+                    error: None,
+                    result: Some(result),
+                });
+            }
+            Err(e) if !batch => return Err(e),
+            // A binary or other unparsed response isn't a failure of the run, as a
+            // crawl meets PDFs and images among a site's links
+            Err(TabexError::ContentType { content_type, url }) => {
+                events.status(
+                    "page_skipped",
+                    &format!("Skipping {}: its Content-Type is {}", url, content_type),
+                    json!({ "url": url, "content_type": content_type }),
+                );
+            }
+            Err(e) => {
+                events.warning(
+                    "page_failed",
+                    &format!("skipping {}: {}", url, e),
+                    json!({ "url": url, "error": e.to_string() }),
+                );
+                pages.push(PageOutcome {
+                    url: url.clone(),
+                    ok: false,
+                    error: Some(e.to_string()),
+                    result: None,
+                });
+                failure = Some(e);
             }
-            current = parent_element;
-        } else {
-            // If parent is not an element, skip it
-            current = scraper::ElementRef::wrap(parent_node.parent()?)?;
         }
     }
-    None
-}
-
-fn find_parent_with_tag<'a>(
-    element: scraper::ElementRef<'a>,
-    tag_name: &str,
-) -> Option<scraper::ElementRef<'a>> {
-    let mut current = element;
+    if let (Some(jar), Some(path)) = (&cookie_jar, save_cookies) {
+        cookies::save_jar(jar, path, &events)?;
+    }
+    let table_count: usize = pages
+        .iter()
+        .filter_map(|page| page.result.as_ref())
+        .map(|result| result.tables.len())
+        .sum();
 
-    while let Some(parent_node) = current.parent() {
-        if let Some(parent_element) = scraper::ElementRef::wrap(parent_node) {
-            if parent_element.value().name().eq_ignore_ascii_case(tag_name) {
-                return Some(parent_element);
+    // Output results
+    if let Some(repo) = commit_repo {
+        for result in pages.iter().filter_map(|page| page.result.as_ref()) {
+            if datarepo::commit_tables(Path::new(repo), result, &extracted_at, &events)? {
+                events.status(
+                    "commit",
+                    &format!("Committed {} table(s) to {}", result.tables.len(), repo),
+                    json!({ "repo": repo, "committed": true, "tables": result.tables.len() }),
+                );
+            } else {
+                events.status(
+                    "commit",
+                    &format!("No changes to commit in {}", repo),
+                    json!({ "repo": repo, "committed": false, "tables": result.tables.len() }),
+                );
             }
-            current = parent_element;
-        } else {
-            // If parent is not an element, skip it
-            current = scraper::ElementRef::wrap(parent_node.parent()?)?;
         }
+    } else if !routes.is_empty() {
+        // --route is refused for several URLs, so this is the only page
+        if let (Some(result), Some(document)) = (pages[0].result.as_mut(), &document) {
+            routing::write_routed(result, &routes, document, &registry, &events)?;
+        }
+    } else if let Some(dir) = output_dir {
+        write_page_files(Path::new(dir), &sinks, &formats, &pages, &events)?;
+    } else if !outputs.is_empty() {
+        for ((sink, format), output_file) in sinks.iter().zip(&formats).zip(&outputs) {
+            write_pages(*sink, format, &pages, Some(output_file), &events)?;
+            events.status(
+                "output",
+                &format!("Results written to {}", output_file),
+                json!({ "path": output_file, "format": format, "tables": table_count }),
+            );
+        }
+    } else {
+        write_pages(sinks[0], formats[0], &pages, None, &events)?;
     }
-    None
-}
-
-fn find_preceding_heading(
-    element: scraper::ElementRef,
-    document: &Html,
-    h_selector: &Selector,
-) -> Option<String> {
-    // This is a simplified approach - ideally you'd traverse the DOM tree
-    // For simplicity, we'll just get all headings and find the last one before our table
-    let all_headings: Vec<_> = document.select(h_selector).collect();
-    let all_elements: Vec<_> = document.select(&Selector::parse("*").unwrap()).collect();
 
-    let table_pos = all_elements.iter().position(|&el| el == element)?;
+    // Print summary, set off from data written to stdout
+    console.blank_line();
+    let results = || pages.iter().filter_map(|page| page.result.as_ref());
+    let timed_out = results().any(|result| result.timed_out);
+    let extraction_time: u128 = results().map(|result| result.extraction_time_ms).sum();
+    let failed: Vec<&str> = pages
+        .iter()
+        .filter(|page| !page.ok)
+        .map(|page| page.url.as_str())
+        .collect();
+    let mut summary = vec![
+        "Extraction Summary:".to_string(),
+        if batch {
+            format!("URLs: {} ({} failed)", pages.len(), failed.len())
+        } else {
+            format!("URL: {}", url)
+        },
+        format!("Tables found: {}", table_count),
+        format!("Extraction time: {} ms", extraction_time),
+    ];
+    summary.extend(downloaded(&bandwidth));
+    let mut data = if batch {
+        let urls: Vec<&str> = pages.iter().map(|page| page.url.as_str()).collect();
+        json!({ "urls": urls, "failed": failed })
+    } else {
+        json!({ "url": url })
+    };
+    if let (Some(data), serde_json::Value::Object(totals)) = (
+        data.as_object_mut(),
+        json!({
+            "tables": table_count,
+            "rows": results()
+                .flat_map(|result| &result.tables)
+                .map(|table| table.data.rows.len())
+                .sum::<usize>(),
+            "extraction_time_ms": extraction_time,
+            "bytes_downloaded": bandwidth.bytes(),
+            "timed_out": timed_out,
+        }),
+    ) {
+        data.extend(totals);
+    }
+    events.status("summary", &summary.join("\n"), data);
+    if timed_out {
+        events.warning(
+            "deadline",
+            "deadline reached; later tables were skipped",
+            json!({}),
+        );
+    }
 
-    all_headings
-        .into_iter()
-        .filter_map(|heading| {
-            let heading_pos = all_elements.iter().position(|&el| el == heading)?;
-            if heading_pos < table_pos {
-                Some((heading_pos, heading.inner_html().trim().to_string()))
-            } else {
-                None
-            }
-        })
-        .max_by_key(|(pos, _)| *pos)
-        .map(|(_, text)| text)
+    // A run given several URLs fails only when none of them could be extracted
+    match failure {
+        Some(e) if failed.len() == pages.len() => Err(e),
+        _ => Ok(()),
+    }
 }
 
-fn clean_cell_text(html: String) -> String {
-    // Remove HTML tags
-    let re = Regex::new(r"<[^>]*>").unwrap();
-    let text = re.replace_all(&html, "");
-
-    // Normalize whitespace
-    let ws_re = Regex::new(r"\s+").unwrap();
-    let text = ws_re.replace_all(&text, " ");
-
-    text.trim().to_string()
+/// Bytes downloaded and the average rate, once anything was downloaded.
+fn downloaded(bandwidth: &Bandwidth) -> Option<String> {
+    let rate = bandwidth.average_rate()?;
+    Some(format!(
+        "Downloaded: {} bytes at {} on average",
+        bandwidth.bytes(),
+        fetch::format_rate(rate)
+    ))
 }
 
-fn output_tables_as_csv(
-    result: &ExtractionResult,
-    output_file: &str,
-) -> Result<(), Box<dyn Error>> {
-    let mut file = File::create(output_file)?;
-
-    // Write metadata as a comment
-    writeln!(file, "# URL: {}", result.page.url)?;
-    if let Some(title) = &result.page.title {
-        writeln!(file, "# Title: {}", title)?;
-    }
-    writeln!(file, "# Tables found: {}", result.tables.len())?;
-    writeln!(file, "# Extraction time: {} ms", result.extraction_time_ms)?;
-    writeln!(file)?;
-
-    // Write each table
-    for (i, table) in result.tables.iter().enumerate() {
-        writeln!(file, "# Table {} of {}", i + 1, result.tables.len())?;
-        writeln!(file, "# Position: {}", table.metadata.position)?;
-        if let Some(caption) = &table.metadata.caption {
-            writeln!(file, "# Caption: {}", caption)?;
-        }
-        if let Some(heading) = &table.metadata.preceding_heading {
-            writeln!(file, "# Preceding heading: {}", heading)?;
-        }
-        writeln!(file)?;
-
-        // Write headers
-        if !table.data.headers.is_empty() {
-            writeln!(file, "{}", table.data.headers.join(","))?;
-        }
+/// Writes the run's pages with `sink`: a single page as its result alone, as it
+/// always has been, several as `Sink::write_pages` lays them out.
+fn write_pages(
+    sink: &dyn Sink,
+    format: &str,
+    pages: &[PageOutcome],
+    path: Option<&str>,
+    events: &Events,
+) -> Result<(), TabexError> {
+    match pages {
+        [
+            PageOutcome {
+                result: Some(result),
+                ..
+            },
+        ] => manifest::write_output(sink, format, result, path, events),
+        _ => manifest::write_pages_output(sink, format, pages, path, events),
+    }
+}
 
-        // Write data rows
-        for row in &table.data.rows {
-            writeln!(file, "{}", row.join(","))?;
+/// Writes each extracted page to `dir` once per format, named by its page slug.
+/// Pages whose slugs collide, such as ones differing only by query string, get
+/// `_2`, `_3`, ... suffixes.
+fn write_page_files(
+    dir: &Path,
+    sinks: &[&dyn Sink],
+    formats: &[&str],
+    pages: &[PageOutcome],
+    events: &Events,
+) -> Result<(), TabexError> {
+    fs::create_dir_all(dir)?;
+    let mut stems: Vec<String> = Vec::new();
+    for page in pages {
+        let Some(result) = &page.result else {
+            continue;
+        };
+        let base = Some(page_slug(&page.url))
+            .filter(|slug| !slug.is_empty())
+            .unwrap_or_else(|| "page".to_string());
+        let mut stem = base.clone();
+        let mut n = 2;
+        while stems.contains(&stem) {
+            stem = format!("{}_{}", base, n);
+            n += 1;
         }
-
-        // Add separator between tables
-        if i < result.tables.len() - 1 {
-            writeln!(file)?;
-            writeln!(file, "# ------------------------------")?;
-            writeln!(file)?;
+        for (sink, format) in sinks.iter().zip(formats) {
+            let path = dir.join(format!("{}.{}", stem, sink.extension()));
+            let path = path.to_string_lossy();
+            manifest::write_output(*sink, format, result, Some(&path), events)?;
+            events.status(
+                "output",
+                &format!("Results written to {}", path),
+                json!({ "path": path, "format": format, "tables": result.tables.len() }),
+            );
         }
+        stems.push(stem);
     }
-
     Ok(())
 }
 
-fn output_tables_as_csv_to_stdout(result: &ExtractionResult) -> Result<(), Box<dyn Error>> {
-    // Write metadata as a comment
-    println!("# URL: {}", result.page.url);
-    if let Some(title) = &result.page.title {
-        println!("# Title: {}", title);
-    }
-    println!("# Tables found: {}", result.tables.len());
-    println!("# Extraction time: {} ms", result.extraction_time_ms);
-    println!();
-
-    // Write each table
-    for (i, table) in result.tables.iter().enumerate() {
-        println!("# Table {} of {}", i + 1, result.tables.len());
-        println!("# Position: {}", table.metadata.position);
-        if let Some(caption) = &table.metadata.caption {
-            println!("# Caption: {}", caption);
-        }
-        if let Some(heading) = &table.metadata.preceding_heading {
-            println!("# Preceding heading: {}", heading);
-        }
-        println!();
+/// Options that change how many requests `--dry-run` estimates for each page.
+struct RequestPlan {
+    /// --compliance-report reads each site's robots.txt
+    robots_txt: bool,
+    /// --wayback lists a page's snapshots and loads them instead of fetching the page
+    wayback: bool,
+    /// --follow-column fetches the detail pages a table links to
+    follow: bool,
+    retries: u32,
+}
 
-        // Write headers
-        if !table.data.headers.is_empty() {
-            println!("{}", table.data.headers.join(","));
+fn print_fetch_plan(
+    urls: &[String],
+    crawl: Option<Crawl>,
+    plan: &RequestPlan,
+    registry: &Registry,
+) -> Result<(), TabexError> {
+    let mut hosts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut origins: HashSet<String> = HashSet::new();
+    let mut local = 0;
+    for url in urls {
+        // --file paths and stdin aren't URLs, and reading them makes no request
+        match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+                let host = parsed.host_str().unwrap_or("<none>").to_string();
+                *hosts.entry(host).or_insert(0) += 1;
+                origins.insert(parsed.origin().ascii_serialization());
+            }
+            _ => local += 1,
         }
-
-        // Write data rows
-        for row in &table.data.rows {
-            println!("{}", row.join(","));
+    }
+    let pages = urls.len() - local;
+    let robots_txt = if plan.robots_txt { origins.len() } else { 0 };
+    // With --wayback the page itself isn't fetched, only the archive's list of snapshots
+    let (lookups, page_requests) = if plan.wayback { (pages, 0) } else { (0, pages) };
+
+    // The sitemap, and the --doh-url lookups of its hosts, have been made by now
+    println!("Dry run: no page requests will be made");
+    println!("URLs to fetch: {}", pages);
+    if local > 0 {
+        println!("Local files and stdin: {}", local);
+    }
+    println!(
+        "Estimated requests: {}",
+        robots_txt + lookups + page_requests
+    );
+    if robots_txt > 0 {
+        println!("  robots.txt: {}", robots_txt);
+    }
+    if lookups > 0 {
+        println!(
+            "  Wayback CDX lookups: {}, plus one per archived snapshot loaded",
+            lookups
+        );
+    }
+    if plan.retries > 0 && page_requests > 0 {
+        println!(
+            "  Up to {} with --retries {}",
+            robots_txt + lookups + page_requests * (plan.retries as usize + 1),
+            plan.retries
+        );
+    }
+    if plan.follow {
+        println!("  Plus one per detail page --follow-column links to");
+    }
+    if crawl.is_some_and(|crawl| crawl.max_depth > 0) {
+        println!("  Plus one per page --crawl finds");
+    }
+    if !hosts.is_empty() {
+        println!("Per-host breakdown:");
+        for (host, count) in &hosts {
+            println!("  {}: {}", host, count);
         }
-
-        // Add separator between tables
-        if i < result.tables.len() - 1 {
-            println!();
-            println!("# ------------------------------");
-            println!();
+    }
+    println!("URLs:");
+    for url in urls {
+        let source = registry
+            .source_for(url)
+            .map_or("<none>", |source| source.name());
+        println!("  - {} ({})", url, source);
+    }
+    // Links are only known once pages are fetched, so the frontier is the start URLs
+    if let Some(crawl) = crawl {
+        println!("Crawl frontier:");
+        println!("  Start URLs: {} at depth 0", urls.len());
+        println!("  Links followed up to depth {}", crawl.max_depth);
+        if crawl.same_domain {
+            println!("  Only links to the start URLs' domains");
         }
+        if crawl.ignore_robots_meta {
+            println!("  nofollow links followed and noindex pages extracted");
+        } else {
+            println!("  nofollow links skipped and noindex pages left out");
+        }
+    }
+    let transforms = registry.transform_names();
+    if !transforms.is_empty() {
+        println!("Transforms: {}", transforms.join(", "));
     }
 
     Ok(())
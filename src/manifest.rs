@@ -0,0 +1,396 @@
+//! `--manifest`: a JSON record of the run for orchestration sensors and downstream
+//! tasks, written when the run ends whether it succeeded or not. A [`Recorder`]
+//! collects it as the run's reporter.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::TabexError;
+use crate::events::{Events, Record, Reporter};
+use crate::plugin::Sink;
+use crate::robots::RobotsRules;
+use crate::{ExtractionResult, PageOutcome, Table};
+
+/// Bumped when a field changes meaning or goes away; new fields may appear at any version.
+const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+struct ManifestInput {
+    url: String,
+    /// Where the page was loaded from after redirects; None when loading failed
+    final_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestOutput {
+    /// `-` for stdout
+    path: String,
+    format: String,
+    tables: usize,
+    rows: usize,
+    bytes: u64,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestWarning {
+    event: String,
+    message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ComplianceHost {
+    /// Requests sent, robots.txt and retries included
+    requests: usize,
+    /// None when no page was fetched from the host, e.g. for a sitemap host
+    #[serde(skip_serializing_if = "Option::is_none")]
+    robots_txt: Option<RobotsRules>,
+    /// Shortest time between two requests for pages, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shortest_interval_secs: Option<f64>,
+    /// Whether requests were at least robots.txt's crawl-delay apart. The run doesn't
+    /// wait out the delay, so this reports what happened rather than a guarantee
+    #[serde(skip_serializing_if = "Option::is_none")]
+    crawl_delay_honored: Option<bool>,
+    /// Pages fetched that robots.txt disallows
+    disallowed_urls: Vec<String>,
+    #[serde(skip)]
+    request_times: Vec<Instant>,
+}
+
+/// `--compliance-report`: what each site asked of the run and what the run did.
+#[derive(Debug, Serialize)]
+struct Compliance {
+    user_agent: String,
+    hosts: BTreeMap<String, ComplianceHost>,
+    /// Options that change how the run identifies itself, e.g. "--user-agent" or
+    /// "--header Referer"
+    overrides: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Manifest {
+    manifest_version: u32,
+    tool_version: &'static str,
+    started_at: String,
+    finished_at: String,
+    /// "success" or "error"
+    status: &'static str,
+    exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    inputs: Vec<ManifestInput>,
+    outputs: Vec<ManifestOutput>,
+    warnings: Vec<ManifestWarning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compliance: Option<Compliance>,
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// A host with the port the URL names, e.g. "example.com" or "localhost:8080".
+fn host_key(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host,
+    })
+}
+
+/// Records a run for a manifest at `path`, passing status and warnings on to
+/// `inner`. The run reports to it like any other reporter; `finish` writes the
+/// manifest.
+pub struct Recorder {
+    path: String,
+    manifest: Mutex<Manifest>,
+    inner: Events,
+}
+
+impl Recorder {
+    pub fn new(path: &str, inner: Events) -> Recorder {
+        Recorder {
+            path: path.to_string(),
+            manifest: Mutex::new(Manifest {
+                manifest_version: MANIFEST_VERSION,
+                tool_version: env!("CARGO_PKG_VERSION"),
+                started_at: now(),
+                finished_at: String::new(),
+                status: "success",
+                exit_code: 0,
+                error: None,
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                warnings: Vec::new(),
+                compliance: None,
+            }),
+            inner,
+        }
+    }
+
+    fn with_manifest(&self, record: impl FnOnce(&mut Manifest)) {
+        record(&mut self.manifest.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    /// Adds the compliance section to the manifest.
+    pub fn start_compliance(&self, user_agent: &str, overrides: Vec<String>) {
+        self.with_manifest(|manifest| {
+            manifest.compliance = Some(Compliance {
+                user_agent: user_agent.to_string(),
+                hosts: BTreeMap::new(),
+                overrides,
+            })
+        });
+    }
+
+    fn with_host(&self, url: &str, record: impl FnOnce(&mut ComplianceHost)) {
+        let Some(key) = host_key(url) else {
+            return;
+        };
+        self.with_manifest(|manifest| {
+            if let Some(compliance) = &mut manifest.compliance {
+                record(compliance.hosts.entry(key).or_default());
+            }
+        });
+    }
+
+    /// Writes the manifest with the outcome of the run. The file is replaced in one
+    /// step so sensors never see it half written.
+    pub fn finish(&self, outcome: &Result<(), TabexError>) -> Result<(), TabexError> {
+        let mut manifest = self.manifest.lock().unwrap_or_else(|e| e.into_inner());
+        manifest.finished_at = now();
+        if let Err(e) = outcome {
+            manifest.status = "error";
+            manifest.exit_code = e.exit_code();
+            manifest.error = Some(e.to_string());
+        }
+        for host in manifest
+            .compliance
+            .iter_mut()
+            .flat_map(|compliance| compliance.hosts.values_mut())
+        {
+            host.shortest_interval_secs = host
+                .request_times
+                .windows(2)
+                .map(|pair| pair[1].duration_since(pair[0]).as_secs_f64())
+                .min_by(f64::total_cmp);
+            if let Some(delay) = host.robots_txt.as_ref().and_then(|rules| rules.crawl_delay) {
+                host.crawl_delay_honored = Some(
+                    host.shortest_interval_secs
+                        .is_none_or(|interval| interval >= delay),
+                );
+            }
+        }
+
+        let partial = format!("{}.partial", self.path);
+        let mut file = File::create(&partial)?;
+        serde_json::to_writer_pretty(&mut file, &*manifest)?;
+        writeln!(file)?;
+        drop(file);
+        fs::rename(&partial, &self.path)?;
+        self.inner.status(
+            "manifest",
+            &format!("Manifest written to {}", self.path),
+            serde_json::json!({ "path": self.path }),
+        );
+        Ok(())
+    }
+}
+
+impl Reporter for Recorder {
+    fn status(&self, event: &str, message: &str, fields: serde_json::Value) {
+        self.inner.status(event, message, fields);
+    }
+
+    fn warning(&self, event: &str, message: &str, fields: serde_json::Value) {
+        self.with_manifest(|manifest| {
+            manifest.warnings.push(ManifestWarning {
+                event: event.to_string(),
+                message: message.to_string(),
+            })
+        });
+        self.inner.warning(event, message, fields);
+    }
+
+    fn record(&self, record: Record) {
+        match record {
+            Record::Request { url } => self.with_host(url, |host| {
+                host.requests += 1;
+                host.request_times.push(Instant::now());
+            }),
+            Record::Robots { url, rules } => self.with_host(url, |host| {
+                host.requests += 1;
+                host.robots_txt = Some(rules.clone());
+            }),
+            Record::Disallowed { url } => {
+                self.with_host(url, |host| host.disallowed_urls.push(url.to_string()))
+            }
+            Record::Input { url, final_url } => self.with_manifest(|manifest| {
+                manifest.inputs.push(ManifestInput {
+                    url: url.to_string(),
+                    final_url: final_url.map(str::to_string),
+                })
+            }),
+            Record::Output {
+                path,
+                format,
+                tables,
+                rows,
+                bytes,
+                sha256,
+            } => self.with_manifest(|manifest| {
+                manifest.outputs.push(ManifestOutput {
+                    path: path.to_string(),
+                    format: format.to_string(),
+                    tables,
+                    rows,
+                    bytes,
+                    sha256,
+                })
+            }),
+        }
+    }
+}
+
+/// Counts and hashes what passes through to `inner`.
+struct Digesting<W: Write> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Digesting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes `result` with `sink` to `path`, or stdout when None, and records the output
+/// with its size and checksum.
+pub fn write_output(
+    sink: &dyn Sink,
+    format: &str,
+    result: &ExtractionResult,
+    path: Option<&str>,
+    events: &Events,
+) -> Result<(), TabexError> {
+    write_recorded(format, path, &result.tables, events, |out| {
+        sink.write(result, out)
+    })
+}
+
+/// Writes the pages of a run given several URLs, like `write_output`.
+pub fn write_pages_output(
+    sink: &dyn Sink,
+    format: &str,
+    pages: &[PageOutcome],
+    path: Option<&str>,
+    events: &Events,
+) -> Result<(), TabexError> {
+    let tables: Vec<&Table> = pages
+        .iter()
+        .filter_map(|page| page.result.as_ref())
+        .flat_map(|result| &result.tables)
+        .collect();
+    write_recorded(format, path, tables, events, |out| {
+        sink.write_pages(pages, out)
+    })
+}
+
+/// Writes `tables` with `write` to `path`, or stdout when None, and records the
+/// output. Also used for files written without a sink, such as data repo tables.
+pub fn write_recorded<'a>(
+    format: &str,
+    path: Option<&str>,
+    tables: impl IntoIterator<Item = &'a Table>,
+    events: &Events,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), TabexError>,
+) -> Result<(), TabexError> {
+    let (bytes, hasher) = match path {
+        Some(path) => {
+            let mut out = Digesting {
+                inner: File::create(path)?,
+                hasher: Sha256::new(),
+                bytes: 0,
+            };
+            write(&mut out)?;
+            (out.bytes, out.hasher)
+        }
+        None => {
+            let mut out = Digesting {
+                inner: io::stdout().lock(),
+                hasher: Sha256::new(),
+                bytes: 0,
+            };
+            write(&mut out)?;
+            (out.bytes, out.hasher)
+        }
+    };
+    let (tables, rows) = tables.into_iter().fold((0, 0), |(tables, rows), table| {
+        (tables + 1, rows + table.data.rows.len())
+    });
+    events.record(Record::Output {
+        path: path.unwrap_or("-"),
+        format,
+        tables,
+        rows,
+        bytes,
+        sha256: format!("{:x}", hasher.finalize()),
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::events::tests::collect;
+
+    #[test]
+    fn records_warnings_requests_and_outputs_of_the_run() {
+        let (inner, reported) = collect();
+        let recorder = Arc::new(Recorder::new("unused.json", inner));
+        recorder.start_compliance("tabex", Vec::new());
+        let events = Events::new(recorder.clone());
+        events.record(Record::Request {
+            url: "https://Example.com:8080/a",
+        });
+        events.record(Record::Input {
+            url: "https://example.com:8080/a",
+            final_url: None,
+        });
+        events.warning("fetch_retry", "retrying", serde_json::Value::Null);
+        let output = std::env::temp_dir().join("tabex-manifest-test.csv");
+        let output = output.to_string_lossy();
+        write_recorded("csv", Some(&output), [], &events, |out| {
+            Ok(out.write_all(b"a,b\n")?)
+        })
+        .unwrap();
+        fs::remove_file(&*output).unwrap();
+
+        let manifest = recorder.manifest.lock().unwrap();
+        let hosts = &manifest.compliance.as_ref().unwrap().hosts;
+        assert_eq!(hosts["example.com:8080"].requests, 1);
+        assert_eq!(manifest.inputs[0].final_url, None);
+        assert_eq!(manifest.warnings[0].event, "fetch_retry");
+        assert_eq!(manifest.outputs[0].bytes, 4);
+        // Warnings still reach the reporter behind the recorder
+        assert_eq!(reported.events(), ["warning fetch_retry"]);
+    }
+}
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Table;
+use crate::error::TabexError;
+use crate::output::slug;
+
+/// How a column named on the command line was found among a table's headers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderMatch {
+    /// The name as given, e.g. in --replace or --key-columns
+    pub requested: String,
+    pub header: String,
+    pub column: usize,
+    /// "exact", "normalized", "synonym" or "edit_distance"
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<usize>,
+}
+
+/// Finds columns by header name. By default names match ignoring case and
+/// surrounding whitespace; `--fuzzy-headers` also ignores punctuation and inner
+/// spacing and accepts small edit distances, and `--header-synonyms` adds names
+/// that stand for each other. Tables record non-default matchers' matches in
+/// `header_matches`.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderMatcher {
+    pub fuzzy: bool,
+    /// Largest edit distance accepted between normalized names when fuzzy
+    pub max_distance: usize,
+    /// Groups of normalized names treated as the same column
+    pub synonyms: Vec<Vec<String>>,
+}
+
+/// Levenshtein distance over characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + (ca != *cb) as usize;
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+impl HeaderMatcher {
+    /// Parses `--header-synonyms`: groups separated by `;`, names within a group by `|`,
+    /// e.g. `"Price|Cost|Amount;Qty|Quantity"`.
+    pub fn parse_synonyms(list: &str) -> Result<Vec<Vec<String>>, TabexError> {
+        list.split(';')
+            .filter(|group| !group.trim().is_empty())
+            .map(|group| {
+                let names: Vec<String> = group
+                    .split('|')
+                    .map(slug)
+                    .filter(|name| !name.is_empty())
+                    .collect();
+                if names.len() < 2 {
+                    return Err(TabexError::Parse(format!(
+                        "Invalid --header-synonyms group '{}' (expected NAME|NAME...)",
+                        group.trim()
+                    )));
+                }
+                Ok(names)
+            })
+            .collect()
+    }
+
+    fn reports(&self) -> bool {
+        self.fuzzy || !self.synonyms.is_empty()
+    }
+
+    /// The best match for `requested`, trying each method in order of strictness.
+    pub fn find(&self, headers: &[String], requested: &str) -> Option<HeaderMatch> {
+        let found = |column: usize, method: &str, distance: Option<usize>| HeaderMatch {
+            requested: requested.to_string(),
+            header: headers[column].clone(),
+            column,
+            method: method.to_string(),
+            distance,
+        };
+
+        if let Some(column) = headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(requested.trim()))
+        {
+            return Some(found(column, "exact", None));
+        }
+
+        let wanted = slug(requested);
+        let normalized: Vec<String> = headers.iter().map(|header| slug(header)).collect();
+        if self.fuzzy
+            && !wanted.is_empty()
+            && let Some(column) = normalized.iter().position(|header| *header == wanted)
+        {
+            return Some(found(column, "normalized", None));
+        }
+
+        if let Some(group) = self.synonyms.iter().find(|group| group.contains(&wanted))
+            && let Some(column) = normalized.iter().position(|header| group.contains(header))
+        {
+            return Some(found(column, "synonym", None));
+        }
+
+        if self.fuzzy && !wanted.is_empty() {
+            // The closest header wins; the first one on a tie
+            let closest = normalized
+                .iter()
+                .enumerate()
+                .filter(|(_, header)| !header.is_empty())
+                .map(|(column, header)| (edit_distance(&wanted, header), column))
+                .min();
+            if let Some((distance, column)) = closest
+                && distance <= self.max_distance
+            {
+                return Some(found(column, "edit_distance", Some(distance)));
+            }
+        }
+        None
+    }
+
+    /// Index of the column matching `requested`, noting the match in the table's
+    /// `header_matches` when fuzzy matching or synonyms are on.
+    pub fn column_index(&self, table: &mut Table, requested: &str) -> Option<usize> {
+        let found = self.find(&table.data.headers, requested)?;
+        let column = found.column;
+        if self.reports() {
+            let matches = table.metadata.header_matches.get_or_insert_with(Vec::new);
+            if !matches.iter().any(|m| m.requested == found.requested) {
+                matches.push(found);
+            }
+        }
+        Some(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn method(matcher: &HeaderMatcher, requested: &str) -> Option<(usize, String, Option<usize>)> {
+        let headers = headers(&["Player Name", "Goals", "Unit Price", "Qty."]);
+        matcher
+            .find(&headers, requested)
+            .map(|found| (found.column, found.method, found.distance))
+    }
+
+    #[test]
+    fn matches_exactly_ignoring_case_by_default() {
+        let matcher = HeaderMatcher::default();
+        assert_eq!(
+            method(&matcher, " goals "),
+            Some((1, "exact".to_string(), None))
+        );
+        assert_eq!(method(&matcher, "player_name"), None);
+        assert_eq!(method(&matcher, "Gaols"), None);
+    }
+
+    #[test]
+    fn normalizes_and_tolerates_typos_when_fuzzy() {
+        let matcher = HeaderMatcher {
+            fuzzy: true,
+            max_distance: 2,
+            synonyms: Vec::new(),
+        };
+        assert_eq!(
+            method(&matcher, "player_name"),
+            Some((0, "normalized".to_string(), None))
+        );
+        assert_eq!(
+            method(&matcher, "qty"),
+            Some((3, "normalized".to_string(), None))
+        );
+        assert_eq!(
+            method(&matcher, "Gaols"),
+            Some((1, "edit_distance".to_string(), Some(2)))
+        );
+        assert_eq!(method(&matcher, "Assists"), None);
+    }
+
+    #[test]
+    fn matches_synonyms() {
+        let matcher = HeaderMatcher {
+            fuzzy: false,
+            max_distance: 0,
+            synonyms: HeaderMatcher::parse_synonyms("Price|Unit Price|Cost; Quantity|Qty").unwrap(),
+        };
+        assert_eq!(
+            method(&matcher, "cost"),
+            Some((2, "synonym".to_string(), None))
+        );
+        assert_eq!(
+            method(&matcher, "Quantity"),
+            Some((3, "synonym".to_string(), None))
+        );
+        assert!(HeaderMatcher::parse_synonyms("Price").is_err());
+        assert!(HeaderMatcher::parse_synonyms("a|b;;").is_ok());
+    }
+
+    #[test]
+    fn measures_edit_distance_over_characters() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("naïve", "naive"), 1);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::TabexError;
+use crate::output::slug;
+use crate::{PageMetadata, Table};
+
+const PLACEHOLDERS: &[&str] = &[
+    "index",
+    "position",
+    "table_key",
+    "id",
+    "caption_slug",
+    "heading_slug",
+    "label_slug",
+    "page_slug",
+];
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Placeholder(&'static str),
+}
+
+/// Slug of a page's host and path, e.g. `example_com_stats_2024`; locations that
+/// aren't URLs, such as `--file` paths, are slugged whole.
+pub fn page_slug(location: &str) -> String {
+    reqwest::Url::parse(location)
+        .map(|url| slug(&format!("{}{}", url.host_str().unwrap_or(""), url.path())))
+        .unwrap_or_else(|_| slug(location))
+}
+
+/// `--table-name-template`, e.g. `"{heading_slug}_{index}"`. Placeholders are
+/// `{index}` (1-based order in the output), `{position}` on the page, `{table_key}`, `{id}`,
+/// `{caption_slug}`, `{heading_slug}`, `{label_slug}` (caption, else heading)
+/// and `{page_slug}` (host and path). Slugs of missing labels are `table`.
+#[derive(Debug)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<NameTemplate, TabexError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}').ok_or_else(|| {
+                TabexError::Parse(format!("Unclosed '{{' in table name template {}", template))
+            })? + start;
+            let name = &rest[start + 1..end];
+            let placeholder = PLACEHOLDERS
+                .iter()
+                .find(|placeholder| **placeholder == name)
+                .ok_or_else(|| {
+                    TabexError::Parse(format!(
+                        "Unknown placeholder {{{}}} in table name template (expected one of: {})",
+                        name,
+                        PLACEHOLDERS.join(", ")
+                    ))
+                })?;
+            parts.push(Part::Placeholder(placeholder));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(NameTemplate { parts })
+    }
+
+    fn render(&self, table: &Table, index: usize, page_slug: &str) -> String {
+        let metadata = &table.metadata;
+        // Missing labels read as "table", so "{heading_slug}_{index}" still gives "table_3"
+        let slug_of = |text: Option<&String>| {
+            Some(text.map(|text| slug(text)).unwrap_or_default())
+                .filter(|slug| !slug.is_empty())
+                .unwrap_or_else(|| "table".to_string())
+        };
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Placeholder("index") => index.to_string(),
+                Part::Placeholder("position") => metadata.position.to_string(),
+                Part::Placeholder("table_key") => metadata.table_key.clone(),
+                Part::Placeholder("id") => metadata.id.clone().unwrap_or_default(),
+                Part::Placeholder("caption_slug") => slug_of(metadata.caption.as_ref()),
+                Part::Placeholder("heading_slug") => slug_of(metadata.preceding_heading.as_ref()),
+                Part::Placeholder("label_slug") => slug_of(
+                    metadata
+                        .caption
+                        .as_ref()
+                        .or(metadata.preceding_heading.as_ref()),
+                ),
+                Part::Placeholder(_) => page_slug.to_string(),
+            })
+            .collect()
+    }
+
+    /// Names every table, stored as `metadata.name` for the sinks to use. Names left
+    /// without letters or digits fall back to `table_<position>`, and repeats get
+    /// `_2`, `_3`, ... suffixes.
+    pub fn apply(&self, tables: &mut [Table], page: &PageMetadata) {
+        let page_slug = page_slug(&page.url);
+
+        let mut names: Vec<String> = Vec::with_capacity(tables.len());
+        for (i, table) in tables.iter_mut().enumerate() {
+            let rendered = self.render(table, i + 1, &page_slug);
+            let trimmed = rendered.trim_matches(|c: char| !c.is_alphanumeric());
+            let base = if trimmed.is_empty() {
+                format!("table_{}", table.metadata.position)
+            } else {
+                trimmed.to_string()
+            };
+
+            let mut name = base.clone();
+            let mut n = 2;
+            while names.contains(&name) {
+                name = format!("{}_{}", base, n);
+                n += 1;
+            }
+            names.push(name.clone());
+            table.metadata.name = Some(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::extract;
+    use crate::{ExtractOptions, Extractor};
+
+    fn names(template: &str, html: &str) -> Vec<String> {
+        let document = scraper::Html::parse_document(html);
+        let page = Extractor::new(ExtractOptions::default())
+            .page_metadata(&document, "https://example.com/stats/2024?x=1");
+        let mut tables = extract(html, ExtractOptions::default());
+        NameTemplate::parse(template)
+            .unwrap()
+            .apply(&mut tables, &page);
+        tables
+            .into_iter()
+            .map(|table| table.metadata.name.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn renders_placeholders_from_table_and_page() {
+        let html = "<h2>Top Scorers</h2><table id=\"t\"><tr><td>1</td></tr></table>
+                    <table><caption>Goals, Assists</caption><tr><td>2</td></tr></table>";
+        assert_eq!(
+            names("{page_slug}-{index}-{id}", html),
+            ["example_com_stats_2024-1-t", "example_com_stats_2024-2"]
+        );
+        assert_eq!(
+            names("{label_slug}", html),
+            ["top_scorers", "goals_assists"]
+        );
+        assert_eq!(
+            names("{caption_slug}_{position}", html),
+            ["table_1", "goals_assists_2"]
+        );
+    }
+
+    #[test]
+    fn falls_back_and_numbers_repeated_names() {
+        let html = "<table><tr><td>1</td></tr></table><table><tr><td>2</td></tr></table>
+                    <table><tr><td>3</td></tr></table>";
+        assert_eq!(names("{id}", html), ["table_1", "table_2", "table_3"]);
+        assert_eq!(names("data", html), ["data", "data_2", "data_3"]);
+    }
+
+    #[test]
+    fn rejects_unknown_and_unclosed_placeholders() {
+        assert!(NameTemplate::parse("{heading}").is_err());
+        assert!(NameTemplate::parse("{index").is_err());
+        assert!(NameTemplate::parse("plain").is_ok());
+    }
+
+    #[test]
+    fn slugs_pages_and_local_paths() {
+        assert_eq!(
+            page_slug("https://Example.com/a/b.html"),
+            "example_com_a_b_html"
+        );
+        assert_eq!(page_slug("saved/page.html"), "saved_page_html");
+    }
+}
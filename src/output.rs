@@ -0,0 +1,544 @@
+use std::io::Write;
+
+use crate::error::TabexError;
+use crate::plugin::Sink;
+use crate::units::declared_type;
+use crate::{ExtractionResult, PageOutcome, Table};
+
+/// The default output: the whole extraction result as pretty-printed JSON.
+pub struct JsonSink {
+    /// Emit rows as objects keyed by header instead of arrays
+    pub records: bool,
+    /// Byte-stable output, see `canonical_value`
+    pub canonical: bool,
+}
+
+impl Sink for JsonSink {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError> {
+        let json = if self.canonical {
+            let value = if self.records {
+                result_with_records(result)?
+            } else {
+                serde_json::to_value(result)?
+            };
+            serde_json::to_string_pretty(&canonical_value(value))?
+        } else if self.records {
+            serde_json::to_string_pretty(&result_with_records(result)?)?
+        } else {
+            serde_json::to_string_pretty(result)?
+        };
+        writeln!(out, "{}", json)?;
+        Ok(())
+    }
+
+    /// An array with one entry per URL: whether it was extracted, and its result
+    /// or error.
+    fn write_pages(&self, pages: &[PageOutcome], out: &mut dyn Write) -> Result<(), TabexError> {
+        let pages = pages
+            .iter()
+            .map(|page| {
+                let mut value = serde_json::to_value(page)?;
+                if self.records
+                    && let Some(result) = &page.result
+                {
+                    value["tables"] = result_with_records(result)?["tables"].take();
+                }
+                Ok(if self.canonical {
+                    canonical_value(value)
+                } else {
+                    value
+                })
+            })
+            .collect::<Result<Vec<_>, TabexError>>()?;
+        writeln!(out, "{}", serde_json::to_string_pretty(&pages)?)?;
+        Ok(())
+    }
+}
+
+/// Tables as CSV, with page and table metadata in `#` comment lines.
+pub struct CsvSink {
+    /// Leave out the timing line and normalize whitespace in every field
+    pub canonical: bool,
+}
+
+impl Sink for CsvSink {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError> {
+        output_tables_as_csv(result, self.canonical, out)
+    }
+}
+
+/// Tables as Org-mode tables, one heading per table.
+pub struct OrgSink;
+
+impl Sink for OrgSink {
+    fn name(&self) -> &str {
+        "org"
+    }
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError> {
+        if let Some(title) = &result.page.title {
+            writeln!(out, "#+TITLE: {}", title)?;
+        }
+        writeln!(out, "# URL: {}", result.page.url)?;
+
+        for table in &result.tables {
+            writeln!(out)?;
+            writeln!(out, "* {}", table_heading(table))?;
+            if let Some(caption) = &table.metadata.caption {
+                writeln!(out, "#+CAPTION: {}", caption)?;
+            }
+
+            let cells = |row: &[String]| -> Vec<String> {
+                row.iter()
+                    .map(|cell| cell.replace('|', "\\vert{}"))
+                    .collect()
+            };
+            let headers = cells(&table.data.headers);
+            let rows: Vec<Vec<String>> = table.data.rows.iter().map(|row| cells(row)).collect();
+            let widths = column_widths(&headers, &rows);
+            let line = |row: &[String]| -> String {
+                let padded: Vec<String> = widths
+                    .iter()
+                    .enumerate()
+                    .map(|(i, width)| {
+                        let cell = row.get(i).map(String::as_str).unwrap_or("");
+                        format!("{:<width$}", cell, width = *width)
+                    })
+                    .collect();
+                format!("| {} |", padded.join(" | "))
+            };
+
+            if !headers.is_empty() {
+                writeln!(out, "{}", line(&headers))?;
+                let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+                writeln!(out, "|-{}-|", rule.join("-+-"))?;
+            }
+            for row in &rows {
+                writeln!(out, "{}", line(row))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tables as AsciiDoc tables, one section per table.
+pub struct AsciiDocSink;
+
+impl Sink for AsciiDocSink {
+    fn name(&self) -> &str {
+        "asciidoc"
+    }
+
+    fn extension(&self) -> &str {
+        "adoc"
+    }
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError> {
+        writeln!(
+            out,
+            "= {}",
+            result.page.title.as_deref().unwrap_or(&result.page.url)
+        )?;
+        writeln!(out, "// URL: {}", result.page.url)?;
+
+        for table in &result.tables {
+            let headers = &table.data.headers;
+            let width = table
+                .data
+                .rows
+                .iter()
+                .map(Vec::len)
+                .chain([headers.len()])
+                .max()
+                .unwrap_or(0);
+            if width == 0 {
+                continue;
+            }
+
+            writeln!(out)?;
+            writeln!(out, "== {}", table_heading(table))?;
+            writeln!(out)?;
+            if let Some(caption) = &table.metadata.caption {
+                writeln!(out, ".{}", caption)?;
+            }
+            let options = if headers.is_empty() { "" } else { "%header," };
+            writeln!(out, "[{}cols=\"{}*\"]", options, width)?;
+            writeln!(out, "|===")?;
+
+            let line = |row: &[String]| -> String {
+                (0..width)
+                    .map(|i| {
+                        let cell = row.get(i).map(String::as_str).unwrap_or("");
+                        format!("|{}", cell.replace('|', "\\|"))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            if !headers.is_empty() {
+                writeln!(out, "{}", line(headers))?;
+                writeln!(out)?;
+            }
+            for row in &table.data.rows {
+                writeln!(out, "{}", line(row))?;
+            }
+            writeln!(out, "|===")?;
+        }
+        Ok(())
+    }
+}
+
+/// "Table 2: Caption", falling back to the preceding heading for the label.
+pub fn table_heading(table: &Table) -> String {
+    let label = table
+        .metadata
+        .caption
+        .as_deref()
+        .or(table.metadata.preceding_heading.as_deref());
+    match label {
+        Some(label) => format!("Table {}: {}", table.metadata.position, label),
+        None => format!("Table {}", table.metadata.position),
+    }
+}
+
+/// Display width of each column, in characters, across the header and rows.
+fn column_widths(headers: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in std::iter::once(headers).chain(rows.iter().map(Vec::as_slice)) {
+        if widths.len() < row.len() {
+            widths.resize(row.len(), 0);
+        }
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    widths
+}
+
+/// Writes a table's header and data rows as RFC 4180 CSV records, quoting fields
+/// with commas, quotes or line breaks. `canonical` normalizes whitespace in every field.
+pub fn write_table_csv(
+    table: &Table,
+    canonical: bool,
+    out: &mut dyn Write,
+) -> Result<(), TabexError> {
+    // Rows may be ragged; lines end in \n like the comment lines around the table
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(true)
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_writer(out);
+    let field = |cell: &String| {
+        if canonical {
+            normalize_whitespace(cell)
+        } else {
+            cell.clone()
+        }
+    };
+
+    if !table.data.headers.is_empty() {
+        writer.write_record(table.data.headers.iter().map(field))?;
+    }
+    for row in &table.data.rows {
+        // An empty row comes out as "" so CSV readers don't skip it
+        writer.write_record(row.iter().map(field))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Collapses whitespace runs to single spaces and trims the ends.
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// `--canonical` JSON: timing and retry counts removed, tables in document order, object keys sorted and
+/// whitespace normalized in every string, so unchanged pages give byte-identical output.
+fn canonical_value(mut value: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = value.as_object_mut() {
+        object.remove("extraction_time_ms");
+        object.remove("attempts");
+    }
+    if let Some(tables) = value["tables"].as_array_mut() {
+        tables.sort_by_key(|table| table["metadata"]["position"].as_u64());
+    }
+    sort_and_normalize(value)
+}
+
+fn sort_and_normalize(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut entries: Vec<(String, serde_json::Value)> = object.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_and_normalize(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(sort_and_normalize).collect())
+        }
+        serde_json::Value::String(text) => serde_json::Value::String(normalize_whitespace(&text)),
+        other => other,
+    }
+}
+
+/// Lowercase alphanumeric runs joined by underscores: "Price ($)" becomes "price".
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    slug.trim_end_matches('_').to_string()
+}
+
+/// Turns headers into JSON-friendly keys: "Price ($)" becomes "price", duplicates get a suffix.
+pub fn record_keys(headers: &[String], width: usize) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::with_capacity(width);
+    for i in 0..width.max(headers.len()) {
+        let key = slug(headers.get(i).map(String::as_str).unwrap_or(""));
+        let base = if key.is_empty() {
+            format!("column_{}", i + 1)
+        } else {
+            key
+        };
+
+        let mut key = base.clone();
+        let mut n = 2;
+        while keys.contains(&key) {
+            key = format!("{}_{}", base, n);
+            n += 1;
+        }
+        keys.push(key);
+    }
+    keys
+}
+
+fn result_with_records(result: &ExtractionResult) -> Result<serde_json::Value, TabexError> {
+    let mut value = serde_json::to_value(result)?;
+
+    for (table, table_value) in result
+        .tables
+        .iter()
+        .zip(value["tables"].as_array_mut().into_iter().flatten())
+    {
+        let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let keys = record_keys(&table.data.headers, width);
+        let rows = table
+            .data
+            .rows
+            .iter()
+            .map(|row| {
+                // Columns with a --types override become numbers; the rest stay as written
+                let record: serde_json::Map<String, serde_json::Value> = keys
+                    .iter()
+                    .zip(row)
+                    .enumerate()
+                    .map(|(index, (key, cell))| {
+                        let value = match declared_type(table, index) {
+                            Some(declared) => {
+                                declared.json_value(cell, table.metadata.sign_conventions)
+                            }
+                            None => serde_json::Value::from(cell.as_str()),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect();
+                serde_json::Value::Object(record)
+            })
+            .collect();
+        table_value["data"]["rows"] = serde_json::Value::Array(rows);
+    }
+
+    Ok(value)
+}
+
+fn output_tables_as_csv(
+    result: &ExtractionResult,
+    canonical: bool,
+    out: &mut dyn Write,
+) -> Result<(), TabexError> {
+    let mut tables: Vec<_> = result.tables.iter().collect();
+    if canonical {
+        tables.sort_by_key(|table| table.metadata.position);
+    }
+
+    // Write metadata as comments, on one line each however the text was laid out
+    let field = normalize_whitespace;
+    writeln!(out, "# URL: {}", result.page.url)?;
+    if let Some(title) = &result.page.title {
+        writeln!(out, "# Title: {}", field(title))?;
+    }
+    writeln!(out, "# Tables found: {}", tables.len())?;
+    if !canonical {
+        writeln!(out, "# Extraction time: {} ms", result.extraction_time_ms)?;
+    }
+    writeln!(out)?;
+
+    // Write each table
+    for (i, table) in tables.iter().enumerate() {
+        writeln!(out, "# Table {} of {}", i + 1, tables.len())?;
+        writeln!(out, "# Position: {}", table.metadata.position)?;
+        if let Some(name) = &table.metadata.name {
+            writeln!(out, "# Name: {}", field(name))?;
+        }
+        if let Some(caption) = &table.metadata.caption {
+            writeln!(out, "# Caption: {}", field(caption))?;
+        }
+        if let Some(heading) = &table.metadata.preceding_heading {
+            writeln!(out, "# Preceding heading: {}", field(heading))?;
+        }
+        writeln!(out)?;
+
+        write_table_csv(table, canonical, out)?;
+
+        // Add separator between tables
+        if i < tables.len() - 1 {
+            writeln!(out)?;
+            writeln!(out, "# ------------------------------")?;
+            writeln!(out)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::{extract, page};
+
+    fn result(html: &str) -> ExtractionResult {
+        ExtractionResult {
+            page: page("https://example.com/"),
+            tables: extract(html, ExtractOptions::default()),
+            relationships: None,
+            timed_out: false,
+            attempts: None,
+            extraction_time_ms: 5,
+        }
+    }
+
+    fn written(sink: &dyn Sink, result: &ExtractionResult) -> String {
+        let mut out = Vec::new();
+        sink.write(result, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_fields_round_trip() {
+        let mut table = extract(
+            "<table><tr><th>Name</th><th>Note</th></tr><tr><td>a</td><td>b</td></tr></table>",
+            ExtractOptions::default(),
+        )
+        .remove(0);
+        let cells = [
+            "Smith, Ann",
+            "said \"hi\"",
+            "two\nlines",
+            "\"quoted, with comma\"\r\n",
+        ];
+        table.data.rows = vec![
+            vec![cells[0].to_string(), cells[1].to_string()],
+            vec![cells[2].to_string(), cells[3].to_string()],
+        ];
+        let mut out = Vec::new();
+        write_table_csv(&table, false, &mut out).unwrap();
+
+        let mut reader = csv::Reader::from_reader(out.as_slice());
+        assert_eq!(reader.headers().unwrap(), vec!["Name", "Note"]);
+        let rows: Vec<Vec<String>> = reader
+            .records()
+            .map(|record| record.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(rows, table.data.rows);
+    }
+
+    #[test]
+    fn csv_comments_stay_on_one_line() {
+        let mut result = result(
+            "<table><caption>Line one
+             line two</caption><tr><th>A</th></tr><tr><td>1</td></tr></table>",
+        );
+        result.page.title = Some("Title\nA,B".to_string());
+        let csv = written(&CsvSink { canonical: false }, &result);
+        assert!(csv.contains("# Title: Title A,B\n"), "{}", csv);
+        assert!(csv.contains("# Caption: Line one line two\n"), "{}", csv);
+        assert!(
+            csv.lines()
+                .all(|line| line.is_empty() || line.starts_with('#') || line == "A" || line == "1"),
+            "{}",
+            csv
+        );
+    }
+
+    #[test]
+    fn org_and_asciidoc_escape_pipes() {
+        let result = result(
+            "<table><tr><th>a|b</th><th>c</th></tr><tr><td>x | y</td><td>z</td></tr></table>",
+        );
+        let org = written(&OrgSink, &result);
+        assert!(org.contains("| a\\vert{}b   | c |\n"), "{}", org);
+        assert!(org.contains("| x \\vert{} y | z |\n"), "{}", org);
+
+        let adoc = written(&AsciiDocSink, &result);
+        assert!(adoc.contains("[%header,cols=\"2*\"]\n"), "{}", adoc);
+        assert!(adoc.contains("|a\\|b |c\n"), "{}", adoc);
+        assert!(adoc.contains("|x \\| y |z\n"), "{}", adoc);
+    }
+
+    /// `value` with the keys of every object in reverse order.
+    fn reversed(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(object) => {
+                let mut entries: Vec<_> = object.into_iter().collect();
+                entries.reverse();
+                serde_json::Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, reversed(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.into_iter().map(reversed).collect())
+            }
+            other => other,
+        }
+    }
+
+    #[test]
+    fn canonical_json_is_byte_stable() {
+        let html = "<table><caption>Scores</caption><tr><th>Team</th><th>Goals</th></tr>
+                    <tr><td>Ann</td><td>3</td></tr></table>";
+        let first = result(html);
+        let mut second = result(html);
+        second.extraction_time_ms = 912;
+        second.attempts = Some(3);
+        second.tables[0].data.rows[0][0] = "  Ann\n".to_string();
+
+        let first = canonical_value(serde_json::to_value(&first).unwrap());
+        let second = canonical_value(reversed(serde_json::to_value(&second).unwrap()));
+        assert_eq!(
+            serde_json::to_string_pretty(&first).unwrap(),
+            serde_json::to_string_pretty(&second).unwrap()
+        );
+        let keys: Vec<&String> = first.as_object().unwrap().keys().collect();
+        assert!(keys.is_sorted(), "{:?}", keys);
+        assert!(first.get("extraction_time_ms").is_none());
+        assert!(first.get("attempts").is_none());
+    }
+}
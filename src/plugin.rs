@@ -0,0 +1,114 @@
+//! Extension points for input formats, cleaning steps and output targets.
+//!
+//! The built-in HTTP source, transforms and JSON/CSV sinks are implemented on
+//! these traits; additional implementations are added through [`Registry`].
+
+use std::io::Write;
+
+use crate::error::TabexError;
+use crate::{ExtractionResult, PageMetadata, PageOutcome, Table};
+
+/// HTML loaded by a source, along with the location recorded in `PageMetadata.url`.
+pub struct SourceDocument {
+    pub url: String,
+    pub html: String,
+    /// Requests made for the document, retries included; None when it wasn't fetched
+    pub attempts: Option<u32>,
+}
+
+/// An input format that turns a location into an HTML document.
+pub trait TableSource {
+    fn name(&self) -> &str;
+
+    /// Whether this source can load `location`.
+    fn handles(&self, location: &str) -> bool;
+
+    fn load(&self, location: &str) -> Result<SourceDocument, TabexError>;
+}
+
+/// A cleaning or reshaping step run over the extracted tables of a page.
+pub trait Transform {
+    fn name(&self) -> &str;
+
+    fn apply(&self, tables: &mut Vec<Table>, page: &PageMetadata);
+}
+
+/// An output target selected with `--format`.
+pub trait Sink {
+    fn name(&self) -> &str;
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError>;
+
+    /// File extension for outputs named by tabex, as with `--output-dir`.
+    fn extension(&self) -> &str {
+        self.name()
+    }
+
+    /// Writes the pages of a run given several URLs. By default each extracted page
+    /// is written in turn, as `write` would write it alone.
+    fn write_pages(&self, pages: &[PageOutcome], out: &mut dyn Write) -> Result<(), TabexError> {
+        for result in pages.iter().filter_map(|page| page.result.as_ref()) {
+            self.write(result, out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sources, transforms and sinks available to a run. Transforms are applied in
+/// registration order; a later source or sink with the same name takes precedence.
+#[derive(Default)]
+pub struct Registry {
+    sources: Vec<Box<dyn TableSource>>,
+    transforms: Vec<Box<dyn Transform>>,
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Registry {
+    pub fn register_source(&mut self, source: Box<dyn TableSource>) {
+        self.sources.push(source);
+    }
+
+    pub fn register_transform(&mut self, transform: Box<dyn Transform>) {
+        self.transforms.push(transform);
+    }
+
+    pub fn register_sink(&mut self, sink: Box<dyn Sink>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn source_for(&self, location: &str) -> Option<&dyn TableSource> {
+        self.sources
+            .iter()
+            .rev()
+            .find(|source| source.handles(location))
+            .map(|source| source.as_ref())
+    }
+
+    pub fn sink(&self, name: &str) -> Option<&dyn Sink> {
+        self.sinks
+            .iter()
+            .rev()
+            .find(|sink| sink.name() == name)
+            .map(|sink| sink.as_ref())
+    }
+
+    pub fn sink_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.sinks.iter().map(|sink| sink.name()).collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    pub fn transform_names(&self) -> Vec<&str> {
+        self.transforms
+            .iter()
+            .map(|transform| transform.name())
+            .collect()
+    }
+
+    pub fn apply_transforms(&self, tables: &mut Vec<Table>, page: &PageMetadata) {
+        for transform in &self.transforms {
+            transform.apply(tables, page);
+        }
+    }
+}
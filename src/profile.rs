@@ -0,0 +1,769 @@
+use std::sync::LazyLock;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::TabexError;
+use crate::lineage;
+use crate::plugin::Transform;
+use crate::rows::RowStyle;
+use crate::types::{format_number, parse_number};
+use crate::{PageMetadata, Table};
+
+/// Layout conventions selected with `--profile`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Profile {
+    Financial,
+    Sports,
+    Timetable,
+}
+
+/// Settings a profile may need beyond the tables themselves.
+pub struct ProfileContext {
+    /// Zone that schedule times are read in
+    pub timezone: Tz,
+    /// Date for times whose day isn't in the table; weekdays count from it
+    pub date: Option<NaiveDate>,
+}
+
+impl Profile {
+    pub fn parse(name: &str) -> Result<Profile, TabexError> {
+        match name {
+            "financial" => Ok(Profile::Financial),
+            "sports" => Ok(Profile::Sports),
+            "timetable" => Ok(Profile::Timetable),
+            other => Err(TabexError::Usage(format!(
+                "Unknown profile {} (expected financial, sports or timetable)",
+                other
+            ))),
+        }
+    }
+
+    pub fn transform(self, context: ProfileContext) -> Box<dyn Transform> {
+        match self {
+            Profile::Financial => Box::new(FinancialProfile),
+            Profile::Sports => Box::new(SportsProfile),
+            Profile::Timetable => Box::new(TimetableProfile { context }),
+        }
+    }
+}
+
+/// Footnote markers after a value: "1,234 (a)", "1,234[1]", "1,234*", "1,234¹", "1,234c".
+/// A bare b, k or m straight after a digit is left alone since it may be a scale suffix.
+static FOOTNOTE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(.*?[\d)%])\s*(?:\([a-z]\)|\[[a-z0-9]+\]|[*†‡]+|[¹²³⁰⁴-⁹]+)$|^(.*?[)%])\s*[a-z]$|^(.*?\d)\s*[ac-jln-z]$",
+    )
+    .unwrap()
+});
+
+/// Accounting negatives: "(1,234)", "$(1,234)", "($1,234)", "(12.5)%".
+static NEGATIVE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([$€£¥]?)\s*\(\s*([$€£¥]?\s*[\d.,]+)\s*\)\s*(%?)$").unwrap());
+
+fn is_dash(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| matches!(c, '-' | '–' | '—' | '−'))
+}
+
+/// Normalizes a financial-statement value to a plain number, keeping a trailing `%`.
+/// Dashes are read as zero; text that isn't a number returns None.
+fn normalize_value(cell: &str) -> Option<String> {
+    let text = cell.trim();
+    if is_dash(text) {
+        return Some("0".to_string());
+    }
+
+    let text = FOOTNOTE_RE
+        .captures(text)
+        .and_then(|captures| captures.iter().skip(1).flatten().next())
+        .map_or(text, |value| value.as_str());
+    let (text, negative) = match NEGATIVE_RE.captures(text) {
+        Some(captures) => (
+            format!("{}{}{}", &captures[1], &captures[2], &captures[3]),
+            true,
+        ),
+        None => (text.to_string(), false),
+    };
+
+    let number = parse_number(&text)?;
+    let number = if negative { -number } else { number };
+    let percent = if text.ends_with('%') { "%" } else { "" };
+    Some(format!("{}{}", format_number(number), percent))
+}
+
+/// `--profile financial`: reads accounting negatives, footnote-marked values and dashes
+/// for zero in numeric columns. Scale notes such as "in thousands" are applied by the
+/// unit conversion step the profile turns on.
+pub struct FinancialProfile;
+
+impl Transform for FinancialProfile {
+    fn name(&self) -> &str {
+        "financial"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        for table in tables {
+            let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+            for index in 0..width {
+                let cells = || {
+                    table
+                        .data
+                        .rows
+                        .iter()
+                        .filter_map(move |row| row.get(index))
+                        .map(|cell| cell.trim())
+                        .filter(|cell| !cell.is_empty() && !is_dash(cell))
+                };
+                let total = cells().count();
+                let numeric = cells()
+                    .filter(|cell| normalize_value(cell).is_some())
+                    .count();
+                // Dashes only mean zero in columns that are otherwise numbers
+                if total == 0 || numeric * 2 < total {
+                    continue;
+                }
+
+                let mut changed = false;
+                for cell in table
+                    .data
+                    .rows
+                    .iter_mut()
+                    .filter_map(|row| row.get_mut(index))
+                {
+                    if let Some(value) = normalize_value(cell) {
+                        changed |= *cell != value;
+                        *cell = value;
+                    }
+                }
+                if changed {
+                    lineage::changed(table, index, "financial");
+                }
+            }
+        }
+    }
+}
+
+/// League-table structure found by `--profile sports`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Standings {
+    pub rank_column: Option<String>,
+    /// Columns of results such as "WWLDW", left as written
+    pub form_columns: Vec<String>,
+    /// Composite columns such as "W-D-L" that were split, with the columns they became
+    pub split_columns: Vec<SplitColumn>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitColumn {
+    pub column: String,
+    pub into: Vec<String>,
+}
+
+static RECORD_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\d+(?:\s*[-–]\s*\d+){1,2}$").unwrap());
+/// "2024-06-03" or "03-06-2024", which are dates rather than records
+static DATE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:\d{4}-\d{1,2}-\d{1,2}|\d{1,2}-\d{1,2}-\d{4})$").unwrap());
+static RECORD_HEADER_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:(?:overall|home|away|season|conf\.?|div\.?)\s+)?(?:record|rec\.?|wdl|w/d/l|w/l)$",
+    )
+    .unwrap()
+});
+static RANK_HEADER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(?:#|no\.?|pos\.?|position|rank|rk|place)$").unwrap());
+static RANK_VALUE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:=|T)?(\d+)\.?$").unwrap());
+static FORM_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[WDLT](?:[\s-]?[WDLT]){2,}$").unwrap());
+
+fn column_cells(table: &Table, index: usize) -> impl Iterator<Item = &str> {
+    table
+        .data
+        .rows
+        .iter()
+        .map(move |row| row.get(index).map(String::as_str).unwrap_or("").trim())
+        .filter(|cell| !cell.is_empty())
+}
+
+/// A rank column: a rank-like header, or the first column counting up from 1 with ties.
+fn is_rank_column(table: &Table, index: usize) -> bool {
+    let header = table
+        .data
+        .headers
+        .get(index)
+        .map(String::as_str)
+        .unwrap_or("");
+    let ranks: Option<Vec<usize>> = column_cells(table, index)
+        .map(|cell| RANK_VALUE_RE.captures(cell)?[1].parse().ok())
+        .collect();
+    let Some(ranks) = ranks.filter(|ranks| !ranks.is_empty()) else {
+        return false;
+    };
+    let ordered = ranks.first() == Some(&1) && ranks.windows(2).all(|pair| pair[0] <= pair[1]);
+    RANK_HEADER_RE.is_match(header.trim()) || (index == 0 && ordered && ranks.len() > 2)
+}
+
+fn is_form_column(table: &Table, index: usize) -> bool {
+    let mut cells = column_cells(table, index).peekable();
+    cells.peek().is_some() && cells.all(|cell| FORM_RE.is_match(cell))
+}
+
+fn is_record(cell: &str) -> bool {
+    RECORD_RE.is_match(cell) && !DATE_RE.is_match(cell)
+}
+
+/// Number of parts when at least 80% of the column's cells are records like "10-3-5".
+fn record_parts(table: &Table, index: usize) -> Option<usize> {
+    let cells: Vec<&str> = column_cells(table, index).collect();
+    let parts = |cell: &str| cell.split(['-', '–']).count();
+    let first = cells.iter().find(|cell| is_record(cell))?;
+    let count = parts(first);
+    let matching = cells
+        .iter()
+        .filter(|cell| is_record(cell) && parts(cell) == count)
+        .count();
+    (matching * 5 >= cells.len() * 4).then_some(count)
+}
+
+/// Names for the parts of a composite column: "W-D-L" gives W, D and L, and a header
+/// such as "Record" the W, D and L defaults. Other headers, e.g. "Score" or "Goals",
+/// hold pairs that aren't records, so the column isn't split.
+fn part_names(header: &str, count: usize) -> Option<Vec<String>> {
+    let header = header.trim();
+    let parts: Vec<&str> = header.split(['-', '–', '/']).map(str::trim).collect();
+    if parts.len() == count && parts.iter().all(|part| !part.is_empty()) {
+        return Some(parts.iter().map(|part| part.to_string()).collect());
+    }
+    if !RECORD_HEADER_RE.is_match(header) {
+        return None;
+    }
+    let defaults: &[&str] = if count == 3 {
+        &["W", "D", "L"]
+    } else {
+        &["W", "L"]
+    };
+    Some(defaults.iter().map(|name| name.to_string()).collect())
+}
+
+/// Zone of each styled row. Class names are read directly; otherwise a run of one
+/// background colour from the top is promotion and one reaching the bottom is relegation.
+/// Colours on more than a third of the rows are taken as striping and ignored.
+fn row_zones(styles: &[RowStyle], row_count: usize) -> Vec<(usize, String)> {
+    let from_class = |class: &str| {
+        let class = class.to_ascii_lowercase();
+        if class.contains("promot") {
+            Some("promotion")
+        } else if class.contains("releg") {
+            Some("relegation")
+        } else if class.contains("playoff") || class.contains("play-off") {
+            Some("playoff")
+        } else if class.contains("qualif") || class.contains("champion") {
+            Some("qualification")
+        } else {
+            None
+        }
+    };
+
+    let background_at = |row: usize| {
+        styles
+            .iter()
+            .find(|style| style.row == row)
+            .and_then(|style| style.background.as_deref())
+    };
+    let top = background_at(1);
+    let bottom = background_at(row_count);
+
+    let mut zones = Vec::new();
+    for style in styles {
+        let zone = style.class.as_deref().and_then(from_class).or_else(|| {
+            let background = style.background.as_deref()?;
+            let uses = (1..=row_count)
+                .filter(|&row| background_at(row) == Some(background))
+                .count();
+            if uses * 3 > row_count {
+                return None;
+            }
+            let run_from_top = (1..=style.row).all(|row| background_at(row) == Some(background));
+            let run_to_bottom =
+                (style.row..=row_count).all(|row| background_at(row) == Some(background));
+            if top == Some(background) && run_from_top {
+                Some("promotion")
+            } else if bottom == Some(background) && run_to_bottom {
+                Some("relegation")
+            } else {
+                Some("highlighted")
+            }
+        });
+        if let Some(zone) = zone {
+            zones.push((style.row, zone.to_string()));
+        }
+    }
+    zones
+}
+
+/// `--profile sports`: splits W-D-L record columns, notes rank and form columns, and
+/// adds a `zone` column for rows styled as promotion, relegation or qualification places.
+pub struct SportsProfile;
+
+impl Transform for SportsProfile {
+    fn name(&self) -> &str {
+        "sports"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        for table in tables {
+            let width = table
+                .data
+                .rows
+                .iter()
+                .map(Vec::len)
+                .chain([table.data.headers.len()])
+                .max()
+                .unwrap_or(0);
+            let header = |table: &Table, index: usize| {
+                table.data.headers.get(index).cloned().unwrap_or_default()
+            };
+
+            let rank_column = (0..width)
+                .find(|&index| is_rank_column(table, index))
+                .map(|index| header(table, index));
+            let form_columns = (0..width)
+                .filter(|&index| is_form_column(table, index))
+                .map(|index| header(table, index))
+                .collect();
+
+            // Split from the right so earlier indices stay valid
+            let mut split_columns = Vec::new();
+            for index in (0..width).rev() {
+                let name = header(table, index);
+                let Some(names) =
+                    record_parts(table, index).and_then(|count| part_names(&name, count))
+                else {
+                    continue;
+                };
+                let count = names.len();
+                if index < table.data.headers.len() {
+                    table.data.headers.splice(index..=index, names.clone());
+                    lineage::split(table, index, &names, "sports");
+                }
+                for row in &mut table.data.rows {
+                    if row.len() <= index {
+                        continue;
+                    }
+                    let cell = row[index].clone();
+                    let mut parts: Vec<String> = cell
+                        .split(['-', '–'])
+                        .map(|part| part.trim().to_string())
+                        .collect();
+                    if parts.len() != count {
+                        // Not a record: keep the text in the first part
+                        parts = vec![String::new(); count];
+                        parts[0] = cell;
+                    }
+                    row.splice(index..=index, parts);
+                }
+                split_columns.insert(
+                    0,
+                    SplitColumn {
+                        column: name,
+                        into: names,
+                    },
+                );
+            }
+
+            if let Some(styles) = &table.metadata.row_styles {
+                let zones = row_zones(styles, table.data.rows.len());
+                if !zones.is_empty() {
+                    let width = table.data.headers.len();
+                    if width > 0 {
+                        table.data.headers.push("zone".to_string());
+                        lineage::push_derived(table, "zone", &[], "sports");
+                    }
+                    for (i, row) in table.data.rows.iter_mut().enumerate() {
+                        if row.len() < width {
+                            row.resize(width, String::new());
+                        }
+                        let zone = zones.iter().find(|(row, _)| *row == i + 1);
+                        row.push(zone.map(|(_, zone)| zone.clone()).unwrap_or_default());
+                    }
+                }
+            }
+
+            table.metadata.standings = Some(Standings {
+                rank_column,
+                form_columns,
+                split_columns,
+            });
+        }
+    }
+}
+
+static TIME_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(\d{1,2})(?:[:.h](\d{2}))?\s*([ap])\.?m\.?\b|\b(\d{1,2})[:.h](\d{2})\b")
+        .unwrap()
+});
+static RANGE_SEPARATOR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^\s*(?:-|–|—|to|until)\s*$").unwrap());
+
+/// The times in a cell, in order: "9am", "09:30", "2.15 pm", "18h00".
+fn parse_times(cell: &str) -> Vec<(NaiveTime, std::ops::Range<usize>)> {
+    TIME_RE
+        .captures_iter(cell)
+        .filter_map(|captures| {
+            let (hour, minute) = match captures.get(1) {
+                Some(hour) => {
+                    let hour: u32 = hour.as_str().parse().ok()?;
+                    let minute: u32 = captures.get(2).map_or(Ok(0), |m| m.as_str().parse()).ok()?;
+                    let pm = captures[3].eq_ignore_ascii_case("p");
+                    if !(1..=12).contains(&hour) {
+                        return None;
+                    }
+                    ((hour % 12) + if pm { 12 } else { 0 }, minute)
+                }
+                None => (captures[4].parse().ok()?, captures[5].parse().ok()?),
+            };
+            let time = NaiveTime::from_hms_opt(hour, minute, 0)?;
+            Some((time, captures.get(0)?.range()))
+        })
+        .collect()
+}
+
+/// A "09:00 – 10:30" range within a cell.
+pub fn parse_range(cell: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let times = parse_times(cell);
+    times.windows(2).find_map(|pair| {
+        let between = &cell[pair[0].1.end..pair[1].1.start];
+        RANGE_SEPARATOR_RE
+            .is_match(between)
+            .then_some((pair[0].0, pair[1].0))
+    })
+}
+
+const WEEKDAYS: &[(&str, Weekday)] = &[
+    ("mon", Weekday::Mon),
+    ("tue", Weekday::Tue),
+    ("wed", Weekday::Wed),
+    ("thu", Weekday::Thu),
+    ("fri", Weekday::Fri),
+    ("sat", Weekday::Sat),
+    ("sun", Weekday::Sun),
+];
+
+/// A day as written in a schedule: "2024-06-03", "3 June 2024", "Mon 3 June", "Monday".
+/// Missing years come from `context`, and a bare weekday is its next occurrence on or after it.
+fn parse_day(cell: &str, context: Option<NaiveDate>) -> Option<NaiveDate> {
+    let text = cell.trim().trim_end_matches(':').replace(',', "");
+    for format in ["%Y-%m-%d", "%d %B %Y", "%B %d %Y", "%d %b %Y", "%b %d %Y"] {
+        if let Ok(date) = NaiveDate::parse_from_str(&text, format) {
+            return Some(date);
+        }
+    }
+
+    // Drop a leading weekday name, then retry with the context year
+    let mut words: Vec<&str> = text.split_whitespace().collect();
+    let weekday = words.first().and_then(|word| {
+        let word = word.to_ascii_lowercase();
+        WEEKDAYS
+            .iter()
+            .find(|(prefix, _)| word.starts_with(prefix) && word.len() <= 9)
+            .map(|(_, weekday)| *weekday)
+    });
+    if weekday.is_some() {
+        words.remove(0);
+    }
+    let context = context?;
+    if words.is_empty() {
+        let weekday = weekday?;
+        let ahead =
+            (weekday.num_days_from_monday() + 7 - context.weekday().num_days_from_monday()) % 7;
+        return Some(context + Duration::days(ahead as i64));
+    }
+    let with_year = format!("{} {}", words.join(" "), context.year());
+    ["%d %B %Y", "%B %d %Y", "%d %b %Y", "%b %d %Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(&with_year, format).ok())
+}
+
+/// `--profile timetable`: reads time ranges, or the first and last time columns, and
+/// appends `start` and `end` timestamps in `--timezone`. Days come from a day column,
+/// a row that only names a day (such as a spanning "Monday" row), or `--date-context`.
+/// Rowspans are expanded at extraction so a merged day cell applies to every row it covers.
+pub struct TimetableProfile {
+    context: ProfileContext,
+}
+
+impl TimetableProfile {
+    fn timestamp(&self, date: Option<NaiveDate>, time: NaiveTime) -> String {
+        match date {
+            Some(date) => self
+                .context
+                .timezone
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+                .map(|datetime| datetime.to_rfc3339())
+                .unwrap_or_default(),
+            None => time.format("%H:%M:%S").to_string(),
+        }
+    }
+}
+
+impl Transform for TimetableProfile {
+    fn name(&self) -> &str {
+        "timetable"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        let context = self.context.date;
+        for table in tables {
+            let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+            let share = |index: usize, test: &dyn Fn(&str) -> bool| {
+                let cells: Vec<&str> = column_cells(table, index).collect();
+                let matching = cells.iter().filter(|cell| test(cell)).count();
+                !cells.is_empty() && matching * 5 >= cells.len() * 3
+            };
+
+            let range_column = (0..width).find(|&i| share(i, &|cell| parse_range(cell).is_some()));
+            let time_columns: Vec<usize> = (0..width)
+                .filter(|&i| share(i, &|cell| parse_times(cell).len() == 1))
+                .collect();
+            let day_column =
+                (0..width).find(|&i| share(i, &|cell| parse_day(cell, context).is_some()));
+            if range_column.is_none() && time_columns.is_empty() {
+                continue;
+            }
+
+            let width = table.data.headers.len();
+            if width > 0 {
+                table.data.headers.push("start".to_string());
+                table.data.headers.push("end".to_string());
+                let inputs: Vec<usize> = range_column
+                    .into_iter()
+                    .chain(time_columns.iter().copied())
+                    .chain(day_column)
+                    .collect();
+                lineage::push_derived(table, "start", &inputs, "timetable");
+                lineage::push_derived(table, "end", &inputs, "timetable");
+            }
+
+            let mut current_day = context;
+            for row in &mut table.data.rows {
+                // A row that only names a day, e.g. spanning cells repeated across the row
+                let mut distinct: Vec<&str> = row
+                    .iter()
+                    .map(|cell| cell.trim())
+                    .filter(|cell| !cell.is_empty())
+                    .collect();
+                distinct.dedup();
+                if distinct.len() == 1
+                    && let Some(day) = parse_day(distinct[0], context)
+                {
+                    current_day = Some(day);
+                }
+                let day = day_column
+                    .and_then(|i| row.get(i))
+                    .and_then(|cell| parse_day(cell, context))
+                    .or(current_day);
+
+                let cell = |i: usize| row.get(i).map(String::as_str).unwrap_or("");
+                let times = match range_column.and_then(|i| parse_range(cell(i))) {
+                    Some(range) => Some(range),
+                    None => {
+                        let found: Vec<NaiveTime> = time_columns
+                            .iter()
+                            .filter_map(|&i| parse_times(cell(i)).first().map(|(time, _)| *time))
+                            .collect();
+                        found.first().map(|start| (*start, *found.last().unwrap()))
+                    }
+                };
+
+                if row.len() < width {
+                    row.resize(width, String::new());
+                }
+                match times {
+                    Some((start, end)) => {
+                        // Ranges past midnight end on the next day
+                        let end_day = match day {
+                            Some(day) if end < start => Some(day + Duration::days(1)),
+                            other => other,
+                        };
+                        row.push(self.timestamp(day, start));
+                        row.push(self.timestamp(end_day, end));
+                    }
+                    None => {
+                        row.push(String::new());
+                        row.push(String::new());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::{extract, page};
+
+    fn apply(transform: &dyn Transform, html: &str) -> Table {
+        let mut tables = extract(html, ExtractOptions::default());
+        transform.apply(&mut tables, &page("https://example.com/"));
+        tables.remove(0)
+    }
+
+    #[test]
+    fn normalizes_financial_values() {
+        for (cell, value) in [
+            ("(1,234)", "-1234"),
+            ("$(1,234)", "-1234"),
+            ("($1,234)", "-1234"),
+            ("1,234(a)", "1234"),
+            ("1,234 [2]", "1234"),
+            ("1,234¹", "1234"),
+            ("1,234*", "1234"),
+            ("—", "0"),
+            ("-", "0"),
+            ("12.5%", "12.5%"),
+            ("(12.5)%", "-12.5%"),
+        ] {
+            assert_eq!(normalize_value(cell).as_deref(), Some(value), "{:?}", cell);
+        }
+        assert_eq!(normalize_value("Revenue"), None);
+    }
+
+    #[test]
+    fn leaves_scale_suffixes_for_unit_conversion() {
+        assert!(!FOOTNOTE_RE.is_match("1.2b"));
+        assert!(!FOOTNOTE_RE.is_match("3k"));
+        assert!(!FOOTNOTE_RE.is_match("40m"));
+        let table = apply(
+            &FinancialProfile,
+            "<table><tr><th>Item</th><th>Value</th></tr>
+             <tr><td>Revenue</td><td>1.2b</td></tr>
+             <tr><td>Cost</td><td>1,000</td></tr>
+             <tr><td>Loss</td><td>(450)</td></tr></table>",
+        );
+        assert_eq!(table.data.rows[0][1], "1.2b");
+        assert_eq!(table.data.rows[1][1], "1000");
+        assert_eq!(table.data.rows[2][1], "-450");
+    }
+
+    #[test]
+    fn splits_named_record_columns() {
+        let table = apply(
+            &SportsProfile,
+            "<table><tr><th>Team</th><th>W-D-L</th><th>Record</th></tr>
+             <tr><td>Ann</td><td>10-3-5</td><td>12-6</td></tr>
+             <tr><td>Bob</td><td>8-4-6</td><td>9-9</td></tr></table>",
+        );
+        assert_eq!(table.data.headers, ["Team", "W", "D", "L", "W", "L"]);
+        assert_eq!(table.data.rows[0], ["Ann", "10", "3", "5", "12", "6"]);
+        let standings = table.metadata.standings.unwrap();
+        assert_eq!(standings.split_columns.len(), 2);
+        assert_eq!(standings.split_columns[0].column, "W-D-L");
+    }
+
+    #[test]
+    fn keeps_dates_and_scores_whole() {
+        let table = apply(
+            &SportsProfile,
+            "<table><tr><th>Date</th><th>Team</th><th>Goals</th><th>Score</th></tr>
+             <tr><td>2024-06-03</td><td>Ann</td><td>45-20</td><td>2-1</td></tr>
+             <tr><td>2024-06-10</td><td>Bob</td><td>30-28</td><td>0-0</td></tr></table>",
+        );
+        assert_eq!(table.data.headers, ["Date", "Team", "Goals", "Score"]);
+        assert_eq!(table.data.rows[0], ["2024-06-03", "Ann", "45-20", "2-1"]);
+        assert!(table.metadata.standings.unwrap().split_columns.is_empty());
+        // A record header doesn't make dates records either
+        let table = apply(
+            &SportsProfile,
+            "<table><tr><th>Team</th><th>Record</th></tr>
+             <tr><td>Ann</td><td>2024-06-03</td></tr></table>",
+        );
+        assert_eq!(table.data.headers, ["Team", "Record"]);
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn timetable(timezone: Tz, date: Option<NaiveDate>) -> TimetableProfile {
+        TimetableProfile {
+            context: ProfileContext { timezone, date },
+        }
+    }
+
+    #[test]
+    fn parses_twelve_hour_and_twenty_four_hour_times() {
+        let times = |cell: &str| -> Vec<NaiveTime> {
+            parse_times(cell)
+                .into_iter()
+                .map(|(time, _)| time)
+                .collect()
+        };
+        assert_eq!(times("12am"), [time(0, 0)]);
+        assert_eq!(times("12 p.m."), [time(12, 0)]);
+        assert_eq!(times("2.15 pm"), [time(14, 15)]);
+        assert_eq!(times("09:30 and 18h00"), [time(9, 30), time(18, 0)]);
+        assert_eq!(times("13pm"), []);
+        assert_eq!(parse_range("9am – 10:30"), Some((time(9, 0), time(10, 30))));
+        assert_eq!(parse_range("9am, 10:30"), None);
+    }
+
+    #[test]
+    fn reads_days_relative_to_the_context_date() {
+        // A Monday
+        let context = NaiveDate::from_ymd_opt(2024, 6, 3);
+        assert_eq!(
+            parse_day("2024-06-05", None),
+            NaiveDate::from_ymd_opt(2024, 6, 5)
+        );
+        assert_eq!(
+            parse_day("Sat 8 June", context),
+            NaiveDate::from_ymd_opt(2024, 6, 8)
+        );
+        assert_eq!(parse_day("Monday:", context), context);
+        assert_eq!(
+            parse_day("Wednesday", context),
+            NaiveDate::from_ymd_opt(2024, 6, 5)
+        );
+        assert_eq!(parse_day("Wednesday", None), None);
+    }
+
+    #[test]
+    fn timestamps_ranges_in_the_timezone_past_midnight() {
+        let profile = timetable(
+            chrono_tz::Europe::London,
+            NaiveDate::from_ymd_opt(2024, 6, 3),
+        );
+        let table = apply(
+            &profile,
+            "<table><tr><th>Show</th><th>Time</th></tr>
+             <tr><td colspan=2>Tuesday</td></tr>
+             <tr><td>News</td><td>18:00 - 19:00</td></tr>
+             <tr><td>Film</td><td>11pm to 1am</td></tr></table>",
+        );
+        assert_eq!(table.data.headers, ["Show", "Time", "start", "end"]);
+        assert_eq!(
+            table.data.rows[1][2..],
+            ["2024-06-04T18:00:00+01:00", "2024-06-04T19:00:00+01:00"]
+        );
+        assert_eq!(
+            table.data.rows[2][2..],
+            ["2024-06-04T23:00:00+01:00", "2024-06-05T01:00:00+01:00"]
+        );
+    }
+
+    #[test]
+    fn keeps_bare_times_without_a_day() {
+        let table = apply(
+            &timetable(chrono_tz::UTC, None),
+            "<table><tr><th>Stop</th><th>Departs</th><th>Arrives</th></tr>
+             <tr><td>A</td><td>12am</td><td>00:45</td></tr></table>",
+        );
+        assert_eq!(table.data.rows[0][3..], ["00:00:00", "00:45:00"]);
+    }
+}
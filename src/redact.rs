@@ -0,0 +1,563 @@
+//! `--redact pii`: finds columns of personal data (names, emails, phone numbers and
+//! addresses) and masks or hashes their cells before anything is written.
+//! `--pseudonymize` replaces the identifiers in chosen columns with keyed hashes.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::TabexError;
+use crate::events::Events;
+use crate::lineage;
+use crate::matching::HeaderMatcher;
+use crate::output::slug;
+use crate::plugin::Transform;
+use crate::semantic::{is_email, is_phone};
+use crate::transform::mostly;
+use crate::{PageMetadata, Table};
+
+/// What a redacted cell becomes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedactMode {
+    /// Replaced with "[REDACTED]"
+    Mask,
+    /// Replaced with the first 16 hex digits of its SHA-256, so equal values stay
+    /// equal. Unkeyed hashes of guessable values such as phone numbers can be reversed
+    /// by trying candidates.
+    Hash,
+}
+
+impl RedactMode {
+    pub fn parse(name: &str) -> Result<RedactMode, TabexError> {
+        match name {
+            "mask" => Ok(RedactMode::Mask),
+            "hash" => Ok(RedactMode::Hash),
+            other => Err(TabexError::Usage(format!(
+                "Unknown redaction mode {} (expected mask or hash)",
+                other
+            ))),
+        }
+    }
+
+    fn redact(self, cell: &str) -> String {
+        match self {
+            RedactMode::Mask => "[REDACTED]".to_string(),
+            RedactMode::Hash => {
+                format!("{:x}", Sha256::digest(cell.trim().as_bytes()))[..16].to_string()
+            }
+        }
+    }
+}
+
+/// Checks `--redact`; "pii" is the only category so far.
+pub fn parse_category(name: &str) -> Result<(), TabexError> {
+    match name {
+        "pii" => Ok(()),
+        other => Err(TabexError::Usage(format!(
+            "Unknown --redact category {} (expected pii)",
+            other
+        ))),
+    }
+}
+
+/// A column whose cells were redacted.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Redaction {
+    pub column: usize,
+    pub header: String,
+    /// "name", "email", "phone" or "address", "contact" for the mailto: and tel: links
+    /// of the links table, or "pseudonym" for `--pseudonymize`
+    pub kind: String,
+    /// Non-empty cells replaced
+    pub cells: usize,
+}
+
+/// Header words that make a "... name" column about something other than a person.
+const NOT_PERSON_WORDS: &[&str] = &[
+    "company",
+    "business",
+    "brand",
+    "product",
+    "item",
+    "model",
+    "file",
+    "domain",
+    "host",
+    "team",
+    "club",
+    "school",
+    "organization",
+    "organisation",
+    "project",
+    "place",
+    "city",
+    "town",
+    "country",
+    "street",
+    "venue",
+    "station",
+    "event",
+    "species",
+    "league",
+];
+
+const PERSON_WORDS: &[&str] = &[
+    "name",
+    "names",
+    "fullname",
+    "firstname",
+    "lastname",
+    "surname",
+    "forename",
+    "given",
+    "contact",
+    "person",
+];
+
+/// One to four capitalized words, or "Last, First": "Ann Smith", "O'Neil, Mary",
+/// "J. R. Doe".
+static PERSON_NAME_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\p{Lu}[\p{L}'’.\-]*(?:,?\s+\p{Lu}[\p{L}'’.\-]*){0,3}$").unwrap()
+});
+
+/// A house number and a street word: "221B Baker Street", "1600 Pennsylvania Ave NW".
+static STREET_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\d+[a-z]?,?\s+.*\b(?:street|st|avenue|ave|road|rd|lane|ln|boulevard|blvd|drive|dr|way|court|ct|place|pl|square|sq|terrace|close|crescent|highway|hwy)\b")
+        .unwrap()
+});
+
+/// Personal data a column holds, judged from its header and cells. Names need both a
+/// person-like header ("Name", "Contact", but not "Company name") and cells that read
+/// as names; emails, phone numbers and street addresses show in the cells alone, and
+/// a header mentioning an address is enough for the rest.
+fn pii_kind(table: &Table, column: usize) -> Option<&'static str> {
+    if mostly(table, column, is_email) {
+        return Some("email");
+    }
+    if mostly(table, column, is_phone) {
+        return Some("phone");
+    }
+    let header = slug(table.data.headers.get(column).map_or("", String::as_str));
+    let words: Vec<&str> = header.split('_').collect();
+    let has = |names: &[&str]| words.iter().any(|word| names.contains(word));
+    if mostly(table, column, |cell| STREET_RE.is_match(cell))
+        || (has(&["address", "addr", "street"]) && !has(&["email", "ip", "web", "mac"]))
+    {
+        return Some("address");
+    }
+    let conventions = table.metadata.sign_conventions;
+    let looks_like_name =
+        |cell: &str| PERSON_NAME_RE.is_match(cell) && conventions.parse_number(cell).is_none();
+    if has(PERSON_WORDS) && !has(NOT_PERSON_WORDS) && mostly(table, column, looks_like_name) {
+        return Some("name");
+    }
+    None
+}
+
+/// Replaces the non-empty cells of `column` in the rows `rows` picks, by 0-based
+/// index, and the labels of total rows that were read from those cells. Returns the
+/// number of cells replaced.
+fn replace_cells(
+    table: &mut Table,
+    column: usize,
+    rows: impl Fn(usize) -> bool,
+    replace: impl Fn(&str) -> String,
+) -> usize {
+    let mut cells = 0;
+    for (i, row) in table.data.rows.iter_mut().enumerate() {
+        let Some(cell) = row.get_mut(column).filter(|cell| !cell.trim().is_empty()) else {
+            continue;
+        };
+        if !rows(i) {
+            continue;
+        }
+        let replaced = replace(cell);
+        for total in &mut table.metadata.total_rows {
+            if total.row == i + 1 && total.label == cell.trim() {
+                total.label = replaced.clone();
+            }
+        }
+        *cell = replaced;
+        cells += 1;
+    }
+    cells
+}
+
+/// Drops the page text of redacted columns that other steps kept in the metadata.
+fn forget_page_text(table: &mut Table, redactions: &[Redaction]) {
+    let redacted = |column: usize| redactions.iter().any(|r| r.column == column);
+    if let Some(originals) = &mut table.metadata.original_values {
+        originals.retain(|original| !redacted(original.column));
+    }
+    if let Some(tooltips) = &mut table.metadata.cell_tooltips {
+        tooltips.retain(|tooltip| !redacted(tooltip.column));
+    }
+    if let Some(tooltips) = &mut table.metadata.header_tooltips {
+        tooltips.retain(|tooltip| !redacted(tooltip.column));
+    }
+}
+
+/// Rows of the links table whose href is a mailto: or tel: link; their text usually
+/// names the person or repeats the address.
+fn contact_links(table: &Table) -> Vec<usize> {
+    if table.metadata.auxiliary.as_deref() != Some("links") {
+        return Vec::new();
+    }
+    table
+        .data
+        .rows
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| {
+            row.get(1).is_some_and(|href| {
+                let href = href.trim().to_ascii_lowercase();
+                href.starts_with("mailto:") || href.starts_with("tel:")
+            })
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// `--redact pii` as a pipeline step. It runs after every other step, so columns
+/// derived from personal data are caught too, and drops the page text other steps
+/// kept for the cells it replaces.
+pub struct RedactPii {
+    pub mode: RedactMode,
+    pub events: Events,
+}
+
+impl Transform for RedactPii {
+    fn name(&self) -> &str {
+        "redact"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, page: &PageMetadata) {
+        for table in tables {
+            let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+            let mut redactions = Vec::new();
+            for column in 0..width {
+                // Pseudonymized columns are already safe and stay joinable
+                let pseudonymized = table
+                    .metadata
+                    .redactions
+                    .iter()
+                    .flatten()
+                    .any(|r| r.column == column);
+                if pseudonymized {
+                    continue;
+                }
+                let Some(kind) = pii_kind(table, column) else {
+                    continue;
+                };
+                let cells = replace_cells(table, column, |_| true, |cell| self.mode.redact(cell));
+                lineage::changed(table, column, "redact");
+                redactions.push(Redaction {
+                    column,
+                    header: table.data.headers.get(column).cloned().unwrap_or_default(),
+                    kind: kind.to_string(),
+                    cells,
+                });
+            }
+            let contacts = contact_links(table);
+            if !contacts.is_empty() {
+                for column in 0..width {
+                    if redactions.iter().any(|r| r.column == column) {
+                        continue;
+                    }
+                    let cells = replace_cells(
+                        table,
+                        column,
+                        |row| contacts.contains(&row),
+                        |cell| self.mode.redact(cell),
+                    );
+                    if cells == 0 {
+                        continue;
+                    }
+                    lineage::changed(table, column, "redact");
+                    redactions.push(Redaction {
+                        column,
+                        header: table.data.headers.get(column).cloned().unwrap_or_default(),
+                        kind: "contact".to_string(),
+                        cells,
+                    });
+                }
+            }
+            if redactions.is_empty() {
+                continue;
+            }
+
+            forget_page_text(table, &redactions);
+            let columns: Vec<String> = redactions
+                .iter()
+                .map(|r| format!("{} ({})", r.header, r.kind))
+                .collect();
+            self.events.status(
+                "redact",
+                &format!(
+                    "Redacted {} in table {} of {}",
+                    columns.join(", "),
+                    table.metadata.position,
+                    page.url
+                ),
+                serde_json::json!({
+                    "url": page.url,
+                    "table": table.metadata.position,
+                    "columns": columns,
+                }),
+            );
+            table
+                .metadata
+                .redactions
+                .get_or_insert_with(Vec::new)
+                .extend(redactions);
+        }
+    }
+}
+
+const HMAC_BLOCK_SIZE: usize = 64;
+
+/// HMAC-SHA256 of `message` under `key` (RFC 2104).
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; HMAC_BLOCK_SIZE];
+    if key.len() > HMAC_BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<u8>>();
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// Reads the `--pseudonymize` key from the environment variable `name`. Without a
+/// key the hashes could be recomputed by anyone guessing the identifiers.
+pub fn salt_from_env(name: &str) -> Result<Vec<u8>, TabexError> {
+    match std::env::var(name) {
+        Ok(salt) if !salt.is_empty() => Ok(salt.into_bytes()),
+        _ => Err(TabexError::Usage(format!(
+            "--pseudonymize needs a key: set the {} environment variable (see --salt-env)",
+            name
+        ))),
+    }
+}
+
+/// `--pseudonymize`: replaces each non-empty cell of the named columns with the first
+/// 16 hex digits of its HMAC-SHA256 under the salt. The same identifier and salt give
+/// the same pseudonym on every run, so tables stay joinable on it.
+pub struct Pseudonymize {
+    pub columns: Vec<String>,
+    pub salt: Vec<u8>,
+    pub matcher: HeaderMatcher,
+}
+
+impl Transform for Pseudonymize {
+    fn name(&self) -> &str {
+        "pseudonymize"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        for table in tables {
+            let mut redactions = Vec::new();
+            for column in &self.columns {
+                let Some(index) = self.matcher.column_index(table, column) else {
+                    continue;
+                };
+                if redactions.iter().any(|r: &Redaction| r.column == index) {
+                    continue;
+                }
+                let cells = replace_cells(
+                    table,
+                    index,
+                    |_| true,
+                    |cell| {
+                        let hash = hmac_sha256(&self.salt, cell.trim().as_bytes());
+                        hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+                    },
+                );
+                lineage::changed(table, index, "pseudonymize");
+                redactions.push(Redaction {
+                    column: index,
+                    header: table.data.headers.get(index).cloned().unwrap_or_default(),
+                    kind: "pseudonym".to_string(),
+                    cells,
+                });
+            }
+            if redactions.is_empty() {
+                continue;
+            }
+            forget_page_text(table, &redactions);
+            table
+                .metadata
+                .redactions
+                .get_or_insert_with(Vec::new)
+                .extend(redactions);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::{extract, page};
+
+    fn table(headers: &[&str], rows: &[&[&str]]) -> Table {
+        let cells = |tag: &str, cells: &[&str]| -> String {
+            cells
+                .iter()
+                .map(|cell| format!("<{0}>{1}</{0}>", tag, cell))
+                .collect()
+        };
+        let rows: String = rows
+            .iter()
+            .map(|row| format!("<tr>{}</tr>", cells("td", row)))
+            .collect();
+        let html = format!("<table><tr>{}</tr>{}</table>", cells("th", headers), rows);
+        extract(&html, ExtractOptions::default()).remove(0)
+    }
+
+    fn kinds(table: &Table) -> Vec<Option<&'static str>> {
+        (0..table.data.headers.len())
+            .map(|column| pii_kind(table, column))
+            .collect()
+    }
+
+    #[test]
+    fn finds_personal_data_columns() {
+        let table = table(
+            &[
+                "Full name",
+                "Company name",
+                "E-mail",
+                "Phone",
+                "Home address",
+                "Score",
+            ],
+            &[
+                &[
+                    "Ann Smith",
+                    "Acme Ltd",
+                    "ann@example.com",
+                    "+44 20 7946 0958",
+                    "Flat 2",
+                    "10",
+                ],
+                &[
+                    "O'Neil, Mary",
+                    "Globex",
+                    "mary@example.com",
+                    "(555) 123-4567",
+                    "",
+                    "12",
+                ],
+            ],
+        );
+        assert_eq!(
+            kinds(&table),
+            [
+                Some("name"),
+                None,
+                Some("email"),
+                Some("phone"),
+                Some("address"),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn needs_name_like_cells_under_a_name_header() {
+        let table = table(
+            &["Name", "Location"],
+            &[
+                &["42", "221B Baker Street"],
+                &["17", "1600 Pennsylvania Ave NW"],
+            ],
+        );
+        assert_eq!(kinds(&table), [None, Some("address")]);
+    }
+
+    #[test]
+    fn masks_or_hashes_redacted_cells() {
+        let mut tables = vec![table(
+            &["Name", "Email"],
+            &[&["Ann Smith", "ann@example.com"], &["Bob Jones", ""]],
+        )];
+        RedactPii {
+            mode: RedactMode::Mask,
+            events: Events::default(),
+        }
+        .apply(&mut tables, &page("https://example.com/"));
+        assert_eq!(
+            tables[0].data.rows,
+            [["[REDACTED]", "[REDACTED]"], ["[REDACTED]", ""]]
+        );
+        let redactions = tables[0].metadata.redactions.as_ref().unwrap();
+        assert_eq!(
+            redactions
+                .iter()
+                .map(|r| (r.kind.as_str(), r.cells))
+                .collect::<Vec<_>>(),
+            [("name", 2), ("email", 1)]
+        );
+
+        assert_eq!(
+            RedactMode::Hash.redact(" ann@example.com"),
+            RedactMode::Hash.redact("ann@example.com ")
+        );
+        assert_eq!(RedactMode::Hash.redact("x").len(), 16);
+        assert!(RedactMode::parse("drop").is_err());
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn computes_hmac_sha256() {
+        // RFC 4231 test cases 2 and 6, the latter with a key longer than a block
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn pseudonymizes_alike_under_one_salt_and_apart_under_another() {
+        let pseudonyms = |salt: &[u8]| {
+            let mut tables = vec![table(
+                &["Player", "Goals"],
+                &[&["Ann Smith", "3"], &["Ann Smith ", "1"], &["", "0"]],
+            )];
+            Pseudonymize {
+                columns: vec!["player".to_string()],
+                salt: salt.to_vec(),
+                matcher: HeaderMatcher::default(),
+            }
+            .apply(&mut tables, &page("https://example.com/"));
+            tables.remove(0)
+        };
+        let table = pseudonyms(b"one");
+        let column: Vec<&str> = table.data.rows.iter().map(|row| row[0].as_str()).collect();
+        assert_eq!(column[0], column[1]);
+        assert_eq!(column[0].len(), 16);
+        assert_eq!(column[2], "");
+        assert_ne!(pseudonyms(b"two").data.rows[0][0], column[0]);
+    }
+}
@@ -0,0 +1,377 @@
+use std::io::Write;
+
+use crate::analysis::TableAnalysis;
+use crate::error::TabexError;
+use crate::license::License;
+use crate::output::table_heading;
+use crate::plugin::Sink;
+use crate::{ExtractionResult, PageOutcome, Table};
+
+/// Click-to-sort for every report table; numeric columns sort by value.
+const SORT_SCRIPT: &str = r#"
+document.querySelectorAll("table.data thead th").forEach(function (th) {
+  th.addEventListener("click", function () {
+    var table = th.closest("table");
+    var body = table.tBodies[0];
+    var index = Array.prototype.indexOf.call(th.parentNode.children, th);
+    var ascending = th.dataset.order !== "asc";
+    th.parentNode.querySelectorAll("th").forEach(function (other) { delete other.dataset.order; });
+    th.dataset.order = ascending ? "asc" : "desc";
+    var key = function (row) {
+      var text = row.children[index] ? row.children[index].textContent : "";
+      var number = parseFloat(text.replace(/[^0-9.\-]/g, ""));
+      return isNaN(number) ? text.toLowerCase() : number;
+    };
+    Array.prototype.slice.call(body.rows)
+      .sort(function (a, b) {
+        var x = key(a), y = key(b);
+        if (typeof x !== typeof y) { x = String(x); y = String(y); }
+        return (x < y ? -1 : x > y ? 1 : 0) * (ascending ? 1 : -1);
+      })
+      .forEach(function (row) { body.appendChild(row); });
+  });
+});
+"#;
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #222; }
+table { border-collapse: collapse; margin: 0.5rem 0 2rem; }
+th, td { border: 1px solid #ccc; padding: 0.25rem 0.5rem; text-align: left; }
+table.data th { background: #f3f3f3; cursor: pointer; user-select: none; }
+table.data th[data-order="asc"]::after { content: " \25B2"; }
+table.data th[data-order="desc"]::after { content: " \25BC"; }
+dl { display: grid; grid-template-columns: max-content auto; gap: 0.25rem 1rem; }
+dt { font-weight: bold; }
+"#;
+
+/// A single self-contained HTML page with page metadata, a table of contents,
+/// sortable tables and run statistics, for sharing outside the tool.
+pub struct ReportSink;
+
+impl Sink for ReportSink {
+    fn name(&self) -> &str {
+        "report"
+    }
+
+    fn extension(&self) -> &str {
+        "html"
+    }
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError> {
+        let page = &result.page;
+        let title = page.title.as_deref().unwrap_or(&page.url);
+        write_head(title, out)?;
+        writeln!(out, "<h1>Tables from {}</h1>", escape(title))?;
+        write_page(result, 2, "", out)?;
+        write_foot(out)
+    }
+
+    /// One document with a section for each page, listed at the top.
+    fn write_pages(&self, pages: &[PageOutcome], out: &mut dyn Write) -> Result<(), TabexError> {
+        let results: Vec<&ExtractionResult> = pages
+            .iter()
+            .filter_map(|page| page.result.as_ref())
+            .collect();
+        let title = format!("{} pages", results.len());
+        write_head(&title, out)?;
+        writeln!(out, "<h1>Tables from {}</h1>", escape(&title))?;
+
+        writeln!(out, "<ol>")?;
+        for (i, result) in results.iter().enumerate() {
+            let page = &result.page;
+            writeln!(
+                out,
+                "<li><a href=\"#page-{}\">{}</a> ({} tables)</li>",
+                i + 1,
+                escape(page.title.as_deref().unwrap_or(&page.url)),
+                result.tables.len()
+            )?;
+        }
+        writeln!(out, "</ol>")?;
+
+        for (i, result) in results.iter().enumerate() {
+            let page = &result.page;
+            writeln!(
+                out,
+                "<section id=\"page-{}\">\n<h2>{}</h2>",
+                i + 1,
+                escape(page.title.as_deref().unwrap_or(&page.url))
+            )?;
+            write_page(result, 3, &format!("page-{}-", i + 1), out)?;
+            writeln!(out, "</section>")?;
+        }
+        write_foot(out)
+    }
+}
+
+fn write_head(title: &str, out: &mut dyn Write) -> Result<(), TabexError> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>Tables from {}</title>", escape(title))?;
+    writeln!(out, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+    Ok(())
+}
+
+fn write_foot(out: &mut dyn Write) -> Result<(), TabexError> {
+    writeln!(out, "<script>{}</script>\n</body>\n</html>", SORT_SCRIPT)?;
+    Ok(())
+}
+
+/// A page's metadata, statistics, contents and tables, under `<h{level}>` headings.
+/// Table anchors start with `id_prefix`, keeping them unique when several pages share
+/// the document.
+fn write_page(
+    result: &ExtractionResult,
+    level: usize,
+    id_prefix: &str,
+    out: &mut dyn Write,
+) -> Result<(), TabexError> {
+    let page = &result.page;
+    writeln!(out, "<dl>")?;
+    let license = page.license.as_ref().map(License::label);
+    let fields = [
+        ("URL", Some(page.url.as_str())),
+        ("Title", page.title.as_deref()),
+        ("Description", page.description.as_deref()),
+        ("Author", page.author.as_deref()),
+        ("Published", page.published_date.as_deref()),
+        ("Last modified", page.last_modified.as_deref()),
+        ("Robots", page.robots.as_deref()),
+        ("License", license.as_deref()),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            writeln!(out, "<dt>{}</dt><dd>{}</dd>", label, escape(value))?;
+        }
+    }
+    writeln!(out, "</dl>")?;
+
+    writeln!(out, "<h{0}>Statistics</h{0}>\n<dl>", level)?;
+    let rows: usize = result
+        .tables
+        .iter()
+        .map(|table| table.data.rows.len())
+        .sum();
+    writeln!(out, "<dt>Tables</dt><dd>{}</dd>", result.tables.len())?;
+    writeln!(out, "<dt>Data rows</dt><dd>{}</dd>", rows)?;
+    writeln!(
+        out,
+        "<dt>Extraction time</dt><dd>{} ms</dd>",
+        result.extraction_time_ms
+    )?;
+    if result.timed_out {
+        writeln!(
+            out,
+            "<dt>Timed out</dt><dd>yes, later tables were skipped</dd>"
+        )?;
+    }
+    writeln!(out, "</dl>")?;
+
+    if !result.tables.is_empty() {
+        writeln!(out, "<h{0}>Contents</h{0}>\n<ol>", level)?;
+        for table in &result.tables {
+            writeln!(
+                out,
+                "<li><a href=\"#{}table-{}\">{}</a></li>",
+                id_prefix,
+                table.metadata.position,
+                escape(&table_heading(table))
+            )?;
+        }
+        writeln!(out, "</ol>")?;
+    }
+
+    for table in &result.tables {
+        write_table(table, level, id_prefix, out)?;
+    }
+    Ok(())
+}
+
+fn write_table(
+    table: &Table,
+    level: usize,
+    id_prefix: &str,
+    out: &mut dyn Write,
+) -> Result<(), TabexError> {
+    let metadata = &table.metadata;
+    writeln!(
+        out,
+        "<h{0} id=\"{1}table-{2}\">{3}</h{0}>",
+        level,
+        id_prefix,
+        metadata.position,
+        escape(&table_heading(table))
+    )?;
+    writeln!(
+        out,
+        "<p>{} data rows, {} columns, score {}</p>",
+        table.data.rows.len(),
+        metadata.column_count,
+        metadata.score
+    )?;
+    for note in &metadata.notes {
+        writeln!(out, "<p><small>{}</small></p>", escape(note))?;
+    }
+    if let Some(analysis) = &metadata.analysis {
+        write_analysis(analysis, out)?;
+    }
+
+    writeln!(out, "<table class=\"data\">")?;
+    if !table.data.headers.is_empty() {
+        write!(out, "<thead><tr>")?;
+        for header in &table.data.headers {
+            write!(out, "<th>{}</th>", escape(header))?;
+        }
+        writeln!(out, "</tr></thead>")?;
+    }
+    writeln!(out, "<tbody>")?;
+    for row in &table.data.rows {
+        write!(out, "<tr>")?;
+        for cell in row {
+            write!(out, "<td>{}</td>", escape(cell))?;
+        }
+        writeln!(out, "</tr>")?;
+    }
+    writeln!(out, "</tbody>\n</table>")?;
+    Ok(())
+}
+
+/// The profiling found by `--analyze`: candidate keys, the `--key-columns` check and outliers.
+fn write_analysis(analysis: &TableAnalysis, out: &mut dyn Write) -> Result<(), TabexError> {
+    writeln!(out, "<dl>")?;
+    let keys = if analysis.candidate_keys.is_empty() {
+        "none".to_string()
+    } else {
+        analysis.candidate_keys.join(", ")
+    };
+    writeln!(out, "<dt>Candidate keys</dt><dd>{}</dd>", escape(&keys))?;
+    if let Some(check) = &analysis.key_check {
+        let outcome = if check.duplicate_count == 0 {
+            "unique".to_string()
+        } else {
+            format!(
+                "{} duplicate value(s): {}",
+                check.duplicate_count,
+                check.duplicates.join("; ")
+            )
+        };
+        writeln!(
+            out,
+            "<dt>Key {}</dt><dd>{}</dd>",
+            escape(&check.columns.join(", ")),
+            escape(&outcome)
+        )?;
+    }
+    if !analysis.outliers.is_empty() {
+        write!(out, "<dt>Outliers</dt><dd><ul>")?;
+        for outlier in &analysis.outliers {
+            write!(
+                out,
+                "<li>{} in {}, row {} (z = {:.1})</li>",
+                escape(&outlier.value),
+                escape(&outlier.column),
+                outlier.row,
+                outlier.z_score
+            )?;
+        }
+        writeln!(out, "</ul></dd>")?;
+    }
+    writeln!(out, "</dl>")?;
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::analysis::{KeyCheck, Outlier};
+    use crate::tests::{extract, page};
+
+    fn report(result: &ExtractionResult) -> String {
+        let mut out = Vec::new();
+        ReportSink.write(result, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn escapes_text_and_links_the_contents() {
+        let mut tables = extract(
+            "<table><caption>Top &lt;script&gt;</caption><tr><th>A</th></tr>
+             <tr><td>&lt;script&gt;alert(1)&lt;/script&gt;</td></tr></table>
+             <table><tr><th>B</th></tr><tr><td>x</td></tr><tr><td>y</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        tables[0].metadata.caption = Some("Top <script>".to_string());
+        let result = ExtractionResult {
+            page: page("https://example.com/"),
+            tables,
+            relationships: None,
+            timed_out: false,
+            attempts: None,
+            extraction_time_ms: 5,
+        };
+        let html = report(&result);
+        assert!(!html.contains("<script>alert"), "{}", html);
+        assert!(html.contains("<td>&lt;script&gt;alert(1)&lt;/script&gt;</td>"));
+        assert!(html.contains("Table 1: Top &lt;script&gt;"));
+        // Outside the sort script, the only script tags are escaped
+        assert_eq!(html.matches("<script>").count(), 1);
+        for position in [1, 2] {
+            assert!(html.contains(&format!("<a href=\"#table-{}\">", position)));
+            assert!(html.contains(&format!(" id=\"table-{}\">", position)));
+        }
+    }
+
+    #[test]
+    fn renders_the_analysis_of_each_table() {
+        let mut tables = extract(
+            "<table><tr><th>Id</th><th>Score</th></tr><tr><td>1</td><td>3</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        tables[0].metadata.analysis = Some(TableAnalysis {
+            candidate_keys: vec!["Id".to_string()],
+            key_check: Some(KeyCheck {
+                columns: vec!["Score".to_string()],
+                duplicate_count: 1,
+                duplicates: vec!["3".to_string()],
+            }),
+            outliers: vec![Outlier {
+                column: "Score".to_string(),
+                row: 1,
+                value: "<900>".to_string(),
+                z_score: 3.25,
+            }],
+        });
+        let result = ExtractionResult {
+            page: page("https://example.com/"),
+            tables,
+            relationships: None,
+            timed_out: false,
+            attempts: None,
+            extraction_time_ms: 5,
+        };
+        let html = report(&result);
+        assert!(
+            html.contains("<dt>Candidate keys</dt><dd>Id</dd>"),
+            "{}",
+            html
+        );
+        assert!(html.contains("<dt>Key Score</dt><dd>1 duplicate value(s): 3</dd>"));
+        assert!(html.contains("<li>&lt;900&gt; in Score, row 1 (z = 3.2)</li>"));
+    }
+}
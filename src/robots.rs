@@ -0,0 +1,346 @@
+//! robots.txt rules for `--compliance-report`: which paths a site asks the run's user
+//! agent to keep away from, and how long to wait between requests. The rules are
+//! reported, not enforced.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::events::{Events, Record};
+use crate::fetch::{Bandwidth, HostFilter};
+
+/// The robots.txt group that applies to one user agent.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RobotsRules {
+    /// "found", "missing" for a 4xx answer, "unreachable" for an error or a 5xx, or
+    /// "not_allowed" when `--allow-hosts`/`--deny-hosts` keep the run from reading it
+    pub status: String,
+    /// The `User-agent` line the rules came from, e.g. "*"; None when no group applies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    pub allow: Vec<String>,
+    pub disallow: Vec<String>,
+    /// Seconds to wait between requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crawl_delay: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    agents: Vec<String>,
+    allow: Vec<String>,
+    disallow: Vec<String>,
+    crawl_delay: Option<f64>,
+}
+
+/// How well an agent token from robots.txt names `user_agent`: the token's length when
+/// the user agent contains it, ignoring case, and 0 for "*".
+fn agent_match(token: &str, user_agent: &str) -> Option<usize> {
+    if token == "*" {
+        Some(0)
+    } else if user_agent
+        .to_ascii_lowercase()
+        .contains(&token.to_ascii_lowercase())
+    {
+        Some(token.len())
+    } else {
+        None
+    }
+}
+
+impl RobotsRules {
+    /// The rules of `text` for `user_agent`: the groups naming the most specific token
+    /// the user agent contains, merged, or else the "*" groups.
+    pub fn parse(text: &str, user_agent: &str) -> RobotsRules {
+        let mut groups: Vec<Group> = Vec::new();
+        // A user-agent line after rules starts a new group
+        let mut in_rules = true;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        groups.push(Group::default());
+                        in_rules = false;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(value.to_string());
+                    }
+                }
+                key => {
+                    in_rules = true;
+                    let Some(group) = groups.last_mut() else {
+                        continue;
+                    };
+                    match key {
+                        "allow" if !value.is_empty() => group.allow.push(value.to_string()),
+                        // An empty disallow allows everything
+                        "disallow" if !value.is_empty() => group.disallow.push(value.to_string()),
+                        "crawl-delay" => {
+                            group.crawl_delay = value.parse().ok().filter(|d: &f64| *d >= 0.0)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let best = groups
+            .iter()
+            .flat_map(|group| &group.agents)
+            .filter_map(|agent| Some((agent_match(agent, user_agent)?, agent)))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, agent)| agent.clone());
+        let mut rules = RobotsRules {
+            status: "found".to_string(),
+            ..RobotsRules::default()
+        };
+        let Some(best) = best else {
+            return rules;
+        };
+        for group in groups.iter().filter(|group| {
+            group
+                .agents
+                .iter()
+                .any(|agent| agent.eq_ignore_ascii_case(&best))
+        }) {
+            rules.allow.extend(group.allow.iter().cloned());
+            rules.disallow.extend(group.disallow.iter().cloned());
+            rules.crawl_delay = rules.crawl_delay.or(group.crawl_delay);
+        }
+        rules.user_agent = Some(best);
+        rules
+    }
+
+    /// Rules for a site without a usable robots.txt.
+    fn none(status: &str) -> RobotsRules {
+        RobotsRules {
+            status: status.to_string(),
+            ..RobotsRules::default()
+        }
+    }
+
+    /// Whether the rules let the user agent fetch `path` (with its query): the longest
+    /// matching pattern decides, and allow wins a tie.
+    pub fn allows(&self, path: &str) -> bool {
+        let longest = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter(|pattern| path_match(pattern, path))
+                .map(String::len)
+                .max()
+        };
+        match (longest(&self.allow), longest(&self.disallow)) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(allow), Some(disallow)) => allow >= disallow,
+        }
+    }
+}
+
+/// Matches a robots.txt path pattern, where `*` is any run of characters and a
+/// trailing `$` anchors the end, against the start of `path`.
+fn path_match(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut pieces = pattern.split('*');
+    let first = pieces.next().unwrap_or("");
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let pieces: Vec<&str> = pieces.collect();
+    for (i, piece) in pieces.iter().enumerate() {
+        // The last piece of an anchored pattern has to end the path
+        if anchored && i == pieces.len() - 1 {
+            return rest.ends_with(piece);
+        }
+        match rest.find(piece) {
+            Some(at) => rest = &rest[at + piece.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// Reads each site's robots.txt once, before the run's first page request to it, and
+/// records the rules and any page they disallow in the manifest.
+pub struct RobotsCheck {
+    pub user_agent: String,
+    rules: Mutex<HashMap<String, RobotsRules>>,
+}
+
+impl RobotsCheck {
+    pub fn new(user_agent: &str) -> RobotsCheck {
+        RobotsCheck {
+            user_agent: user_agent.to_string(),
+            rules: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn load(
+        &self,
+        client: &reqwest::blocking::Client,
+        origin: &reqwest::Url,
+        timeout: Option<Duration>,
+        host_filter: &HostFilter,
+        bandwidth: &Bandwidth,
+    ) -> RobotsRules {
+        let Ok(url) = origin.join("/robots.txt") else {
+            return RobotsRules::none("unreachable");
+        };
+        if !host_filter.allows(&url) {
+            return RobotsRules::none("not_allowed");
+        }
+        let mut request = client.get(url);
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+        match request.send() {
+            Ok(mut resp) if resp.status().is_success() => match bandwidth.read(&mut resp) {
+                Ok(body) => RobotsRules::parse(&String::from_utf8_lossy(&body), &self.user_agent),
+                Err(_) => RobotsRules::none("unreachable"),
+            },
+            Ok(resp) if resp.status().is_client_error() => RobotsRules::none("missing"),
+            _ => RobotsRules::none("unreachable"),
+        }
+    }
+
+    /// Checks `url` against its site's robots.txt, reading the file first if this is
+    /// the first request to the site. Disallowed pages are still fetched; they are
+    /// warned about and listed in the manifest.
+    pub fn check(
+        &self,
+        client: &reqwest::blocking::Client,
+        url: &str,
+        timeout: Option<Duration>,
+        host_filter: &HostFilter,
+        bandwidth: &Bandwidth,
+        events: &Events,
+    ) {
+        let Ok(parsed) = reqwest::Url::parse(url) else {
+            return;
+        };
+        let origin = parsed.origin().ascii_serialization();
+        let mut cache = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+        let rules = cache.entry(origin.clone()).or_insert_with(|| {
+            let rules = self.load(client, &parsed, timeout, host_filter, bandwidth);
+            events.record(Record::Robots { url, rules: &rules });
+            rules
+        });
+        let path = match parsed.query() {
+            Some(query) => format!("{}?{}", parsed.path(), query),
+            None => parsed.path().to_string(),
+        };
+        if !rules.allows(&path) {
+            events.record(Record::Disallowed { url });
+            events.warning(
+                "robots_disallowed",
+                &format!("{}/robots.txt disallows {}", origin, url),
+                serde_json::json!({ "origin": origin, "url": url }),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_path_prefixes() {
+        assert!(path_match("/private", "/private/a.html"));
+        assert!(path_match("/private", "/privateer"));
+        assert!(!path_match("/private", "/public/private"));
+        assert!(path_match("/", "/anything"));
+        assert!(path_match("", "/anything"));
+    }
+
+    #[test]
+    fn matches_wildcards_anywhere_in_the_pattern() {
+        assert!(path_match("/*.pdf", "/docs/report.pdf"));
+        assert!(path_match("/*.pdf", "/docs/report.pdf?download=1"));
+        assert!(path_match("/search*q=", "/search/all?lang=en&q=x"));
+        assert!(!path_match("/search*q=", "/search/all"));
+        assert!(path_match("/a*b*c", "/aXbYc/d"));
+        assert!(!path_match("/a*c*b", "/abc"));
+        assert!(path_match("*", ""));
+    }
+
+    #[test]
+    fn anchors_patterns_ending_in_dollar() {
+        assert!(path_match("/*.php$", "/index.php"));
+        assert!(!path_match("/*.php$", "/index.php?x=1"));
+        assert!(!path_match("/*.php$", "/index.php5"));
+        assert!(path_match("/exact$", "/exact"));
+        assert!(!path_match("/exact$", "/exact/more"));
+        assert!(path_match("/$", "/"));
+        assert!(!path_match("/a*a$", "/a"));
+        assert!(path_match("/a*a$", "/aa"));
+        assert!(path_match("/*$", "/anything"));
+    }
+
+    #[test]
+    fn lets_the_longest_rule_decide_and_allow_win_ties() {
+        let rules = RobotsRules::parse(
+            "User-agent: *\nDisallow: /shop\nAllow: /shop/public\nDisallow: /*.json$\n\
+             Allow: /same\nDisallow: /same\n",
+            "tabex/0.1",
+        );
+        assert!(!rules.allows("/shop/cart"));
+        assert!(rules.allows("/shop/public/list"));
+        assert!(!rules.allows("/data/items.json"));
+        assert!(rules.allows("/data/items.json?page=2"));
+        assert!(rules.allows("/same"));
+        assert!(rules.allows("/about"));
+    }
+
+    #[test]
+    fn picks_the_group_naming_the_user_agent_most_specifically() {
+        let text = "# comment\nUser-agent: *\nDisallow: /\n\n\
+                    User-agent: Tabex\nUser-agent: other\nDisallow: /private # inline\nCrawl-delay: 2.5\n\n\
+                    User-agent: tabex\nAllow: /private/ok\n";
+        let rules = RobotsRules::parse(text, "Mozilla/5.0 (compatible; tabex/0.1)");
+        assert!(rules.user_agent.unwrap().eq_ignore_ascii_case("tabex"));
+        assert_eq!(rules.disallow, ["/private"]);
+        assert_eq!(rules.allow, ["/private/ok"]);
+        assert_eq!(rules.crawl_delay, Some(2.5));
+
+        let fallback = RobotsRules::parse(text, "curl/8.0");
+        assert_eq!(fallback.user_agent.as_deref(), Some("*"));
+        assert!(!fallback.allows("/index.html"));
+
+        let empty = RobotsRules::parse("User-agent: *\nDisallow:\n", "curl/8.0");
+        assert!(empty.allows("/anything"));
+    }
+
+    #[test]
+    fn skips_robots_txt_on_denied_hosts_without_a_request() {
+        let check = RobotsCheck::new("tabex");
+        let (events, reported) = crate::events::tests::collect();
+        let host_filter = HostFilter {
+            allow: Vec::new(),
+            deny: vec!["example.com".to_string()],
+        };
+        let bandwidth = Bandwidth::new(None);
+        check.check(
+            &reqwest::blocking::Client::new(),
+            "https://example.com/page",
+            None,
+            &host_filter,
+            &bandwidth,
+            &events,
+        );
+        let rules = check.rules.lock().unwrap();
+        assert_eq!(rules["https://example.com"].status, "not_allowed");
+        assert_eq!(bandwidth.bytes(), 0);
+        assert!(reported.events().is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+use scraper::{ElementRef, Html, Selector};
+use serde_json::json;
+
+use crate::error::TabexError;
+use crate::events::Events;
+use crate::manifest;
+use crate::plugin::Registry;
+use crate::{ExtractionResult, TABLE_SELECTOR, Table};
+
+/// Which tables a `--route` takes.
+#[derive(Debug)]
+enum RouteMatch {
+    /// Caption containing the text, ignoring case
+    Caption(String),
+    /// Preceding heading containing the text, ignoring case
+    Heading(String),
+    /// Tables matching the selector or inside an element that does
+    Selector(Selector),
+    All,
+}
+
+/// A `--route` rule: tables it matches are written with `format` to `path`.
+#[derive(Debug)]
+pub struct Route {
+    matcher: RouteMatch,
+    pub format: String,
+    pub path: String,
+}
+
+impl Route {
+    /// Parses `MATCH=FORMAT:FILE`, where MATCH is `caption:TEXT`, `heading:TEXT`,
+    /// `selector:CSS` or `*`, e.g. `"caption:Prices=sql:prices.sql"`.
+    pub fn parse(spec: &str) -> Result<Route, TabexError> {
+        let invalid = |reason: &str| {
+            TabexError::Parse(format!(
+                "Invalid --route '{}': {} (expected MATCH=FORMAT:FILE)",
+                spec, reason
+            ))
+        };
+        // Selectors may contain '=', file names rarely do
+        let (rule, destination) = spec
+            .rsplit_once('=')
+            .ok_or_else(|| invalid("missing '='"))?;
+        let (format, path) = destination
+            .split_once(':')
+            .filter(|(format, path)| !format.trim().is_empty() && !path.trim().is_empty())
+            .ok_or_else(|| invalid("missing FORMAT:FILE"))?;
+
+        let rule = rule.trim();
+        let matcher = if rule == "*" {
+            RouteMatch::All
+        } else {
+            match rule.split_once(':') {
+                Some(("caption", text)) => RouteMatch::Caption(text.trim().to_lowercase()),
+                Some(("heading", text)) => RouteMatch::Heading(text.trim().to_lowercase()),
+                Some(("selector", css)) => {
+                    RouteMatch::Selector(Selector::parse(css.trim()).map_err(|_| {
+                        TabexError::Selector {
+                            selector: css.trim().to_string(),
+                            message: format!("not valid CSS in --route '{}'", spec),
+                        }
+                    })?)
+                }
+                _ => return Err(invalid("MATCH must be caption:, heading:, selector: or *")),
+            }
+        };
+        Ok(Route {
+            matcher,
+            format: format.trim().to_string(),
+            path: path.trim().to_string(),
+        })
+    }
+
+    fn matches(&self, table: &Table, document: &Html) -> bool {
+        let contains = |text: &Option<String>, wanted: &str| {
+            text.as_deref()
+                .is_some_and(|text| text.to_lowercase().contains(wanted))
+        };
+        match &self.matcher {
+            RouteMatch::Caption(text) => contains(&table.metadata.caption, text),
+            RouteMatch::Heading(text) => contains(&table.metadata.preceding_heading, text),
+            // Tables tabex adds, such as the links table, have no element to match
+            RouteMatch::Selector(selector) => document
+                .select(&TABLE_SELECTOR)
+                .nth(table.metadata.position.wrapping_sub(1))
+                .is_some_and(|element| {
+                    std::iter::successors(Some(element), |element| {
+                        element.parent().and_then(ElementRef::wrap)
+                    })
+                    .any(|element| selector.matches(&element))
+                }),
+            RouteMatch::All => true,
+        }
+    }
+}
+
+/// Files named by the routes, each with its format, in order of first mention. A
+/// file named with two formats is an error.
+pub fn destinations(routes: &[Route]) -> Result<Vec<(&str, &str)>, TabexError> {
+    let mut destinations: Vec<(&str, &str)> = Vec::new();
+    for route in routes {
+        match destinations.iter().find(|(_, path)| *path == route.path) {
+            Some((format, _)) if *format != route.format => {
+                return Err(TabexError::Usage(format!(
+                    "--route writes {} as both {} and {}",
+                    route.path, format, route.format
+                )));
+            }
+            Some(_) => {}
+            None => destinations.push((&route.format, &route.path)),
+        }
+    }
+    Ok(destinations)
+}
+
+/// Writes each table to the file of the first route matching it, skipping tables no
+/// route matches. Relationships go with a file when both of their tables do. The
+/// result's tables are left as they were.
+pub fn write_routed(
+    result: &mut ExtractionResult,
+    routes: &[Route],
+    document: &Html,
+    registry: &Registry,
+    events: &Events,
+) -> Result<(), TabexError> {
+    let destinations = destinations(routes)?;
+    let mut groups: Vec<Vec<Table>> = destinations.iter().map(|_| Vec::new()).collect();
+    let mut skipped: Vec<Table> = Vec::new();
+    for table in std::mem::take(&mut result.tables) {
+        let destination = routes
+            .iter()
+            .find(|route| route.matches(&table, document))
+            .and_then(|route| {
+                destinations
+                    .iter()
+                    .position(|(_, path)| *path == route.path)
+            });
+        match destination {
+            Some(index) => groups[index].push(table),
+            None => skipped.push(table),
+        }
+    }
+    let relationships = result.relationships.take();
+
+    let mut written = Ok(());
+    for ((format, path), group) in destinations.iter().zip(&mut groups) {
+        let sink = registry
+            .sink(format)
+            .ok_or_else(|| TabexError::Usage(format!("Unsupported output format {}", format)))?;
+        let positions: Vec<usize> = group.iter().map(|table| table.metadata.position).collect();
+        result.relationships = relationships.as_ref().map(|relationships| {
+            relationships
+                .iter()
+                .filter(|r| positions.contains(&r.from_table) && positions.contains(&r.to_table))
+                .cloned()
+                .collect()
+        });
+        result.tables = std::mem::take(group);
+        written = manifest::write_output(sink, format, result, Some(path), events);
+        *group = std::mem::take(&mut result.tables);
+        if written.is_err() {
+            break;
+        }
+        events.status(
+            "output",
+            &format!("Results written to {} ({} table(s))", path, group.len()),
+            json!({ "path": path, "format": format, "tables": group.len() }),
+        );
+    }
+    if !skipped.is_empty() {
+        events.status(
+            "route_skipped",
+            &format!("Skipped {} table(s) matching no --route", skipped.len()),
+            json!({ "tables": skipped.len() }),
+        );
+    }
+
+    result.tables = groups.into_iter().flatten().chain(skipped).collect();
+    result.tables.sort_by_key(|table| table.metadata.position);
+    result.relationships = relationships;
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    const PAGE: &str =
+        "<h2>Fixtures</h2><table><caption>Weekly Prices</caption><tr><td>1</td></tr></table>
+        <div class=\"stats\"><table><tr><td>2</td></tr></table></div>";
+
+    fn routed(spec: &str) -> Vec<usize> {
+        let route = Route::parse(spec).unwrap();
+        let document = Html::parse_document(PAGE);
+        extract(PAGE, ExtractOptions::default())
+            .iter()
+            .filter(|table| route.matches(table, &document))
+            .map(|table| table.metadata.position)
+            .collect()
+    }
+
+    #[test]
+    fn matches_tables_by_caption_heading_or_selector() {
+        assert_eq!(routed("caption:prices=csv:prices.csv"), [1]);
+        assert_eq!(routed("heading:FIXTURES=json:f.json"), [1, 2]);
+        assert_eq!(routed("selector:div.stats=sql:stats.sql"), [2]);
+        assert_eq!(
+            routed("selector:table[data-x='1']=csv:x.csv"),
+            Vec::<usize>::new()
+        );
+        assert_eq!(routed("*=csv:all.csv"), [1, 2]);
+    }
+
+    #[test]
+    fn parses_format_and_path() {
+        let route = Route::parse(" caption:Prices = sql : out/prices.sql ").unwrap();
+        assert_eq!(
+            (route.format.as_str(), route.path.as_str()),
+            ("sql", "out/prices.sql")
+        );
+        for spec in [
+            "caption:Prices",
+            "caption:Prices=sql",
+            "caption:Prices=:x.sql",
+            "title:Prices=csv:x.csv",
+            "selector:[[=csv:x.csv",
+        ] {
+            assert!(Route::parse(spec).is_err(), "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn refuses_one_file_in_two_formats() {
+        let routes = [
+            Route::parse("caption:a=csv:out.csv").unwrap(),
+            Route::parse("caption:b=csv:out.csv").unwrap(),
+            Route::parse("*=json:rest.json").unwrap(),
+        ];
+        assert_eq!(
+            destinations(&routes).unwrap(),
+            [("csv", "out.csv"), ("json", "rest.json")]
+        );
+        let clash = [
+            Route::parse("caption:a=csv:out.txt").unwrap(),
+            Route::parse("caption:b=json:out.txt").unwrap(),
+        ];
+        assert!(destinations(&clash).is_err());
+    }
+}
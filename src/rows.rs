@@ -0,0 +1,645 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::types::SignConventions;
+use crate::{CELL_SELECTOR, TD_SELECTOR, clean_cell_text};
+
+static BOLD_SELECTOR: LazyLock<Selector> = LazyLock::new(|| Selector::parse("strong, b").unwrap());
+static BACKGROUND_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)background(?:-color)?\s*:\s*([^;]+)").unwrap());
+static STRUCK_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("s, strike, del").unwrap());
+static STYLED_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("[class], [style], font[color]").unwrap());
+static COLOR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|[;\s])color\s*:\s*([^;]+)").unwrap());
+/// Colour words in classes like "negative", "text-danger" or "red"
+const COLOR_WORDS: &str = "red|green|blue|orange|yellow|gr[ae]y|purple|black|white|negative|positive|danger|success|warning|info|muted|loss|gain|up|down";
+static COLOR_CLASS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)^(?:(?:text|fg|font|color)[-_])?(?:{})$",
+        COLOR_WORDS
+    ))
+    .unwrap()
+});
+static BACKGROUND_CLASS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)^(?:bg|background|highlight)(?:[-_]|$)|^(?:table|row)[-_](?:{})$",
+        COLOR_WORDS
+    ))
+    .unwrap()
+});
+static INDENT_STYLE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:padding-left|text-indent|margin-left)\s*:\s*(\d+)").unwrap()
+});
+static INDENT_CLASS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:indent|level)[-_]?(\d)").unwrap());
+static TOTAL_LABEL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*(grand\s+)?(sub-?\s*)?totals?\b|^\s*sum\b|^\s*overall\b").unwrap()
+});
+static SUBTOTAL_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\bsub-?\s*total").unwrap());
+
+/// Presentation hints for a data row, read from the DOM before cells are cleaned.
+#[derive(Debug, Clone, Default)]
+pub struct RowHint {
+    /// Indentation rank of the first cell: 0 is flush left
+    pub level: usize,
+    /// The row only labels a group: a bold or spanning first cell and no other values
+    pub group_header: bool,
+    /// Cleaned text of the first cell, th or td
+    pub label: String,
+    /// Every non-empty cell is bold
+    pub bold: bool,
+    /// Class attribute of the tr
+    pub class: Option<String>,
+    /// Background colour of the tr, or of its first cell
+    pub background: Option<String>,
+}
+
+/// Styling of a data row, kept with `--profile sports` to find promotion and relegation zones.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RowStyle {
+    /// 1-based position among the table's data rows
+    pub row: usize,
+    pub class: Option<String>,
+    pub background: Option<String>,
+}
+
+/// Presentation signals of a data cell, which sites use to mark negative values,
+/// withdrawn entries and the like.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StyleFlags {
+    pub bold: bool,
+    pub strikethrough: bool,
+    /// Inline text colour, else a colour-like class such as "text-danger"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Inline background colour, else a background class such as "bg-warning"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+}
+
+/// A styled data cell, listed with `--cell-styles`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CellStyle {
+    /// 1-based position among the table's data rows
+    pub row: usize,
+    pub column: usize,
+    #[serde(flatten)]
+    pub flags: StyleFlags,
+}
+
+/// A data row recognized as a total or subtotal.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotalRow {
+    /// 1-based position among the table's data rows
+    pub row: usize,
+    pub label: String,
+    /// "total" or "subtotal"
+    pub kind: String,
+    /// Signals that identified the row: label, bold, arithmetic
+    pub reasons: Vec<String>,
+}
+
+fn first_cell(row: ElementRef) -> Option<ElementRef> {
+    row.select(&CELL_SELECTOR).next()
+}
+
+/// Rough indentation of a cell in pixels, from leading spaces, inline styles and classes.
+fn indent_amount(cell: ElementRef) -> usize {
+    let text: String = cell.text().collect();
+    let leading_spaces = text
+        .chars()
+        .take_while(|c| matches!(c, '\u{a0}' | '\u{2002}' | '\u{2003}' | '\u{3000}'))
+        .count();
+
+    let style = cell.value().attr("style").unwrap_or("");
+    let style_px = INDENT_STYLE_RE
+        .captures(style)
+        .and_then(|c| c[1].parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let class = cell.value().attr("class").unwrap_or("");
+    let class_px = INDENT_CLASS_RE
+        .captures(class)
+        .and_then(|c| c[1].parse::<usize>().ok())
+        .map(|level| level * 20)
+        .unwrap_or(0);
+
+    leading_spaces * 5 + style_px + class_px
+}
+
+/// Background colour from an inline style or the legacy bgcolor attribute.
+fn background(element: ElementRef) -> Option<String> {
+    let style = element.value().attr("style").unwrap_or("");
+    BACKGROUND_RE
+        .captures(style)
+        .map(|c| c[1].trim().to_ascii_lowercase())
+        .or_else(|| {
+            element
+                .value()
+                .attr("bgcolor")
+                .map(|color| color.trim().to_ascii_lowercase())
+        })
+        .filter(|color| !color.is_empty())
+}
+
+/// Whether all of a cell's text is bold, via strong/b markup or inline font-weight.
+pub fn is_bold(cell: ElementRef) -> bool {
+    let style = cell
+        .value()
+        .attr("style")
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    if style.contains("font-weight:bold")
+        || style.contains("font-weight: bold")
+        || style.contains("font-weight:700")
+        || style.contains("font-weight: 700")
+    {
+        return true;
+    }
+
+    let text: String = cell.text().collect::<String>().trim().to_string();
+    let bold_text: String = cell
+        .select(&BOLD_SELECTOR)
+        .flat_map(|bold| bold.text())
+        .collect::<String>()
+        .trim()
+        .to_string();
+    !text.is_empty() && text == bold_text
+}
+
+/// Whether all of a cell's text is struck through, by markup or by an inline
+/// text-decoration on the cell or its row.
+fn is_struck(cell: ElementRef) -> bool {
+    let line_through = |element: ElementRef| {
+        element
+            .value()
+            .attr("style")
+            .is_some_and(|style| style.to_ascii_lowercase().contains("line-through"))
+    };
+    let row = cell.parent().and_then(ElementRef::wrap);
+    if line_through(cell) || row.is_some_and(line_through) {
+        return true;
+    }
+
+    let text: String = cell.text().collect::<String>().trim().to_string();
+    let struck_text: String = cell
+        .select(&STRUCK_SELECTOR)
+        .flat_map(|struck| struck.text())
+        .collect::<String>()
+        .trim()
+        .to_string();
+    !text.is_empty() && text == struck_text
+}
+
+/// Classes of a cell and the elements inside it that match `pattern`.
+fn matching_class(cell: ElementRef, pattern: &Regex) -> Option<String> {
+    std::iter::once(cell)
+        .chain(cell.select(&STYLED_SELECTOR))
+        .flat_map(|element| element.value().classes())
+        .find(|class| pattern.is_match(class))
+        .map(String::from)
+}
+
+/// Text colour of a cell from inline styles or `<font color>`, on the cell or inside it.
+fn text_color(cell: ElementRef) -> Option<String> {
+    std::iter::once(cell)
+        .chain(cell.select(&STYLED_SELECTOR))
+        .find_map(|element| {
+            let style = element.value().attr("style").unwrap_or("");
+            COLOR_RE
+                .captures(style)
+                .map(|c| c[1].to_string())
+                .or_else(|| element.value().attr("color").map(String::from))
+        })
+        .map(|color| color.trim().to_ascii_lowercase())
+        .filter(|color| !color.is_empty())
+}
+
+/// Presentation signals of a cell, or None for a plain one.
+pub fn style_flags(cell: ElementRef) -> Option<StyleFlags> {
+    let flags = StyleFlags {
+        bold: is_bold(cell),
+        strikethrough: is_struck(cell),
+        color: text_color(cell).or_else(|| matching_class(cell, &COLOR_CLASS_RE)),
+        background: background(cell).or_else(|| matching_class(cell, &BACKGROUND_CLASS_RE)),
+    };
+    let styled =
+        flags.bold || flags.strikethrough || flags.color.is_some() || flags.background.is_some();
+    styled.then_some(flags)
+}
+
+/// Lists the styled cells of a layout shaped like the table's data rows.
+pub fn list_cell_styles(layout: Vec<Vec<Option<StyleFlags>>>) -> Vec<CellStyle> {
+    layout
+        .into_iter()
+        .enumerate()
+        .flat_map(|(r, row)| {
+            row.into_iter()
+                .enumerate()
+                .filter_map(move |(column, flags)| {
+                    Some(CellStyle {
+                        row: r + 1,
+                        column,
+                        flags: flags?,
+                    })
+                })
+        })
+        .collect()
+}
+
+pub fn row_hints(rows: &[ElementRef], cleaned: &[Vec<String>]) -> Vec<RowHint> {
+    let amounts: Vec<usize> = rows
+        .iter()
+        .map(|row| first_cell(*row).map_or(0, indent_amount))
+        .collect();
+
+    // Rank distinct indent amounts so 2 and 4 non-breaking spaces become levels 1 and 2
+    let mut distinct = amounts.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+
+    rows.iter()
+        .zip(cleaned)
+        .zip(&amounts)
+        .map(|((row, cells), amount)| {
+            let tds: Vec<ElementRef> = row.select(&TD_SELECTOR).collect();
+            let rest_empty = cells.iter().skip(1).all(|cell| cell.is_empty());
+            let label = first_cell(*row).map(clean_cell_text).unwrap_or_default();
+            let has_label = !label.is_empty();
+            let spanning = tds.len() == 1
+                && tds[0]
+                    .value()
+                    .attr("colspan")
+                    .and_then(|span| span.parse::<usize>().ok())
+                    .is_some_and(|span| span > 1);
+            // Rows with only th cells carry no data in td-based extraction
+            let th_only = tds.is_empty() && has_label;
+            let non_empty: Vec<&ElementRef> = tds
+                .iter()
+                .filter(|td| td.text().any(|text| !text.trim().is_empty()))
+                .collect();
+            let bold = !non_empty.is_empty() && non_empty.iter().all(|td| is_bold(**td));
+
+            RowHint {
+                level: distinct.binary_search(amount).unwrap_or(0),
+                group_header: th_only
+                    || (has_label
+                        && rest_empty
+                        && (spanning || tds.first().is_some_and(|td| is_bold(*td)))),
+                label,
+                bold,
+                class: row
+                    .value()
+                    .attr("class")
+                    .map(str::trim)
+                    .filter(|class| !class.is_empty())
+                    .map(String::from),
+                background: background(*row).or_else(|| first_cell(*row).and_then(background)),
+            }
+        })
+        .collect()
+}
+
+pub fn is_hierarchical(hints: &[RowHint]) -> bool {
+    let indented = hints.iter().any(|hint| hint.level > 0);
+    let grouped = hints
+        .windows(2)
+        .any(|pair| pair[0].group_header && !pair[1].group_header);
+    indented || grouped
+}
+
+struct Ancestor {
+    level: usize,
+    label: String,
+    header: bool,
+}
+
+/// Prepends a `group` column holding each row's ancestor labels joined by " > ".
+/// Group header rows are folded into that column; labelled rows with values are kept.
+pub fn apply_row_groups(
+    headers: &mut Vec<String>,
+    rows: Vec<Vec<String>>,
+    hints: &[RowHint],
+) -> Vec<Vec<String>> {
+    if !headers.is_empty() {
+        headers.insert(0, "group".to_string());
+    }
+
+    let mut stack: Vec<Ancestor> = Vec::new();
+    let mut grouped = Vec::new();
+    for (i, mut row) in rows.into_iter().enumerate() {
+        let hint = &hints[i];
+        let label = hint.label.clone();
+
+        // Headers stay open for members at their own level; value rows only for deeper ones
+        while stack.last().is_some_and(|top| {
+            top.level > hint.level
+                || (top.level == hint.level && (!top.header || hint.group_header))
+        }) {
+            stack.pop();
+        }
+
+        if hint.group_header {
+            stack.push(Ancestor {
+                level: hint.level,
+                label,
+                header: true,
+            });
+            continue;
+        }
+
+        let group = stack
+            .iter()
+            .map(|ancestor| ancestor.label.as_str())
+            .collect::<Vec<_>>()
+            .join(" > ");
+        row.insert(0, group);
+        grouped.push(row);
+
+        let next_is_deeper = hints.get(i + 1).is_some_and(|next| next.level > hint.level);
+        if next_is_deeper {
+            stack.push(Ancestor {
+                level: hint.level,
+                label,
+                header: false,
+            });
+        }
+    }
+
+    grouped
+}
+
+/// Running column sums over the member rows of a block, so each total is
+/// checked without rescanning the rows above it.
+struct ColumnSums {
+    conventions: SignConventions,
+    rows: usize,
+    /// None once the column holds a non-numeric value
+    sums: Vec<Option<f64>>,
+}
+
+impl ColumnSums {
+    fn new(conventions: SignConventions) -> ColumnSums {
+        ColumnSums {
+            conventions,
+            rows: 0,
+            sums: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, row: &[String]) {
+        if self.sums.len() < row.len() {
+            self.sums.resize(row.len(), Some(0.0));
+        }
+        for (sum, cell) in self.sums.iter_mut().zip(row) {
+            if !cell.is_empty() {
+                *sum = sum
+                    .zip(self.conventions.parse_number(cell))
+                    .map(|(sum, value)| sum + value);
+            }
+        }
+        self.rows += 1;
+    }
+
+    /// Number of numeric columns in which `row` equals the column sum,
+    /// or 0 when any checkable column disagrees.
+    fn matches(&self, row: &[String]) -> usize {
+        if self.rows < 2 {
+            return 0;
+        }
+
+        let mut matching = 0;
+        for (index, cell) in row.iter().enumerate().skip(1) {
+            let Some(total) = self.conventions.parse_number(cell) else {
+                continue;
+            };
+            // Columns no member reaches count as empty, i.e. zero
+            let Some(sum) = self.sums.get(index).copied().unwrap_or(Some(0.0)) else {
+                continue;
+            };
+            if (sum - total).abs() <= total.abs() * 0.005 + 1e-9 {
+                matching += 1;
+            } else {
+                return 0;
+            }
+        }
+        matching
+    }
+}
+
+/// Classifies total and subtotal rows by label, bold styling and arithmetic
+/// consistency with the rows above them.
+pub fn classify_totals(
+    rows: &[Vec<String>],
+    hints: &[RowHint],
+    conventions: SignConventions,
+) -> Vec<Option<TotalRow>> {
+    let mut totals: Vec<Option<TotalRow>> = Vec::with_capacity(rows.len());
+    // Members since the last total, and members of the whole table
+    let mut block = ColumnSums::new(conventions);
+    let mut all = ColumnSums::new(conventions);
+    for (i, row) in rows.iter().enumerate() {
+        let label = row
+            .iter()
+            .find(|cell| !cell.is_empty())
+            .cloned()
+            .unwrap_or_default();
+        let hint = hints.get(i).cloned().unwrap_or_default();
+        let arithmetic = block.matches(row).max(all.matches(row));
+
+        let mut reasons = Vec::new();
+        if TOTAL_LABEL_RE.is_match(&label) {
+            reasons.push("label".to_string());
+        }
+        if hint.bold {
+            reasons.push("bold".to_string());
+        }
+        if arithmetic > 0 {
+            reasons.push("arithmetic".to_string());
+        }
+
+        let is_total = reasons.iter().any(|r| r == "label")
+            || (hint.bold && arithmetic > 0)
+            || arithmetic >= 2;
+        if is_total {
+            totals.push(Some(TotalRow {
+                row: i + 1,
+                kind: String::new(),
+                label,
+                reasons,
+            }));
+            block = ColumnSums::new(conventions);
+        } else {
+            totals.push(None);
+            if !hint.group_header {
+                block.add(row);
+                all.add(row);
+            }
+        }
+    }
+
+    // A total followed by another total is a subtotal
+    let last_total = totals.iter().rposition(Option::is_some);
+    for (i, total) in totals.iter_mut().enumerate() {
+        if let Some(total) = total {
+            total.kind = if SUBTOTAL_RE.is_match(&total.label) || Some(i) != last_total {
+                "subtotal".to_string()
+            } else {
+                "total".to_string()
+            };
+        }
+    }
+
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::extract;
+    use crate::{ExtractOptions, Table};
+
+    fn grouped(html: &str) -> Table {
+        let options = ExtractOptions {
+            row_groups: true,
+            ..ExtractOptions::default()
+        };
+        extract(html, options).remove(0)
+    }
+
+    #[test]
+    fn folds_group_header_rows_into_a_group_column() {
+        let table = grouped(
+            "<table><tr><th>Item</th><th>Cost</th></tr>
+             <tr><td><b>Food</b></td><td></td></tr>
+             <tr><td>Bread</td><td>2</td></tr>
+             <tr><td>Milk</td><td>1</td></tr>
+             <tr><td colspan=\"2\">Drink</td></tr>
+             <tr><td>Tea</td><td>3</td></tr></table>",
+        );
+        assert!(table.metadata.hierarchical_rows);
+        assert_eq!(table.data.headers, ["group", "Item", "Cost"]);
+        assert_eq!(
+            table.data.rows,
+            [
+                ["Food", "Bread", "2"],
+                ["Food", "Milk", "1"],
+                ["Drink", "Tea", "3"],
+            ]
+        );
+    }
+
+    #[test]
+    fn nests_indented_labels_under_the_row_above() {
+        let table = grouped(
+            "<table><tr><th>Account</th><th>Amount</th></tr>
+             <tr><td>Assets</td><td>10</td></tr>
+             <tr><td style=\"padding-left: 20px\">Cash</td><td>4</td></tr>
+             <tr><td style=\"padding-left: 40px\">Petty cash</td><td>1</td></tr>
+             <tr><td style=\"padding-left: 20px\">Stock</td><td>6</td></tr>
+             <tr><td>Liabilities</td><td>3</td></tr></table>",
+        );
+        let groups: Vec<&str> = table.data.rows.iter().map(|row| row[0].as_str()).collect();
+        assert_eq!(groups, ["", "Assets", "Assets > Cash", "Assets", ""]);
+    }
+
+    #[test]
+    fn leaves_flat_tables_ungrouped() {
+        let table = grouped(
+            "<table><tr><th>Name</th><th>Age</th></tr>
+             <tr><td>Ann</td><td>30</td></tr><tr><td>Bob</td><td>31</td></tr></table>",
+        );
+        assert!(!table.metadata.hierarchical_rows);
+        assert_eq!(table.data.headers, ["Name", "Age"]);
+    }
+
+    #[test]
+    fn needs_indentation_or_a_group_header_above_a_member() {
+        let hint = |level: usize, group_header: bool| RowHint {
+            level,
+            group_header,
+            ..RowHint::default()
+        };
+        assert!(is_hierarchical(&[hint(0, false), hint(1, false)]));
+        assert!(is_hierarchical(&[hint(0, true), hint(0, false)]));
+        assert!(!is_hierarchical(&[hint(0, false), hint(0, true)]));
+        assert!(!is_hierarchical(&[]));
+    }
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+    }
+
+    fn kinds(rows: &[Vec<String>], hints: &[RowHint]) -> Vec<Option<(String, Vec<String>)>> {
+        classify_totals(rows, hints, SignConventions::DEFAULT)
+            .into_iter()
+            .map(|total| total.map(|total| (total.kind, total.reasons)))
+            .collect()
+    }
+
+    fn total(kind: &str, reasons: &[&str]) -> Option<(String, Vec<String>)> {
+        Some((
+            kind.to_string(),
+            reasons.iter().map(|reason| reason.to_string()).collect(),
+        ))
+    }
+
+    #[test]
+    fn classifies_labelled_totals_and_subtotals() {
+        let rows = rows(&[
+            &["North", "2", "3"],
+            &["South", "4", "5"],
+            &["Subtotal", "6", "8"],
+            &["East", "1", "1"],
+            &["Grand total", "7", "9"],
+        ]);
+        assert_eq!(
+            kinds(&rows, &[]),
+            [
+                None,
+                None,
+                total("subtotal", &["label", "arithmetic"]),
+                None,
+                total("total", &["label", "arithmetic"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn classifies_unlabelled_rows_that_add_up() {
+        let rows = rows(&[&["A", "1", "10"], &["B", "2", "20"], &["", "3", "30"]]);
+        assert_eq!(
+            kinds(&rows, &[]),
+            [None, None, total("total", &["arithmetic"])]
+        );
+    }
+
+    #[test]
+    fn needs_more_than_one_matching_column_or_bold_type() {
+        let rows = rows(&[&["A", "1", "x"], &["B", "2", "y"], &["C", "3", "z"]]);
+        assert_eq!(kinds(&rows, &[]), [None, None, None]);
+
+        let bold = RowHint {
+            bold: true,
+            ..RowHint::default()
+        };
+        let hints = [RowHint::default(), RowHint::default(), bold];
+        assert_eq!(
+            kinds(&rows, &hints),
+            [None, None, total("total", &["bold", "arithmetic"])]
+        );
+    }
+
+    #[test]
+    fn doesnt_mistake_a_row_that_disagrees_for_a_total() {
+        let rows = rows(&[&["A", "1", "10"], &["B", "2", "20"], &["C", "3", "31"]]);
+        assert_eq!(kinds(&rows, &[]), [None, None, None]);
+    }
+}
@@ -0,0 +1,404 @@
+//! Semantic column types recorded in the schema: what a column's text means, such as
+//! emails or phone numbers, beyond whether it parses as a number.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::Table;
+use crate::error::TabexError;
+use crate::transform::mostly;
+use crate::types::parse_number;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SemanticType {
+    Email,
+    Url,
+    Phone,
+    /// Two-letter ISO 3166-1 codes such as "FR"
+    CountryCode,
+    Percentage,
+    /// Stock ticker symbols such as "AAPL", "BRK.B" or "NASDAQ:MSFT"
+    Ticker,
+    /// International Securities Identification Numbers, check digit verified
+    Isin,
+    /// International Bank Account Numbers, checksum verified
+    Iban,
+    /// ISO 4217 currency codes such as "EUR"
+    CurrencyCode,
+}
+
+impl SemanticType {
+    /// Whether a single cell has this type.
+    pub fn matches(self, cell: &str) -> bool {
+        match self {
+            SemanticType::Email => is_email(cell),
+            SemanticType::Url => is_url(cell),
+            SemanticType::Phone => is_phone(cell),
+            SemanticType::CountryCode => is_country_code(cell),
+            SemanticType::Percentage => is_percentage(cell),
+            SemanticType::Ticker => is_ticker(cell),
+            SemanticType::Isin => is_isin(cell),
+            SemanticType::Iban => is_iban(cell),
+            SemanticType::CurrencyCode => is_currency_code(cell),
+        }
+    }
+}
+
+static EMAIL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i:mailto:)?[^\s@<>()]+@[^\s@<>()]+\.[A-Za-z]{2,}$").unwrap());
+
+static URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i:https?://|www\.)[^\s/$.?#][^\s]*$").unwrap());
+
+/// Digits with the separators phone numbers are written with: "+44 20 7946 0958",
+/// "(555) 123-4567", "0033 1 42 68 53 00".
+static PHONE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?i:tel:)?\+?\(?\d[\d\s().\-]*\d(?:\s*(?i:ext\.?|x)\s*\d{1,5})?$").unwrap()
+});
+
+/// Dates and grouped numbers ("2023-01-15", "1.234.567") are digits and separators
+/// too, but aren't phone numbers.
+static NOT_PHONE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:\d{1,4}[-./]\d{1,2}[-./]\d{1,4}|\d{1,3}(?:[.,\s]\d{3})+)$").unwrap()
+});
+
+const COUNTRY_CODES: &str = "AD AE AF AG AI AL AM AO AQ AR AS AT AU AW AX AZ BA BB BD BE \
+    BF BG BH BI BJ BL BM BN BO BQ BR BS BT BV BW BY BZ CA CC CD CF CG CH CI CK CL CM CN \
+    CO CR CU CV CW CX CY CZ DE DJ DK DM DO DZ EC EE EG EH ER ES ET FI FJ FK FM FO FR GA \
+    GB GD GE GF GG GH GI GL GM GN GP GQ GR GS GT GU GW GY HK HM HN HR HT HU ID IE IL IM \
+    IN IO IQ IR IS IT JE JM JO JP KE KG KH KI KM KN KP KR KW KY KZ LA LB LC LI LK LR LS \
+    LT LU LV LY MA MC MD ME MF MG MH MK ML MM MN MO MP MQ MR MS MT MU MV MW MX MY MZ NA \
+    NC NE NF NG NI NL NO NP NR NU NZ OM PA PE PF PG PH PK PL PM PN PR PS PT PW PY QA RE \
+    RO RS RU RW SA SB SC SD SE SG SH SI SJ SK SL SM SN SO SR SS ST SV SX SY SZ TC TD TF \
+    TG TH TJ TK TL TM TN TO TR TT TV TW TZ UA UG UM US UY UZ VA VC VE VG VI VN VU WF WS \
+    YE YT ZA ZM ZW";
+
+pub fn is_email(cell: &str) -> bool {
+    EMAIL_RE.is_match(cell.trim())
+}
+
+pub fn is_url(cell: &str) -> bool {
+    URL_RE.is_match(cell.trim())
+}
+
+/// A phone number has 7 to 15 digits (E.164's limit) and is written as one: with a
+/// leading "+" or broken up by separators, so plain numbers such as IDs don't count.
+pub fn is_phone(cell: &str) -> bool {
+    let cell = cell.trim();
+    if !PHONE_RE.is_match(cell)
+        || NOT_PHONE_RE.is_match(cell)
+        || cell.chars().all(|c| c.is_ascii_digit())
+    {
+        return false;
+    }
+    // Digits of the number itself, without "tel:" or an extension
+    let number = cell
+        .get(..4)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("tel:"))
+        .map_or(cell, |_| &cell[4..]);
+    let number = number
+        .split(|c: char| c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or(number);
+    let digits = number.chars().filter(char::is_ascii_digit).count();
+    (7..=15).contains(&digits)
+}
+
+pub fn is_country_code(cell: &str) -> bool {
+    let cell = cell.trim();
+    cell.len() == 2 && COUNTRY_CODES.split_whitespace().any(|code| code == cell)
+}
+
+pub fn is_percentage(cell: &str) -> bool {
+    let cell = cell.trim();
+    cell.ends_with('%') && parse_number(cell).is_some()
+}
+
+/// Three-letter ISO 4217 codes in circulation
+const CURRENCY_CODES: &str = "AED AFN ALL AMD ANG AOA ARS AUD AWG AZN BAM BBD BDT BGN BHD \
+    BIF BMD BND BOB BRL BSD BTN BWP BYN BZD CAD CDF CHF CLP CNY COP CRC CUP CVE CZK DJF \
+    DKK DOP DZD EGP ERN ETB EUR FJD FKP GBP GEL GHS GIP GMD GNF GTQ GYD HKD HNL HTG HUF \
+    IDR ILS INR IQD IRR ISK JMD JOD JPY KES KGS KHR KMF KPW KRW KWD KYD KZT LAK LBP LKR \
+    LRD LSL LYD MAD MDL MGA MKD MMK MNT MOP MRU MUR MVR MWK MXN MYR MZN NAD NGN NIO NOK \
+    NPR NZD OMR PAB PEN PGK PHP PKR PLN PYG QAR RON RSD RUB RWF SAR SBD SCR SDG SEK SGD \
+    SHP SLE SOS SRD SSP STN SVC SYP SZL THB TJS TMT TND TOP TRY TTD TWD TZS UAH UGX USD \
+    UYU UZS VES VND VUV WST XAF XCD XOF XPF YER ZAR ZMW ZWL";
+
+/// An optional exchange prefix, one to five letters, and an optional class or market
+/// suffix: "AAPL", "BRK.B", "RDS-A", "NYSE:IBM", "VOD.L".
+static TICKER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:[A-Z]{2,10}:)?[A-Z]{1,5}(?:[.\-][A-Z]{1,2})?$").unwrap());
+
+static ISIN_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Z]{2}[A-Z0-9]{9}[0-9]$").unwrap());
+
+static IBAN_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Z]{2}[0-9]{2}[A-Z0-9]{11,30}$").unwrap());
+
+pub fn is_currency_code(cell: &str) -> bool {
+    let cell = cell.trim();
+    cell.len() == 3 && CURRENCY_CODES.split_whitespace().any(|code| code == cell)
+}
+
+pub fn is_ticker(cell: &str) -> bool {
+    TICKER_RE.is_match(cell.trim())
+}
+
+/// Digits for a check computation: letters count as two digits, A being 10.
+fn expand_letters(text: &str) -> String {
+    text.chars()
+        .map(|c| match c.to_digit(36) {
+            Some(value) if c.is_ascii_alphabetic() => value.to_string(),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+/// An ISIN's last digit is the Luhn check digit of its letters expanded to digits.
+pub fn is_isin(cell: &str) -> bool {
+    let cell = cell.trim();
+    if !ISIN_RE.is_match(cell) {
+        return false;
+    }
+    let sum: u32 = expand_letters(cell)
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, digit)| match (i % 2 == 1, digit * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => digit,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// An IBAN, written with or without spaces, is valid when its first four characters
+/// moved to the end and read as a number leave 1 modulo 97.
+pub fn is_iban(cell: &str) -> bool {
+    let compact: String = cell.split_whitespace().collect();
+    if !IBAN_RE.is_match(&compact) || !is_country_code(&compact[..2]) {
+        return false;
+    }
+    let rearranged = expand_letters(&format!("{}{}", &compact[4..], &compact[..4]));
+    let remainder = rearranged
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .fold(0, |remainder, digit| (remainder * 10 + digit) % 97);
+    remainder == 1
+}
+
+/// Whether every non-empty cell of a column, of which there is at least one, passes
+/// `test`.
+fn every(table: &Table, index: usize, test: impl Fn(&str) -> bool) -> bool {
+    let mut cells = table
+        .data
+        .rows
+        .iter()
+        .filter_map(|row| row.get(index))
+        .filter(|cell| !cell.trim().is_empty())
+        .peekable();
+    cells.peek().is_some() && cells.all(|cell| test(cell))
+}
+
+/// The semantic type of the column at `index`, when at least half its non-empty cells
+/// have it. Country codes need every cell to be one, as short uppercase words such as
+/// "IT" or "NO" could be anything.
+pub fn detect(table: &Table, index: usize) -> Option<SemanticType> {
+    [
+        SemanticType::Percentage,
+        SemanticType::Email,
+        SemanticType::Url,
+        SemanticType::Phone,
+    ]
+    .into_iter()
+    .find(|semantic| mostly(table, index, |cell| semantic.matches(cell)))
+    .or_else(|| every(table, index, is_country_code).then_some(SemanticType::CountryCode))
+}
+
+/// A set of extra semantic types chosen with `--semantic-pack`, for types too
+/// specialized to look for in every table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SemanticPack {
+    /// Tickers, ISINs, IBANs and currency codes
+    Finance,
+}
+
+impl SemanticPack {
+    /// Parses a comma-separated list of packs.
+    pub fn parse_list(list: &str) -> Result<Vec<SemanticPack>, TabexError> {
+        list.split(',')
+            .map(|name| match name.trim() {
+                "finance" => Ok(SemanticPack::Finance),
+                other => Err(TabexError::Usage(format!(
+                    "Unknown semantic pack {} (expected finance)",
+                    other
+                ))),
+            })
+            .collect()
+    }
+
+    /// The pack's type for the column at `index`. ISINs and IBANs carry check digits,
+    /// so half the cells passing is enough; currency codes and tickers need every cell,
+    /// and tickers also a "Ticker" or "Symbol" header or an exchange prefix, since a
+    /// column of short uppercase words is usually something else.
+    fn detect(self, table: &Table, index: usize) -> Option<SemanticType> {
+        match self {
+            SemanticPack::Finance => {
+                if let Some(semantic) = [SemanticType::Isin, SemanticType::Iban]
+                    .into_iter()
+                    .find(|semantic| mostly(table, index, |cell| semantic.matches(cell)))
+                {
+                    return Some(semantic);
+                }
+                if every(table, index, is_currency_code) {
+                    return Some(SemanticType::CurrencyCode);
+                }
+                let header = table
+                    .data
+                    .headers
+                    .get(index)
+                    .map(|header| header.to_lowercase())
+                    .unwrap_or_default();
+                let named = header.contains("ticker") || header.contains("symbol");
+                (every(table, index, is_ticker)
+                    && (named || every(table, index, |cell| cell.contains(':'))))
+                .then_some(SemanticType::Ticker)
+            }
+        }
+    }
+}
+
+/// Tags the schema of columns the packs recognize, leaving columns that already have a
+/// semantic type alone.
+pub fn apply_packs(table: &mut Table, packs: &[SemanticPack]) {
+    for index in 0..table.metadata.schema.len() {
+        if table.metadata.schema[index].semantic_type.is_some() {
+            continue;
+        }
+        let detected = packs.iter().find_map(|pack| pack.detect(table, index));
+        table.metadata.schema[index].semantic_type = detected;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    /// A one-column table with `cells` under `header`.
+    fn column(header: &str, cells: &[&str]) -> Table {
+        let rows: String = cells
+            .iter()
+            .map(|cell| format!("<tr><td>{}</td></tr>", cell))
+            .collect();
+        let html = format!("<table><tr><th>{}</th></tr>{}</table>", header, rows);
+        extract(&html, ExtractOptions::default()).remove(0)
+    }
+
+    #[test]
+    fn recognizes_emails_and_urls() {
+        assert!(is_email("ann@example.com"));
+        assert!(is_email("mailto:Ann.Lee+news@mail.example.co.uk"));
+        assert!(!is_email("ann@localhost"));
+        assert!(!is_email("ann at example.com"));
+        assert!(is_url("https://example.com/a?b=1"));
+        assert!(is_url("www.example.com"));
+        assert!(!is_url("example.com"));
+        assert!(!is_url("https:// example.com"));
+    }
+
+    #[test]
+    fn recognizes_phone_numbers_but_not_dates_or_ids() {
+        for cell in [
+            "+44 20 7946 0958",
+            "(555) 123-4567",
+            "0033 1 42 68 53 00",
+            "tel:+1-555-123-4567",
+            "555-123-4567 ext. 89",
+        ] {
+            assert!(is_phone(cell), "{:?}", cell);
+        }
+        for cell in [
+            "2023-01-15",
+            "1.234.567",
+            "1234567",
+            "123-45",
+            "+1 234 567 890 123 456 7",
+        ] {
+            assert!(!is_phone(cell), "{:?}", cell);
+        }
+    }
+
+    #[test]
+    fn recognizes_country_codes_and_percentages() {
+        assert!(is_country_code("FR"));
+        assert!(!is_country_code("fr"));
+        assert!(!is_country_code("XX"));
+        assert!(is_percentage("12.5%"));
+        assert!(is_percentage("-3 %"));
+        assert!(!is_percentage("12.5"));
+        assert!(!is_percentage("n/a%"));
+    }
+
+    #[test]
+    fn types_columns_by_most_of_their_cells() {
+        let emails = column("Contact", &["a@example.com", "b@example.com", "ask"]);
+        assert_eq!(detect(&emails, 0), Some(SemanticType::Email));
+        let countries = column("Country", &["FR", "DE", ""]);
+        assert_eq!(detect(&countries, 0), Some(SemanticType::CountryCode));
+        let words = column("Answer", &["NO", "OK"]);
+        assert_eq!(detect(&words, 0), None);
+        let empty = column("Notes", &["", ""]);
+        assert_eq!(detect(&empty, 0), None);
+    }
+
+    #[test]
+    fn verifies_isin_and_iban_check_digits() {
+        assert!(is_isin("US0378331005"));
+        assert!(is_isin("GB0002634946"));
+        assert!(!is_isin("US0378331006"));
+        assert!(!is_isin("US037833100"));
+        assert!(is_iban("GB82 WEST 1234 5698 7654 32"));
+        assert!(is_iban("DE89370400440532013000"));
+        assert!(!is_iban("GB82 WEST 1234 5698 7654 33"));
+        assert!(!is_iban("XX82WEST12345698765432"));
+    }
+
+    #[test]
+    fn recognizes_tickers_and_currency_codes() {
+        for cell in ["AAPL", "BRK.B", "RDS-A", "NYSE:IBM", "VOD.L"] {
+            assert!(is_ticker(cell), "{:?}", cell);
+        }
+        for cell in ["aapl", "TOOLONG", "BRK.BBB", "12AB"] {
+            assert!(!is_ticker(cell), "{:?}", cell);
+        }
+        assert!(is_currency_code("EUR"));
+        assert!(!is_currency_code("EURO"));
+        assert!(!is_currency_code("ABC"));
+    }
+
+    #[test]
+    fn needs_a_header_or_exchange_prefix_to_call_a_column_tickers() {
+        let finance = SemanticPack::Finance;
+        let named = column("Symbol", &["AAPL", "MSFT"]);
+        assert_eq!(finance.detect(&named, 0), Some(SemanticType::Ticker));
+        let prefixed = column("Stock", &["NASDAQ:AAPL", "NYSE:IBM"]);
+        assert_eq!(finance.detect(&prefixed, 0), Some(SemanticType::Ticker));
+        let words = column("Grade", &["A", "B"]);
+        assert_eq!(finance.detect(&words, 0), None);
+        let currencies = column("Currency", &["EUR", "USD"]);
+        assert_eq!(
+            finance.detect(&currencies, 0),
+            Some(SemanticType::CurrencyCode)
+        );
+        let isins = column("ISIN", &["US0378331005", "pending"]);
+        assert_eq!(finance.detect(&isins, 0), Some(SemanticType::Isin));
+        assert!(SemanticPack::parse_list("finance, crypto").is_err());
+    }
+}
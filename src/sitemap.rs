@@ -0,0 +1,245 @@
+//! `--sitemap`: lists the pages of a site from its `sitemap.xml`, following sitemap
+//! indexes to the sitemaps they name.
+
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::error::TabexError;
+use crate::events::{Events, Record};
+use crate::fetch::HostFilter;
+
+/// Indexes should only name sitemaps, but nothing stops a site nesting them; chains
+/// deeper than this are not followed.
+const MAX_DEPTH: usize = 3;
+
+/// The root element, with the prefix the sitemap namespace is bound to if any.
+static ROOT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(?:(\w+):)?(urlset|sitemapindex)\b").unwrap());
+
+/// The text of a `<loc>`, with CDATA unwrapped or XML's predefined entities decoded.
+fn loc_text(raw: &str) -> String {
+    if let Some(text) = raw
+        .strip_prefix("<![CDATA[")
+        .and_then(|inner| inner.strip_suffix("]]>"))
+    {
+        return text.trim().to_string();
+    }
+    raw.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Whether a sitemap is an index naming other sitemaps, and the URLs it lists. Only
+/// `<loc>`s in the sitemap's own namespace count, so the `<image:loc>` of an image
+/// extension isn't taken for a page.
+pub fn parse_sitemap(xml: &str) -> (bool, Vec<String>) {
+    let root = ROOT_RE.captures(xml);
+    let index = root.as_ref().is_some_and(|root| &root[2] == "sitemapindex");
+    let prefix = root
+        .as_ref()
+        .and_then(|root| root.get(1))
+        .map(|prefix| format!("{}:", prefix.as_str()))
+        .unwrap_or_default();
+    let loc_re = Regex::new(&format!(
+        r"(?s)<{0}loc>\s*(.*?)\s*</{0}loc>",
+        regex::escape(&prefix)
+    ))
+    .unwrap();
+    let locs = loc_re
+        .captures_iter(xml)
+        .map(|captures| loc_text(&captures[1]))
+        .filter(|loc| !loc.is_empty())
+        .collect();
+    (index, locs)
+}
+
+fn fetch_sitemap(
+    client: &reqwest::blocking::Client,
+    host_filter: &HostFilter,
+    url: &str,
+    events: &Events,
+) -> Result<String, TabexError> {
+    if reqwest::Url::parse(url).is_ok_and(|parsed| !host_filter.allows(&parsed)) {
+        return Err(TabexError::HostNotAllowed {
+            url: url.to_string(),
+        });
+    }
+    events.status(
+        "sitemap",
+        &format!("Reading sitemap {}", url),
+        serde_json::json!({ "url": url }),
+    );
+    events.record(Record::Request { url });
+    let resp = client.get(url).send()?;
+    if !resp.status().is_success() {
+        return Err(TabexError::Fetch {
+            status: resp.status().as_u16(),
+            url: url.to_string(),
+        });
+    }
+    Ok(resp.text()?)
+}
+
+/// The pages listed by the sitemap at `url`, in sitemap order, keeping those `filter`
+/// matches. Sitemaps named by an index that can't be read are skipped with a warning;
+/// the first sitemap must load.
+pub fn page_urls(
+    client: &reqwest::blocking::Client,
+    host_filter: &HostFilter,
+    url: &str,
+    filter: Option<&Regex>,
+    events: &Events,
+) -> Result<Vec<String>, TabexError> {
+    let mut pages = Vec::new();
+    let mut visited = HashSet::from([url.to_string()]);
+    let mut pending = vec![(url.to_string(), 0)];
+    while let Some((sitemap, depth)) = pending.pop() {
+        let xml = match fetch_sitemap(client, host_filter, &sitemap, events) {
+            Ok(xml) => xml,
+            Err(e) if sitemap == url => return Err(e),
+            Err(e) => {
+                events.warning(
+                    "sitemap_skipped",
+                    &format!("skipping sitemap {}: {}", sitemap, e),
+                    serde_json::json!({ "url": sitemap, "error": e.to_string() }),
+                );
+                continue;
+            }
+        };
+        let (index, locs) = parse_sitemap(&xml);
+        let locs = web_locs(&sitemap, locs, events).into_iter();
+        if !index {
+            pages.extend(locs.filter(|loc| filter.is_none_or(|filter| filter.is_match(loc))));
+            continue;
+        }
+        if depth >= MAX_DEPTH {
+            events.warning(
+                "sitemap_skipped",
+                &format!("not following sitemap index {}: nested too deeply", sitemap),
+                serde_json::json!({ "url": sitemap }),
+            );
+            continue;
+        }
+        // Pushed in reverse so children are read in the order the index lists them
+        for loc in locs.rev() {
+            if visited.insert(loc.clone()) {
+                pending.push((loc, depth + 1));
+            }
+        }
+    }
+    if pages.is_empty() {
+        return Err(TabexError::EmptySitemap {
+            url: url.to_string(),
+        });
+    }
+    Ok(pages)
+}
+
+/// The locs that are http(s) URLs, read against `sitemap` when relative. Others are
+/// skipped with a warning, since a remote sitemap mustn't point the run at local files.
+fn web_locs(sitemap: &str, locs: Vec<String>, events: &Events) -> Vec<String> {
+    let base = reqwest::Url::parse(sitemap).ok();
+    locs.into_iter()
+        .filter_map(|loc| {
+            let url = match &base {
+                Some(base) => base.join(&loc),
+                None => reqwest::Url::parse(&loc),
+            };
+            match url {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => Some(url.into()),
+                _ => {
+                    events.warning(
+                        "page_skipped",
+                        &format!("skipping sitemap entry {}: not an http(s) URL", loc),
+                        serde_json::json!({ "url": loc, "sitemap": sitemap }),
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_the_locs_of_a_urlset() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <url><loc>https://example.com/a?x=1&amp;y=2</loc><lastmod>2024-01-01</lastmod></url>
+              <url><loc>
+                https://example.com/b
+              </loc></url>
+              <url><loc><![CDATA[ https://example.com/c?p=<1> ]]></loc></url>
+              <url><loc></loc></url>
+              <url><image:image><image:loc>https://example.com/i.png</image:loc></image:image></url>
+            </urlset>"#;
+        assert_eq!(
+            parse_sitemap(xml),
+            (
+                false,
+                vec![
+                    "https://example.com/a?x=1&y=2".to_string(),
+                    "https://example.com/b".to_string(),
+                    "https://example.com/c?p=<1>".to_string(),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn recognizes_sitemap_indexes_with_or_without_a_prefix() {
+        let (index, locs) = parse_sitemap(
+            "<sitemapindex><sitemap><loc>/sitemap-1.xml</loc></sitemap></sitemapindex>",
+        );
+        assert!(index);
+        assert_eq!(locs, ["/sitemap-1.xml"]);
+        let (index, locs) = parse_sitemap(
+            "<sm:sitemapindex xmlns:sm=\"x\"><sm:sitemap><sm:loc>https://example.com/s.xml</sm:loc></sm:sitemap></sm:sitemapindex>",
+        );
+        assert!(index);
+        assert_eq!(locs, ["https://example.com/s.xml"]);
+        // Only the index's own namespace names sitemaps
+        let (_, locs) = parse_sitemap(
+            "<sm:sitemapindex xmlns:sm=\"x\"><loc>https://example.com/other</loc></sm:sitemapindex>",
+        );
+        assert!(locs.is_empty());
+        assert_eq!(
+            parse_sitemap("<html>not a sitemap</html>"),
+            (false, Vec::new())
+        );
+    }
+
+    #[test]
+    fn decodes_entities_once() {
+        assert_eq!(loc_text("a&amp;lt;b"), "a&lt;b");
+        assert_eq!(loc_text("&quot;&apos;&gt;"), "\"'>");
+    }
+
+    #[test]
+    fn skips_locs_that_are_not_web_urls() {
+        let (events, reported) = crate::events::tests::collect();
+        let locs = [
+            "https://example.com/a",
+            "/b",
+            "file:///etc/passwd",
+            "C:/Users/x/secret.html",
+            "ftp://example.com/c",
+        ];
+        assert_eq!(
+            web_locs(
+                "https://example.com/sitemap.xml",
+                locs.iter().map(|loc| loc.to_string()).collect(),
+                &events,
+            ),
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        assert_eq!(reported.events(), ["warning page_skipped"; 3]);
+    }
+}
@@ -0,0 +1,401 @@
+use std::io::Write;
+
+use crate::error::TabexError;
+use crate::output::record_keys;
+use crate::plugin::Sink;
+use crate::types::{DeclaredType, SignConventions, Value, format_number, money_type};
+use crate::units::declared_type;
+use crate::{ExtractionResult, PageOutcome, Table};
+
+/// Rows per INSERT statement.
+const INSERT_BATCH: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Postgres,
+    Mysql,
+    Sqlite,
+}
+
+impl Dialect {
+    pub fn parse(name: &str) -> Result<Dialect, TabexError> {
+        match name {
+            "postgres" => Ok(Dialect::Postgres),
+            "mysql" => Ok(Dialect::Mysql),
+            "sqlite" => Ok(Dialect::Sqlite),
+            other => Err(TabexError::Usage(format!(
+                "Unknown SQL dialect {} (expected postgres, mysql or sqlite)",
+                other
+            ))),
+        }
+    }
+
+    fn quote_identifier(self, name: &str) -> String {
+        match self {
+            Dialect::Mysql => format!("`{}`", name.replace('`', "``")),
+            Dialect::Postgres | Dialect::Sqlite => format!("\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+
+    fn quote_string(self, text: &str) -> String {
+        let escaped = text.replace('\'', "''");
+        // MySQL treats backslashes in string literals as escapes by default
+        match self {
+            Dialect::Mysql => format!("'{}'", escaped.replace('\\', "\\\\")),
+            Dialect::Postgres | Dialect::Sqlite => format!("'{}'", escaped),
+        }
+    }
+
+    fn type_name(self, column: ColumnType) -> String {
+        match (self, column) {
+            (Dialect::Sqlite, ColumnType::Integer) => "INTEGER".to_string(),
+            (_, ColumnType::Integer) => "BIGINT".to_string(),
+            (Dialect::Postgres, ColumnType::Real) => "DOUBLE PRECISION".to_string(),
+            (Dialect::Mysql, ColumnType::Real) => "DOUBLE".to_string(),
+            (Dialect::Sqlite, ColumnType::Real) => "REAL".to_string(),
+            (Dialect::Postgres | Dialect::Sqlite, ColumnType::Decimal(precision, scale)) => {
+                format!("NUMERIC({}, {})", precision, scale)
+            }
+            (Dialect::Mysql, ColumnType::Decimal(precision, scale)) => {
+                format!("DECIMAL({}, {})", precision, scale)
+            }
+            (_, ColumnType::Text) => "TEXT".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ColumnType {
+    Integer,
+    Real,
+    /// Precision and scale of a --types decimal or a monetary column
+    Decimal(u32, u32),
+    Text,
+}
+
+impl From<DeclaredType> for ColumnType {
+    fn from(declared: DeclaredType) -> ColumnType {
+        match declared {
+            DeclaredType::String => ColumnType::Text,
+            DeclaredType::Int => ColumnType::Integer,
+            DeclaredType::Float => ColumnType::Real,
+            DeclaredType::Decimal { precision, scale } => ColumnType::Decimal(precision, scale),
+        }
+    }
+}
+
+/// The column's --types override, else the narrowest type holding every non-empty
+/// cell. Amounts of money are exact decimals rather than floating point.
+pub(crate) fn infer_type(table: &Table, index: usize) -> ColumnType {
+    if let Some(declared) = declared_type(table, index) {
+        return declared.into();
+    }
+    let cells = || {
+        table
+            .data
+            .rows
+            .iter()
+            .map(|row| row.get(index).map(String::as_str).unwrap_or(""))
+    };
+    let conventions = table.metadata.sign_conventions;
+    if let Some(money) = money_type(cells(), conventions) {
+        return money.into();
+    }
+    let mut column_type = ColumnType::Integer;
+    for cell in cells() {
+        match Value::from_cell(cell, conventions) {
+            Value::Null => {}
+            Value::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {}
+            Value::Number(_) => column_type = ColumnType::Real,
+            Value::Text(_) => return ColumnType::Text,
+        }
+    }
+    column_type
+}
+
+/// A cell of a --types or monetary column as a literal; NULL where the value doesn't fit the type.
+fn declared_value(
+    dialect: Dialect,
+    declared: DeclaredType,
+    cell: &str,
+    conventions: SignConventions,
+) -> String {
+    let cell = cell.trim();
+    match declared {
+        _ if cell.is_empty() => "NULL".to_string(),
+        DeclaredType::String => dialect.quote_string(cell),
+        DeclaredType::Decimal { scale, .. } => declared
+            .decimal(cell, conventions)
+            .map_or("NULL".to_string(), |decimal| {
+                format!("{:.*}", scale as usize, decimal)
+            }),
+        DeclaredType::Int | DeclaredType::Float => declared
+            .number(cell, conventions)
+            .map_or("NULL".to_string(), format_number),
+    }
+}
+
+/// SQL name for a table: its templated name, else its caption or preceding heading
+/// as an identifier, else `table_<position>`.
+fn table_name(table: &Table) -> String {
+    if let Some(name) = &table.metadata.name {
+        return name.clone();
+    }
+    let label = table
+        .metadata
+        .caption
+        .as_deref()
+        .or(table.metadata.preceding_heading.as_deref())
+        .unwrap_or("");
+    let name = record_keys(&[label.to_string()], 1).remove(0);
+    if label.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("table_{}", table.metadata.position)
+    } else {
+        name
+    }
+}
+
+/// `name`, or when an earlier table took it, `name_<position>`, numbered further if
+/// that is taken too, as with tables at the same position of different pages.
+fn unique_name(name: String, position: usize, names: &[String]) -> String {
+    if !names.contains(&name) {
+        return name;
+    }
+    let name = format!("{}_{}", name, position);
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while names.contains(&candidate) {
+        candidate = format!("{}_{}", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// `CREATE TABLE` statements with inferred column types, followed by batched `INSERT`s.
+pub struct SqlSink {
+    pub dialect: Dialect,
+}
+
+impl SqlSink {
+    /// Writes the statements for one page's tables; `names` holds the table names
+    /// already created in the script.
+    fn write_tables(
+        &self,
+        result: &ExtractionResult,
+        names: &mut Vec<String>,
+        out: &mut dyn Write,
+    ) -> Result<(), TabexError> {
+        let dialect = self.dialect;
+        writeln!(out, "-- Tables extracted from {}", result.page.url)?;
+
+        for table in &result.tables {
+            let width = table
+                .data
+                .rows
+                .iter()
+                .map(Vec::len)
+                .chain([table.data.headers.len()])
+                .max()
+                .unwrap_or(0);
+            if width == 0 {
+                continue;
+            }
+
+            let name = unique_name(table_name(table), table.metadata.position, names);
+            names.push(name.clone());
+            let name = dialect.quote_identifier(&name);
+
+            let columns = record_keys(&table.data.headers, width);
+            let types: Vec<ColumnType> = (0..width).map(|i| infer_type(table, i)).collect();
+            // Decimal columns are written exactly, whether declared or inferred
+            let declared: Vec<Option<DeclaredType>> = (0..width)
+                .map(|i| match types[i] {
+                    ColumnType::Decimal(precision, scale) => {
+                        Some(DeclaredType::Decimal { precision, scale })
+                    }
+                    _ => declared_type(table, i),
+                })
+                .collect();
+
+            writeln!(out)?;
+            writeln!(out, "CREATE TABLE {} (", name)?;
+            let definitions: Vec<String> = columns
+                .iter()
+                .zip(&types)
+                .map(|(column, column_type)| {
+                    format!(
+                        "    {} {}",
+                        dialect.quote_identifier(column),
+                        dialect.type_name(*column_type)
+                    )
+                })
+                .collect();
+            writeln!(out, "{}", definitions.join(",\n"))?;
+            writeln!(out, ");")?;
+
+            let column_list = columns
+                .iter()
+                .map(|column| dialect.quote_identifier(column))
+                .collect::<Vec<_>>()
+                .join(", ");
+            for batch in table.data.rows.chunks(INSERT_BATCH) {
+                writeln!(out, "INSERT INTO {} ({}) VALUES", name, column_list)?;
+                let values: Vec<String> = batch
+                    .iter()
+                    .map(|row| {
+                        let cells: Vec<String> = (0..width)
+                            .map(|i| {
+                                let cell = row.get(i).map(String::as_str).unwrap_or("");
+                                if let Some(declared) = declared[i] {
+                                    return declared_value(
+                                        dialect,
+                                        declared,
+                                        cell,
+                                        table.metadata.sign_conventions,
+                                    );
+                                }
+                                let value = Value::from_cell(cell, table.metadata.sign_conventions);
+                                match (types[i], value) {
+                                    (_, Value::Null) => "NULL".to_string(),
+                                    (ColumnType::Text, _) => dialect.quote_string(cell.trim()),
+                                    (_, Value::Number(number)) => format_number(number),
+                                    (_, Value::Text(text)) => dialect.quote_string(&text),
+                                }
+                            })
+                            .collect();
+                        format!("    ({})", cells.join(", "))
+                    })
+                    .collect();
+                writeln!(out, "{};", values.join(",\n"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Sink for SqlSink {
+    fn name(&self) -> &str {
+        "sql"
+    }
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError> {
+        self.write_tables(result, &mut Vec::new(), out)
+    }
+
+    /// One script for every page, with table names kept unique across pages.
+    fn write_pages(&self, pages: &[PageOutcome], out: &mut dyn Write) -> Result<(), TabexError> {
+        let mut names = Vec::new();
+        for (i, result) in pages
+            .iter()
+            .filter_map(|page| page.result.as_ref())
+            .enumerate()
+        {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            self.write_tables(result, &mut names, out)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    fn table(html: &str) -> Table {
+        extract(html, ExtractOptions::default()).remove(0)
+    }
+
+    #[test]
+    fn quotes_identifiers_per_dialect() {
+        assert_eq!(Dialect::Postgres.quote_identifier("a\"b"), "\"a\"\"b\"");
+        assert_eq!(Dialect::Sqlite.quote_identifier("order"), "\"order\"");
+        assert_eq!(Dialect::Mysql.quote_identifier("a`b"), "`a``b`");
+        assert_eq!(Dialect::Mysql.quote_identifier("a\"b"), "`a\"b`");
+    }
+
+    #[test]
+    fn quotes_strings_per_dialect() {
+        assert_eq!(Dialect::Postgres.quote_string("O'Brien"), "'O''Brien'");
+        assert_eq!(Dialect::Sqlite.quote_string("C:\\temp"), "'C:\\temp'");
+        assert_eq!(Dialect::Mysql.quote_string("O'Brien"), "'O''Brien'");
+        assert_eq!(
+            Dialect::Mysql.quote_string("C:\\temp\\'"),
+            "'C:\\\\temp\\\\'''"
+        );
+    }
+
+    #[test]
+    fn infers_the_narrowest_column_type() {
+        let table = table(
+            "<table><tr><th>Id</th><th>Score</th><th>Name</th><th>Big</th></tr>
+             <tr><td>1</td><td>2.5</td><td>Ann</td><td>1e16</td></tr>
+             <tr><td></td><td>3</td><td>7</td><td>2</td></tr></table>",
+        );
+        let types: Vec<ColumnType> = (0..4).map(|i| infer_type(&table, i)).collect();
+        assert_eq!(
+            types,
+            [
+                ColumnType::Integer,
+                ColumnType::Real,
+                ColumnType::Text,
+                ColumnType::Real
+            ]
+        );
+        assert_eq!(Dialect::Sqlite.type_name(ColumnType::Integer), "INTEGER");
+        assert_eq!(Dialect::Mysql.type_name(ColumnType::Integer), "BIGINT");
+        assert_eq!(
+            Dialect::Postgres.type_name(ColumnType::Real),
+            "DOUBLE PRECISION"
+        );
+    }
+
+    #[test]
+    fn names_tables_from_captions_or_positions() {
+        let named = table("<table><caption>Top Scorers</caption><tr><td>1</td></tr></table>");
+        assert_eq!(table_name(&named), "top_scorers");
+        let numeric = table("<table><caption>2024</caption><tr><td>1</td></tr></table>");
+        assert_eq!(table_name(&numeric), "table_1");
+        let names = ["stats".to_string(), "stats_3".to_string()];
+        assert_eq!(unique_name("stats".to_string(), 3, &names), "stats_3_2");
+        assert_eq!(unique_name("other".to_string(), 3, &names), "other");
+    }
+
+    #[test]
+    fn writes_a_create_and_insert_script() {
+        let result = ExtractionResult {
+            page: crate::Extractor::new(ExtractOptions::default())
+                .page_metadata(&scraper::Html::parse_document(""), "https://example.com/"),
+            tables: vec![table(
+                "<table><caption>Scores</caption><tr><th>Name</th><th>Goals</th></tr>
+                 <tr><td>O'Neil</td><td>3</td></tr><tr><td>Bo</td><td></td></tr></table>",
+            )],
+            relationships: None,
+            timed_out: false,
+            attempts: None,
+            extraction_time_ms: 0,
+        };
+        let mut out = Vec::new();
+        SqlSink {
+            dialect: Dialect::Postgres,
+        }
+        .write(&result, &mut out)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "-- Tables extracted from https://example.com/
+
+CREATE TABLE \"scores\" (
+    \"name\" TEXT,
+    \"goals\" BIGINT
+);
+INSERT INTO \"scores\" (\"name\", \"goals\") VALUES
+    ('O''Neil', 3),
+    ('Bo', NULL);
+"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::LazyLock;
+
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::clean_cell_text;
+use crate::grid;
+
+static ABBR_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("abbr[title]").unwrap());
+
+/// The spelled-out meaning of an abbreviated header, such as "Pts" → "Points".
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HeaderTooltip {
+    pub column: usize,
+    pub header: String,
+    pub tooltip: String,
+}
+
+/// A data cell carrying a tooltip.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CellTooltip {
+    /// 1-based position among the table's data rows
+    pub row: usize,
+    pub column: usize,
+    pub tooltip: String,
+}
+
+/// The cell's own `title`, else the expansion of the first `<abbr title>` inside it.
+/// A title repeating the cell's text adds nothing and is ignored.
+pub fn cell_tooltip(cell: ElementRef) -> Option<String> {
+    let title = cell
+        .value()
+        .attr("title")
+        .or_else(|| {
+            cell.select(&ABBR_SELECTOR)
+                .find_map(|abbr| abbr.value().attr("title"))
+        })?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!title.is_empty() && title != clean_cell_text(cell)).then_some(title)
+}
+
+/// Tooltip of each header column. With several header rows the lowest row with a
+/// tooltip wins, as it is the most specific.
+pub fn header_tooltips(header_rows: &[ElementRef]) -> Vec<Option<String>> {
+    let grid = grid::expand_spans(header_rows);
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    (0..width)
+        .map(|c| {
+            grid.iter()
+                .rev()
+                .find_map(|row| row.get(c)?.as_ref()?.tooltip.clone())
+        })
+        .collect()
+}
+
+/// Lists the columns of `tooltips` that have one, named by `headers`.
+pub fn list_header_tooltips(
+    tooltips: Vec<Option<String>>,
+    headers: &[String],
+) -> Vec<HeaderTooltip> {
+    tooltips
+        .into_iter()
+        .enumerate()
+        .filter_map(|(column, tooltip)| {
+            Some(HeaderTooltip {
+                column,
+                header: headers.get(column).cloned().unwrap_or_default(),
+                tooltip: tooltip?,
+            })
+        })
+        .collect()
+}
+
+/// Lists the data cells of `tooltips`, laid out like the table's rows, that have one.
+pub fn list_cell_tooltips(tooltips: Vec<Vec<Option<String>>>) -> Vec<CellTooltip> {
+    tooltips
+        .into_iter()
+        .enumerate()
+        .flat_map(|(r, row)| {
+            row.into_iter()
+                .enumerate()
+                .filter_map(move |(column, tooltip)| {
+                    Some(CellTooltip {
+                        row: r + 1,
+                        column,
+                        tooltip: tooltip?,
+                    })
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    #[test]
+    fn reads_titles_and_abbreviation_expansions() {
+        let table = extract(
+            "<table>
+             <tr><th colspan=2 title='Season  totals'>Totals</th><th>Team</th></tr>
+             <tr><th><abbr title='Points'>Pts</abbr></th><th title='Goals'>Goals</th><th>Name</th></tr>
+             <tr><td title='Estimated'>12</td><td>4</td><td><abbr title='Arsenal FC'>ARS</abbr></td></tr>
+             <tr><td>9</td><td title='3'>3</td><td>Chelsea</td></tr></table>",
+            ExtractOptions::default(),
+        )
+        .remove(0);
+
+        let headers: Vec<(usize, &str)> = table
+            .metadata
+            .header_tooltips
+            .iter()
+            .flatten()
+            .map(|tooltip| (tooltip.column, tooltip.tooltip.as_str()))
+            .collect();
+        // The lower header row wins, and a title repeating the text is dropped
+        assert_eq!(headers, [(0, "Points"), (1, "Season totals")]);
+
+        let cells: Vec<(usize, usize, &str)> = table
+            .metadata
+            .cell_tooltips
+            .iter()
+            .flatten()
+            .map(|tooltip| (tooltip.row, tooltip.column, tooltip.tooltip.as_str()))
+            .collect();
+        assert_eq!(cells, [(1, 0, "Estimated"), (1, 2, "Arsenal FC")]);
+    }
+}
@@ -0,0 +1,686 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::error::TabexError;
+use crate::expr::{EvalContext, Expr};
+use crate::lineage::{self, ColumnLineage};
+use crate::matching::HeaderMatcher;
+use crate::plugin::Transform;
+use crate::types::{SignConventions, Value, format_number, parse_suffixed};
+use crate::{PageMetadata, Table};
+
+/// A sed-style substitution applied to every cell of one column.
+pub struct ReplaceRule {
+    column: String,
+    pattern: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl ReplaceRule {
+    /// Parses `COLUMN:s/PATTERN/REPLACEMENT/FLAGS`. Any delimiter may follow the `s`,
+    /// and `\1` / `&` in the replacement refer to capture groups as in sed.
+    pub fn parse(spec: &str) -> Result<ReplaceRule, TabexError> {
+        let invalid = || TabexError::Parse(format!("Invalid --replace rule: {}", spec));
+
+        let (column, expr) = spec.split_once(':').ok_or_else(invalid)?;
+        let mut chars = expr.chars();
+        if chars.next() != Some('s') {
+            return Err(invalid());
+        }
+        let delimiter = chars.next().ok_or_else(invalid)?;
+
+        // Split on unescaped delimiters
+        let mut parts = vec![String::new()];
+        let mut escaped = false;
+        for c in chars {
+            if escaped {
+                if c != delimiter {
+                    parts.last_mut().unwrap().push('\\');
+                }
+                parts.last_mut().unwrap().push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == delimiter {
+                parts.push(String::new());
+            } else {
+                parts.last_mut().unwrap().push(c);
+            }
+        }
+        if parts.len() != 3 {
+            return Err(invalid());
+        }
+
+        let flags = &parts[2];
+        if let Some(flag) = flags.chars().find(|f| !matches!(f, 'g' | 'i')) {
+            return Err(TabexError::Parse(format!(
+                "Unsupported flag '{}' in --replace rule: {}",
+                flag, spec
+            )));
+        }
+
+        let pattern = RegexBuilder::new(&parts[0])
+            .case_insensitive(flags.contains('i'))
+            .build()
+            .map_err(|e| {
+                TabexError::Parse(format!("Invalid pattern in --replace rule {}: {}", spec, e))
+            })?;
+
+        Ok(ReplaceRule {
+            column: column.trim().to_string(),
+            pattern,
+            replacement: sed_replacement(&parts[1]),
+            global: flags.contains('g'),
+        })
+    }
+
+    fn apply(&self, cell: &str) -> String {
+        if self.global {
+            self.pattern.replace_all(cell, self.replacement.as_str())
+        } else {
+            self.pattern.replace(cell, self.replacement.as_str())
+        }
+        .trim()
+        .to_string()
+    }
+}
+
+/// Converts a sed replacement (`\1`, `&`) into the regex crate's syntax (`${1}`, `${0}`).
+fn sed_replacement(replacement: &str) -> String {
+    let mut converted = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => converted.push_str(&format!("${{{}}}", d)),
+                Some('n') => converted.push('\n'),
+                Some(other) => converted.push(other),
+                None => converted.push('\\'),
+            },
+            '&' => converted.push_str("${0}"),
+            '$' => converted.push_str("$$"),
+            _ => converted.push(c),
+        }
+    }
+    converted
+}
+
+pub fn apply_replacements(tables: &mut [Table], rules: &[ReplaceRule], matcher: &HeaderMatcher) {
+    for table in tables {
+        for rule in rules {
+            let Some(index) = matcher.column_index(table, &rule.column) else {
+                continue;
+            };
+            lineage::changed(table, index, "replace");
+            for row in &mut table.data.rows {
+                if let Some(cell) = row.get_mut(index) {
+                    *cell = rule.apply(cell);
+                }
+            }
+        }
+    }
+}
+
+/// A column computed from other columns, e.g. `total = price * quantity`.
+pub struct DerivedColumn {
+    name: String,
+    expr: Expr,
+}
+
+impl DerivedColumn {
+    pub fn parse(spec: &str) -> Result<DerivedColumn, TabexError> {
+        let (name, expr) = spec.split_once('=').ok_or_else(|| {
+            TabexError::Parse(format!(
+                "Invalid --add-column (expected NAME = EXPR): {}",
+                spec
+            ))
+        })?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(TabexError::Parse(format!(
+                "Missing column name in --add-column: {}",
+                spec
+            )));
+        }
+        let expr = Expr::parse(expr)
+            .map_err(|e| TabexError::Parse(format!("Invalid --add-column {}: {}", spec, e)))?;
+
+        Ok(DerivedColumn {
+            name: name.to_string(),
+            expr,
+        })
+    }
+}
+
+/// Appends derived columns, skipping tables that lack a column the expression reads.
+pub fn apply_derived_columns(
+    tables: &mut [Table],
+    columns: &[DerivedColumn],
+    context: &EvalContext,
+    matcher: &HeaderMatcher,
+) {
+    for table in tables {
+        for column in columns {
+            let Some(indices) = column
+                .expr
+                .columns()
+                .into_iter()
+                .map(|name| matcher.column_index(table, name).map(|index| (name, index)))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            let width = table.data.headers.len();
+            if width > 0 {
+                table.data.headers.push(column.name.clone());
+                let inputs: Vec<usize> = indices.iter().map(|(_, index)| *index).collect();
+                lineage::push_derived(table, &column.name, &inputs, "add-column");
+            }
+            let conventions = table.metadata.sign_conventions;
+            for row in &mut table.data.rows {
+                let lookup = |name: &str| {
+                    indices
+                        .iter()
+                        .find(|(column, _)| *column == name)
+                        .and_then(|(_, index)| row.get(*index))
+                        .map(|cell| Value::from_cell(cell, conventions))
+                        .unwrap_or(Value::Null)
+                };
+                let value = column.expr.eval(&lookup, context).to_string();
+                if row.len() < width {
+                    row.resize(width, String::new());
+                }
+                row.push(value);
+            }
+        }
+    }
+}
+
+/// Provenance values that `--stamp-columns` can add to every row.
+#[derive(Debug, Clone, Copy)]
+pub enum StampColumn {
+    Url,
+    Title,
+    Caption,
+    Heading,
+    Position,
+    TableId,
+    ExtractedAt,
+}
+
+impl StampColumn {
+    pub fn parse_list(list: &str) -> Result<Vec<StampColumn>, TabexError> {
+        list.split(',')
+            .map(|name| match name.trim() {
+                "url" => Ok(StampColumn::Url),
+                "title" => Ok(StampColumn::Title),
+                "caption" => Ok(StampColumn::Caption),
+                "heading" => Ok(StampColumn::Heading),
+                "position" => Ok(StampColumn::Position),
+                "table_id" => Ok(StampColumn::TableId),
+                "extracted_at" => Ok(StampColumn::ExtractedAt),
+                other => Err(TabexError::Parse(format!(
+                    "Unknown stamp column '{}' (expected url, title, caption, heading, position, table_id or extracted_at)",
+                    other
+                ))),
+            })
+            .collect()
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            StampColumn::Url => "url",
+            StampColumn::Title => "title",
+            StampColumn::Caption => "caption",
+            StampColumn::Heading => "heading",
+            StampColumn::Position => "position",
+            StampColumn::TableId => "table_id",
+            StampColumn::ExtractedAt => "extracted_at",
+        }
+    }
+
+    fn value(self, page: &PageMetadata, table: &Table, context: &EvalContext) -> String {
+        let metadata = &table.metadata;
+        match self {
+            StampColumn::Url => page.url.clone(),
+            StampColumn::Title => page.title.clone().unwrap_or_default(),
+            StampColumn::Caption => metadata.caption.clone().unwrap_or_default(),
+            StampColumn::Heading => metadata.preceding_heading.clone().unwrap_or_default(),
+            StampColumn::Position => metadata.position.to_string(),
+            StampColumn::TableId => metadata.id.clone().unwrap_or_default(),
+            StampColumn::ExtractedAt => context.now.clone(),
+        }
+    }
+}
+
+/// Appends the same provenance values to every row of each table.
+pub fn apply_stamp_columns(
+    tables: &mut [Table],
+    stamps: &[StampColumn],
+    page: &PageMetadata,
+    context: &EvalContext,
+) {
+    for table in tables {
+        let values: Vec<String> = stamps
+            .iter()
+            .map(|stamp| stamp.value(page, table, context))
+            .collect();
+
+        let width = table.data.headers.len();
+        if width > 0 {
+            table
+                .data
+                .headers
+                .extend(stamps.iter().map(|stamp| stamp.name().to_string()));
+            for stamp in stamps {
+                lineage::push_derived(table, stamp.name(), &[], "stamp-columns");
+            }
+        }
+        for row in &mut table.data.rows {
+            if row.len() < width {
+                row.resize(width, String::new());
+            }
+            row.extend(values.iter().cloned());
+        }
+    }
+}
+
+/// Converts matrix tables into long form with one (row_label, col_label, value) row per cell.
+pub fn melt_matrix_tables(tables: &mut [Table]) {
+    for table in tables.iter_mut().filter(|table| table.metadata.matrix) {
+        let values: Vec<usize> = (1..table.data.headers.len()).collect();
+        let melted: Option<Vec<ColumnLineage>> = [
+            ("row_label", &[0][..]),
+            ("col_label", &values[..]),
+            ("value", &values[..]),
+        ]
+        .iter()
+        .map(|(name, inputs)| lineage::derived(table, name, inputs, "melt"))
+        .collect();
+        if melted.is_some() {
+            table.metadata.lineage = melted;
+        }
+
+        let headers = std::mem::take(&mut table.data.headers);
+        let rows = std::mem::take(&mut table.data.rows);
+
+        table.data.headers = vec![
+            "row_label".to_string(),
+            "col_label".to_string(),
+            "value".to_string(),
+        ];
+        for row in rows {
+            let mut cells = row.into_iter();
+            let label = cells.next().unwrap_or_default();
+            for (header, value) in headers.iter().skip(1).zip(cells) {
+                table
+                    .data
+                    .rows
+                    .push(vec![label.clone(), header.clone(), value]);
+            }
+        }
+    }
+}
+
+/// Which tables `--transpose` turns around.
+#[derive(Debug, Clone)]
+pub enum TransposeTables {
+    /// Tables detected as key-value
+    Auto,
+    Positions(Vec<usize>),
+}
+
+impl TransposeTables {
+    /// Parses `auto` or comma-separated table positions, e.g. `2,5`.
+    pub fn parse(spec: &str) -> Result<TransposeTables, TabexError> {
+        if spec.trim() == "auto" {
+            return Ok(TransposeTables::Auto);
+        }
+        spec.split(',')
+            .map(|position| {
+                position
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|position| *position > 0)
+                    .ok_or_else(|| {
+                        TabexError::Parse(format!(
+                            "Invalid --transpose {} (expected auto or table positions, e.g. 2,5)",
+                            spec
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(TransposeTables::Positions)
+    }
+
+    fn includes(&self, table: &Table) -> bool {
+        match self {
+            TransposeTables::Auto => table.metadata.key_value,
+            TransposeTables::Positions(positions) => positions.contains(&table.metadata.position),
+        }
+    }
+}
+
+/// Turns attribute-per-row tables around so that the first column's cells become
+/// headers. A two-column spec sheet becomes a single record; each further value
+/// column becomes another record, labelled by its header when there is one.
+pub fn transpose_tables(tables: &mut [Table], which: &TransposeTables) {
+    for table in tables.iter_mut().filter(|table| which.includes(table)) {
+        let width = table
+            .data
+            .rows
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(table.data.headers.len());
+        if width < 2 {
+            continue;
+        }
+        let headers = std::mem::take(&mut table.data.headers);
+        let rows = std::mem::take(&mut table.data.rows);
+        let cell = |row: &Vec<String>, index: usize| row.get(index).cloned().unwrap_or_default();
+
+        let label = (width > 2 && !headers.is_empty()).then(|| match headers[0].trim() {
+            "" => "column".to_string(),
+            label => label.to_string(),
+        });
+        let mut transposed: Vec<String> = label.iter().cloned().collect();
+        transposed.extend(rows.iter().map(|row| cell(row, 0)));
+
+        let inputs: Vec<usize> = (0..width).collect();
+        let columns: Option<Vec<ColumnLineage>> = transposed
+            .iter()
+            .map(|name| lineage::derived(table, name, &inputs, "transpose"))
+            .collect();
+        if columns.is_some() {
+            table.metadata.lineage = columns;
+        }
+
+        table.data.headers = transposed;
+        table.data.rows = (1..width)
+            .map(|index| {
+                let mut record: Vec<String> = label.iter().map(|_| cell(&headers, index)).collect();
+                record.extend(rows.iter().map(|row| cell(row, index)));
+                record
+            })
+            .collect();
+    }
+}
+
+/// A cell rewritten by `--expand-suffixes` or `--timezone`, with the text it had on the page.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OriginalValue {
+    /// 1-based position among the table's data rows
+    pub row: usize,
+    pub column: usize,
+    pub original: String,
+}
+
+/// The number a cell spells with a magnitude suffix ("15k") or in scientific
+/// notation ("1.2e6"), or None for a plainly written or non-numeric cell.
+fn expanded_number(cell: &str, conventions: SignConventions) -> Option<f64> {
+    parse_suffixed(cell).or_else(|| {
+        cell.contains(['e', 'E'])
+            .then(|| conventions.parse_number(cell))
+            .flatten()
+    })
+}
+
+/// Rewrites "3.4M", "2.1bn" and "1.2e6" to plain numbers in columns that are mostly
+/// numeric, keeping the page's text in `metadata.original_values`.
+pub fn expand_suffix_tables(tables: &mut [Table]) {
+    for table in tables {
+        let width = table.data.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let conventions = table.metadata.sign_conventions;
+        let mut originals = Vec::new();
+        for column in 0..width {
+            let cells = || {
+                table
+                    .data
+                    .rows
+                    .iter()
+                    .filter_map(move |row| row.get(column))
+                    .map(|cell| cell.trim())
+                    .filter(|cell| !cell.is_empty())
+            };
+            let total = cells().count();
+            let numeric = cells()
+                .filter(|cell| {
+                    conventions
+                        .parse_number(cell)
+                        .or_else(|| parse_suffixed(cell))
+                        .is_some()
+                })
+                .count();
+            // "Model 3K" in a column of names stays text
+            if total == 0 || numeric * 2 < total {
+                continue;
+            }
+
+            let mut changed = false;
+            for (r, row) in table.data.rows.iter_mut().enumerate() {
+                let Some(cell) = row.get_mut(column) else {
+                    continue;
+                };
+                if let Some(number) = expanded_number(cell, conventions) {
+                    originals.push(OriginalValue {
+                        row: r + 1,
+                        column,
+                        original: std::mem::replace(cell, format_number(number)),
+                    });
+                    changed = true;
+                }
+            }
+            if changed {
+                lineage::changed(table, column, "expand-suffixes");
+            }
+        }
+        keep_originals(table, originals);
+    }
+}
+
+/// Adds rewritten cells to the table's `original_values`, in row order.
+pub fn keep_originals(table: &mut Table, originals: Vec<OriginalValue>) {
+    if originals.is_empty() {
+        return;
+    }
+    let kept = table.metadata.original_values.get_or_insert_with(Vec::new);
+    kept.extend(originals);
+    kept.sort_by_key(|original| (original.row, original.column));
+}
+
+/// Whether at least half the non-empty cells of a column pass `test`.
+pub fn mostly(table: &Table, column: usize, test: impl Fn(&str) -> bool) -> bool {
+    let cells: Vec<&str> = table
+        .data
+        .rows
+        .iter()
+        .filter_map(|row| row.get(column))
+        .map(|cell| cell.trim())
+        .filter(|cell| !cell.is_empty())
+        .collect();
+    let matching = cells.iter().filter(|cell| test(cell)).count();
+    !cells.is_empty() && matching * 2 >= cells.len()
+}
+
+/// `--expand-suffixes` as a pipeline step.
+pub struct ExpandSuffixes;
+
+impl Transform for ExpandSuffixes {
+    fn name(&self) -> &str {
+        "expand-suffixes"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        expand_suffix_tables(tables);
+    }
+}
+
+/// `--replace` rules as a pipeline step.
+pub struct Replacements {
+    pub rules: Vec<ReplaceRule>,
+    pub matcher: HeaderMatcher,
+}
+
+impl Transform for Replacements {
+    fn name(&self) -> &str {
+        "replace"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        apply_replacements(tables, &self.rules, &self.matcher);
+    }
+}
+
+/// `--melt` as a pipeline step.
+pub struct MeltMatrix;
+
+impl Transform for MeltMatrix {
+    fn name(&self) -> &str {
+        "melt"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        melt_matrix_tables(tables);
+    }
+}
+
+/// `--transpose` as a pipeline step.
+pub struct Transpose {
+    pub tables: TransposeTables,
+}
+
+impl Transform for Transpose {
+    fn name(&self) -> &str {
+        "transpose"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        transpose_tables(tables, &self.tables);
+    }
+}
+
+/// `--add-column` expressions as a pipeline step.
+pub struct DerivedColumns {
+    pub columns: Vec<DerivedColumn>,
+    pub context: EvalContext,
+    pub matcher: HeaderMatcher,
+}
+
+impl Transform for DerivedColumns {
+    fn name(&self) -> &str {
+        "add-column"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        apply_derived_columns(tables, &self.columns, &self.context, &self.matcher);
+    }
+}
+
+/// `--stamp-columns` as a pipeline step.
+pub struct StampColumns {
+    pub stamps: Vec<StampColumn>,
+    pub context: EvalContext,
+}
+
+impl Transform for StampColumns {
+    fn name(&self) -> &str {
+        "stamp-columns"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, page: &PageMetadata) {
+        apply_stamp_columns(tables, &self.stamps, page, &self.context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    fn tables(html: &str) -> Vec<Table> {
+        extract(html, ExtractOptions::default())
+    }
+
+    #[test]
+    fn melts_matrix_tables_into_long_form() {
+        let mut tables = tables(
+            "<table><tr><th>Region</th><th>2023</th><th>2024</th></tr>
+             <tr><td>North</td><td>1</td><td>2</td></tr>
+             <tr><td>South</td><td>3</td><td>4</td></tr></table>",
+        );
+        melt_matrix_tables(&mut tables);
+        let data = &tables[0].data;
+        assert_eq!(data.headers, ["row_label", "col_label", "value"]);
+        assert_eq!(
+            data.rows,
+            [
+                ["North", "2023", "1"],
+                ["North", "2024", "2"],
+                ["South", "2023", "3"],
+                ["South", "2024", "4"],
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_other_tables_unmelted() {
+        let mut tables = tables(
+            "<table><tr><th>Name</th><th>Team</th><th>Goals</th></tr>
+             <tr><td>Ann</td><td>Reds</td><td>3</td></tr>
+             <tr><td>Bob</td><td>Blues</td><td>1</td></tr></table>",
+        );
+        melt_matrix_tables(&mut tables);
+        assert_eq!(tables[0].data.headers, ["Name", "Team", "Goals"]);
+        assert_eq!(tables[0].data.rows.len(), 2);
+    }
+
+    #[test]
+    fn turns_a_spec_sheet_into_one_record() {
+        let mut tables = tables(
+            "<table><tr><th>Weight</th><td>1.2 kg</td></tr>
+             <tr><th>Colour</th><td>Red</td></tr>
+             <tr><th>Price</th><td>$10</td></tr></table>",
+        );
+        assert!(tables[0].metadata.key_value);
+        transpose_tables(&mut tables, &TransposeTables::Auto);
+        let data = &tables[0].data;
+        assert_eq!(data.headers, ["Weight", "Colour", "Price"]);
+        assert_eq!(data.rows, [["1.2 kg", "Red", "$10"]]);
+    }
+
+    #[test]
+    fn labels_each_value_column_as_a_record() {
+        let mut tables = tables(
+            "<table><tr><th>Spec</th><th>Model A</th><th>Model B</th></tr>
+             <tr><td>Weight</td><td>1 kg</td><td>2 kg</td></tr>
+             <tr><td>Colour</td><td>Red</td><td>Blue</td></tr></table>",
+        );
+        assert!(!tables[0].metadata.key_value);
+        transpose_tables(&mut tables, &TransposeTables::parse("1").unwrap());
+        let data = &tables[0].data;
+        assert_eq!(data.headers, ["Spec", "Weight", "Colour"]);
+        assert_eq!(
+            data.rows,
+            [["Model A", "1 kg", "Red"], ["Model B", "2 kg", "Blue"]]
+        );
+    }
+
+    #[test]
+    fn leaves_tables_that_are_not_key_value_alone_in_auto_mode() {
+        let mut tables = tables(
+            "<table><tr><th>Name</th><th>Age</th></tr>
+             <tr><td>Ann</td><td>30</td></tr><tr><td>Bob</td><td>31</td></tr></table>",
+        );
+        assert!(!tables[0].metadata.key_value);
+        transpose_tables(&mut tables, &TransposeTables::Auto);
+        assert_eq!(tables[0].data.headers, ["Name", "Age"]);
+        assert!(TransposeTables::parse("0").is_err());
+        assert!(TransposeTables::parse("2,x").is_err());
+    }
+}
@@ -0,0 +1,491 @@
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+use crate::error::TabexError;
+
+/// A cell value as seen by expressions and analysis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    /// Interprets a cleaned cell, treating anything that parses as a number with the
+    /// table's sign conventions as numeric.
+    pub fn from_cell(cell: &str, conventions: SignConventions) -> Value {
+        let cell = cell.trim();
+        if cell.is_empty() {
+            Value::Null
+        } else if let Some(number) = conventions.parse_number(cell) {
+            Value::Number(number)
+        } else {
+            Value::Text(cell.to_string())
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Number(number) => write!(f, "{}", format_number(*number)),
+            Value::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// A column type set with `--types`, overriding what typed sinks would infer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum DeclaredType {
+    /// Kept as text, so zip codes and IDs keep their leading zeros
+    String,
+    Int,
+    Float,
+    /// Exact to `scale` decimals, with at most `precision` digits
+    Decimal {
+        precision: u32,
+        scale: u32,
+    },
+}
+
+impl DeclaredType {
+    pub fn parse(name: &str) -> Result<DeclaredType, TabexError> {
+        let name = name.trim().to_ascii_lowercase();
+        let invalid = || {
+            TabexError::Usage(format!(
+                "Unknown column type {} (expected string, int, float or decimal(P,S))",
+                name
+            ))
+        };
+        match name.as_str() {
+            "string" | "text" => Ok(DeclaredType::String),
+            "int" | "integer" => Ok(DeclaredType::Int),
+            "float" | "real" | "number" => Ok(DeclaredType::Float),
+            _ => {
+                let arguments = name
+                    .strip_prefix("decimal(")
+                    .and_then(|rest| rest.strip_suffix(')'))
+                    .ok_or_else(invalid)?;
+                let (precision, scale) = arguments
+                    .split_once(',')
+                    .and_then(|(p, s)| Some((p.trim().parse().ok()?, s.trim().parse().ok()?)))
+                    .filter(|(precision, scale): &(u32, u32)| *precision > 0 && scale <= precision)
+                    .ok_or_else(invalid)?;
+                Ok(DeclaredType::Decimal { precision, scale })
+            }
+        }
+    }
+
+    /// Parses `COLUMN:TYPE,...`, e.g. "id:string,year:int,price:decimal(10,2)".
+    /// Commas inside a type's parentheses don't separate entries.
+    pub fn parse_list(list: &str) -> Result<Vec<(String, DeclaredType)>, TabexError> {
+        let mut entries = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in list.char_indices().chain([(list.len(), ',')]) {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    let entry = list[start..i].trim();
+                    start = i + 1;
+                    if entry.is_empty() {
+                        continue;
+                    }
+                    let (column, name) = entry.rsplit_once(':').ok_or_else(|| {
+                        TabexError::Usage(format!(
+                            "Invalid --types entry (expected COLUMN:TYPE): {}",
+                            entry
+                        ))
+                    })?;
+                    entries.push((column.trim().to_string(), DeclaredType::parse(name)?));
+                }
+                _ => {}
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The cell as a number of this type, or None for text, empty cells and values
+    /// that don't fit, such as "2.5" in an int column.
+    pub fn number(self, cell: &str, conventions: SignConventions) -> Option<f64> {
+        match self {
+            DeclaredType::String => None,
+            DeclaredType::Int => conventions
+                .parse_number(cell)
+                .filter(|number| number.fract() == 0.0),
+            DeclaredType::Float => conventions.parse_number(cell),
+            DeclaredType::Decimal { .. } => self.decimal(cell, conventions)?.to_f64(),
+        }
+    }
+
+    /// The cell of a decimal column rounded half away from zero and padded to the
+    /// scale, or None when it isn't a number or has more integer digits than the
+    /// precision allows.
+    pub fn decimal(self, cell: &str, conventions: SignConventions) -> Option<Decimal> {
+        let DeclaredType::Decimal { precision, scale } = self else {
+            return None;
+        };
+        let mut decimal = conventions
+            .parse_decimal(cell)?
+            .round_dp_with_strategy(scale, RoundingStrategy::MidpointAwayFromZero);
+        decimal.rescale(scale);
+        let integer_digits = decimal
+            .trunc()
+            .abs()
+            .to_string()
+            .trim_start_matches('0')
+            .len();
+        (integer_digits as u32 <= precision - scale).then_some(decimal)
+    }
+
+    /// The cell as JSON: a string for string columns, else a number, or null when
+    /// the cell is empty or doesn't fit the type. Decimals are written digit for
+    /// digit, trailing zeros included.
+    pub fn json_value(self, cell: &str, conventions: SignConventions) -> serde_json::Value {
+        match self {
+            DeclaredType::String => serde_json::Value::from(cell),
+            DeclaredType::Int => self
+                .number(cell, conventions)
+                .map_or(serde_json::Value::Null, |number| (number as i64).into()),
+            DeclaredType::Float => self
+                .number(cell, conventions)
+                .map_or(serde_json::Value::Null, serde_json::Value::from),
+            DeclaredType::Decimal { .. } => self
+                .decimal(cell, conventions)
+                .and_then(|decimal| decimal.to_string().parse::<serde_json::Number>().ok())
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+        }
+    }
+}
+
+impl fmt::Display for DeclaredType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeclaredType::String => write!(f, "string"),
+            DeclaredType::Int => write!(f, "int"),
+            DeclaredType::Float => write!(f, "float"),
+            DeclaredType::Decimal { precision, scale } => {
+                write!(f, "decimal({},{})", precision, scale)
+            }
+        }
+    }
+}
+
+impl From<DeclaredType> for String {
+    fn from(declared: DeclaredType) -> String {
+        declared.to_string()
+    }
+}
+
+impl TryFrom<String> for DeclaredType {
+    type Error = TabexError;
+
+    fn try_from(name: String) -> Result<DeclaredType, TabexError> {
+        DeclaredType::parse(&name)
+    }
+}
+
+/// How negative numbers are written in a page's tables, from `--negatives`. The
+/// extractor records them on each table, and numbers in its cells are read with them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignConventions {
+    /// Accounting style "(1,234)"
+    pub parens: bool,
+    /// Trailing minus "1234-", as in some ledgers and bank statements
+    pub trailing: bool,
+    /// Minus lookalikes such as U+2212 "−3" and the fullwidth "－3"
+    pub unicode: bool,
+}
+
+impl SignConventions {
+    pub const DEFAULT: SignConventions = SignConventions {
+        parens: false,
+        trailing: false,
+        unicode: true,
+    };
+
+    pub fn parse(list: &str) -> Result<SignConventions, TabexError> {
+        let mut conventions = SignConventions {
+            parens: false,
+            trailing: false,
+            unicode: false,
+        };
+        for name in list.split(',') {
+            match name.trim() {
+                "parens" => conventions.parens = true,
+                "trailing" => conventions.trailing = true,
+                "unicode" => conventions.unicode = true,
+                "all" => {
+                    conventions = SignConventions {
+                        parens: true,
+                        trailing: true,
+                        unicode: true,
+                    }
+                }
+                "none" => {}
+                other => {
+                    return Err(TabexError::Usage(format!(
+                        "Unknown negative number convention {} (expected parens, trailing, unicode, all or none)",
+                        other
+                    )));
+                }
+            }
+        }
+        Ok(conventions)
+    }
+
+    /// A number's text without grouping, currency symbols and percent sign, with
+    /// these conventions applied: the digits to parse and whether to negate them.
+    fn clean_number(self, text: &str) -> Option<(String, bool)> {
+        let mut cleaned: String = text
+            .trim()
+            .trim_end_matches('%')
+            .chars()
+            .filter(|c| !matches!(c, ',' | ' ' | '\u{a0}') && !CURRENCY_SYMBOLS.contains(c))
+            .map(|c| match c {
+                '\u{2212}' | '\u{2012}' | '\u{fe63}' | '\u{ff0d}' if self.unicode => '-',
+                c => c,
+            })
+            .collect();
+
+        let mut negative = false;
+        if self.parens
+            && let Some(inner) = cleaned
+                .strip_prefix('(')
+                .and_then(|rest| rest.strip_suffix(')'))
+        {
+            cleaned = inner.to_string();
+            negative = true;
+        }
+        if self.trailing
+            && !cleaned.starts_with(['-', '+'])
+            && let Some(inner) = cleaned.strip_suffix('-')
+        {
+            cleaned = inner.to_string();
+            negative = !negative;
+        }
+
+        if cleaned.is_empty() || !cleaned.chars().any(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        Some((cleaned, negative))
+    }
+
+    /// Parses numbers as they commonly appear in tables: "1,234", "$1.20", "45%",
+    /// "−3", and "(1,234)" or "1234-" when those conventions are on.
+    pub fn parse_number(self, text: &str) -> Option<f64> {
+        let (cleaned, negative) = self.clean_number(text)?;
+        let number = cleaned.parse::<f64>().ok().filter(|n| n.is_finite())?;
+        Some(if negative { -number } else { number })
+    }
+
+    /// Parses a number like `parse_number` does, but exactly: "0.10" stays 0.10
+    /// rather than the nearest binary fraction.
+    pub fn parse_decimal(self, text: &str) -> Option<Decimal> {
+        let (cleaned, negative) = self.clean_number(text)?;
+        let decimal = if cleaned.contains(['e', 'E']) {
+            Decimal::from_scientific(&cleaned).ok()?
+        } else {
+            cleaned.parse::<Decimal>().ok()?
+        };
+        Some(if negative { -decimal } else { decimal })
+    }
+}
+
+impl Default for SignConventions {
+    fn default() -> SignConventions {
+        SignConventions::DEFAULT
+    }
+}
+
+/// Currency symbols stripped from numbers.
+const CURRENCY_SYMBOLS: [char; 4] = ['$', '€', '£', '¥'];
+
+/// Parses a number with the default sign conventions, for text that isn't read from
+/// a table's cells, such as coordinates and parts of a cell already split apart.
+pub fn parse_number(text: &str) -> Option<f64> {
+    SignConventions::DEFAULT.parse_number(text)
+}
+
+/// Whether the cell is an amount of money, written with a currency symbol.
+pub fn is_money(cell: &str, conventions: SignConventions) -> bool {
+    let cell = cell.trim();
+    cell.trim_start_matches(['-', '+', '(', '\u{2212}'])
+        .starts_with(CURRENCY_SYMBOLS)
+        && !cell.ends_with('%')
+        && conventions.parse_number(cell).is_some()
+}
+
+/// A decimal type for a monetary column: every non-empty cell is a number and at
+/// least one carries a currency symbol. Precision and scale fit the widest values,
+/// so "$1,234.5" and "$0.99" make decimal(6,2).
+pub fn money_type<'a>(
+    cells: impl IntoIterator<Item = &'a str>,
+    conventions: SignConventions,
+) -> Option<DeclaredType> {
+    let mut money = false;
+    let (mut integer_digits, mut scale) = (1, 0);
+    for cell in cells {
+        if cell.trim().is_empty() {
+            continue;
+        }
+        let decimal = conventions
+            .parse_decimal(cell)
+            .filter(|_| !cell.trim().ends_with('%'))?;
+        money |= is_money(cell, conventions);
+        scale = scale.max(decimal.scale());
+        let digits = decimal
+            .trunc()
+            .abs()
+            .to_string()
+            .trim_start_matches('0')
+            .len();
+        integer_digits = integer_digits.max(digits as u32);
+    }
+    money.then_some(DeclaredType::Decimal {
+        precision: integer_digits + scale,
+        scale,
+    })
+}
+
+/// A number followed by a magnitude: "15k", "3.4M", "$2.1 bn", "1.2 million".
+/// Lone lowercase m, b and t are left alone, as they are more often metres, bytes
+/// or tonnes.
+static SUFFIXED_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^([^\sA-Za-z]*?\d[\d.,]*)\s*([kK]|M|B|T|(?i:thousand|mn|mln|million|bn|billion|tn|trillion))\.?$")
+        .unwrap()
+});
+
+/// Parses a number with a magnitude suffix such as "3.4M" or "2.1bn". The result is
+/// rounded to the decimals the written number implies, so "2.1bn" is exactly 2100000000.
+pub fn parse_suffixed(text: &str) -> Option<f64> {
+    let captures = SUFFIXED_RE.captures(text.trim())?;
+    let exponent: i32 = match captures[2].to_lowercase().as_str() {
+        "k" | "thousand" => 3,
+        "m" | "mn" | "mln" | "million" => 6,
+        "b" | "bn" | "billion" => 9,
+        _ => 12,
+    };
+    let mantissa = &captures[1];
+    let number = parse_number(mantissa)?;
+    let decimals = mantissa.rsplit_once('.').map_or(0, |(_, fraction)| {
+        fraction.chars().filter(char::is_ascii_digit).count()
+    });
+    let places = (decimals as i32 - exponent).max(0);
+    let factor = 10f64.powi(places);
+    Some((number * 10f64.powi(exponent) * factor).round() / factor)
+}
+
+/// Formats a number without float noise such as 12.000000000000002.
+pub fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        return format!("{}", number as i64);
+    }
+    let formatted = format!("{:.10}", number);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: SignConventions = SignConventions {
+        parens: true,
+        trailing: true,
+        unicode: true,
+    };
+
+    #[test]
+    fn parses_numbers_as_tables_write_them() {
+        let default = SignConventions::DEFAULT;
+        assert_eq!(default.parse_number("1,234"), Some(1234.0));
+        assert_eq!(default.parse_number(" $1.20 "), Some(1.2));
+        assert_eq!(default.parse_number("45%"), Some(45.0));
+        assert_eq!(default.parse_number("\u{2212}3"), Some(-3.0));
+    }
+
+    #[test]
+    fn reads_accounting_and_trailing_minus_only_when_enabled() {
+        let default = SignConventions::DEFAULT;
+        assert_eq!(default.parse_number("(1,234)"), None);
+        assert_eq!(default.parse_number("1234-"), None);
+        assert_eq!(ALL.parse_number("(1,234)"), Some(-1234.0));
+        assert_eq!(ALL.parse_number("1234-"), Some(-1234.0));
+        assert_eq!(ALL.parse_number("$(1,234.50)"), Some(-1234.5));
+
+        let no_unicode = SignConventions::parse("parens,trailing").unwrap();
+        assert_eq!(no_unicode.parse_number("\u{2212}3"), None);
+    }
+
+    #[test]
+    fn rejects_text_that_is_not_a_number() {
+        for text in ["", "()", "-", "(-)", "abc", "1.2.3", "12 apples"] {
+            assert_eq!(ALL.parse_number(text), None, "{:?}", text);
+        }
+        assert!(SignConventions::parse("parens,brackets").is_err());
+    }
+
+    #[test]
+    fn parses_scientific_notation() {
+        let default = SignConventions::DEFAULT;
+        assert_eq!(default.parse_number("1.2e6"), Some(1_200_000.0));
+        assert_eq!(default.parse_number("-3E-2"), Some(-0.03));
+        assert_eq!(ALL.parse_number("1e"), None);
+        assert_eq!(ALL.parse_number("e5"), None);
+    }
+
+    #[test]
+    fn expands_magnitude_suffixes() {
+        assert_eq!(parse_suffixed("15k"), Some(15_000.0));
+        assert_eq!(parse_suffixed("3.4M"), Some(3_400_000.0));
+        assert_eq!(parse_suffixed("2.1bn"), Some(2_100_000_000.0));
+        assert_eq!(parse_suffixed("$2.1 bn"), Some(2_100_000_000.0));
+        assert_eq!(parse_suffixed("1.2 million"), Some(1_200_000.0));
+        assert_eq!(parse_suffixed("4 Trillion."), Some(4e12));
+    }
+
+    #[test]
+    fn leaves_ambiguous_suffixes_alone() {
+        for text in ["100m", "5b", "k", "Model 3K", "15 kg", "3.4MB", ""] {
+            assert_eq!(parse_suffixed(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn parses_decimals_exactly() {
+        let default = SignConventions::DEFAULT;
+        assert_eq!(default.parse_decimal("0.10"), Some(Decimal::new(10, 2)));
+        assert_eq!(
+            default.parse_decimal("1.2e6"),
+            Some(Decimal::from(1_200_000))
+        );
+        assert_eq!(default.parse_decimal("1.2e"), None);
+        assert_eq!(ALL.parse_decimal("(0.10)"), Some(Decimal::new(-10, 2)));
+    }
+
+    #[test]
+    fn sizes_money_columns() {
+        let conventions = SignConventions::DEFAULT;
+        assert_eq!(
+            money_type(["$1,234.5", "$0.99", ""], conventions),
+            Some(DeclaredType::Decimal {
+                precision: 6,
+                scale: 2
+            })
+        );
+        assert_eq!(money_type(["1.5", "2"], conventions), None);
+        assert_eq!(money_type(["$1", "n/a"], conventions), None);
+    }
+}
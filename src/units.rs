@@ -0,0 +1,364 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::is_numeric_column;
+use crate::lineage;
+use crate::matching::HeaderMatcher;
+use crate::plugin::Transform;
+use crate::semantic::{self, SemanticType};
+use crate::types::{DeclaredType, format_number};
+use crate::{PageMetadata, Table};
+
+/// Per-column schema entry: the header split into a name and its unit.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub header: String,
+    /// Unit as written, e.g. "$M" or "km"
+    pub unit: Option<String>,
+    /// Unit the values convert to with `--convert-units`, e.g. "USD" or "m"
+    pub base_unit: Option<String>,
+    /// Multiplier from the written unit (and any scale note) to the base unit
+    pub factor: f64,
+    /// Type set with --types, which typed sinks use instead of inferring one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declared_type: Option<DeclaredType>,
+    /// What the values are, such as emails or phone numbers, when the column shows it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub semantic_type: Option<SemanticType>,
+}
+
+struct Unit {
+    written: String,
+    base: String,
+    factor: f64,
+}
+
+const CURRENCIES: &[(&str, &str)] = &[
+    ("us$", "USD"),
+    ("$", "USD"),
+    ("usd", "USD"),
+    ("€", "EUR"),
+    ("eur", "EUR"),
+    ("£", "GBP"),
+    ("gbp", "GBP"),
+    ("¥", "JPY"),
+    ("jpy", "JPY"),
+    ("chf", "CHF"),
+    ("cad", "CAD"),
+    ("aud", "AUD"),
+];
+
+const PHYSICAL_UNITS: &[(&str, &str, f64)] = &[
+    ("mm", "m", 0.001),
+    ("cm", "m", 0.01),
+    ("m", "m", 1.0),
+    ("km", "m", 1000.0),
+    ("mi", "m", 1609.344),
+    ("ft", "m", 0.3048),
+    ("in", "m", 0.0254),
+    ("mg", "kg", 0.000001),
+    ("g", "kg", 0.001),
+    ("kg", "kg", 1.0),
+    ("t", "kg", 1000.0),
+    ("lb", "kg", 0.45359237),
+    ("ms", "s", 0.001),
+    ("s", "s", 1.0),
+    ("min", "s", 60.0),
+    ("h", "s", 3600.0),
+    ("ml", "l", 0.001),
+    ("l", "l", 1.0),
+    ("%", "%", 1.0),
+];
+
+/// Scale words such as "thousands" or "bn"; `short` also allows the one-letter forms
+/// ("k", "M") that are only unambiguous after a currency.
+fn parse_scale(text: &str, short: bool) -> Option<f64> {
+    let scale = match text.trim().trim_start_matches("in ").trim() {
+        "" => 1.0,
+        "thousand" | "thousands" | "000s" | "'000" | "'000s" => 1e3,
+        "million" | "millions" | "mn" | "mln" => 1e6,
+        "billion" | "billions" | "bn" => 1e9,
+        "k" | "K" if short => 1e3,
+        "m" | "M" | "mm" | "MM" if short => 1e6,
+        "b" | "B" if short => 1e9,
+        _ => return None,
+    };
+    Some(scale)
+}
+
+fn parse_unit(text: &str) -> Option<Unit> {
+    let trimmed = text.trim();
+    // "Size ()" has no unit, though an empty scale is a factor of 1
+    if trimmed.is_empty() {
+        return None;
+    }
+    let written = trimmed.to_string();
+    let lower = trimmed.to_lowercase();
+    let lower = lower.trim_start_matches("in ").trim();
+
+    for (symbol, code) in CURRENCIES {
+        let rest = lower
+            .strip_prefix(symbol)
+            .or_else(|| lower.strip_suffix(symbol));
+        if let Some(rest) = rest
+            && let Some(scale) = parse_scale(rest.trim_start_matches(" of").trim(), true)
+        {
+            return Some(Unit {
+                written,
+                base: code.to_string(),
+                factor: scale,
+            });
+        }
+    }
+
+    if let Some(scale) = parse_scale(lower, false) {
+        return Some(Unit {
+            written,
+            base: String::new(),
+            factor: scale,
+        });
+    }
+
+    // Physical units are case-sensitive ("m" is metres, "M" is not)
+    if let Some((_, base, factor)) = PHYSICAL_UNITS.iter().find(|(unit, _, _)| *unit == trimmed) {
+        return Some(Unit {
+            written,
+            base: base.to_string(),
+            factor: *factor,
+        });
+    }
+
+    None
+}
+
+/// Splits "Revenue ($M)" into ("Revenue", unit $M). Headers without a recognized
+/// trailing unit in parentheses or brackets are returned unchanged.
+fn split_header(header: &str) -> (String, Option<Unit>) {
+    let header = header.trim();
+    let Some(close) = header.chars().last().filter(|c| matches!(c, ')' | ']')) else {
+        return (header.to_string(), None);
+    };
+    let open = if close == ')' { '(' } else { '[' };
+    let Some(start) = header.rfind(open) else {
+        return (header.to_string(), None);
+    };
+
+    let inner = &header[start + 1..header.len() - 1];
+    match parse_unit(inner) {
+        Some(unit) => (header[..start].trim().to_string(), Some(unit)),
+        None => (header.to_string(), None),
+    }
+}
+
+/// The header with its unit, if any, replaced by `unit`: "Time (h:mm)" becomes "Time (s)".
+pub fn with_unit(header: &str, unit: &str) -> String {
+    let (name, _) = split_header(header);
+    let name = match name.rfind(['(', '[']) {
+        Some(start) if name.ends_with([')', ']']) => name[..start].trim().to_string(),
+        _ => name,
+    };
+    format!("{} ({})", name, unit)
+}
+
+static NOTE_SCALE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:in|of)\s+(thousands|millions|billions)\b|\((000s|'000)\)").unwrap()
+});
+
+/// Scale stated in a table note or the caption, e.g. "in thousands, 2023 prices".
+fn scale_from_notes<'a>(mut notes: impl Iterator<Item = &'a String>) -> Option<f64> {
+    notes.find_map(|note| {
+        let captures = NOTE_SCALE_RE.captures(note)?;
+        let word = captures.get(1).or_else(|| captures.get(2))?;
+        parse_scale(&word.as_str().to_lowercase(), false)
+    })
+}
+
+pub fn build_schema(table: &Table, apply_notes: bool) -> Vec<ColumnSchema> {
+    let note_scale = if apply_notes {
+        scale_from_notes(table.metadata.notes.iter().chain(&table.metadata.caption))
+    } else {
+        None
+    };
+
+    table
+        .data
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(index, header)| {
+            let (name, unit) = split_header(header);
+            let mut factor = unit.as_ref().map_or(1.0, |unit| unit.factor);
+            // A note's scale only applies when the header doesn't state one
+            if let Some(scale) = note_scale
+                && factor == 1.0
+                && unit.as_ref().is_none_or(|unit| unit.base != "%")
+                && is_numeric_column(table, index)
+            {
+                factor = scale;
+            }
+
+            // Numbers under a "(%)" header are percentages without their sign
+            let semantic_type = semantic::detect(table, index).or_else(|| {
+                (unit.as_ref().is_some_and(|unit| unit.base == "%")
+                    && is_numeric_column(table, index))
+                .then_some(SemanticType::Percentage)
+            });
+
+            ColumnSchema {
+                name,
+                header: header.clone(),
+                base_unit: unit
+                    .as_ref()
+                    .map(|unit| unit.base.clone())
+                    .filter(|base| !base.is_empty()),
+                unit: unit.map(|unit| unit.written),
+                factor,
+                declared_type: None,
+                semantic_type,
+            }
+        })
+        .collect()
+}
+
+/// Records `--types` overrides in the schema of the columns they name.
+pub fn declare_types(table: &mut Table, types: &[(String, DeclaredType)], matcher: &HeaderMatcher) {
+    for (column, declared) in types {
+        if let Some(index) = matcher.column_index(table, column)
+            && let Some(schema) = table.metadata.schema.get_mut(index)
+        {
+            schema.declared_type = Some(*declared);
+        }
+    }
+}
+
+/// The `--types` override of the column at `index`, if any.
+pub fn declared_type(table: &Table, index: usize) -> Option<DeclaredType> {
+    table.metadata.schema.get(index)?.declared_type
+}
+
+/// Converts numeric values to base units and rewrites headers as "Name (base unit)".
+pub fn convert_units(tables: &mut [Table]) {
+    for table in tables {
+        let schema = build_schema(table, true);
+        let conventions = table.metadata.sign_conventions;
+        for (index, column) in schema.iter().enumerate() {
+            if column.factor != 1.0 {
+                for cell in table
+                    .data
+                    .rows
+                    .iter_mut()
+                    .filter_map(|row| row.get_mut(index))
+                {
+                    // A percentage isn't in the column's unit, so its scale doesn't apply
+                    if cell.trim_end().ends_with('%') && column.base_unit.as_deref() != Some("%") {
+                        continue;
+                    }
+                    if let Some(number) = conventions.parse_number(cell) {
+                        *cell = format_number(number * column.factor);
+                    }
+                }
+            }
+            let header = match &column.base_unit {
+                Some(base) => format!("{} ({})", column.name, base),
+                None => column.name.clone(),
+            };
+            if column.factor != 1.0 || header != table.data.headers[index] {
+                table.data.headers[index] = header;
+                lineage::changed(table, index, "convert-units");
+            }
+        }
+    }
+}
+
+/// `--convert-units` as a pipeline step.
+pub struct ConvertUnits;
+
+impl Transform for ConvertUnits {
+    fn name(&self) -> &str {
+        "convert-units"
+    }
+
+    fn apply(&self, tables: &mut Vec<Table>, _page: &PageMetadata) {
+        convert_units(tables);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_of(header: &str) -> Option<(String, String, String, f64)> {
+        let (name, unit) = split_header(header);
+        unit.map(|unit| (name, unit.written, unit.base, unit.factor))
+    }
+
+    fn unit(
+        name: &str,
+        written: &str,
+        base: &str,
+        factor: f64,
+    ) -> Option<(String, String, String, f64)> {
+        Some((
+            name.to_string(),
+            written.to_string(),
+            base.to_string(),
+            factor,
+        ))
+    }
+
+    #[test]
+    fn splits_currency_and_scale_units() {
+        assert_eq!(unit_of("Revenue ($M)"), unit("Revenue", "$M", "USD", 1e6));
+        assert_eq!(
+            unit_of("Revenue (€ bn)"),
+            unit("Revenue", "€ bn", "EUR", 1e9)
+        );
+        assert_eq!(
+            unit_of("Cost [USD thousands]"),
+            unit("Cost", "USD thousands", "USD", 1e3)
+        );
+        assert_eq!(
+            unit_of("Staff (in thousands)"),
+            unit("Staff", "in thousands", "", 1e3)
+        );
+        assert_eq!(unit_of("Sales ('000s)"), unit("Sales", "'000s", "", 1e3));
+    }
+
+    #[test]
+    fn splits_physical_units_case_sensitively() {
+        assert_eq!(
+            unit_of("Distance (km)"),
+            unit("Distance", "km", "m", 1000.0)
+        );
+        assert_eq!(unit_of("Height [cm]"), unit("Height", "cm", "m", 0.01));
+        assert_eq!(unit_of("Growth (%)"), unit("Growth", "%", "%", 1.0));
+        assert_eq!(unit_of("Length (M)"), None);
+    }
+
+    #[test]
+    fn leaves_headers_without_a_unit_unchanged() {
+        for header in [
+            "Name",
+            "Notes (see below)",
+            "Total)",
+            "Depth (km",
+            "Size ()",
+        ] {
+            let (name, unit) = split_header(header);
+            assert_eq!(name, header, "{:?}", header);
+            assert!(unit.is_none(), "{:?}", header);
+        }
+        assert_eq!(parse_scale("k", false), None);
+        assert_eq!(parse_scale("k", true), Some(1e3));
+    }
+
+    #[test]
+    fn replaces_the_unit_of_a_header() {
+        assert_eq!(with_unit("Time (h:mm)", "s"), "Time (s)");
+        assert_eq!(with_unit("Distance (km)", "m"), "Distance (m)");
+        assert_eq!(with_unit("Time", "s"), "Time (s)");
+    }
+}
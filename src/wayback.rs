@@ -0,0 +1,508 @@
+//! `--wayback`: backfills a page's history from Internet Archive snapshots, stacking
+//! each table's rows across snapshots into one longitudinal table.
+
+use std::collections::HashMap;
+
+use chrono::{Duration, Months, NaiveDate, NaiveDateTime};
+use scraper::Html;
+
+use crate::error::TabexError;
+use crate::events::{Events, Record};
+use crate::fetch::HostFilter;
+use crate::lineage::ColumnLineage;
+use crate::plugin::TableSource;
+use crate::{DocumentTables, PageMetadata, Table};
+
+const ARCHIVE: &str = "https://web.archive.org";
+
+/// Spacing between kept snapshots, from `--every`.
+#[derive(Debug, Clone, Copy)]
+enum Every {
+    Days(i64),
+    Months(u32),
+}
+
+impl Every {
+    /// Parses `7d`, `2w`, `1mo` or `1y`.
+    fn parse(text: &str) -> Result<Every, TabexError> {
+        let text = text.trim();
+        let digits = text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len());
+        let count = text[..digits]
+            .parse::<u32>()
+            .ok()
+            .filter(|count| *count > 0);
+        match (count, &text[digits..]) {
+            (Some(count), "d") => Ok(Every::Days(count.into())),
+            (Some(count), "w") => Ok(Every::Days(i64::from(count) * 7)),
+            (Some(count), "mo") => Ok(Every::Months(count)),
+            (Some(count), "y") => Ok(Every::Months(count * 12)),
+            _ => Err(TabexError::Usage(format!(
+                "Invalid --every {} (expected a count and d, w, mo or y, e.g. 1mo)",
+                text
+            ))),
+        }
+    }
+
+    fn after(self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Every::Days(days) => time.checked_add_signed(Duration::days(days)),
+            Every::Months(months) => time.checked_add_months(Months::new(months)),
+        }
+    }
+}
+
+/// Start of a `--from`/`--to` date given as `YYYY`, `YYYY-MM` or `YYYY-MM-DD`.
+fn parse_bound(option: &str, text: &str) -> Result<NaiveDateTime, TabexError> {
+    let parts: Vec<&str> = text.trim().split('-').collect();
+    let number = |index: usize, default: u32| match parts.get(index) {
+        Some(part) => part.parse::<u32>().ok(),
+        None => Some(default),
+    };
+    let date = match (parts.len(), number(0, 0), number(1, 1), number(2, 1)) {
+        (1..=3, Some(year), Some(month), Some(day)) => {
+            NaiveDate::from_ymd_opt(year as i32, month, day)
+        }
+        _ => None,
+    };
+    date.and_then(|date| date.and_hms_opt(0, 0, 0))
+        .ok_or_else(|| {
+            TabexError::Usage(format!(
+                "Invalid {} {} (expected YYYY, YYYY-MM or YYYY-MM-DD)",
+                option, text
+            ))
+        })
+}
+
+/// Which snapshots `--wayback` takes.
+#[derive(Debug)]
+pub struct WaybackRange {
+    from: Option<NaiveDateTime>,
+    /// Digits the CDX API compares timestamps with, as given, e.g. `202312`
+    to: Option<String>,
+    every: Option<Every>,
+}
+
+impl WaybackRange {
+    pub fn parse(
+        from: Option<&str>,
+        to: Option<&str>,
+        every: Option<&str>,
+    ) -> Result<WaybackRange, TabexError> {
+        let from = from.map(|from| parse_bound("--from", from)).transpose()?;
+        if let Some(to) = to {
+            parse_bound("--to", to)?;
+        }
+        Ok(WaybackRange {
+            from,
+            to: to.map(|to| to.trim().replace('-', "")),
+            every: every.map(Every::parse).transpose()?,
+        })
+    }
+}
+
+/// An archived capture of the page.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// `YYYYMMDDhhmmss`, as the archive names captures
+    timestamp: String,
+    original: String,
+}
+
+impl Snapshot {
+    /// The capture as originally served, without the archive's toolbar and link rewriting.
+    fn archive_url(&self) -> String {
+        format!("{}/web/{}id_/{}", ARCHIVE, self.timestamp, self.original)
+    }
+
+    fn time(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(&self.timestamp, "%Y%m%d%H%M%S").ok()
+    }
+
+    fn iso_time(&self) -> String {
+        self.time()
+            .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            .unwrap_or_else(|| self.timestamp.clone())
+    }
+}
+
+/// Successful captures of `url` in the range from the archive's CDX API, oldest first,
+/// skipping captures identical to the previous one and thinned to one per `--every`.
+pub fn snapshots(
+    client: &reqwest::blocking::Client,
+    host_filter: &HostFilter,
+    url: &str,
+    range: &WaybackRange,
+    events: &Events,
+) -> Result<Vec<Snapshot>, TabexError> {
+    let endpoint = format!("{}/cdx/search/cdx", ARCHIVE);
+    if reqwest::Url::parse(&endpoint).is_ok_and(|parsed| !host_filter.allows(&parsed)) {
+        return Err(TabexError::HostNotAllowed { url: endpoint });
+    }
+    let mut query: Vec<(&str, String)> = vec![
+        ("url", url.to_string()),
+        ("fl", "timestamp,original".to_string()),
+        ("filter", "statuscode:200".to_string()),
+        ("collapse", "digest".to_string()),
+    ];
+    if let Some(from) = range.from {
+        query.push(("from", from.format("%Y%m%d%H%M%S").to_string()));
+    }
+    if let Some(to) = &range.to {
+        query.push(("to", to.clone()));
+    }
+    events.status(
+        "wayback",
+        &format!("Listing archived snapshots of {}", url),
+        serde_json::json!({ "url": url }),
+    );
+    events.record(Record::Request { url: &endpoint });
+    let resp = client.get(&endpoint).query(&query).send()?;
+    if !resp.status().is_success() {
+        return Err(TabexError::Fetch {
+            status: resp.status().as_u16(),
+            url: endpoint,
+        });
+    }
+
+    let snapshots = read_cdx(&resp.text()?, range);
+    if snapshots.is_empty() {
+        return Err(TabexError::NoSnapshots {
+            url: url.to_string(),
+        });
+    }
+    Ok(snapshots)
+}
+
+/// Snapshots listed in a CDX response of `timestamp original` lines, thinned to one
+/// per `--every` counted from the range start.
+fn read_cdx(body: &str, range: &WaybackRange) -> Vec<Snapshot> {
+    let mut snapshots = Vec::new();
+    let mut due = range.from;
+    for line in body.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(timestamp), Some(original)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let snapshot = Snapshot {
+            timestamp: timestamp.to_string(),
+            original: original.to_string(),
+        };
+        let Some(time) = snapshot.time() else {
+            continue;
+        };
+        if let Some(every) = range.every {
+            if due.is_some_and(|due| time < due) {
+                continue;
+            }
+            // The schedule keeps to the range start rather than drifting with captures
+            let mut next = due.unwrap_or(time);
+            while next <= time {
+                match every.after(next) {
+                    Some(after) => next = after,
+                    None => break,
+                }
+            }
+            due = Some(next);
+        }
+        snapshots.push(snapshot);
+    }
+    snapshots
+}
+
+/// Loads each snapshot and runs `process` over it, then stacks the tables. Snapshots
+/// that fail to load are skipped with a warning; a passed deadline stops the backfill.
+/// Returns the latest loaded snapshot's document and page metadata, with the page URL
+/// set back to `url`.
+pub fn backfill(
+    url: &str,
+    snapshots: &[Snapshot],
+    source: &dyn TableSource,
+    events: &Events,
+    process: impl Fn(&Html, &str) -> Result<(PageMetadata, DocumentTables), TabexError>,
+) -> Result<(Html, PageMetadata, DocumentTables), TabexError> {
+    let mut runs: Vec<(&Snapshot, Vec<Table>)> = Vec::new();
+    let mut latest: Option<(Html, PageMetadata)> = None;
+    let mut timed_out = false;
+    for snapshot in snapshots {
+        if timed_out {
+            break;
+        }
+        let archive_url = snapshot.archive_url();
+        let loaded = source.load(&archive_url);
+        events.record(Record::Input {
+            url: &archive_url,
+            final_url: loaded.as_ref().ok().map(|document| document.url.as_str()),
+        });
+        match loaded {
+            Ok(loaded) => {
+                let document = Html::parse_document(&loaded.html);
+                let (page, tables) = process(&document, &loaded.url)?;
+                timed_out = tables.timed_out;
+                runs.push((snapshot, tables.tables));
+                latest = Some((document, page));
+            }
+            Err(TabexError::Timeout { .. }) => timed_out = true,
+            Err(e) => events.warning(
+                "snapshot_skipped",
+                &format!("skipping snapshot {}: {}", archive_url, e),
+                serde_json::json!({ "url": archive_url, "error": e.to_string() }),
+            ),
+        }
+    }
+
+    let (document, mut page) = latest.ok_or_else(|| TabexError::NoSnapshots {
+        url: url.to_string(),
+    })?;
+    page.url = url.to_string();
+    let tables = DocumentTables {
+        tables: stack(runs),
+        timed_out,
+    };
+    Ok((document, page, tables))
+}
+
+/// Identifies a table across snapshots. `table_key` covers the headers, which are
+/// what drift over time, so the table's id or caption comes first; repeats within
+/// a snapshot are told apart by their order.
+fn stack_key(table: &Table, seen: &mut HashMap<String, usize>) -> String {
+    let metadata = &table.metadata;
+    let key = match (&metadata.id, &metadata.caption) {
+        (Some(id), _) => format!("id:{}", id),
+        (None, Some(caption)) => format!("caption:{}", caption.trim()),
+        (None, None) => metadata.table_key.clone(),
+    };
+    let count = seen.entry(key.clone()).or_insert(0);
+    *count += 1;
+    format!("{}#{}", key, count)
+}
+
+/// Each header with the number of earlier headers of the same name, so repeated
+/// names such as two "Total" columns or blank headers stay apart.
+fn numbered(headers: &[String]) -> Vec<(&str, usize)> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    headers
+        .iter()
+        .map(|header| {
+            let count = seen.entry(header.as_str()).or_insert(0);
+            *count += 1;
+            (header.as_str(), *count - 1)
+        })
+        .collect()
+}
+
+/// One table per `stack_key`, in order of first appearance, holding the rows of every
+/// snapshot it appeared in followed by a `snapshot` column with the capture time.
+/// Columns are matched by header and its occurrence among equal headers, so columns
+/// added or dropped over time line up; the metadata is the latest snapshot's.
+fn stack(runs: Vec<(&Snapshot, Vec<Table>)>) -> Vec<Table> {
+    let mut order: Vec<String> = Vec::new();
+    let mut appearances: HashMap<String, Vec<(&Snapshot, Table)>> = HashMap::new();
+    for (snapshot, tables) in runs {
+        let mut seen = HashMap::new();
+        for table in tables {
+            let key = stack_key(&table, &mut seen);
+            if !appearances.contains_key(&key) {
+                order.push(key.clone());
+            }
+            appearances.entry(key).or_default().push((snapshot, table));
+        }
+    }
+
+    order
+        .into_iter()
+        .filter_map(|key| {
+            let appearances = appearances.remove(&key)?;
+            let mut columns: Vec<(String, usize)> = Vec::new();
+            for (_, table) in &appearances {
+                for (header, occurrence) in numbered(&table.data.headers) {
+                    if !columns.iter().any(|(h, o)| h == header && *o == occurrence) {
+                        columns.push((header.to_string(), occurrence));
+                    }
+                }
+            }
+            let mut headers: Vec<String> =
+                columns.iter().map(|(header, _)| header.clone()).collect();
+            let width = appearances
+                .iter()
+                .flat_map(|(_, table)| table.data.rows.iter().map(Vec::len))
+                .max()
+                .unwrap_or(0);
+
+            let mut rows: Vec<Vec<String>> = Vec::new();
+            let mut times: Vec<String> = Vec::new();
+            for (snapshot, table) in &appearances {
+                let time = snapshot.iso_time();
+                let numbered = numbered(&table.data.headers);
+                let positions: Vec<Option<usize>> = columns
+                    .iter()
+                    .map(|(header, occurrence)| {
+                        numbered
+                            .iter()
+                            .position(|(h, o)| h == header && o == occurrence)
+                    })
+                    .collect();
+                for row in &table.data.rows {
+                    let mut stacked: Vec<String> = if headers.is_empty() {
+                        let mut row = row.clone();
+                        row.resize(width, String::new());
+                        row
+                    } else {
+                        positions
+                            .iter()
+                            .map(|column| {
+                                column.and_then(|i| row.get(i)).cloned().unwrap_or_default()
+                            })
+                            .collect()
+                    };
+                    stacked.push(time.clone());
+                    rows.push(stacked);
+                }
+                times.push(time);
+            }
+
+            let (_, mut table) = appearances.into_iter().last()?;
+            if let Some(latest) = table.metadata.lineage.take() {
+                let mut stacked: Vec<ColumnLineage> = columns
+                    .iter()
+                    .map(|(header, occurrence)| {
+                        latest
+                            .iter()
+                            .filter(|column| column.column == *header)
+                            .nth(*occurrence)
+                            .cloned()
+                            .unwrap_or_else(|| ColumnLineage {
+                                column: header.clone(),
+                                sources: Vec::new(),
+                                transforms: Vec::new(),
+                            })
+                    })
+                    .collect();
+                if !headers.is_empty() {
+                    stacked.push(ColumnLineage {
+                        column: "snapshot".to_string(),
+                        sources: Vec::new(),
+                        transforms: vec!["wayback".to_string()],
+                    });
+                }
+                table.metadata.lineage = Some(stacked);
+            }
+            if !headers.is_empty() {
+                headers.push("snapshot".to_string());
+            }
+            table.metadata.snapshots = Some(times);
+            table.data.headers = headers;
+            table.data.rows = rows;
+            Some(table)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    fn at(date: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} 00:00:00", date), "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn snapshot(timestamp: &str) -> Snapshot {
+        Snapshot {
+            timestamp: timestamp.to_string(),
+            original: "https://example.com/".to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_every() {
+        assert!(matches!(Every::parse("7d"), Ok(Every::Days(7))));
+        assert!(matches!(Every::parse("2w"), Ok(Every::Days(14))));
+        assert!(matches!(Every::parse(" 1mo "), Ok(Every::Months(1))));
+        assert!(matches!(Every::parse("1y"), Ok(Every::Months(12))));
+        for invalid in ["0d", "mo", "3", "3m", "-1d"] {
+            assert!(Every::parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn parses_range_bounds() {
+        assert_eq!(parse_bound("--from", "2023").unwrap(), at("2023-01-01"));
+        assert_eq!(parse_bound("--from", "2023-06").unwrap(), at("2023-06-01"));
+        assert_eq!(
+            parse_bound("--from", "2023-06-15").unwrap(),
+            at("2023-06-15")
+        );
+        for invalid in ["2023-13", "2023-02-30", "June", "2023-06-15-1", ""] {
+            assert!(parse_bound("--from", invalid).is_err(), "{:?}", invalid);
+        }
+        let range = WaybackRange::parse(None, Some("2023-12"), None).unwrap();
+        assert_eq!(range.to.as_deref(), Some("202312"));
+    }
+
+    #[test]
+    fn thins_snapshots_to_one_per_period_from_the_range_start() {
+        let body = "20231231000000 https://example.com/
+                    20240101120000 https://example.com/
+                    20240115000000 https://example.com/
+                    not-a-time https://example.com/
+                    20240201000000 https://example.com/
+                    20240305000000 https://example.com/
+                    20240310000000 https://example.com/";
+        let timestamps = |range: &WaybackRange| -> Vec<String> {
+            read_cdx(body, range)
+                .into_iter()
+                .map(|snapshot| snapshot.timestamp)
+                .collect()
+        };
+        let range = WaybackRange::parse(Some("2024"), None, Some("1mo")).unwrap();
+        assert_eq!(
+            timestamps(&range),
+            ["20240101120000", "20240201000000", "20240305000000"]
+        );
+        let range = WaybackRange::parse(None, None, None).unwrap();
+        assert_eq!(timestamps(&range).len(), 6);
+    }
+
+    #[test]
+    fn stacks_columns_added_dropped_and_repeated_between_snapshots() {
+        let first = extract(
+            "<table><caption>Scores</caption>
+             <tr><th>Name</th><th>Old</th><th>Total</th><th>Total</th></tr>
+             <tr><td>Ann</td><td>x</td><td>1</td><td>2</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        let second = extract(
+            "<table><caption>Scores</caption>
+             <tr><th>Name</th><th>Total</th><th>Total</th><th>New</th></tr>
+             <tr><td>Bob</td><td>3</td><td>4</td><td>y</td></tr></table>",
+            ExtractOptions::default(),
+        );
+        let (january, february) = (snapshot("20240101000000"), snapshot("20240201000000"));
+        let tables = stack(vec![(&january, first), (&february, second)]);
+        assert_eq!(tables.len(), 1);
+        let data = &tables[0].data;
+        assert_eq!(
+            data.headers,
+            ["Name", "Old", "Total", "Total", "New", "snapshot"]
+        );
+        assert_eq!(
+            data.rows,
+            [
+                ["Ann", "x", "1", "2", "", "2024-01-01T00:00:00Z"],
+                ["Bob", "", "3", "4", "y", "2024-02-01T00:00:00Z"],
+            ]
+        );
+        assert_eq!(
+            tables[0].metadata.snapshots.as_deref(),
+            Some(
+                &[
+                    "2024-01-01T00:00:00Z".to_string(),
+                    "2024-02-01T00:00:00Z".to_string()
+                ][..]
+            )
+        );
+    }
+}
@@ -0,0 +1,497 @@
+use std::io::Write;
+
+use chrono::{Datelike, NaiveDate};
+use rust_xlsxwriter::{ExcelDateTime, Format, Url, Workbook, Worksheet};
+
+use crate::error::TabexError;
+use crate::license::License;
+use crate::plugin::Sink;
+use crate::types::{DeclaredType, SignConventions, Value};
+use crate::units::declared_type;
+use crate::{ExtractionResult, PageOutcome, Table};
+
+/// Excel's limit on sheet name length, in characters.
+const SHEET_NAME_LIMIT: usize = 31;
+
+const INDEX_SHEET: &str = "Index";
+const METADATA_SHEET: &str = "Metadata";
+
+/// Presentation options for `--xlsx-options`; all off by default.
+#[derive(Debug, Default)]
+pub struct XlsxOptions {
+    /// Keep the header row visible while scrolling
+    pub freeze_header: bool,
+    pub autofilter: bool,
+    /// Size columns to their contents
+    pub autofit: bool,
+    /// Number, percent, currency and date formats from each column's values
+    pub formats: bool,
+    /// A sheet of page metadata and run statistics
+    pub metadata_sheet: bool,
+}
+
+impl XlsxOptions {
+    pub fn parse_list(list: &str) -> Result<XlsxOptions, TabexError> {
+        let mut options = XlsxOptions::default();
+        for name in list.split(',') {
+            match name.trim() {
+                "freeze" => options.freeze_header = true,
+                "filter" => options.autofilter = true,
+                "autofit" => options.autofit = true,
+                "formats" => options.formats = true,
+                "metadata" => options.metadata_sheet = true,
+                "all" => {
+                    options = XlsxOptions {
+                        freeze_header: true,
+                        autofilter: true,
+                        autofit: true,
+                        formats: true,
+                        metadata_sheet: true,
+                    }
+                }
+                other => {
+                    return Err(TabexError::Parse(format!(
+                        "Unknown xlsx option '{}' (expected freeze, filter, autofit, formats, metadata or all)",
+                        other
+                    )));
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// How a column's cells are written when `formats` is on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CellKind {
+    Integer,
+    Decimal,
+    Percent,
+    Currency(char),
+    Date,
+    Text,
+}
+
+fn parse_date(cell: &str) -> Option<NaiveDate> {
+    ["%Y-%m-%d", "%d %B %Y", "%B %d, %Y", "%d %b %Y", "%b %d, %Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(cell, format).ok())
+}
+
+fn cell_kind(cell: &str, conventions: SignConventions) -> CellKind {
+    if parse_date(cell).is_some() {
+        return CellKind::Date;
+    }
+    let Some(number) = conventions.parse_number(cell) else {
+        return CellKind::Text;
+    };
+    let sign_stripped = cell.trim_start_matches(['-', '\u{2212}']);
+    match sign_stripped.chars().next() {
+        _ if cell.ends_with('%') => CellKind::Percent,
+        Some(symbol @ ('$' | '€' | '£' | '¥')) => CellKind::Currency(symbol),
+        _ if number.fract() != 0.0 => CellKind::Decimal,
+        _ => CellKind::Integer,
+    }
+}
+
+/// The kind shared by every non-empty cell of a column. Whole and fractional
+/// numbers combine as decimals; any other mix is text.
+fn column_kind(table: &Table, index: usize) -> CellKind {
+    let mut kind: Option<CellKind> = None;
+    for row in &table.data.rows {
+        let cell = row.get(index).map(|cell| cell.trim()).unwrap_or("");
+        if cell.is_empty() {
+            continue;
+        }
+        let next = cell_kind(cell, table.metadata.sign_conventions);
+        kind = Some(match (kind, next) {
+            (None, next) => next,
+            (Some(current), next) if current == next => current,
+            (
+                Some(CellKind::Integer | CellKind::Decimal),
+                CellKind::Integer | CellKind::Decimal,
+            ) => CellKind::Decimal,
+            _ => return CellKind::Text,
+        });
+    }
+    kind.unwrap_or(CellKind::Text)
+}
+
+fn kind_format(kind: CellKind) -> Option<Format> {
+    let format = match kind {
+        CellKind::Integer => "#,##0".to_string(),
+        CellKind::Decimal => "#,##0.00".to_string(),
+        CellKind::Percent => "0.0%".to_string(),
+        CellKind::Currency(symbol) => format!("\"{}\"#,##0.00", symbol),
+        CellKind::Date => "yyyy-mm-dd".to_string(),
+        CellKind::Text => return None,
+    };
+    Some(Format::new().set_num_format(format))
+}
+
+/// Number format for a `--types` column; a decimal keeps its declared scale.
+fn declared_format(declared: DeclaredType) -> Option<Format> {
+    let format = match declared {
+        DeclaredType::Int => "#,##0".to_string(),
+        DeclaredType::Decimal { scale: 0, .. } => "#,##0".to_string(),
+        DeclaredType::Decimal { scale, .. } => format!("#,##0.{}", "0".repeat(scale as usize)),
+        DeclaredType::String | DeclaredType::Float => return None,
+    };
+    Some(Format::new().set_num_format(format))
+}
+
+/// A sheet name from the table's templated name, caption or heading, else `Table <position>`, with
+/// characters Excel rejects replaced and cut to the length limit. Names already in
+/// `taken` get a ` (2)`, ` (3)`, ... suffix; Excel compares them case-insensitively.
+fn sheet_name(table: &Table, taken: &[String]) -> String {
+    let label = table
+        .metadata
+        .name
+        .as_deref()
+        .or(table.metadata.caption.as_deref())
+        .or(table.metadata.preceding_heading.as_deref())
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(String::from)
+        .unwrap_or_else(|| format!("Table {}", table.metadata.position));
+    let cleaned: String = label
+        .chars()
+        .map(|c| match c {
+            '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches(|c: char| c == '\'' || c.is_whitespace());
+    let base = if cleaned.is_empty() {
+        format!("Table {}", table.metadata.position)
+    } else {
+        cleaned.to_string()
+    };
+
+    let is_taken = |name: &str| taken.iter().any(|other| other.eq_ignore_ascii_case(name));
+    // Cutting may leave an apostrophe at the end, which Excel rejects as well
+    let truncate = |name: &str, limit: usize| -> String {
+        name.chars()
+            .take(limit)
+            .collect::<String>()
+            .trim_end_matches(|c: char| c == '\'' || c.is_whitespace())
+            .to_string()
+    };
+    let mut name = truncate(&base, SHEET_NAME_LIMIT);
+    let mut counter = 2;
+    while is_taken(&name) {
+        let suffix = format!(" ({})", counter);
+        name = truncate(&base, SHEET_NAME_LIMIT - suffix.len()) + &suffix;
+        counter += 1;
+    }
+    name
+}
+
+fn write_cell(
+    sheet: &mut Worksheet,
+    row: u32,
+    column: u16,
+    cell: &str,
+    format: Option<(CellKind, &Format)>,
+    conventions: SignConventions,
+) -> Result<(), TabexError> {
+    let value = Value::from_cell(cell, conventions);
+    match (format, value) {
+        (_, Value::Null) => {}
+        (Some((CellKind::Date, format)), _) => {
+            if let Some(date) = parse_date(cell.trim()) {
+                let date = ExcelDateTime::from_ymd(
+                    date.year() as u16,
+                    date.month() as u8,
+                    date.day() as u8,
+                )?;
+                sheet.write_date_with_format(row, column, &date, format)?;
+            }
+        }
+        // Excel stores percentages as fractions
+        (Some((CellKind::Percent, format)), Value::Number(number)) => {
+            sheet.write_number_with_format(row, column, number / 100.0, format)?;
+        }
+        (Some((_, format)), Value::Number(number)) => {
+            sheet.write_number_with_format(row, column, number, format)?;
+        }
+        (None, Value::Number(number)) => {
+            sheet.write_number(row, column, number)?;
+        }
+        (_, Value::Text(_)) => {
+            sheet.write_string(row, column, cell.trim())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a cell of a `--types` column: strings stay text, and numbers that don't
+/// fit the declared type are kept as text rather than dropped.
+fn write_declared_cell(
+    sheet: &mut Worksheet,
+    row: u32,
+    column: u16,
+    cell: &str,
+    declared: DeclaredType,
+    format: Option<&Format>,
+    conventions: SignConventions,
+) -> Result<(), TabexError> {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return Ok(());
+    }
+    match (declared.number(cell, conventions), format) {
+        (Some(number), Some(format)) => {
+            sheet.write_number_with_format(row, column, number, format)?;
+        }
+        (Some(number), None) => {
+            sheet.write_number(row, column, number)?;
+        }
+        (None, _) => {
+            sheet.write_string(row, column, cell)?;
+        }
+    }
+    Ok(())
+}
+
+/// The metadata sheet: a row per field, with a column of values for each page.
+fn write_metadata(
+    workbook: &mut Workbook,
+    results: &[&ExtractionResult],
+) -> Result<(), TabexError> {
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(METADATA_SHEET)?;
+    let bold = Format::new().set_bold();
+    for (column, result) in results.iter().enumerate() {
+        let page = &result.page;
+        let extraction_time = result.extraction_time_ms.to_string();
+        let tables = result.tables.len().to_string();
+        let license = page.license.as_ref().map(License::label);
+        let fields = [
+            ("URL", Some(page.url.as_str())),
+            ("Title", page.title.as_deref()),
+            ("Description", page.description.as_deref()),
+            ("Author", page.author.as_deref()),
+            ("Published", page.published_date.as_deref()),
+            ("Last modified", page.last_modified.as_deref()),
+            ("Robots", page.robots.as_deref()),
+            ("License", license.as_deref()),
+            ("Tables", Some(tables.as_str())),
+            ("Extraction time (ms)", Some(extraction_time.as_str())),
+            (
+                "Timed out",
+                Some(if result.timed_out { "yes" } else { "no" }),
+            ),
+        ];
+        for (row, (label, value)) in fields.into_iter().enumerate() {
+            if column == 0 {
+                sheet.write_string_with_format(row as u32, 0, label, &bold)?;
+            }
+            sheet.write_string(row as u32, column as u16 + 1, value.unwrap_or(""))?;
+        }
+    }
+    sheet.autofit();
+    Ok(())
+}
+
+/// An Excel workbook with one sheet per table and an index sheet linking to each.
+pub struct XlsxSink {
+    pub options: XlsxOptions,
+}
+
+impl XlsxSink {
+    /// One workbook for the tables of `results`, in page order. Sheet names are kept
+    /// unique across pages, and the index gives each table's page.
+    fn write_workbook(
+        &self,
+        results: &[&ExtractionResult],
+        out: &mut dyn Write,
+    ) -> Result<(), TabexError> {
+        let mut workbook = Workbook::new();
+        let options = &self.options;
+        let mut names: Vec<String> = vec![INDEX_SHEET.to_string(), METADATA_SHEET.to_string()];
+        let bold = Format::new().set_bold();
+
+        let index = workbook.add_worksheet();
+        index.set_name(INDEX_SHEET)?;
+        let index_headers = ["Sheet", "Page", "Position", "Caption", "Heading", "Rows"];
+        for (column, header) in index_headers.iter().enumerate() {
+            index.write_string_with_format(0, column as u16, *header, &bold)?;
+        }
+        let tables: Vec<(&ExtractionResult, &Table)> = results
+            .iter()
+            .flat_map(|result| result.tables.iter().map(move |table| (*result, table)))
+            .collect();
+        for (i, (result, table)) in tables.iter().enumerate() {
+            let name = sheet_name(table, &names);
+            let row = i as u32 + 1;
+            let link = format!("internal:'{}'!A1", name.replace('\'', "''"));
+            index.write_url_with_text(row, 0, Url::new(link), &name)?;
+            index.write_url(row, 1, Url::new(result.page.url.as_str()))?;
+            index.write_number(row, 2, table.metadata.position as f64)?;
+            index.write_string(row, 3, table.metadata.caption.as_deref().unwrap_or(""))?;
+            index.write_string(
+                row,
+                4,
+                table.metadata.preceding_heading.as_deref().unwrap_or(""),
+            )?;
+            index.write_number(row, 5, table.data.rows.len() as f64)?;
+            names.push(name);
+        }
+        if options.freeze_header {
+            index.set_freeze_panes(1, 0)?;
+        }
+        if options.autofilter && !tables.is_empty() {
+            index.autofilter(0, 0, tables.len() as u32, index_headers.len() as u16 - 1)?;
+        }
+        if options.autofit {
+            index.autofit();
+        }
+        if options.metadata_sheet {
+            write_metadata(&mut workbook, results)?;
+        }
+
+        for ((_, table), name) in tables.iter().zip(&names[2..]) {
+            let sheet = workbook.add_worksheet();
+            sheet.set_name(name)?;
+            let width = table
+                .data
+                .rows
+                .iter()
+                .map(Vec::len)
+                .chain([table.data.headers.len()])
+                .max()
+                .unwrap_or(0);
+            let declared: Vec<Option<(DeclaredType, Option<Format>)>> = (0..width)
+                .map(|i| {
+                    let declared = declared_type(table, i)?;
+                    let format = declared_format(declared).filter(|_| options.formats);
+                    Some((declared, format))
+                })
+                .collect();
+            let formats: Vec<Option<(CellKind, Format)>> = (0..width)
+                .map(|i| {
+                    let kind = column_kind(table, i);
+                    kind_format(kind)
+                        .filter(|_| options.formats)
+                        .map(|format| (kind, format))
+                })
+                .collect();
+
+            let has_headers = !table.data.headers.is_empty();
+            for (column, header) in table.data.headers.iter().enumerate() {
+                if options.formats {
+                    sheet.write_string_with_format(0, column as u16, header, &bold)?;
+                } else {
+                    sheet.write_string(0, column as u16, header)?;
+                }
+            }
+            let offset = has_headers as u32;
+            for (i, row) in table.data.rows.iter().enumerate() {
+                for (column, cell) in row.iter().enumerate() {
+                    if let Some((declared, format)) = &declared[column] {
+                        write_declared_cell(
+                            sheet,
+                            i as u32 + offset,
+                            column as u16,
+                            cell,
+                            *declared,
+                            format.as_ref(),
+                            table.metadata.sign_conventions,
+                        )?;
+                        continue;
+                    }
+                    let format = formats[column]
+                        .as_ref()
+                        .map(|(kind, format)| (*kind, format));
+                    write_cell(
+                        sheet,
+                        i as u32 + offset,
+                        column as u16,
+                        cell,
+                        format,
+                        table.metadata.sign_conventions,
+                    )?;
+                }
+            }
+
+            if has_headers && options.freeze_header {
+                sheet.set_freeze_panes(1, 0)?;
+            }
+            if has_headers && options.autofilter && width > 0 {
+                let last_row = table.data.rows.len() as u32;
+                sheet.autofilter(0, 0, last_row, width as u16 - 1)?;
+            }
+            if options.autofit {
+                sheet.autofit();
+            }
+        }
+
+        out.write_all(&workbook.save_to_buffer()?)?;
+        Ok(())
+    }
+}
+
+impl Sink for XlsxSink {
+    fn name(&self) -> &str {
+        "xlsx"
+    }
+
+    fn write(&self, result: &ExtractionResult, out: &mut dyn Write) -> Result<(), TabexError> {
+        self.write_workbook(&[result], out)
+    }
+
+    /// Every page's tables in one workbook.
+    fn write_pages(&self, pages: &[PageOutcome], out: &mut dyn Write) -> Result<(), TabexError> {
+        let results: Vec<&ExtractionResult> = pages
+            .iter()
+            .filter_map(|page| page.result.as_ref())
+            .collect();
+        self.write_workbook(&results, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractOptions;
+    use crate::tests::extract;
+
+    fn captioned(caption: &str) -> Table {
+        let mut table = extract(
+            "<table><tr><th>A</th></tr><tr><td>1</td></tr></table>",
+            ExtractOptions::default(),
+        )
+        .remove(0);
+        table.metadata.caption = Some(caption.to_string());
+        table
+    }
+
+    #[test]
+    fn truncates_sheet_names_to_the_excel_limit() {
+        let name = sheet_name(&captioned(&"x".repeat(40)), &[]);
+        assert_eq!(name, "x".repeat(31));
+        // The 31st character is an apostrophe
+        let name = sheet_name(
+            &captioned("Revenue of the Holding Company's subsidiaries"),
+            &[],
+        );
+        assert_eq!(name, "Revenue of the Holding Company");
+        assert_eq!(sheet_name(&captioned("'Q1: [draft]'"), &[]), "Q1_ _draft_");
+        assert_eq!(sheet_name(&captioned(" '' "), &[]), "Table 1");
+    }
+
+    #[test]
+    fn suffixes_names_taken_in_any_case() {
+        let taken = vec!["Scores".to_string(), "scores (2)".to_string()];
+        assert_eq!(sheet_name(&captioned("SCORES"), &taken), "SCORES (3)");
+        let long = "y".repeat(40);
+        let taken = vec!["y".repeat(31)];
+        assert_eq!(
+            sheet_name(&captioned(&long), &taken),
+            format!("{} (2)", "y".repeat(27))
+        );
+        assert_eq!(sheet_name(&captioned("Other"), &taken), "Other");
+    }
+}